@@ -2,7 +2,7 @@ use darling::FromMeta;
 use proc_macro::TokenStream;
 use proc_macro_crate::*;
 use quote::{format_ident, quote};
-use syn::{parse_macro_input, AttributeArgs, Data, DeriveInput, Fields, ItemFn};
+use syn::{parse_macro_input, AttributeArgs, Data, DeriveInput, ItemFn};
 
 #[derive(Debug, FromMeta)]
 struct ConnectorFactoryArgs {
@@ -15,6 +15,10 @@ struct ConnectorFactoryArgs {
     help_fn: Option<String>,
     #[darling(default)]
     target_list_fn: Option<String>,
+    #[darling(default)]
+    arg_list_fn: Option<String>,
+    #[darling(default)]
+    compatible_version: Option<String>,
 }
 
 #[derive(Debug, FromMeta)]
@@ -26,6 +30,56 @@ struct OsFactoryArgs {
     description: Option<String>,
     #[darling(default)]
     help_fn: Option<String>,
+    #[darling(default)]
+    arg_list_fn: Option<String>,
+    #[darling(default)]
+    compatible_version: Option<String>,
+}
+
+/// Generates the descriptor entry and the callback for the `arg_list_fn` attribute.
+///
+/// The function has to return an `ArgsValidator`, the arguments known to the validator are
+/// handed out to the inventory.
+fn gen_arg_list(
+    crate_path: &proc_macro2::TokenStream,
+    arg_list_fn: Option<String>,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    match arg_list_fn.map(|v| v.parse::<proc_macro2::TokenStream>().unwrap()) {
+        Some(func_name) => (
+            quote! { Some(mf_arg_list_callback) },
+            quote! {
+                #[doc(hidden)]
+                extern "C" fn mf_arg_list_callback(
+                    mut callback: #crate_path::plugins::ArgListCallback,
+                ) {
+                    #func_name()
+                        .arg_list()
+                        .into_iter()
+                        .take_while(|a| callback.call(a.clone()))
+                        .for_each(|_| ());
+                }
+            },
+        ),
+        None => (quote! { None }, proc_macro2::TokenStream::new()),
+    }
+}
+
+/// Generates the `compatible_version` descriptor entry.
+///
+/// Plugins are only compatible with the plugin api version they were built for by default.
+fn gen_compatible_version(
+    crate_path: &proc_macro2::TokenStream,
+    compatible_version: Option<String>,
+) -> proc_macro2::TokenStream {
+    match compatible_version {
+        Some(version) => {
+            let version: i32 = version
+                .parse()
+                .expect("compatible_version must be a plugin api version");
+            quote! { #version }
+        }
+        None => quote! { #crate_path::plugins::MEMFLOW_PLUGIN_VERSION },
+    }
 }
 
 fn validate_plugin_name(name: &str) {
@@ -81,6 +135,9 @@ pub fn connector(args: TokenStream, input: TokenStream) -> TokenStream {
         quote! { None }
     };
 
+    let (arg_list_gen, arg_list_fn_gen) = gen_arg_list(&crate_path, args.arg_list_fn);
+    let compatible_version_gen = gen_compatible_version(&crate_path, args.compatible_version);
+
     let connector_descriptor: proc_macro2::TokenStream =
         ["MEMFLOW_CONNECTOR_", &(&connector_name).to_uppercase()]
             .concat()
@@ -144,6 +201,7 @@ pub fn connector(args: TokenStream, input: TokenStream) -> TokenStream {
         #[no_mangle]
         pub static #connector_descriptor: #crate_path::plugins::ConnectorDescriptor = #crate_path::plugins::ConnectorDescriptor {
             plugin_version: #crate_path::plugins::MEMFLOW_PLUGIN_VERSION,
+            compatible_version: #compatible_version_gen,
             input_layout: <<#crate_path::plugins::LoadableConnector as #crate_path::plugins::Loadable>::CInputArg as #crate_path::abi_stable::StableAbi>::LAYOUT,
             output_layout: <<#crate_path::plugins::LoadableConnector as #crate_path::plugins::Loadable>::Instance as #crate_path::abi_stable::StableAbi>::LAYOUT,
             name: #crate_path::cglue::CSliceRef::from_str(#connector_name),
@@ -151,6 +209,7 @@ pub fn connector(args: TokenStream, input: TokenStream) -> TokenStream {
             description: #crate_path::cglue::CSliceRef::from_str(#description_gen),
             help_callback: #help_gen,
             target_list_callback: #target_list_gen,
+            arg_list_callback: #arg_list_gen,
             create: mf_create,
         };
 
@@ -160,6 +219,8 @@ pub fn connector(args: TokenStream, input: TokenStream) -> TokenStream {
 
         #target_list_fn_gen
 
+        #arg_list_fn_gen
+
         #func
     };
 
@@ -200,6 +261,9 @@ pub fn connector_bare(args: TokenStream, input: TokenStream) -> TokenStream {
         quote! { None }
     };
 
+    let (arg_list_gen, arg_list_fn_gen) = gen_arg_list(&crate_path, args.arg_list_fn);
+    let compatible_version_gen = gen_compatible_version(&crate_path, args.compatible_version);
+
     let connector_descriptor: proc_macro2::TokenStream =
         ["MEMFLOW_CONNECTOR_", &(&connector_name).to_uppercase()]
             .concat()
@@ -263,6 +327,7 @@ pub fn connector_bare(args: TokenStream, input: TokenStream) -> TokenStream {
         #[no_mangle]
         pub static #connector_descriptor: #crate_path::plugins::ConnectorDescriptor = #crate_path::plugins::ConnectorDescriptor {
             plugin_version: #crate_path::plugins::MEMFLOW_PLUGIN_VERSION,
+            compatible_version: #compatible_version_gen,
             input_layout: <<#crate_path::plugins::LoadableConnector as #crate_path::plugins::Loadable>::CInputArg as #crate_path::abi_stable::StableAbi>::LAYOUT,
             output_layout: <<#crate_path::plugins::LoadableConnector as #crate_path::plugins::Loadable>::Instance as #crate_path::abi_stable::StableAbi>::LAYOUT,
             name: #crate_path::cglue::CSliceRef::from_str(#connector_name),
@@ -270,6 +335,7 @@ pub fn connector_bare(args: TokenStream, input: TokenStream) -> TokenStream {
             description: #crate_path::cglue::CSliceRef::from_str(#description_gen),
             help_callback: #help_gen,
             target_list_callback: #target_list_gen,
+            arg_list_callback: #arg_list_gen,
             create: mf_create,
         };
 
@@ -279,6 +345,8 @@ pub fn connector_bare(args: TokenStream, input: TokenStream) -> TokenStream {
 
         #target_list_fn_gen
 
+        #arg_list_fn_gen
+
         #func
     };
 
@@ -400,6 +468,9 @@ pub fn os_layer_bare(args: TokenStream, input: TokenStream) -> TokenStream {
         quote! { None }
     };
 
+    let (arg_list_gen, arg_list_fn_gen) = gen_arg_list(&crate_path, args.arg_list_fn);
+    let compatible_version_gen = gen_compatible_version(&crate_path, args.compatible_version);
+
     let os_descriptor: proc_macro2::TokenStream = ["MEMFLOW_OS_", &(&os_name).to_uppercase()]
         .concat()
         .parse()
@@ -441,6 +512,7 @@ pub fn os_layer_bare(args: TokenStream, input: TokenStream) -> TokenStream {
         #[no_mangle]
         pub static #os_descriptor: #crate_path::plugins::os::OsDescriptor = #crate_path::plugins::os::OsDescriptor {
             plugin_version: #crate_path::plugins::MEMFLOW_PLUGIN_VERSION,
+            compatible_version: #compatible_version_gen,
             input_layout: <<#crate_path::plugins::os::LoadableOs as #crate_path::plugins::Loadable>::CInputArg as #crate_path::abi_stable::StableAbi>::LAYOUT,
             output_layout: <<#crate_path::plugins::os::LoadableOs as #crate_path::plugins::Loadable>::Instance as #crate_path::abi_stable::StableAbi>::LAYOUT,
             name: #crate_path::cglue::CSliceRef::from_str(#os_name),
@@ -448,6 +520,7 @@ pub fn os_layer_bare(args: TokenStream, input: TokenStream) -> TokenStream {
             description: #crate_path::cglue::CSliceRef::from_str(#description_gen),
             help_callback: #help_gen,
             target_list_callback: None, // non existent on Os Plugins
+            arg_list_callback: #arg_list_gen,
             create: mf_create,
         };
 
@@ -455,6 +528,8 @@ pub fn os_layer_bare(args: TokenStream, input: TokenStream) -> TokenStream {
 
         #help_fn_gen
 
+        #arg_list_fn_gen
+
         #func
     };
 
@@ -471,61 +546,181 @@ pub fn os_layer_bare(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// * Not have any padding between its fields.
 ///
+/// Once derived, the safe byte view functions of `Pod` (like `as_bytes` and `as_bytes_mut`)
+/// can be used and the type can be read with `MemoryView::read` and `read_into` without any
+/// `unsafe` code.
+///
 /// # Compile errors
 ///
-/// Error reporting is not very ergonomic due to how errors are detected:
+/// * `Pod can only be derived for structs with #[repr(C)] or #[repr(transparent)]`
+///
+///   The struct is missing the required representation, or the type is not a struct.
 ///
 /// * `error[E0277]: the trait bound $TYPE: Pod is not satisfied`
 ///
 ///   The struct contains a field whose type does not implement `Pod`.
 ///
-/// * `error[E0512]: cannot transmute between types of different sizes, or dependently-sized types`
+/// * `error[E0308]: mismatched types` with `expected an array with a fixed size of 1 element`
 ///
-///   This error means your struct has padding as its size is not equal to a byte array of length equal to the sum of the size of its fields.
+///   This error means your struct has padding as its size is not equal to the sum of the size of its fields.
 ///
-/// * `error: no rules expected the token <`
+/// * `Pod can not be derived for generic structs`
 ///
 ///   The struct contains generic parameters which are not supported. It may still be possible to manually implement `Pod` but extra care should be taken to ensure its invariants are upheld.
-///
-/// # Remarks:
-/// This custom derive macro is required because the dataview proc macro searches for ::dataview::derive_pod!().
-/// See https://github.com/CasualX/dataview/blob/master/derive_pod/lib.rs for the original implementation.
 #[proc_macro_derive(Pod)]
 pub fn pod_derive(input: TokenStream) -> TokenStream {
     let crate_path = crate_path();
 
-    format!("{}::dataview::derive_pod!{{ {} }}", crate_path, input)
-        .parse()
-        .unwrap()
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let fields = match &input.data {
+        Data::Struct(data) if has_repr(&input.attrs, &["C", "transparent"]) => &data.fields,
+        _ => {
+            return syn::Error::new_spanned(
+                name,
+                "Pod can only be derived for structs with #[repr(C)] or #[repr(transparent)]",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &input.generics,
+            "Pod can not be derived for generic structs",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    let gen = quote!(
+        unsafe impl #crate_path::dataview::Pod for #name {}
+
+        const _: () = {
+            fn assert_pod<T: #crate_path::dataview::Pod + ?Sized>() {}
+
+            #[allow(dead_code)]
+            fn assert_fields() {
+                #(assert_pod::<#types>();)*
+            }
+
+            // the sizes only differ if the struct contains padding
+            const _: [(); 1] = [(); (::core::mem::size_of::<#name>()
+                == 0 #(+ ::core::mem::size_of::<#types>())*) as usize];
+        };
+    );
+
+    gen.into()
+}
+
+/// Returns true if a `#[repr(..)]` attribute contains any of the given representations.
+fn has_repr(attrs: &[syn::Attribute], reprs: &[&str]) -> bool {
+    repr_idents(attrs).any(|ident| reprs.iter().any(|r| ident == r))
 }
 
+fn repr_idents(attrs: &[syn::Attribute]) -> impl Iterator<Item = syn::Ident> + '_ {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("repr"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) => path.get_ident().cloned(),
+            _ => None,
+        })
+}
+
+/// Auto derive the `ByteSwap` trait.
+///
+/// Structs swap each of their fields, so every field type has to implement `ByteSwap` itself.
+/// Generic type parameters are required to implement `ByteSwap` as well.
+///
+/// Enums have to be fieldless and annotated with a primitive representation like `#[repr(u32)]`.
+/// The discriminant is swapped and the enum is set to the variant with the swapped value. If no
+/// variant matches the swapped value the enum is left unchanged.
 #[proc_macro_derive(ByteSwap)]
 pub fn byteswap_derive(input: TokenStream) -> TokenStream {
     let crate_path = crate_path();
 
-    let input = parse_macro_input!(input as DeriveInput);
+    let mut input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let mut gen_inner = quote!();
-    match input.data {
-        Data::Struct(data) => match data.fields {
-            Fields::Named(named) => {
-                for field in named.named.iter() {
-                    let name = field.ident.as_ref().unwrap();
-                    gen_inner.extend(quote!(
-                        self.#name.byte_swap();
-                    ));
+    let gen_inner = match &input.data {
+        Data::Struct(data) => {
+            let fields = data.fields.iter().enumerate().map(|(i, field)| {
+                let member = match &field.ident {
+                    Some(ident) => quote!(#ident),
+                    None => {
+                        let idx = syn::Index::from(i);
+                        quote!(#idx)
+                    }
+                };
+                quote!(self.#member.byte_swap();)
+            });
+            quote!(#(#fields)*)
+        }
+        Data::Enum(data) => {
+            let repr = match enum_repr(&input.attrs) {
+                Some(repr) => repr,
+                None => {
+                    return syn::Error::new_spanned(
+                        name,
+                        "ByteSwap can only be derived for enums with a primitive representation like #[repr(u32)]",
+                    )
+                    .to_compile_error()
+                    .into()
                 }
+            };
+
+            if let Some(variant) = data.variants.iter().find(|v| !v.fields.is_empty()) {
+                return syn::Error::new_spanned(
+                    variant,
+                    "ByteSwap can only be derived for enums without fields",
+                )
+                .to_compile_error()
+                .into();
             }
-            _ => unimplemented!(),
-        },
-        _ => unimplemented!(),
+
+            let variants = data.variants.iter().map(|v| {
+                let ident = &v.ident;
+                quote!(if discriminant == Self::#ident as #repr {
+                    *self = Self::#ident;
+                })
+            });
+
+            quote!(
+                // fieldless enums with a primitive representation are laid out like the primitive
+                let mut discriminant = unsafe { *(self as *const Self as *const #repr) };
+                discriminant.byte_swap();
+                #(#variants else)* {}
+            )
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "ByteSwap can not be derived for unions")
+                .to_compile_error()
+                .into()
+        }
     };
 
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(syn::parse_quote!(#crate_path::types::byte_swap::ByteSwap));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
     let gen = quote!(
         impl #impl_generics #crate_path::types::byte_swap::ByteSwap for #name #ty_generics #where_clause {
             fn byte_swap(&mut self) {
+                use #crate_path::types::byte_swap::ByteSwap as _;
                 #gen_inner
             }
         }
@@ -534,6 +729,15 @@ pub fn byteswap_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Returns the primitive type of a `#[repr(..)]` attribute.
+fn enum_repr(attrs: &[syn::Attribute]) -> Option<syn::Ident> {
+    const PRIMITIVES: &[&str] = &[
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    ];
+
+    repr_idents(attrs).find(|ident| PRIMITIVES.iter().any(|p| ident == p))
+}
+
 fn crate_path() -> proc_macro2::TokenStream {
     let (col, ident) = crate_path_ident();
     quote!(#col #ident)
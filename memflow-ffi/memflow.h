@@ -296,6 +296,14 @@ typedef struct ArchitectureObj ArchitectureObj;
  */
 typedef struct Inventory Inventory;
 
+/**
+ * Polls a set of memory ranges and reports changes through callbacks.
+ *
+ * The first poll after a range has been registered only records its contents.
+ * Returning `false` from a callback removes the watch.
+ */
+typedef struct MemoryWatcher MemoryWatcher;
+
 /**
  * The largest target memory type
  * The following core rule is defined for these memory types:
@@ -323,7 +331,7 @@ typedef umem Address;
 /**
  * Describes the type of a page using a bitflag.
  */
-typedef uint8_t PageType;
+typedef uint16_t PageType;
 /**
  * The page explicitly has no flags.
  */
@@ -348,6 +356,38 @@ typedef uint8_t PageType;
  * The page is not executable.
  */
 #define PageType_NOEXEC 16
+/**
+ * The page is accessible from user mode.
+ */
+#define PageType_USER 32
+/**
+ * The page was accessed since the flag was last cleared by the OS.
+ */
+#define PageType_ACCESSED 64
+/**
+ * The page was written to since the flag was last cleared by the OS.
+ */
+#define PageType_DIRTY 128
+/**
+ * The page is mapped as write-combined memory, like framebuffers.
+ */
+#define PageType_WRITE_COMBINED 256
+/**
+ * The page is uncacheable, like memory mapped device registers (MMIO).
+ */
+#define PageType_UNCACHEABLE 512
+/**
+ * The page is encrypted by the hardware (e.g. AMD SEV or Intel TME).
+ */
+#define PageType_ENCRYPTED 1024
+/**
+ * The page content is compressed by the OS.
+ */
+#define PageType_COMPRESSED 2048
+/**
+ * The page is backed by the pagefile and currently not resident in memory.
+ */
+#define PageType_PAGEFILE 4096
 
 /**
  * This type represents a wrapper over a [address](address/index.html)
@@ -1149,7 +1189,8 @@ typedef char *ReprCString;
 typedef enum ArchitectureIdent_Tag {
     /**
      * Unknown architecture. Could be third-party implemented. memflow knows how to work on them,
-     * but is unable to instantiate them.
+     * but is only able to instantiate them after they were registered with
+     * [`custom::register_architecture`].
      */
     ArchitectureIdent_Unknown,
     /**
@@ -1157,15 +1198,21 @@ typedef enum ArchitectureIdent_Tag {
      *
      * First argument - `bitness` controls whether it's 32, or 64 bit variant.
      * Second argument - `address_extensions` control whether address extensions are
-     * enabled (PAE on x32, or LA57 on x64). Warning: LA57 is currently unsupported.
+     * enabled (PAE on x32, or LA57 on x64).
      */
     ArchitectureIdent_X86,
     /**
      * Arm 64-bit architecture with specified page size
      *
-     * Valid page sizes are 4kb, 16kb, 64kb. Only 4kb is supported at the moment
+     * Valid page sizes are 4kb, 16kb, 64kb.
      */
     ArchitectureIdent_AArch64,
+    /**
+     * RISC-V 64-bit architecture with the specified virtual address size
+     *
+     * Valid sizes are 39 (Sv39) and 48 (Sv48).
+     */
+    ArchitectureIdent_RiscV64,
 } ArchitectureIdent_Tag;
 
 typedef struct ArchitectureIdent_X86_Body {
@@ -1183,6 +1230,9 @@ typedef struct ArchitectureIdent {
         struct {
             uintptr_t a_arch64;
         };
+        struct {
+            uint8_t risc_v64;
+        };
     };
 } ArchitectureIdent;
 
@@ -1240,6 +1290,32 @@ typedef struct ProcessInfo {
      * On windows this technique is called [`WOW64`](https://docs.microsoft.com/en-us/windows/win32/winprog64/wow64-implementation-details).
      */
     struct ArchitectureIdent proc_arch;
+    /**
+     * ID of the parent process.
+     *
+     * # Remarks
+     *
+     * This is 0 if the parent is not known. Note that the parent process
+     * might have already exited and its pid might have been reused.
+     */
+    Pid parent_pid;
+    /**
+     * ID of the session (e.g. windows terminal session) this process belongs to, or 0 if not applicable.
+     */
+    uint32_t session_id;
+    /**
+     * Time the process was created at in nanoseconds since the unix epoch, or 0 if it is not known.
+     */
+    uint64_t create_time;
+    /**
+     * Time the process exited at in nanoseconds since the unix epoch.
+     *
+     * # Remarks
+     *
+     * This is 0 if the process is still running or if the time is not known.
+     * The exit code of the process is contained in [`ProcessState::Dead`].
+     */
+    uint64_t exit_time;
 } ProcessInfo;
 
 typedef struct Callback_c_void__ProcessInfo {
@@ -2103,6 +2179,77 @@ typedef struct PhysicalMemoryVtbl_ConnectorInstanceContainer_CBox_c_void_____CAr
     MemoryViewBase_CBox_c_void_____CArc_c_void (*phys_view)(struct ConnectorInstanceContainer_CBox_c_void_____CArc_c_void *cont);
 } PhysicalMemoryVtbl_ConnectorInstanceContainer_CBox_c_void_____CArc_c_void;
 
+/**
+ * Identifies a watch registered on a [`MemoryWatcher`].
+ */
+typedef uintptr_t WatchId;
+
+/**
+ * Describes the changed part of a watched range.
+ *
+ * `addr` and `len` span from the first to the last byte that changed.
+ */
+typedef struct WatchEvent {
+    WatchId id;
+    Address addr;
+    umem len;
+} WatchEvent;
+
+typedef struct Callback_c_void__WatchEvent {
+    void *context;
+    bool (*func)(void*, struct WatchEvent);
+} Callback_c_void__WatchEvent;
+
+typedef struct Callback_c_void__WatchEvent OpaqueCallback_WatchEvent;
+
+/**
+ * Callback invoked when a watched range changes.
+ */
+typedef OpaqueCallback_WatchEvent WatchCallback;
+
+typedef struct CTup3_Address__u32__u32 {
+    Address _0;
+    uint32_t _1;
+    uint32_t _2;
+} CTup3_Address__u32__u32;
+
+typedef struct Callback_c_void__CTup3_Address__u32__u32 {
+    void *context;
+    bool (*func)(void*, struct CTup3_Address__u32__u32);
+} Callback_c_void__CTup3_Address__u32__u32;
+
+typedef struct Callback_c_void__CTup3_Address__u32__u32 OpaqueCallback_CTup3_Address__u32__u32;
+
+/**
+ * Callback invoked with the address, the previous and the new value of a watched value.
+ */
+typedef OpaqueCallback_CTup3_Address__u32__u32 ValueWatchCallback_u32;
+
+typedef struct CTup3_Address__u64__u64 {
+    Address _0;
+    uint64_t _1;
+    uint64_t _2;
+} CTup3_Address__u64__u64;
+
+typedef struct Callback_c_void__CTup3_Address__u64__u64 {
+    void *context;
+    bool (*func)(void*, struct CTup3_Address__u64__u64);
+} Callback_c_void__CTup3_Address__u64__u64;
+
+typedef struct Callback_c_void__CTup3_Address__u64__u64 OpaqueCallback_CTup3_Address__u64__u64;
+
+/**
+ * Callback invoked with the address, the previous and the new value of a watched value.
+ */
+typedef OpaqueCallback_CTup3_Address__u64__u64 ValueWatchCallback_u64;
+
+/**
+ * Callback receiving the chunks of a streamed read.
+ *
+ * `context` is passed through unmodified. Returning `false` stops the stream.
+ */
+typedef bool (*ReadStreamCallback)(void *context, Address addr, struct CSliceRef_u8 data);
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -2325,6 +2472,103 @@ void connector_drop(ConnectorInstanceArcBox *conn);
  */
 void inventory_free(struct Inventory *inv);
 
+/**
+ * Streams a large memory range of the os through a fixed size buffer
+ *
+ * The callback is invoked for every chunk of up to `chunk_size` bytes.
+ * The data passed to the callback is only valid for the duration of the call.
+ *
+ * Fails if the os does not implement `MemoryView`.
+ *
+ * # Safety
+ *
+ * `context` has to be valid for the callback.
+ */
+int32_t os_read_stream(OsInstanceArcBox *os,
+                         Address addr,
+                         umem len,
+                         uintptr_t chunk_size,
+                         ReadStreamCallback callback,
+                         void *context);
+
+/**
+ * Streams a large memory range of a process through a fixed size buffer
+ *
+ * The callback is invoked for every chunk of up to `chunk_size` bytes.
+ * The data passed to the callback is only valid for the duration of the call.
+ *
+ * # Safety
+ *
+ * `context` has to be valid for the callback.
+ */
+int32_t process_read_stream(ProcessInstanceArcBox *process,
+                              Address addr,
+                              umem len,
+                              uintptr_t chunk_size,
+                              ReadStreamCallback callback,
+                              void *context);
+
+/**
+ * Create a new memory watcher that polls every `interval_ms` milliseconds
+ */
+struct MemoryWatcher *mem_watcher_new(uint64_t interval_ms);
+
+/**
+ * Watch the range `addr..addr + len`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch(struct MemoryWatcher *watcher,
+                          Address addr,
+                          uintptr_t len,
+                          WatchCallback callback);
+
+/**
+ * Watch a 32-bit value at `addr`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch_u32(struct MemoryWatcher *watcher,
+                              Address addr,
+                              ValueWatchCallback_u32 callback);
+
+/**
+ * Watch a 64-bit value at `addr`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch_u64(struct MemoryWatcher *watcher,
+                              Address addr,
+                              ValueWatchCallback_u64 callback);
+
+/**
+ * Remove a watch, returns `false` if the watch does not exist
+ */
+bool mem_watcher_unwatch(struct MemoryWatcher *watcher, WatchId id);
+
+/**
+ * Wait for the watcher interval and poll all watched ranges of a process
+ *
+ * Returns the number of changed ranges, or a negative error code.
+ * The number of changed ranges is clamped to `i32::MAX`.
+ */
+int32_t process_watcher_tick(struct MemoryWatcher *watcher, ProcessInstanceArcBox *process);
+
+/**
+ * Free a memory watcher
+ *
+ * # Safety
+ *
+ * `watcher` must point to a valid `MemoryWatcher` that was created using `mem_watcher_new`.
+ */
+void mem_watcher_free(struct MemoryWatcher *watcher);
+
 uint8_t arch_bits(const struct ArchitectureObj *arch);
 
 Endianess arch_endianess(const struct ArchitectureObj *arch);
@@ -210,6 +210,14 @@ using CpuStateRetTmp = void;
  */
 struct Inventory;
 
+/**
+ * Polls a set of memory ranges and reports changes through callbacks.
+ *
+ * The first poll after a range has been registered only records its contents.
+ * Returning `false` from a callback removes the watch.
+ */
+struct MemoryWatcher;
+
 template<typename CGlueCtx = void>
 using KeyboardRetTmp = void;
 
@@ -272,7 +280,7 @@ using Address = umem;
 /**
  * Describes the type of a page using a bitflag.
  */
-using PageType = uint8_t;
+using PageType = uint16_t;
 /**
  * The page explicitly has no flags.
  */
@@ -297,6 +305,38 @@ static const PageType PageType_READ_ONLY = 8;
  * The page is not executable.
  */
 static const PageType PageType_NOEXEC = 16;
+/**
+ * The page is accessible from user mode.
+ */
+static const PageType PageType_USER = 32;
+/**
+ * The page was accessed since the flag was last cleared by the OS.
+ */
+static const PageType PageType_ACCESSED = 64;
+/**
+ * The page was written to since the flag was last cleared by the OS.
+ */
+static const PageType PageType_DIRTY = 128;
+/**
+ * The page is mapped as write-combined memory, like framebuffers.
+ */
+static const PageType PageType_WRITE_COMBINED = 256;
+/**
+ * The page is uncacheable, like memory mapped device registers (MMIO).
+ */
+static const PageType PageType_UNCACHEABLE = 512;
+/**
+ * The page is encrypted by the hardware (e.g. AMD SEV or Intel TME).
+ */
+static const PageType PageType_ENCRYPTED = 1024;
+/**
+ * The page content is compressed by the OS.
+ */
+static const PageType PageType_COMPRESSED = 2048;
+/**
+ * The page is backed by the pagefile and currently not resident in memory.
+ */
+static const PageType PageType_PAGEFILE = 4096;
 
 /**
  * This type represents a wrapper over a [address](address/index.html)
@@ -1331,7 +1371,8 @@ struct ArchitectureIdent {
     enum class Tag {
         /**
          * Unknown architecture. Could be third-party implemented. memflow knows how to work on them,
-         * but is unable to instantiate them.
+         * but is only able to instantiate them after they were registered with
+         * [`custom::register_architecture`].
          */
         ArchitectureIdent_Unknown,
         /**
@@ -1339,15 +1380,21 @@ struct ArchitectureIdent {
          *
          * First argument - `bitness` controls whether it's 32, or 64 bit variant.
          * Second argument - `address_extensions` control whether address extensions are
-         * enabled (PAE on x32, or LA57 on x64). Warning: LA57 is currently unsupported.
+         * enabled (PAE on x32, or LA57 on x64).
          */
         ArchitectureIdent_X86,
         /**
          * Arm 64-bit architecture with specified page size
          *
-         * Valid page sizes are 4kb, 16kb, 64kb. Only 4kb is supported at the moment
+         * Valid page sizes are 4kb, 16kb, 64kb.
          */
         ArchitectureIdent_AArch64,
+        /**
+         * RISC-V 64-bit architecture with the specified virtual address size
+         *
+         * Valid sizes are 39 (Sv39) and 48 (Sv48).
+         */
+        ArchitectureIdent_RiscV64,
     };
 
     struct ArchitectureIdent_Unknown_Body {
@@ -1363,11 +1410,16 @@ struct ArchitectureIdent {
         uintptr_t _0;
     };
 
+    struct ArchitectureIdent_RiscV64_Body {
+        uint8_t _0;
+    };
+
     Tag tag;
     union {
         ArchitectureIdent_Unknown_Body unknown;
         ArchitectureIdent_X86_Body x86;
         ArchitectureIdent_AArch64_Body a_arch64;
+        ArchitectureIdent_RiscV64_Body risc_v64;
     };
 };
 
@@ -1425,6 +1477,32 @@ struct ProcessInfo {
      * On windows this technique is called [`WOW64`](https://docs.microsoft.com/en-us/windows/win32/winprog64/wow64-implementation-details).
      */
     ArchitectureIdent proc_arch;
+    /**
+     * ID of the parent process.
+     *
+     * # Remarks
+     *
+     * This is 0 if the parent is not known. Note that the parent process
+     * might have already exited and its pid might have been reused.
+     */
+    Pid parent_pid;
+    /**
+     * ID of the session (e.g. windows terminal session) this process belongs to, or 0 if not applicable.
+     */
+    uint32_t session_id;
+    /**
+     * Time the process was created at in nanoseconds since the unix epoch, or 0 if it is not known.
+     */
+    uint64_t create_time;
+    /**
+     * Time the process exited at in nanoseconds since the unix epoch.
+     *
+     * # Remarks
+     *
+     * This is 0 if the process is still running or if the time is not known.
+     * The exit code of the process is contained in [`ProcessState::Dead`].
+     */
+    uint64_t exit_time;
 };
 
 using ProcessInfoCallback = OpaqueCallback<ProcessInfo>;
@@ -2847,6 +2925,40 @@ using MemoryViewArcBox = MemoryViewBaseArcBox<void, void>;
 // Typedef for default contaienr and context type
 using MemoryView = MemoryViewArcBox;
 
+/**
+ * Identifies a watch registered on a [`MemoryWatcher`].
+ */
+using WatchId = uintptr_t;
+
+/**
+ * Describes the changed part of a watched range.
+ *
+ * `addr` and `len` span from the first to the last byte that changed.
+ */
+struct WatchEvent {
+    WatchId id;
+    Address addr;
+    umem len;
+};
+
+/**
+ * Callback invoked when a watched range changes.
+ */
+using WatchCallback = OpaqueCallback<WatchEvent>;
+
+/**
+ * Callback invoked with the address, the previous and the new value of a watched value.
+ */
+template<typename T>
+using ValueWatchCallback = OpaqueCallback<CTup3<Address, T, T>>;
+
+/**
+ * Callback receiving the chunks of a streamed read.
+ *
+ * `context` is passed through unmodified. Returning `false` stops the stream.
+ */
+using ReadStreamCallback = bool(*)(void *context, Address addr, CSliceRef<uint8_t> data);
+
 extern "C" {
 
 extern const ArchitectureObj *X86_32;
@@ -3067,6 +3179,103 @@ void connector_drop(ConnectorInstanceArcBox *conn);
  */
 void inventory_free(Inventory *inv);
 
+/**
+ * Streams a large memory range of the os through a fixed size buffer
+ *
+ * The callback is invoked for every chunk of up to `chunk_size` bytes.
+ * The data passed to the callback is only valid for the duration of the call.
+ *
+ * Fails if the os does not implement `MemoryView`.
+ *
+ * # Safety
+ *
+ * `context` has to be valid for the callback.
+ */
+int32_t os_read_stream(OsInstanceArcBox *os,
+                         Address addr,
+                         umem len,
+                         uintptr_t chunk_size,
+                         ReadStreamCallback callback,
+                         void *context);
+
+/**
+ * Streams a large memory range of a process through a fixed size buffer
+ *
+ * The callback is invoked for every chunk of up to `chunk_size` bytes.
+ * The data passed to the callback is only valid for the duration of the call.
+ *
+ * # Safety
+ *
+ * `context` has to be valid for the callback.
+ */
+int32_t process_read_stream(ProcessInstanceArcBox *process,
+                              Address addr,
+                              umem len,
+                              uintptr_t chunk_size,
+                              ReadStreamCallback callback,
+                              void *context);
+
+/**
+ * Create a new memory watcher that polls every `interval_ms` milliseconds
+ */
+MemoryWatcher *mem_watcher_new(uint64_t interval_ms);
+
+/**
+ * Watch the range `addr..addr + len`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch(MemoryWatcher *watcher,
+                          Address addr,
+                          uintptr_t len,
+                          WatchCallback callback);
+
+/**
+ * Watch a 32-bit value at `addr`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch_u32(MemoryWatcher *watcher,
+                              Address addr,
+                              ValueWatchCallback<uint32_t> callback);
+
+/**
+ * Watch a 64-bit value at `addr`
+ *
+ * # Safety
+ *
+ * The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+ */
+WatchId mem_watcher_watch_u64(MemoryWatcher *watcher,
+                              Address addr,
+                              ValueWatchCallback<uint64_t> callback);
+
+/**
+ * Remove a watch, returns `false` if the watch does not exist
+ */
+bool mem_watcher_unwatch(MemoryWatcher *watcher, WatchId id);
+
+/**
+ * Wait for the watcher interval and poll all watched ranges of a process
+ *
+ * Returns the number of changed ranges, or a negative error code.
+ * The number of changed ranges is clamped to `i32::MAX`.
+ */
+int32_t process_watcher_tick(MemoryWatcher *watcher, ProcessInstanceArcBox *process);
+
+/**
+ * Free a memory watcher
+ *
+ * # Safety
+ *
+ * `watcher` must point to a valid `MemoryWatcher` that was created using `mem_watcher_new`.
+ */
+void mem_watcher_free(MemoryWatcher *watcher);
+
 uint8_t arch_bits(const ArchitectureObj *arch);
 
 Endianess arch_endianess(const ArchitectureObj *arch);
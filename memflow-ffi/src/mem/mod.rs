@@ -2,3 +2,165 @@
 pub use memflow::mem::phys_mem::*;
 #[allow(unused)]
 pub use memflow::mem::virt_mem::*;
+
+use memflow::cglue::result::IntResult;
+use memflow::cglue::CSliceRef;
+use memflow::error::{Error, ErrorKind, ErrorOrigin};
+use memflow::mem::watch::{MemoryWatcher, ValueWatchCallback, WatchCallback, WatchId};
+use memflow::mem::MemoryView;
+use memflow::plugins::{OsInstanceArcBox, ProcessInstanceArcBox};
+use memflow::types::{umem, Address};
+
+use std::ffi::c_void;
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Callback receiving the chunks of a streamed read.
+///
+/// `context` is passed through unmodified. Returning `false` stops the stream.
+pub type ReadStreamCallback =
+    extern "C" fn(context: *mut c_void, addr: Address, data: CSliceRef<u8>) -> bool;
+
+fn read_stream_internal(
+    mem: &mut impl MemoryView,
+    addr: Address,
+    len: umem,
+    chunk_size: usize,
+    callback: ReadStreamCallback,
+    context: *mut c_void,
+) -> i32 {
+    mem.read_stream(addr, len, chunk_size, |addr, data| {
+        callback(context, addr, data.into())
+    })
+    .into_int_result()
+}
+
+/// Streams a large memory range of the os through a fixed size buffer
+///
+/// The callback is invoked for every chunk of up to `chunk_size` bytes.
+/// The data passed to the callback is only valid for the duration of the call.
+///
+/// Fails if the os does not implement `MemoryView`.
+///
+/// # Safety
+///
+/// `context` has to be valid for the callback.
+#[no_mangle]
+pub unsafe extern "C" fn os_read_stream(
+    os: &mut OsInstanceArcBox<'static>,
+    addr: Address,
+    len: umem,
+    chunk_size: usize,
+    callback: ReadStreamCallback,
+    context: *mut c_void,
+) -> i32 {
+    match os.as_mut_impl_memoryview() {
+        Some(mem) => read_stream_internal(mem, addr, len, chunk_size, callback, context),
+        None => Err::<(), _>(Error(
+            ErrorOrigin::Ffi,
+            ErrorKind::UnsupportedOptionalFeature,
+        ))
+        .into_int_result(),
+    }
+}
+
+/// Streams a large memory range of a process through a fixed size buffer
+///
+/// The callback is invoked for every chunk of up to `chunk_size` bytes.
+/// The data passed to the callback is only valid for the duration of the call.
+///
+/// # Safety
+///
+/// `context` has to be valid for the callback.
+#[no_mangle]
+pub unsafe extern "C" fn process_read_stream(
+    process: &mut ProcessInstanceArcBox<'static>,
+    addr: Address,
+    len: umem,
+    chunk_size: usize,
+    callback: ReadStreamCallback,
+    context: *mut c_void,
+) -> i32 {
+    read_stream_internal(process, addr, len, chunk_size, callback, context)
+}
+
+/// Create a new memory watcher that polls every `interval_ms` milliseconds
+#[no_mangle]
+pub extern "C" fn mem_watcher_new(interval_ms: u64) -> &'static mut MemoryWatcher<'static> {
+    crate::util::to_heap(MemoryWatcher::new().interval(Duration::from_millis(interval_ms)))
+}
+
+/// Watch the range `addr..addr + len`
+///
+/// # Safety
+///
+/// The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+#[no_mangle]
+pub unsafe extern "C" fn mem_watcher_watch(
+    watcher: &mut MemoryWatcher<'static>,
+    addr: Address,
+    len: usize,
+    callback: WatchCallback<'static>,
+) -> WatchId {
+    watcher.watch(addr, len, callback)
+}
+
+/// Watch a 32-bit value at `addr`
+///
+/// # Safety
+///
+/// The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+#[no_mangle]
+pub unsafe extern "C" fn mem_watcher_watch_u32(
+    watcher: &mut MemoryWatcher<'static>,
+    addr: Address,
+    callback: ValueWatchCallback<'static, u32>,
+) -> WatchId {
+    watcher.watch_value(addr, callback)
+}
+
+/// Watch a 64-bit value at `addr`
+///
+/// # Safety
+///
+/// The context of `callback` has to stay valid until the watch is removed or the watcher is freed.
+#[no_mangle]
+pub unsafe extern "C" fn mem_watcher_watch_u64(
+    watcher: &mut MemoryWatcher<'static>,
+    addr: Address,
+    callback: ValueWatchCallback<'static, u64>,
+) -> WatchId {
+    watcher.watch_value(addr, callback)
+}
+
+/// Remove a watch, returns `false` if the watch does not exist
+#[no_mangle]
+pub extern "C" fn mem_watcher_unwatch(watcher: &mut MemoryWatcher<'static>, id: WatchId) -> bool {
+    watcher.unwatch(id)
+}
+
+/// Wait for the watcher interval and poll all watched ranges of a process
+///
+/// Returns the number of changed ranges, or a negative error code.
+/// The number of changed ranges is clamped to `i32::MAX`.
+#[no_mangle]
+pub extern "C" fn process_watcher_tick(
+    watcher: &mut MemoryWatcher<'static>,
+    process: &mut ProcessInstanceArcBox<'static>,
+) -> i32 {
+    match watcher.tick(process) {
+        Ok(changes) => i32::try_from(changes).unwrap_or(i32::MAX),
+        Err(err) => Err::<(), _>(err).into_int_result(),
+    }
+}
+
+/// Free a memory watcher
+///
+/// # Safety
+///
+/// `watcher` must point to a valid `MemoryWatcher` that was created using `mem_watcher_new`.
+#[no_mangle]
+pub unsafe extern "C" fn mem_watcher_free(watcher: &'static mut MemoryWatcher<'static>) {
+    let _ = Box::from_raw(watcher);
+}
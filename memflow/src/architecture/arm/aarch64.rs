@@ -16,9 +16,13 @@ pub(super) static ARCH_SPEC: ArmArchitecture = ArmArchitecture {
         endianess: Endianess::LittleEndian,
         addr_size: 8,
         pte_size: 8,
+        pte_addr_shift: 0,
         present_bit: |a| a.bit_at(0),
         writeable_bit: |a, _| a.bit_at(10),
         nx_bit: |a, _| a.bit_at(54),
+        supervisor_bit: |a, _| !a.bit_at(6),
+        accessed_bit: |a| a.bit_at(10),
+        dirty_bit: |a| a.bit_at(51) && !a.bit_at(7),
         large_page_bit: |a| !a.bit_at(1),
     }
     .into_spec(),
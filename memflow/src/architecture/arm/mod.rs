@@ -1,4 +1,6 @@
 pub mod aarch64;
+pub mod aarch64_16k;
+pub mod aarch64_64k;
 
 use super::{Architecture, ArchitectureIdent, ArchitectureObj, Endianess};
 
@@ -7,13 +9,13 @@ use crate::mem::virt_translate::{
         translate_data::{TranslateDataVec, TranslationChunk},
         ArchMmuSpec, MmuTranslationBase,
     },
-    VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
+    PageTableDump, TranslationTrace, VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
 };
 
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::iter::SplitAtIndex;
 use crate::mem::PhysicalMemory;
-use crate::types::{size, umem, Address};
+use crate::types::{umem, Address};
 use cglue::tuple::*;
 
 pub struct ArmArchitecture {
@@ -45,11 +47,10 @@ impl Architecture for ArmArchitecture {
     }
 
     fn ident(&self) -> ArchitectureIdent {
-        ArchitectureIdent::AArch64(size::kb(4))
+        ArchitectureIdent::AArch64(self.page_size())
     }
 }
 
-// TODO: Add granularity
 #[derive(Clone, Copy)]
 pub struct ArmVirtualTranslate {
     arch: &'static ArmArchitecture,
@@ -58,20 +59,26 @@ pub struct ArmVirtualTranslate {
 
 impl ArmVirtualTranslate {
     pub fn new(arch: &'static ArmArchitecture, dtb1: Address, dtb2: Address) -> Self {
+        // the upper half of the first level is translated through TTBR1
+        let split_index = 1 << (arch.mmu.def.virtual_address_splits[0] - 1);
         Self {
             arch,
-            dtb: ArmPageTableBase(dtb1, dtb2),
+            dtb: ArmPageTableBase(dtb1, dtb2, split_index),
         }
     }
 }
 
+/// Translation table bases of the lower (TTBR0) and upper (TTBR1) half of the address space.
+///
+/// The last field is the first index of the top-level table that belongs to the upper half.
 #[derive(Clone, Copy, Debug)]
-pub struct ArmPageTableBase(Address, Address);
+pub struct ArmPageTableBase(Address, Address, usize);
 
 impl MmuTranslationBase for ArmPageTableBase {
     fn get_pt_by_virt_addr(&self, addr: Address) -> Address {
         //TODO: handle for Arm 32
-        if (addr.to_umem().to_be() & 1) == 1 {
+        // bit 55 selects the translation table base, regardless of the configured VA size
+        if addr.bit_at(55) {
             self.1
         } else {
             self.0
@@ -79,7 +86,7 @@ impl MmuTranslationBase for ArmPageTableBase {
     }
 
     fn get_pt_by_index(&self, idx: usize) -> (Address, usize) {
-        if idx < 256 {
+        if idx < self.2 {
             (self.0, idx)
         } else {
             (self.1, idx)
@@ -121,6 +128,18 @@ impl VirtualTranslate3 for ArmVirtualTranslate {
             .virt_to_phys_iter(mem, self.dtb, addrs, out, out_fail, tmp_buf)
     }
 
+    fn virt_to_phys_trace<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        addr: Address,
+    ) -> Result<TranslationTrace> {
+        self.arch.mmu.virt_to_phys_trace(mem, self.dtb, addr)
+    }
+
+    fn dump_page_tables<T: PhysicalMemory>(&self, mem: &mut T) -> Result<PageTableDump> {
+        self.arch.mmu.dump_page_tables(mem, self.dtb)
+    }
+
     fn translation_table_id(&self, address: Address) -> umem {
         self.dtb
             .get_pt_by_virt_addr(address)
@@ -139,6 +158,10 @@ impl VirtualTranslate3 for ArmVirtualTranslate {
 fn underlying_arch(arch: ArchitectureObj) -> Option<&'static ArmArchitecture> {
     if arch == aarch64::ARCH {
         Some(&aarch64::ARCH_SPEC)
+    } else if arch == aarch64_16k::ARCH {
+        Some(&aarch64_16k::ARCH_SPEC)
+    } else if arch == aarch64_64k::ARCH {
+        Some(&aarch64_64k::ARCH_SPEC)
     } else {
         None
     }
@@ -159,7 +182,10 @@ pub fn new_translator_nonsplit(
     arch: ArchitectureObj,
 ) -> Result<impl VirtualTranslate3> {
     // TODO: Handle 32 bit arm
-    new_translator(dtb, dtb + size::kb(2), arch)
+    let spec =
+        underlying_arch(arch).ok_or(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture))?;
+    // both halves share a single table, the upper half starts in the middle of it
+    new_translator(dtb, dtb + spec.mmu.pt_leaf_size(0) / 2, arch)
 }
 
 pub fn is_arm_arch(arch: ArchitectureObj) -> bool {
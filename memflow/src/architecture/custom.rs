@@ -0,0 +1,301 @@
+/*!
+Support for architectures that are implemented outside of memflow.
+
+Third-party crates can define their own architectures in two ways:
+
+- Architectures using a regular multi-level page table only need an [`ArchMmuDef`] describing
+  the page table layout, which is turned into an [`MmuArchitecture`]. Its translator reuses the
+  page walker of memflow.
+- Translation schemes that do not fit into a multi-level page table (e.g. hashed page tables)
+  implement the [`Architecture`] and [`VirtualTranslate3`] traits directly.
+
+In both cases the architecture identifies itself with [`ArchitectureIdent::Unknown`] and a unique
+id. Architecture identifiers are passed between plugins and the host instead of the architecture
+objects, registering the architecture with [`register_architecture`] allows memflow to turn
+the identifier back into the architecture object.
+
+# Examples
+
+```
+use memflow::architecture::custom::{register_architecture, MmuArchitecture};
+use memflow::architecture::{ArchitectureIdent, ArchitectureObj, Endianess};
+use memflow::mem::virt_translate::mmu::ArchMmuDef;
+
+// two level page table with 4 byte entries and 4kb pages
+static ARCH_SPEC: MmuArchitecture = MmuArchitecture::new(
+    0x1234,
+    32,
+    ArchMmuDef {
+        virtual_address_splits: &[10, 10, 12],
+        valid_final_page_steps: &[1, 2],
+        address_space_bits: 32,
+        endianess: Endianess::BigEndian,
+        addr_size: 4,
+        pte_size: 4,
+        pte_addr_shift: 0,
+        present_bit: |a| a.bit_at(0),
+        writeable_bit: |a, pb| pb || a.bit_at(1),
+        nx_bit: |_, _| false,
+        supervisor_bit: |a, pb| pb || !a.bit_at(2),
+        accessed_bit: |a| a.bit_at(5),
+        dirty_bit: |a| a.bit_at(6),
+        large_page_bit: |a| a.bit_at(7),
+    },
+);
+static ARCH: ArchitectureObj = &ARCH_SPEC;
+
+register_architecture(ARCH).unwrap();
+
+assert_eq!(ArchitectureIdent::Unknown(0x1234).into_obj(), ARCH);
+```
+*/
+
+use super::{Architecture, ArchitectureIdent, ArchitectureObj, Endianess};
+
+use crate::mem::virt_translate::{
+    mmu::{ArchMmuDef, ArchMmuSpec},
+    PageTableDump, TranslationTrace, VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
+};
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::iter::SplitAtIndex;
+use crate::mem::PhysicalMemory;
+use crate::types::{umem, Address};
+use cglue::tuple::*;
+
+use std::prelude::v1::*;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+/// An architecture using a multi-level page table described by an [`ArchMmuDef`].
+pub struct MmuArchitecture {
+    /// Identifier reported as `ArchitectureIdent::Unknown`
+    id: usize,
+    /// Defines how many bits does the native word size have
+    bits: u8,
+    /// Defines the underlying MMU used for address translation
+    mmu: ArchMmuSpec,
+}
+
+impl MmuArchitecture {
+    /// Creates a new architecture with the given unique `id`.
+    pub const fn new(id: usize, bits: u8, mmu: ArchMmuDef) -> Self {
+        Self {
+            id,
+            bits,
+            mmu: mmu.into_spec(),
+        }
+    }
+
+    /// Creates a translator for the page table at the physical address `dtb`.
+    pub fn new_translator(&'static self, dtb: Address) -> MmuVirtualTranslate {
+        MmuVirtualTranslate::new(self, dtb)
+    }
+}
+
+impl Architecture for MmuArchitecture {
+    fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    fn endianess(&self) -> Endianess {
+        self.mmu.def.endianess
+    }
+
+    fn page_size(&self) -> usize {
+        self.mmu.page_size_level(1) as usize
+    }
+
+    fn size_addr(&self) -> usize {
+        self.mmu.def.addr_size.into()
+    }
+
+    fn address_space_bits(&self) -> u8 {
+        self.mmu.def.address_space_bits
+    }
+
+    fn ident(&self) -> ArchitectureIdent {
+        ArchitectureIdent::Unknown(self.id)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MmuVirtualTranslate {
+    arch: &'static MmuArchitecture,
+    dtb: Address,
+}
+
+impl MmuVirtualTranslate {
+    pub fn new(arch: &'static MmuArchitecture, dtb: Address) -> Self {
+        Self { arch, dtb }
+    }
+}
+
+impl VirtualTranslate3 for MmuVirtualTranslate {
+    fn virt_to_phys_iter<
+        T: PhysicalMemory + ?Sized,
+        B: SplitAtIndex,
+        VI: Iterator<Item = CTup3<Address, Address, B>>,
+    >(
+        &self,
+        mem: &mut T,
+        addrs: VI,
+        out: &mut VtopOutputCallback<B>,
+        out_fail: &mut VtopFailureCallback<B>,
+        tmp_buf: &mut [std::mem::MaybeUninit<u8>],
+    ) {
+        self.arch
+            .mmu
+            .virt_to_phys_iter(mem, self.dtb, addrs, out, out_fail, tmp_buf)
+    }
+
+    fn virt_to_phys_trace<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        addr: Address,
+    ) -> Result<TranslationTrace> {
+        self.arch.mmu.virt_to_phys_trace(mem, self.dtb, addr)
+    }
+
+    fn dump_page_tables<T: PhysicalMemory>(&self, mem: &mut T) -> Result<PageTableDump> {
+        self.arch.mmu.dump_page_tables(mem, self.dtb)
+    }
+
+    fn translation_table_id(&self, _address: Address) -> umem {
+        self.dtb.to_umem().overflowing_shr(12).0
+    }
+
+    fn arch(&self) -> ArchitectureObj {
+        self.arch
+    }
+}
+
+struct RegisteredArchitecture {
+    arch: ArchitectureObj,
+    next: *mut RegisteredArchitecture,
+}
+
+// Registered entries are leaked and never modified after being inserted, so the list can be
+// walked without locking.
+static REGISTRY: AtomicPtr<RegisteredArchitecture> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers a third-party architecture.
+///
+/// Afterwards [`ArchitectureIdent::Unknown`] with the id of the architecture can be converted into
+/// the architecture object. Registered architectures stay registered for the lifetime of the
+/// program.
+///
+/// # Errors
+///
+/// Fails if the architecture is not identified by `ArchitectureIdent::Unknown`, or if a different
+/// architecture with the same id has been registered before.
+pub fn register_architecture(arch: ArchitectureObj) -> Result<()> {
+    let id = match arch.ident() {
+        ArchitectureIdent::Unknown(id) => id,
+        ident => {
+            return Err(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture)
+                .log_error(format!("{} is a builtin architecture", ident)))
+        }
+    };
+
+    let entry = Box::into_raw(Box::new(RegisteredArchitecture {
+        arch,
+        next: ptr::null_mut(),
+    }));
+
+    let mut head = REGISTRY.load(Ordering::Acquire);
+    loop {
+        if let Some(registered) = find_registered(head, id) {
+            // Safety: the entry was never published
+            drop(unsafe { Box::from_raw(entry) });
+            return if registered == arch {
+                Ok(())
+            } else {
+                Err(Error(ErrorOrigin::Mmu, ErrorKind::AlreadyExists)
+                    .log_error(format!("architecture id {:#x} is already in use", id)))
+            };
+        }
+
+        // Safety: the entry is only published by the exchange below
+        unsafe { (*entry).next = head };
+
+        match REGISTRY.compare_exchange_weak(head, entry, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return Ok(()),
+            Err(new_head) => head = new_head,
+        }
+    }
+}
+
+/// Returns the registered architecture with the given id.
+pub fn find_architecture(id: usize) -> Option<ArchitectureObj> {
+    find_registered(REGISTRY.load(Ordering::Acquire), id)
+}
+
+fn find_registered(mut entry: *mut RegisteredArchitecture, id: usize) -> Option<ArchitectureObj> {
+    // Safety: published entries are valid for the rest of the program
+    while let Some(registered) = unsafe { entry.as_ref() } {
+        if registered.arch.ident() == ArchitectureIdent::Unknown(id) {
+            return Some(registered.arch);
+        }
+        entry = registered.next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::dummy::DummyMemory;
+    use crate::mem::{MemoryView, VirtualTranslate3};
+    use crate::types::{mem, size};
+
+    static ARCH_SPEC: MmuArchitecture = MmuArchitecture::new(
+        0x6d66_0001,
+        32,
+        ArchMmuDef {
+            virtual_address_splits: &[10, 10, 12],
+            valid_final_page_steps: &[2],
+            address_space_bits: 32,
+            endianess: Endianess::LittleEndian,
+            addr_size: 4,
+            pte_size: 4,
+            pte_addr_shift: 0,
+            present_bit: |a| a.bit_at(0),
+            writeable_bit: |a, pb| pb || a.bit_at(1),
+            nx_bit: |_, _| false,
+            supervisor_bit: |a, pb| pb || !a.bit_at(2),
+            accessed_bit: |a| a.bit_at(5),
+            dirty_bit: |a| a.bit_at(6),
+            large_page_bit: |_| false,
+        },
+    );
+    static ARCH: ArchitectureObj = &ARCH_SPEC;
+
+    #[test]
+    fn register() {
+        register_architecture(ARCH).unwrap();
+        // registering the same architecture again is fine
+        register_architecture(ARCH).unwrap();
+
+        assert_eq!(find_architecture(0x6d66_0001), Some(ARCH));
+        assert_eq!(ArchitectureIdent::Unknown(0x6d66_0001).into_obj(), ARCH);
+        assert_eq!(find_architecture(0x6d66_0002), None);
+        assert!(register_architecture(x64::ARCH).is_err());
+    }
+
+    #[test]
+    fn translate() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut phys_view = mem.phys_view();
+        phys_view.write(Address::from(0x1004), &0x2007u32).unwrap();
+        phys_view.write(Address::from(0x2008), &0x9007u32).unwrap();
+
+        let translator = ARCH_SPEC.new_translator(Address::from(0x1000));
+        let paddr = translator
+            .virt_to_phys(&mut mem, Address::from(0x40_2123))
+            .unwrap();
+        assert_eq!(paddr.address(), Address::from(0x9123));
+        assert_eq!(paddr.page_size(), mem::kb(4));
+    }
+}
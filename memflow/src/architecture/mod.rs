@@ -11,9 +11,13 @@ trait, which is linked to a particular architecture.
 Each architecture also has a `ByteOrder` assigned to it.
 When reading/writing data from/to the target it is necessary
 that memflow know the proper byte order of the target system.
+
+Architectures that are not part of memflow can be added through the `custom` module.
 */
 
 pub mod arm;
+pub mod custom;
+pub mod riscv;
 pub mod x86;
 
 use crate::types::size;
@@ -26,7 +30,7 @@ use crate::types::size;
 /// See the [wikipedia article](https://en.wikipedia.org/wiki/Endianness) for more information on the subject.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 #[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
 pub enum Endianess {
     /// Little Endianess
@@ -146,18 +150,23 @@ impl std::cmp::PartialEq<ArchitectureObj> for ArchitectureObj {
 #[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
 pub enum ArchitectureIdent {
     /// Unknown architecture. Could be third-party implemented. memflow knows how to work on them,
-    /// but is unable to instantiate them.
+    /// but is only able to instantiate them after they were registered with
+    /// [`custom::register_architecture`].
     Unknown(usize),
     /// X86 with specified bitness and address extensions
     ///
     /// First argument - `bitness` controls whether it's 32, or 64 bit variant.
     /// Second argument - `address_extensions` control whether address extensions are
-    /// enabled (PAE on x32, or LA57 on x64). Warning: LA57 is currently unsupported.
+    /// enabled (PAE on x32, or LA57 on x64).
     X86(u8, bool),
     /// Arm 64-bit architecture with specified page size
     ///
-    /// Valid page sizes are 4kb, 16kb, 64kb. Only 4kb is supported at the moment
+    /// Valid page sizes are 4kb, 16kb, 64kb.
     AArch64(usize),
+    /// RISC-V 64-bit architecture with the specified virtual address size
+    ///
+    /// Valid sizes are 39 (Sv39) and 48 (Sv48).
+    RiscV64(u8),
 }
 
 impl std::fmt::Display for ArchitectureIdent {
@@ -169,6 +178,9 @@ impl std::fmt::Display for ArchitectureIdent {
             ArchitectureIdent::X86(64, true) => f.pad("x86_64 LA57"),
             ArchitectureIdent::X86(_, _) => f.pad("x86"),
             ArchitectureIdent::AArch64(_) => f.pad("AArch64"),
+            ArchitectureIdent::RiscV64(39) => f.pad("riscv64 Sv39"),
+            ArchitectureIdent::RiscV64(48) => f.pad("riscv64 Sv48"),
+            ArchitectureIdent::RiscV64(_) => f.pad("riscv64"),
             ArchitectureIdent::Unknown(id) => f.debug_tuple("Unknown").field(&id).finish(),
         }
     }
@@ -178,18 +190,34 @@ impl ArchitectureIdent {
     pub fn into_obj(self) -> ArchitectureObj {
         self.into()
     }
+
+    /// Returns the architecture object, or `None` if the architecture is unsupported.
+    ///
+    /// Unlike `into_obj` this will not panic for unregistered third-party architectures.
+    pub fn try_into_obj(self) -> Option<ArchitectureObj> {
+        const KB4: usize = size::kb(4);
+        const KB16: usize = size::kb(16);
+        const KB64: usize = size::kb(64);
+        match self {
+            ArchitectureIdent::X86(32, false) => Some(x86::x32::ARCH),
+            ArchitectureIdent::X86(32, true) => Some(x86::x32_pae::ARCH),
+            ArchitectureIdent::X86(64, false) => Some(x86::x64::ARCH),
+            ArchitectureIdent::X86(64, true) => Some(x86::x64_la57::ARCH),
+            ArchitectureIdent::AArch64(KB4) => Some(arm::aarch64::ARCH),
+            ArchitectureIdent::AArch64(KB16) => Some(arm::aarch64_16k::ARCH),
+            ArchitectureIdent::AArch64(KB64) => Some(arm::aarch64_64k::ARCH),
+            ArchitectureIdent::RiscV64(39) => Some(riscv::sv39::ARCH),
+            ArchitectureIdent::RiscV64(48) => Some(riscv::sv48::ARCH),
+            ArchitectureIdent::Unknown(id) => custom::find_architecture(id),
+            _ => None,
+        }
+    }
 }
 
 impl From<ArchitectureIdent> for ArchitectureObj {
     fn from(arch: ArchitectureIdent) -> ArchitectureObj {
-        const KB4: usize = size::kb(4);
-        match arch {
-            ArchitectureIdent::X86(32, false) => x86::x32::ARCH,
-            ArchitectureIdent::X86(32, true) => x86::x32_pae::ARCH,
-            ArchitectureIdent::X86(64, false) => x86::x64::ARCH,
-            ArchitectureIdent::AArch64(KB4) => arm::aarch64::ARCH,
-            _ => panic!("unsupported architecture! {:?}", arch),
-        }
+        arch.try_into_obj()
+            .unwrap_or_else(|| panic!("unsupported architecture! {:?}", arch))
     }
 }
 
@@ -201,7 +229,8 @@ impl serde::Serialize for ArchitectureObj {
     {
         use serde::ser::SerializeStruct;
 
-        let mut state = serializer.serialize_struct("ArchitectureObj", 5)?;
+        let mut state = serializer.serialize_struct("ArchitectureObj", 6)?;
+        state.serialize_field("ident", &self.ident())?;
         state.serialize_field("bits", &self.bits())?;
         state.serialize_field("endianess", &self.endianess())?;
         state.serialize_field("page_size", &self.page_size())?;
@@ -210,3 +239,23 @@ impl serde::Serialize for ArchitectureObj {
         state.end()
     }
 }
+
+/// Architectures are deserialized from their `ident` field, the remaining properties are
+/// derived from the architecture itself.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ArchitectureObj {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(::serde::Deserialize)]
+        struct Ident {
+            ident: ArchitectureIdent,
+        }
+
+        let Ident { ident } = Ident::deserialize(deserializer)?;
+        ident.try_into_obj().ok_or_else(|| {
+            serde::de::Error::custom(format_args!("unsupported architecture: {:?}", ident))
+        })
+    }
+}
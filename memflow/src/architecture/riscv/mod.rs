@@ -0,0 +1,190 @@
+pub mod sv39;
+pub mod sv48;
+
+use super::{Architecture, ArchitectureIdent, ArchitectureObj, Endianess};
+
+use crate::mem::virt_translate::{
+    mmu::{
+        translate_data::{TranslateDataVec, TranslationChunk},
+        ArchMmuSpec, MmuTranslationBase,
+    },
+    PageTableDump, TranslationTrace, VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
+};
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::iter::SplitAtIndex;
+use crate::mem::PhysicalMemory;
+use crate::types::{umem, Address};
+use cglue::tuple::*;
+
+/// Paging mode value of the `satp` register for Sv39.
+pub const SATP_MODE_SV39: umem = 8;
+/// Paging mode value of the `satp` register for Sv48.
+pub const SATP_MODE_SV48: umem = 9;
+
+pub struct RiscVArchitecture {
+    /// Defines how many bits does the native word size have
+    bits: u8,
+    /// Defines the underlying MMU used for address translation
+    mmu: ArchMmuSpec,
+}
+
+impl Architecture for RiscVArchitecture {
+    fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    fn endianess(&self) -> Endianess {
+        self.mmu.def.endianess
+    }
+
+    fn page_size(&self) -> usize {
+        self.mmu.page_size_level(1) as usize
+    }
+
+    fn size_addr(&self) -> usize {
+        self.mmu.def.addr_size.into()
+    }
+
+    fn address_space_bits(&self) -> u8 {
+        self.mmu.def.address_space_bits
+    }
+
+    fn ident(&self) -> ArchitectureIdent {
+        ArchitectureIdent::RiscV64(self.mmu.virt_addr_bit_ranges[0].1)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RiscVVirtualTranslate {
+    arch: &'static RiscVArchitecture,
+    dtb: RiscVPageTableBase,
+}
+
+impl RiscVVirtualTranslate {
+    /// Creates a translator for the root page table at the physical address `root`.
+    pub fn new(arch: &'static RiscVArchitecture, root: Address) -> Self {
+        Self {
+            arch,
+            dtb: RiscVPageTableBase::from_root(root),
+        }
+    }
+}
+
+/// Root page table of a RISC-V address space.
+///
+/// Page table entries hold the physical page number starting at bit 10. The root is stored in
+/// the same encoding, so every level of the page walk can be decoded in the same way.
+#[derive(Clone, Copy, Debug)]
+pub struct RiscVPageTableBase(Address);
+
+impl RiscVPageTableBase {
+    pub fn from_root(root: Address) -> Self {
+        Self(Address::from(root.to_umem() >> 2))
+    }
+
+    /// Returns the physical address of the root page table.
+    pub fn root(&self) -> Address {
+        Address::from(self.0.to_umem() << 2)
+    }
+}
+
+impl MmuTranslationBase for RiscVPageTableBase {
+    fn get_pt_by_virt_addr(&self, _: Address) -> Address {
+        self.0
+    }
+
+    fn get_pt_by_index(&self, idx: usize) -> (Address, usize) {
+        (self.0, idx)
+    }
+
+    fn pt_count(&self) -> usize {
+        1
+    }
+
+    fn virt_addr_filter<B>(
+        &self,
+        spec: &ArchMmuSpec,
+        addr: CTup3<Address, Address, B>,
+        work_group: (&mut TranslationChunk<Self>, &mut TranslateDataVec<B>),
+        out_fail: &mut VtopFailureCallback<B>,
+    ) where
+        B: SplitAtIndex,
+    {
+        spec.virt_addr_filter(addr, work_group, out_fail);
+    }
+}
+
+impl VirtualTranslate3 for RiscVVirtualTranslate {
+    fn virt_to_phys_iter<
+        T: PhysicalMemory + ?Sized,
+        B: SplitAtIndex,
+        VI: Iterator<Item = CTup3<Address, Address, B>>,
+    >(
+        &self,
+        mem: &mut T,
+        addrs: VI,
+        out: &mut VtopOutputCallback<B>,
+        out_fail: &mut VtopFailureCallback<B>,
+        tmp_buf: &mut [std::mem::MaybeUninit<u8>],
+    ) {
+        self.arch
+            .mmu
+            .virt_to_phys_iter(mem, self.dtb, addrs, out, out_fail, tmp_buf)
+    }
+
+    fn virt_to_phys_trace<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        addr: Address,
+    ) -> Result<TranslationTrace> {
+        self.arch.mmu.virt_to_phys_trace(mem, self.dtb, addr)
+    }
+
+    fn dump_page_tables<T: PhysicalMemory>(&self, mem: &mut T) -> Result<PageTableDump> {
+        self.arch.mmu.dump_page_tables(mem, self.dtb)
+    }
+
+    fn translation_table_id(&self, _address: Address) -> umem {
+        self.dtb.root().to_umem().overflowing_shr(12).0
+    }
+
+    fn arch(&self) -> ArchitectureObj {
+        self.arch
+    }
+}
+
+// This lint doesn't make any sense in our usecase, since we nevel leak ARCH_SPECs, and ARCH is
+// a static trait object with a consistent address.
+fn underlying_arch(arch: ArchitectureObj) -> Option<&'static RiscVArchitecture> {
+    if arch == sv39::ARCH {
+        Some(&sv39::ARCH_SPEC)
+    } else if arch == sv48::ARCH {
+        Some(&sv48::ARCH_SPEC)
+    } else {
+        None
+    }
+}
+
+pub fn new_translator(root: Address, arch: ArchitectureObj) -> Result<RiscVVirtualTranslate> {
+    let arch =
+        underlying_arch(arch).ok_or(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture))?;
+    Ok(RiscVVirtualTranslate::new(arch, root))
+}
+
+/// Creates a translator from the value of the `satp` register.
+///
+/// The paging mode is taken from the register, the ASID is ignored.
+pub fn new_translator_satp(satp: u64) -> Result<RiscVVirtualTranslate> {
+    let root = Address::from((satp & 0xfff_ffff_ffff) << 12);
+    match (satp >> 60) as umem {
+        SATP_MODE_SV39 => Ok(sv39::new_translator(root)),
+        SATP_MODE_SV48 => Ok(sv48::new_translator(root)),
+        _ => Err(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture)
+            .log_info("satp does not contain a supported paging mode")),
+    }
+}
+
+pub fn is_riscv_arch(arch: ArchitectureObj) -> bool {
+    underlying_arch(arch).is_some()
+}
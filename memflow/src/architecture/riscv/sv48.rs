@@ -0,0 +1,36 @@
+use super::{
+    super::{ArchitectureObj, Endianess},
+    RiscVArchitecture, RiscVVirtualTranslate,
+};
+
+use crate::mem::virt_translate::mmu::ArchMmuDef;
+
+use crate::types::Address;
+
+pub(super) static ARCH_SPEC: RiscVArchitecture = RiscVArchitecture {
+    bits: 64,
+    mmu: ArchMmuDef {
+        virtual_address_splits: &[9, 9, 9, 9, 12],
+        valid_final_page_steps: &[1, 2, 3, 4],
+        address_space_bits: 56,
+        endianess: Endianess::LittleEndian,
+        addr_size: 8,
+        pte_size: 8,
+        pte_addr_shift: 2,
+        present_bit: |a| a.bit_at(0),
+        writeable_bit: |a, _| a.bit_at(2),
+        nx_bit: |a, _| !a.bit_at(3),
+        supervisor_bit: |a, _| !a.bit_at(4),
+        accessed_bit: |a| a.bit_at(6),
+        dirty_bit: |a| a.bit_at(7),
+        // any entry with read, write or execute permissions is a leaf
+        large_page_bit: |a| a.to_umem() & 0b1110 != 0,
+    }
+    .into_spec(),
+};
+
+pub static ARCH: ArchitectureObj = &ARCH_SPEC;
+
+pub fn new_translator(root: Address) -> RiscVVirtualTranslate {
+    RiscVVirtualTranslate::new(&ARCH_SPEC, root)
+}
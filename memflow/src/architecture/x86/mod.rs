@@ -1,17 +1,20 @@
 pub mod x32;
 pub mod x32_pae;
 pub mod x64;
+pub mod x64_ept;
+pub mod x64_la57;
 
 use super::{Architecture, ArchitectureIdent, ArchitectureObj, Endianess};
 
 use crate::mem::virt_translate::{
-    mmu::ArchMmuSpec, VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
+    mmu::ArchMmuSpec, PageTableDump, TranslationTrace, VirtualTranslate3, VtopFailureCallback,
+    VtopOutputCallback,
 };
 
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::iter::SplitAtIndex;
-use crate::mem::PhysicalMemory;
-use crate::types::{umem, Address};
+use crate::mem::{MemoryView, PhysicalMemory};
+use crate::types::{size, umem, Address};
 use cglue::tuple::*;
 
 use std::ptr;
@@ -47,7 +50,8 @@ impl Architecture for X86Architecture {
     fn ident(&self) -> ArchitectureIdent {
         ArchitectureIdent::X86(
             self.bits,
-            ptr::eq(self as *const _, &x32_pae::ARCH_SPEC as *const _),
+            ptr::eq(self as *const _, &x32_pae::ARCH_SPEC as *const _)
+                || ptr::eq(self as *const _, &x64_la57::ARCH_SPEC as *const _),
         )
     }
 }
@@ -82,6 +86,18 @@ impl VirtualTranslate3 for X86VirtualTranslate {
             .virt_to_phys_iter(mem, self.dtb, addrs, out, out_fail, tmp_buf)
     }
 
+    fn virt_to_phys_trace<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        addr: Address,
+    ) -> Result<TranslationTrace> {
+        self.arch.mmu.virt_to_phys_trace(mem, self.dtb, addr)
+    }
+
+    fn dump_page_tables<T: PhysicalMemory>(&self, mem: &mut T) -> Result<PageTableDump> {
+        self.arch.mmu.dump_page_tables(mem, self.dtb)
+    }
+
     fn translation_table_id(&self, _address: Address) -> umem {
         self.dtb.to_umem().overflowing_shr(12).0
     }
@@ -100,6 +116,10 @@ fn underlying_arch(arch: ArchitectureObj) -> Option<&'static X86Architecture> {
         Some(&x32::ARCH_SPEC)
     } else if arch == x32_pae::ARCH {
         Some(&x32_pae::ARCH_SPEC)
+    } else if arch == x64_la57::ARCH {
+        Some(&x64_la57::ARCH_SPEC)
+    } else if arch == x64_ept::ARCH {
+        Some(&x64_ept::ARCH_SPEC)
     } else {
         None
     }
@@ -114,3 +134,103 @@ pub fn new_translator(dtb: Address, arch: ArchitectureObj) -> Result<X86VirtualT
 pub fn is_x86_arch(arch: ArchitectureObj) -> bool {
     underlying_arch(arch).is_some()
 }
+
+/// Bit in the CR4 register that enables physical address extensions.
+pub const CR4_PAE: umem = 1 << 5;
+
+/// Bit in the CR4 register that enables 5-level paging.
+pub const CR4_LA57: umem = 1 << 12;
+
+/// Returns the x86 (32-bit) architecture matching the paging mode set in `cr4`.
+pub fn x86_arch_from_cr4(cr4: umem) -> ArchitectureObj {
+    if cr4 & CR4_PAE != 0 {
+        x32_pae::ARCH
+    } else {
+        x32::ARCH
+    }
+}
+
+/// Returns the x64 architecture matching the paging mode set in `cr4`.
+pub fn x64_arch_from_cr4(cr4: umem) -> ArchitectureObj {
+    if cr4 & CR4_LA57 != 0 {
+        x64_la57::ARCH
+    } else {
+        x64::ARCH
+    }
+}
+
+fn probe_translates<T: PhysicalMemory>(
+    translator: X86VirtualTranslate,
+    mem: &mut T,
+    probe: Address,
+) -> bool {
+    translator
+        .virt_to_phys_trace(mem, probe)
+        .map(|trace| trace.phys_addr.is_some())
+        .unwrap_or_default()
+}
+
+/// Checks if the 4 entries at `dtb` are a valid PAE page directory pointer table.
+///
+/// Besides the present bit, the entries may only contain the caching bits and the address
+/// of the page directory. A regular 32-bit page directory will almost always have bits set in
+/// the upper half of the 8-byte entries, since it consists of 4-byte entries.
+fn is_pae_pdpt<T: PhysicalMemory>(mem: &mut T, dtb: Address) -> bool {
+    const ALLOWED_BITS: u64 = 0xf_ffff_f000 | 0xe19;
+
+    let mut entries = [0u64; 4];
+    mem.phys_view().read_into(dtb, &mut entries).is_ok()
+        && entries.iter().all(|e| e & 1 != 0 && e & !ALLOWED_BITS == 0)
+}
+
+/// Guesses whether `dtb` is the root of a regular 32-bit or a PAE page table hierarchy.
+///
+/// This is meant for cases where the value of CR4 is not available, e.g. when the DTB was
+/// recovered from the start block of the target. `probe` has to be a virtual address that is
+/// known to be mapped in the address space, like the kernel entry point.
+///
+/// A DTB that is not page aligned can only point to a PAE page directory pointer table. Otherwise
+/// the address is translated with both paging modes. If both succeed, the layout of the root
+/// table decides.
+pub fn detect_x86_arch<T: PhysicalMemory>(
+    mem: &mut T,
+    dtb: Address,
+    probe: Address,
+) -> Result<ArchitectureObj> {
+    let pae = probe_translates(x32_pae::new_translator(dtb), mem, probe);
+    let plain = dtb.as_page_aligned(size::kb(4)) == dtb
+        && probe_translates(x32::new_translator(dtb), mem, probe);
+
+    match (pae, plain) {
+        (true, false) => Ok(x32_pae::ARCH),
+        (false, true) => Ok(x32::ARCH),
+        (true, true) if is_pae_pdpt(mem, dtb) => Ok(x32_pae::ARCH),
+        (true, true) => Ok(x32::ARCH),
+        (false, false) => Err(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture)
+            .log_info("unable to detect the paging mode, the probe address is not mapped")),
+    }
+}
+
+/// Guesses whether `dtb` is the root of a 4-level or a 5-level page table hierarchy.
+///
+/// Like [`detect_x86_arch`], this is meant for cases where CR4 is not available. `probe` has to
+/// be a virtual address that is known to be mapped in the address space.
+///
+/// The address is translated with both paging modes. 5-level paging is only assumed if the
+/// translation succeeds with it but fails with 4-level paging, since an unrelated page table
+/// can occasionally be walked successfully with one level too many.
+pub fn detect_x64_arch<T: PhysicalMemory>(
+    mem: &mut T,
+    dtb: Address,
+    probe: Address,
+) -> Result<ArchitectureObj> {
+    let la57 = probe_translates(x64_la57::new_translator(dtb), mem, probe);
+    let four_level = probe_translates(x64::new_translator(dtb), mem, probe);
+
+    match (four_level, la57) {
+        (true, _) => Ok(x64::ARCH),
+        (false, true) => Ok(x64_la57::ARCH),
+        (false, false) => Err(Error(ErrorOrigin::Mmu, ErrorKind::InvalidArchitecture)
+            .log_info("unable to detect the paging mode, the probe address is not mapped")),
+    }
+}
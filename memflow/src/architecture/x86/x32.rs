@@ -16,9 +16,13 @@ pub(super) static ARCH_SPEC: X86Architecture = X86Architecture {
         endianess: Endianess::LittleEndian,
         addr_size: 4,
         pte_size: 4,
+        pte_addr_shift: 0,
         present_bit: |a| a.bit_at(0),
         writeable_bit: |a, pb| pb || a.bit_at(1),
         nx_bit: |_, _| false,
+        supervisor_bit: |a, pb| pb || !a.bit_at(2),
+        accessed_bit: |a| a.bit_at(5),
+        dirty_bit: |a| a.bit_at(6),
         large_page_bit: |a| a.bit_at(7),
     }
     .into_spec(),
@@ -16,9 +16,13 @@ pub(super) static ARCH_SPEC: X86Architecture = X86Architecture {
         endianess: Endianess::LittleEndian,
         addr_size: 8,
         pte_size: 8,
+        pte_addr_shift: 0,
         present_bit: |a| a.bit_at(0),
         writeable_bit: |a, pb| pb || a.bit_at(1),
         nx_bit: |a, pb| pb || a.bit_at(63),
+        supervisor_bit: |a, pb| pb || !a.bit_at(2),
+        accessed_bit: |a| a.bit_at(5),
+        dirty_bit: |a| a.bit_at(6),
         large_page_bit: |a| a.bit_at(7),
     }
     .into_spec(),
@@ -184,6 +188,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn x64_page_attributes() {
+        let mmu = get_mmu_spec();
+        let virt_address = Address::from(0x1234);
+
+        // present, writeable, user, accessed and dirty
+        let pte_address = Address::from(mem::gb(57) | 0x67);
+        let page_type = mmu
+            .get_phys_page(pte_address, virt_address, 4, FlagsType::NONE)
+            .page_type();
+        assert_eq!(
+            page_type,
+            PageType::WRITEABLE | PageType::USER | PageType::ACCESSED | PageType::DIRTY
+        );
+        assert!(page_type.is_executable());
+
+        // a supervisor entry higher up in the hierarchy hides the page from user mode
+        let page_type = mmu
+            .get_phys_page(pte_address, virt_address, 4, FlagsType::SUPERVISOR)
+            .page_type();
+        assert!(!page_type.contains(PageType::USER));
+
+        let pte_address = Address::from(mem::gb(57) | 0x21 | (1 << 63));
+        let page_type = mmu
+            .get_phys_page(pte_address, virt_address, 4, FlagsType::NONE)
+            .page_type();
+        assert_eq!(
+            page_type,
+            PageType::READ_ONLY | PageType::NOEXEC | PageType::ACCESSED
+        );
+        assert!(!page_type.is_executable());
+        assert!(!page_type.is_writeable());
+    }
+
     #[test]
     fn x64_check_entry() {
         let mmu = get_mmu_spec();
@@ -0,0 +1,45 @@
+//! Extended page tables (EPT), the second translation stage of Intel VT-x.
+//!
+//! AMD's nested page tables (NPT) use the regular x64 page table format, so
+//! [`x64::new_translator`](super::x64::new_translator) can walk them directly.
+
+use super::{
+    super::{ArchitectureObj, Endianess},
+    X86Architecture, X86VirtualTranslate,
+};
+
+use crate::mem::virt_translate::mmu::ArchMmuDef;
+
+use crate::types::Address;
+
+pub(super) static ARCH_SPEC: X86Architecture = X86Architecture {
+    bits: 64,
+    mmu: ArchMmuDef {
+        virtual_address_splits: &[9, 9, 9, 9, 12],
+        valid_final_page_steps: &[2, 3, 4],
+        address_space_bits: 52,
+        endianess: Endianess::LittleEndian,
+        addr_size: 8,
+        pte_size: 8,
+        pte_addr_shift: 0,
+        // an entry is present if it grants any of the read, write or execute permissions
+        present_bit: |a| a.to_umem() & 0b111 != 0,
+        writeable_bit: |a, _| a.bit_at(1),
+        nx_bit: |a, pb| pb || !a.bit_at(2),
+        supervisor_bit: |_, _| false,
+        accessed_bit: |a| a.bit_at(8),
+        dirty_bit: |a| a.bit_at(9),
+        large_page_bit: |a| a.bit_at(7),
+    }
+    .into_spec(),
+};
+
+pub static ARCH: ArchitectureObj = &ARCH_SPEC;
+
+/// Creates a translator from guest physical to host physical addresses.
+///
+/// `eptp` can be the raw EPT pointer of the VMCS, the memory type and page-walk length
+/// stored in the lower bits are ignored.
+pub fn new_translator(eptp: Address) -> X86VirtualTranslate {
+    X86VirtualTranslate::new(&ARCH_SPEC, eptp)
+}
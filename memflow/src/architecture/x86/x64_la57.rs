@@ -0,0 +1,85 @@
+use super::{
+    super::{ArchitectureObj, Endianess},
+    X86Architecture, X86VirtualTranslate,
+};
+
+use crate::mem::virt_translate::mmu::ArchMmuDef;
+
+use crate::types::Address;
+
+pub(super) static ARCH_SPEC: X86Architecture = X86Architecture {
+    bits: 64,
+    mmu: ArchMmuDef {
+        virtual_address_splits: &[9, 9, 9, 9, 9, 12],
+        valid_final_page_steps: &[3, 4, 5],
+        address_space_bits: 52,
+        endianess: Endianess::LittleEndian,
+        addr_size: 8,
+        pte_size: 8,
+        pte_addr_shift: 0,
+        present_bit: |a| a.bit_at(0),
+        writeable_bit: |a, pb| pb || a.bit_at(1),
+        nx_bit: |a, pb| pb || a.bit_at(63),
+        supervisor_bit: |a, pb| pb || !a.bit_at(2),
+        accessed_bit: |a| a.bit_at(5),
+        dirty_bit: |a| a.bit_at(6),
+        large_page_bit: |a| a.bit_at(7),
+    }
+    .into_spec(),
+};
+
+pub static ARCH: ArchitectureObj = &ARCH_SPEC;
+
+pub fn new_translator(dtb: Address) -> X86VirtualTranslate {
+    X86VirtualTranslate::new(&ARCH_SPEC, dtb)
+}
+
+//x64 tests MMU rigorously, here we will only test the additional level
+#[cfg(test)]
+mod tests {
+    use crate::architecture::ArchitectureIdent;
+    use crate::mem::virt_translate::mmu::ArchMmuSpec;
+    use crate::types::{mem, Address};
+
+    fn get_mmu_spec() -> &'static ArchMmuSpec {
+        &super::ARCH_SPEC.mmu
+    }
+
+    #[test]
+    fn x64_la57_ident() {
+        assert_eq!(super::ARCH.ident(), ArchitectureIdent::X86(64, true));
+        assert_eq!(ArchitectureIdent::X86(64, true).into_obj(), super::ARCH);
+    }
+
+    #[test]
+    fn x64_la57_page_size_level() {
+        let mmu = get_mmu_spec();
+        assert_eq!(mmu.page_size_level(1), mem::kb(4));
+        assert_eq!(mmu.page_size_level(2), mem::mb(2));
+        assert_eq!(mmu.page_size_level(3), mem::gb(1));
+    }
+
+    #[test]
+    fn x64_la57_canonical() {
+        let mmu = get_mmu_spec();
+        assert!(mmu.is_canonical(Address::from(0x00ff_ffff_ffff_f000u64)));
+        assert!(mmu.is_canonical(Address::from(0xff00_0000_0000_0000u64)));
+        assert!(!mmu.is_canonical(Address::from(0x0100_0000_0000_0000u64)));
+        assert!(!mmu.is_canonical(Address::from(0xfe00_0000_0000_0000u64)));
+    }
+
+    #[test]
+    fn x64_la57_vtop_step() {
+        let mmu = get_mmu_spec();
+        let virt_address = Address::from((0x1a5u64 << 48) | (0x10u64 << 39));
+        let pte_address = Address::from(mem::kb(4 * 19));
+        assert_eq!(
+            mmu.vtop_step(pte_address, virt_address, 0),
+            pte_address + 0x1a5usize * 8
+        );
+        assert_eq!(
+            mmu.vtop_step(pte_address, virt_address, 1),
+            pte_address + 0x10usize * 8
+        );
+    }
+}
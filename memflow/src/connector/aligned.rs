@@ -0,0 +1,546 @@
+/*!
+Helper connector for backends with alignment or transfer size constraints.
+
+Hardware backends (e.g. DMA engines) are often only able to access physical memory
+in aligned blocks and limit the number of bytes that can be moved in a single transfer.
+The [`AlignedPhysicalMemory`] wrapper takes care of splitting, padding and bouncing
+requests so the backend only ever receives requests it is able to handle.
+*/
+
+use std::prelude::v1::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::iter::SplitAtIndex;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{umem, Address, PhysicalAddress};
+
+/// Wraps a [`PhysicalMemory`] backend that requires aligned and size limited transfers.
+///
+/// Every request that is forwarded to the underlying backend starts at a multiple of `alignment`,
+/// its length is a multiple of `alignment` and it is at most `max_transfer` bytes long.
+///
+/// Requests that already satisfy the alignment are only split into `max_transfer` sized chunks
+/// and are passed through without copying. The unaligned head and tail of a request are
+/// transferred through an internal bounce buffer. Unaligned writes are performed as a
+/// read-modify-write of the surrounding aligned block, partial writes into the same block
+/// within a single batch are merged before the block is written back.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::AlignedPhysicalMemory;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// // the backend transfers at most 4kb at once and only in 8 byte blocks
+/// let mut mem = AlignedPhysicalMemory::new(mem, 8, size::kb(4)).unwrap();
+///
+/// mem.phys_write(0x1003.into(), &0x1122_3344u32).unwrap();
+///
+/// let mut value = 0u32;
+/// mem.phys_read_into(0x1003.into(), &mut value).unwrap();
+/// assert_eq!(value, 0x1122_3344);
+/// ```
+#[derive(Clone)]
+pub struct AlignedPhysicalMemory<T> {
+    mem: T,
+    alignment: umem,
+    max_transfer: umem,
+    bounce: Vec<u8>,
+}
+
+/// A chunk of a request that does not cover its whole aligned block.
+///
+/// Bounced blocks are always exactly `alignment` bytes long.
+struct BouncedBlock<D> {
+    block: PhysicalAddress,
+    offset: usize,
+    meta_addr: Address,
+    data: Option<D>,
+}
+
+impl<T: PhysicalMemory> AlignedPhysicalMemory<T> {
+    /// Constructs a new `AlignedPhysicalMemory` around the given backend.
+    ///
+    /// `alignment` has to be a power of two and `max_transfer` has to be a non-zero
+    /// multiple of `alignment`, otherwise an error is returned.
+    pub fn new(mem: T, alignment: usize, max_transfer: usize) -> Result<Self> {
+        if !alignment.is_power_of_two() || max_transfer < alignment || max_transfer % alignment != 0
+        {
+            return Err(Error(ErrorOrigin::Connector, ErrorKind::InvalidArgument)
+                .log_error("invalid alignment or transfer size"));
+        }
+
+        Ok(Self {
+            mem,
+            alignment: alignment as umem,
+            max_transfer: max_transfer as umem,
+            bounce: vec![],
+        })
+    }
+
+    /// Consumes this wrapper and returns the underlying backend.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+
+    /// Splits a single request into aligned blocks.
+    ///
+    /// Blocks that are entirely covered by the request are pushed into `direct`,
+    /// partially covered blocks are pushed into `bounced`. The unaligned head and tail
+    /// are bounced one aligned block at a time, so the bounced blocks of all requests
+    /// line up with each other.
+    fn split_request<D: SplitAtIndex>(
+        &self,
+        CTup3(addr, meta_addr, data): CTup3<PhysicalAddress, Address, D>,
+        direct: &mut Vec<CTup3<PhysicalAddress, Address, D>>,
+        bounced: &mut Vec<BouncedBlock<D>>,
+    ) {
+        let len = data.length();
+
+        if len == 0 {
+            direct.push(CTup3(addr, meta_addr, data));
+            return;
+        }
+
+        let mask = !(self.alignment - 1);
+        let start = addr.address().to_umem();
+        let end = start + len;
+
+        let mut block = start & mask;
+        let mut rest = Some((meta_addr, data));
+
+        while let Some(chunk) = rest.take() {
+            let offset = start.saturating_sub(block);
+            let block_end = if offset != 0 || block + self.alignment > end {
+                block + self.alignment
+            } else {
+                core::cmp::min(block + self.max_transfer, end & mask)
+            };
+            let (left, right) = chunk.split_at(block_end - block - offset);
+            rest = right;
+
+            if let Some((meta_addr, data)) = left {
+                let mut block_addr = addr;
+                block_addr.address = block.into();
+                let block_len = block_end - block;
+
+                if offset == 0 && data.length() == block_len {
+                    direct.push(CTup3(block_addr, meta_addr, data));
+                } else {
+                    bounced.push(BouncedBlock {
+                        block: block_addr,
+                        offset: offset as usize,
+                        meta_addr,
+                        data: Some(data),
+                    });
+                }
+            }
+
+            block = block_end;
+        }
+    }
+
+    /// Writes out all aligned requests, followed by a read-modify-write of the bounced blocks.
+    ///
+    /// Every aligned block is read back and written once, with all partial writes
+    /// into it applied in request order.
+    #[allow(clippy::needless_option_as_deref)]
+    fn flush_writes<'a, 'b>(
+        &mut self,
+        direct: &mut Vec<PhysicalWriteData<'a>>,
+        bounced: &mut Vec<BouncedBlock<CSliceRef<'a, u8>>>,
+        mut out: Option<&mut WriteCallback<'b, 'a>>,
+        mut out_fail: Option<&mut WriteCallback<'b, 'a>>,
+    ) -> Result<()> {
+        if !direct.is_empty() {
+            let mem = &mut self.mem;
+            MemOps::with_raw(
+                direct.drain(..),
+                out.as_deref_mut(),
+                out_fail.as_deref_mut(),
+                |data| mem.phys_write_raw_iter(data),
+            )?;
+        }
+
+        if bounced.is_empty() {
+            return Ok(());
+        }
+
+        let mut blocks = vec![];
+        let mut block_indices = BTreeMap::new();
+        let indices = bounced
+            .iter()
+            .map(|b| {
+                *block_indices.entry(b.block.to_umem()).or_insert_with(|| {
+                    blocks.push(b.block);
+                    blocks.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let alignment = self.alignment as usize;
+        self.bounce.clear();
+        self.bounce.resize(blocks.len() * alignment, 0);
+
+        let mem = &mut self.mem;
+
+        // read back the surrounding blocks so bytes outside of the requests are preserved
+        let mut valid = vec![false; blocks.len()];
+        {
+            let reqs = blocks
+                .iter()
+                .zip(self.bounce.chunks_mut(alignment))
+                .enumerate()
+                .map(|(i, (block, buf))| {
+                    CTup3(*block, Address::from(i as umem), CSliceMut::from(buf))
+                })
+                .collect::<Vec<_>>();
+
+            let callback = &mut |CTup2(idx, _): ReadData| {
+                valid[idx.to_umem() as usize] = true;
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(reqs.into_iter(), Some(&mut callback), None, |data| {
+                mem.phys_read_raw_iter(data)
+            })?;
+        }
+
+        for (b, &i) in bounced.iter().zip(&indices) {
+            if let (true, Some(data)) = (valid[i], &b.data) {
+                let buf = &mut self.bounce[(i * alignment)..((i + 1) * alignment)];
+                buf[b.offset..(b.offset + data.len())].copy_from_slice(data);
+            }
+        }
+
+        let mut written = vec![false; blocks.len()];
+        {
+            let reqs = blocks
+                .iter()
+                .zip(self.bounce.chunks(alignment))
+                .enumerate()
+                .filter(|(i, _)| valid[*i])
+                .map(|(i, (block, buf))| {
+                    CTup3(*block, Address::from(i as umem), CSliceRef::from(buf))
+                })
+                .collect::<Vec<_>>();
+
+            let callback = &mut |CTup2(idx, _): WriteData| {
+                written[idx.to_umem() as usize] = true;
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(reqs.into_iter(), Some(&mut callback), None, |data| {
+                mem.phys_write_raw_iter(data)
+            })?;
+        }
+
+        for (b, i) in bounced.drain(..).zip(indices) {
+            if let Some(data) = b.data {
+                let cb = if written[i] {
+                    out.as_deref_mut()
+                } else {
+                    out_fail.as_deref_mut()
+                };
+                opt_call(cb, CTup2(b.meta_addr, data));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for AlignedPhysicalMemory<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let mut direct = vec![];
+        let mut bounced = vec![];
+        inp.for_each(|req| self.split_request(req, &mut direct, &mut bounced));
+
+        if !direct.is_empty() {
+            MemOps::with_raw(
+                direct.into_iter(),
+                out.as_deref_mut(),
+                out_fail.as_deref_mut(),
+                |data| self.mem.phys_read_raw_iter(data),
+            )?;
+        }
+
+        if bounced.is_empty() {
+            return Ok(());
+        }
+
+        let alignment = self.alignment as usize;
+        self.bounce.clear();
+        self.bounce.resize(bounced.len() * alignment, 0);
+
+        let mem = &mut self.mem;
+        let reqs = bounced
+            .iter()
+            .zip(self.bounce.chunks_mut(alignment))
+            .enumerate()
+            .map(|(i, (b, buf))| CTup3(b.block, Address::from(i as umem), CSliceMut::from(buf)))
+            .collect::<Vec<_>>();
+
+        {
+            let callback = &mut |CTup2(idx, buf): ReadData| {
+                let b = &mut bounced[idx.to_umem() as usize];
+                if let Some(mut data) = b.data.take() {
+                    let len = data.len();
+                    data.copy_from_slice(&buf[b.offset..(b.offset + len)]);
+                    opt_call(out.as_deref_mut(), CTup2(b.meta_addr, data));
+                }
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(reqs.into_iter(), Some(&mut callback), None, |data| {
+                mem.phys_read_raw_iter(data)
+            })?;
+        }
+
+        // everything that was not handed out by now failed to be read
+        for b in bounced {
+            if let Some(data) = b.data {
+                opt_call(out_fail.as_deref_mut(), CTup2(b.meta_addr, data));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        let mut direct = vec![];
+        let mut bounced = vec![];
+        let mut pending = BTreeSet::new();
+
+        for req in inp {
+            let (first_direct, first_bounced) = (direct.len(), bounced.len());
+            self.split_request(req, &mut direct, &mut bounced);
+            let new_bounced = bounced.len() - first_bounced;
+
+            // aligned writes are issued before the read-modify-write of the bounced blocks,
+            // so pending partial writes into the same blocks have to be flushed first
+            let overlaps = direct[first_direct..].iter().any(|CTup3(addr, _, data)| {
+                let start = addr.to_umem();
+                pending
+                    .range(start..(start + data.len() as umem))
+                    .next()
+                    .is_some()
+            });
+
+            if overlaps {
+                let mut next_direct = direct.split_off(first_direct);
+                let mut next_bounced = bounced.split_off(first_bounced);
+                self.flush_writes(
+                    &mut direct,
+                    &mut bounced,
+                    out.as_deref_mut(),
+                    out_fail.as_deref_mut(),
+                )?;
+                pending.clear();
+                direct.append(&mut next_direct);
+                bounced.append(&mut next_bounced);
+            }
+
+            pending.extend(
+                bounced[(bounced.len() - new_bounced)..]
+                    .iter()
+                    .map(|b| b.block.to_umem()),
+            );
+        }
+
+        self.flush_writes(
+            &mut direct,
+            &mut bounced,
+            out.as_deref_mut(),
+            out_fail.as_deref_mut(),
+        )
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    AlignedPhysicalMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    /// Backend that rejects any request that is not aligned to 16 bytes or longer than 64 bytes.
+    struct StrictMemory(DummyMemory);
+
+    fn is_valid(addr: PhysicalAddress, len: usize) -> bool {
+        addr.to_umem() % 16 == 0 && len % 16 == 0 && len <= 64
+    }
+
+    impl PhysicalMemory for StrictMemory {
+        fn phys_read_raw_iter(&mut self, data: PhysicalReadMemOps) -> Result<()> {
+            let inp = data.inp.inspect(|CTup3(addr, _, buf)| {
+                assert!(
+                    is_valid(*addr, buf.len()),
+                    "invalid read {:x}+{:x}",
+                    addr,
+                    buf.len()
+                )
+            });
+            MemOps::with_raw(inp, data.out, data.out_fail, |data| {
+                self.0.phys_read_raw_iter(data)
+            })
+        }
+
+        fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+            let inp = data.inp.inspect(|CTup3(addr, _, buf)| {
+                assert!(
+                    is_valid(*addr, buf.len()),
+                    "invalid write {:x}+{:x}",
+                    addr,
+                    buf.len()
+                )
+            });
+            MemOps::with_raw(inp, data.out, data.out_fail, |data| {
+                self.0.phys_write_raw_iter(data)
+            })
+        }
+
+        fn metadata(&self) -> PhysicalMemoryMetadata {
+            self.0.metadata()
+        }
+    }
+
+    /// Issues all writes in a single batch.
+    fn write_batch(mem: &mut impl PhysicalMemory, writes: Vec<(PhysicalAddress, &[u8])>) {
+        MemOps::with(
+            writes
+                .into_iter()
+                .map(|(addr, data)| (addr, CSliceRef::from(data))),
+            None,
+            None,
+            |data| mem.phys_write_raw_iter(data),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn unaligned_roundtrip() {
+        let mut mem =
+            AlignedPhysicalMemory::new(StrictMemory(DummyMemory::new(size::kb(64))), 16, 64)
+                .unwrap();
+
+        let data = (0..200).map(|i| i as u8).collect::<Vec<_>>();
+        mem.phys_write(0x1007.into(), data.as_slice()).unwrap();
+
+        let mut out = vec![0u8; 200];
+        mem.phys_read_into(0x1007.into(), out.as_mut_slice())
+            .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn unaligned_write_preserves_surroundings() {
+        let mut mem =
+            AlignedPhysicalMemory::new(StrictMemory(DummyMemory::new(size::kb(64))), 16, 64)
+                .unwrap();
+
+        mem.phys_write(0x2000.into(), &[0xffu8; 32]).unwrap();
+        mem.phys_write(0x2005.into(), &[0u8; 3]).unwrap();
+
+        let mut out = [0u8; 32];
+        mem.phys_read_into(0x2000.into(), &mut out).unwrap();
+
+        let mut expected = [0xffu8; 32];
+        expected[5..8].copy_from_slice(&[0; 3]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn unaligned_writes_into_same_block() {
+        let mut mem =
+            AlignedPhysicalMemory::new(StrictMemory(DummyMemory::new(size::kb(64))), 16, 64)
+                .unwrap();
+
+        mem.phys_write(0x3000.into(), &[0xffu8; 32]).unwrap();
+
+        let writes = vec![
+            (0x3002.into(), [1u8; 2].as_ref()),
+            (0x3008.into(), [2u8; 3].as_ref()),
+            (0x3009.into(), [3u8; 1].as_ref()),
+            (0x300e.into(), [4u8; 4].as_ref()),
+        ];
+        write_batch(&mut mem, writes);
+
+        let mut out = [0u8; 32];
+        mem.phys_read_into(0x3000.into(), &mut out).unwrap();
+
+        let mut expected = [0xffu8; 32];
+        expected[2..4].copy_from_slice(&[1; 2]);
+        expected[8..11].copy_from_slice(&[2, 3, 2]);
+        expected[14..18].copy_from_slice(&[4; 4]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn aligned_write_after_unaligned_write() {
+        let mut mem =
+            AlignedPhysicalMemory::new(StrictMemory(DummyMemory::new(size::kb(64))), 16, 64)
+                .unwrap();
+
+        let writes = vec![
+            (0x4004.into(), [1u8; 4].as_ref()),
+            (0x4000.into(), [2u8; 16].as_ref()),
+        ];
+        write_batch(&mut mem, writes);
+
+        let mut out = [0u8; 16];
+        mem.phys_read_into(0x4000.into(), &mut out).unwrap();
+        assert_eq!(out, [2u8; 16]);
+    }
+
+    #[test]
+    fn invalid_args() {
+        assert!(AlignedPhysicalMemory::new(DummyMemory::new(size::kb(4)), 3, 6).is_err());
+        assert!(AlignedPhysicalMemory::new(DummyMemory::new(size::kb(4)), 16, 24).is_err());
+        assert!(AlignedPhysicalMemory::new(DummyMemory::new(size::kb(4)), 16, 32).is_ok());
+    }
+}
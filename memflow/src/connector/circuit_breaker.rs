@@ -0,0 +1,287 @@
+/*!
+Helper connector that stops issuing requests to a target that keeps failing.
+
+When a target becomes unavailable (e.g. the device was unplugged or the vm was shut down)
+every request will fail, often only after running into a timeout in the backend.
+The [`CircuitBreaker`] counts consecutive failed operations and, once a threshold
+is reached, fails all subsequent requests immediately with [`ErrorKind::TargetUnhealthy`]
+until it is reset manually or (when compiled with `std`) a reset timeout elapsed.
+*/
+
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+
+#[cfg(feature = "std")]
+use coarsetime::{Duration, Instant};
+
+/// Wraps a [`PhysicalMemory`] backend and fails fast after too many consecutive failures.
+///
+/// An operation is considered failed if the backend returns an error or if none of the
+/// requested chunks could be accessed. Any successful chunk resets the failure counter.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::CircuitBreaker;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// let mut mem = CircuitBreaker::new(mem, 8)
+///     .reset_timeout(std::time::Duration::from_secs(5).into());
+///
+/// let mut value = 0u64;
+/// mem.phys_read_into(0x1000.into(), &mut value).unwrap();
+/// assert!(!mem.is_tripped());
+/// ```
+#[derive(Clone)]
+pub struct CircuitBreaker<T> {
+    mem: T,
+    threshold: usize,
+    failures: usize,
+    #[cfg(feature = "std")]
+    reset_timeout: Option<Duration>,
+    #[cfg(feature = "std")]
+    tripped_at: Option<Instant>,
+}
+
+impl<T: PhysicalMemory> CircuitBreaker<T> {
+    /// Constructs a new `CircuitBreaker` that trips after `threshold` consecutive failures.
+    ///
+    /// A `threshold` of 0 is treated as 1.
+    pub fn new(mem: T, threshold: usize) -> Self {
+        Self {
+            mem,
+            threshold: core::cmp::max(threshold, 1),
+            failures: 0,
+            #[cfg(feature = "std")]
+            reset_timeout: None,
+            #[cfg(feature = "std")]
+            tripped_at: None,
+        }
+    }
+
+    /// Lets a single request through to the backend after the breaker
+    /// has been tripped for the given amount of time.
+    ///
+    /// If this request fails again the breaker trips again immediately.
+    #[cfg(feature = "std")]
+    pub fn reset_timeout(mut self, timeout: Duration) -> Self {
+        self.reset_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns `true` if the breaker has been tripped and requests are currently rejected.
+    pub fn is_tripped(&self) -> bool {
+        self.failures >= self.threshold
+    }
+
+    /// Returns the number of consecutive failed operations.
+    pub fn consecutive_failures(&self) -> usize {
+        self.failures
+    }
+
+    /// Resets the breaker so requests are passed to the backend again.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+        #[cfg(feature = "std")]
+        {
+            self.tripped_at = None;
+        }
+    }
+
+    /// Consumes the breaker and returns the underlying backend.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+
+    fn check(&mut self) -> Result<()> {
+        if !self.is_tripped() {
+            Ok(())
+        } else if self.reset_timeout_elapsed() {
+            // let a single request through, another failure will trip the breaker again
+            self.failures = self.threshold - 1;
+            Ok(())
+        } else {
+            Err(Error(ErrorOrigin::Connector, ErrorKind::TargetUnhealthy))
+        }
+    }
+
+    fn update(&mut self, failed: bool) {
+        if !failed {
+            self.failures = 0;
+            return;
+        }
+
+        self.failures = self.failures.saturating_add(1);
+        if self.failures == self.threshold {
+            log::warn!(
+                "circuit breaker tripped after {} consecutive failures",
+                self.failures
+            );
+            self.set_tripped();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn reset_timeout_elapsed(&self) -> bool {
+        match (self.reset_timeout, self.tripped_at) {
+            (Some(timeout), Some(tripped_at)) => tripped_at.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn reset_timeout_elapsed(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "std")]
+    fn set_tripped(&mut self) {
+        self.tripped_at = Some(Instant::now());
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn set_tripped(&mut self) {}
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for CircuitBreaker<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        self.check()?;
+
+        let mut succeeded = 0;
+        let mut failed = 0;
+
+        let res = {
+            let out = &mut |CTup2(addr, buf): CTup2<_, _>| {
+                succeeded += 1;
+                opt_call(out.as_deref_mut(), CTup2(addr, buf))
+            };
+            let out = &mut out.into();
+            let out_fail = &mut |CTup2(addr, buf): CTup2<_, _>| {
+                failed += 1;
+                opt_call(out_fail.as_deref_mut(), CTup2(addr, buf))
+            };
+            let out_fail = &mut out_fail.into();
+
+            self.mem.phys_read_raw_iter(MemOps {
+                inp,
+                out: Some(out),
+                out_fail: Some(out_fail),
+            })
+        };
+
+        self.update(res.is_err() || (succeeded == 0 && failed > 0));
+        res
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        self.check()?;
+
+        let mut succeeded = 0;
+        let mut failed = 0;
+
+        let res = {
+            let out = &mut |CTup2(addr, buf): CTup2<_, _>| {
+                succeeded += 1;
+                opt_call(out.as_deref_mut(), CTup2(addr, buf))
+            };
+            let out = &mut out.into();
+            let out_fail = &mut |CTup2(addr, buf): CTup2<_, _>| {
+                failed += 1;
+                opt_call(out_fail.as_deref_mut(), CTup2(addr, buf))
+            };
+            let out_fail = &mut out_fail.into();
+
+            self.mem.phys_write_raw_iter(MemOps {
+                inp,
+                out: Some(out),
+                out_fail: Some(out_fail),
+            })
+        };
+
+        self.update(res.is_err() || (succeeded == 0 && failed > 0));
+        res
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    CircuitBreaker<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    #[test]
+    fn trips_and_resets() {
+        let mut mem = CircuitBreaker::new(DummyMemory::new(size::kb(64)), 2);
+        let mut buf = [0u8; 8];
+
+        // reads outside of the memory map fail
+        assert!(mem.phys_read_into(size::mb(1).into(), &mut buf).is_ok());
+        assert_eq!(mem.consecutive_failures(), 1);
+        assert!(mem.phys_read_into(size::mb(1).into(), &mut buf).is_ok());
+        assert!(mem.is_tripped());
+
+        assert_eq!(
+            mem.phys_read_into(0x1000.into(), &mut buf),
+            Err(Error(ErrorOrigin::Connector, ErrorKind::TargetUnhealthy))
+        );
+
+        mem.reset();
+        assert!(mem.phys_read_into(0x1000.into(), &mut buf).is_ok());
+        assert_eq!(mem.consecutive_failures(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn timed_reset() {
+        let mut mem = CircuitBreaker::new(DummyMemory::new(size::kb(64)), 1)
+            .reset_timeout(Duration::from_millis(0));
+        let mut buf = [0u8; 8];
+
+        mem.phys_read_into(size::mb(1).into(), &mut buf).unwrap();
+        assert!(mem.is_tripped());
+
+        // the timeout already elapsed, so the next request is let through
+        assert!(mem.phys_read_into(0x1000.into(), &mut buf).is_ok());
+        assert!(!mem.is_tripped());
+    }
+}
@@ -0,0 +1,342 @@
+/*!
+Helper connector that merges scattered physical reads.
+
+Virtual reads are translated into one physical request per page chunk. Reads of nearby virtual
+addresses frequently end up next to each other (or even overlap) in physical memory, which
+results in many small round-trips to the backend. The [`CoalescingPhysicalMemory`] wrapper merges
+such requests before they are dispatched.
+*/
+
+use std::prelude::v1::*;
+
+use crate::cglue::*;
+use crate::error::Result;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{umem, PhysicalAddress};
+
+use core::ops::Range;
+
+/// Wraps a [`PhysicalMemory`] backend and merges physically adjacent or overlapping reads.
+///
+/// Requests of a single read call are sorted by their physical address. Requests which overlap,
+/// or are at most `max_gap` bytes apart from each other, are read from the backend at once
+/// through an internal buffer and the results are split back into the original requests
+/// afterwards. Requests that are not adjacent to any other request are passed through without
+/// copying.
+///
+/// If the backend only manages to read parts of a merged request, the affected requests are
+/// retried individually, so failures are still reported with the same granularity.
+///
+/// Writes are always passed through unchanged.
+///
+/// # Examples
+/// ```
+/// use memflow::cglue::CTup2;
+/// use memflow::connector::CoalescingPhysicalMemory;
+/// use memflow::mem::{MemoryView, PhysicalMemory};
+/// use memflow::types::{size, Address};
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// // merge requests that are up to 64 bytes apart
+/// let mut mem = CoalescingPhysicalMemory::new(mem, 64);
+///
+/// // both buffers are filled by a single request to the backend
+/// let (mut a, mut b) = ([0u8; 4], [0u8; 8]);
+/// mem.phys_view()
+///     .read_raw_list(&mut [
+///         CTup2(Address::from(0x1000), (&mut a[..]).into()),
+///         CTup2(Address::from(0x1020), (&mut b[..]).into()),
+///     ])
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct CoalescingPhysicalMemory<T> {
+    mem: T,
+    max_gap: umem,
+    bounce: Vec<u8>,
+}
+
+/// Requests that are read from the backend in a single request.
+struct ReadGroup {
+    start: PhysicalAddress,
+    len: umem,
+    /// offset of the group in the bounce buffer
+    offset: usize,
+    /// indices of the requests in the sorted request list
+    reqs: Range<usize>,
+}
+
+impl ReadGroup {
+    fn end(&self) -> umem {
+        self.start.address().to_umem() + self.len
+    }
+}
+
+impl<T: PhysicalMemory> CoalescingPhysicalMemory<T> {
+    /// Constructs a new `CoalescingPhysicalMemory` around the given backend.
+    ///
+    /// Requests that are up to `max_gap` bytes apart are merged, the bytes in between are read
+    /// and discarded. A `max_gap` of 0 only merges adjacent and overlapping requests.
+    pub fn new(mem: T, max_gap: usize) -> Self {
+        Self {
+            mem,
+            max_gap: max_gap as umem,
+            bounce: vec![],
+        }
+    }
+
+    /// Consumes this wrapper and returns the underlying backend.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for CoalescingPhysicalMemory<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let mut reqs = inp.collect::<Vec<_>>();
+        reqs.sort_by_key(|CTup3(addr, _, _)| addr.address());
+
+        let mut groups: Vec<ReadGroup> = vec![];
+        for (i, CTup3(addr, _, buf)) in reqs.iter().enumerate() {
+            let start = addr.address().to_umem();
+            let end = start + buf.len() as umem;
+
+            match groups.last_mut() {
+                Some(group) if start <= group.end() + self.max_gap => {
+                    group.len = core::cmp::max(group.end(), end) - group.start.address().to_umem();
+                    group.reqs.end = i + 1;
+                }
+                _ => groups.push(ReadGroup {
+                    start: *addr,
+                    len: end - start,
+                    offset: 0,
+                    reqs: i..(i + 1),
+                }),
+            }
+        }
+
+        let mut reqs = reqs.into_iter().map(Some).collect::<Vec<_>>();
+
+        let (single, mut merged): (Vec<_>, Vec<_>) =
+            groups.into_iter().partition(|group| group.reqs.len() == 1);
+
+        if !single.is_empty() {
+            let direct = single
+                .into_iter()
+                .filter_map(|group| reqs[group.reqs.start].take());
+
+            MemOps::with_raw(
+                direct,
+                out.as_deref_mut(),
+                out_fail.as_deref_mut(),
+                |data| self.mem.phys_read_raw_iter(data),
+            )?;
+        }
+
+        if merged.is_empty() {
+            return Ok(());
+        }
+
+        let mut total = 0;
+        for group in merged.iter_mut() {
+            group.offset = total;
+            total += group.len as usize;
+        }
+
+        self.bounce.clear();
+        self.bounce.resize(total, 0);
+
+        // ranges of the bounce buffer that were filled in, identified by their physical address
+        let mut filled = vec![];
+        {
+            let mem = &mut self.mem;
+            let mut bounce = &mut self.bounce[..];
+            let group_reqs = merged
+                .iter()
+                .map(|group| {
+                    let (buf, rest) = std::mem::take(&mut bounce).split_at_mut(group.len as usize);
+                    bounce = rest;
+                    CTup3(group.start, group.start.address(), CSliceMut::from(buf))
+                })
+                .collect::<Vec<_>>();
+
+            let callback = &mut |CTup2(addr, buf): ReadData| {
+                filled.push((addr, buf.len() as umem));
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(group_reqs.into_iter(), Some(&mut callback), None, |data| {
+                mem.phys_read_raw_iter(data)
+            })?;
+        }
+
+        filled.sort_unstable_by_key(|&(addr, _)| addr);
+
+        let mut retry = vec![];
+
+        for group in merged {
+            for req in reqs[group.reqs].iter_mut().filter_map(Option::take) {
+                let CTup3(addr, meta_addr, mut buf) = req;
+                let start = addr.address();
+                let len = buf.len() as umem;
+
+                let idx = filled.partition_point(|&(addr, _)| addr <= start);
+                let is_filled = idx
+                    .checked_sub(1)
+                    .map(|idx| filled[idx])
+                    .filter(|&(addr, filled_len)| start + len <= addr + filled_len)
+                    .is_some();
+
+                if is_filled {
+                    let offset = group.offset + (start - group.start.address()) as usize;
+                    buf.copy_from_slice(&self.bounce[offset..(offset + len as usize)]);
+                    opt_call(out.as_deref_mut(), CTup2(meta_addr, buf));
+                } else {
+                    retry.push(CTup3(addr, meta_addr, buf));
+                }
+            }
+        }
+
+        if !retry.is_empty() {
+            MemOps::with_raw(retry.into_iter(), out, out_fail, |data| {
+                self.mem.phys_read_raw_iter(data)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+        self.mem.phys_write_raw_iter(data)
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    CoalescingPhysicalMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::MemoryView;
+    use crate::types::{size, Address};
+
+    /// Backend that records the physical requests it receives.
+    struct CountingMemory {
+        mem: DummyMemory,
+        reads: Vec<(Address, usize)>,
+    }
+
+    impl PhysicalMemory for CountingMemory {
+        fn phys_read_raw_iter(&mut self, data: PhysicalReadMemOps) -> Result<()> {
+            let (mem, reads) = (&mut self.mem, &mut self.reads);
+            let inp = data
+                .inp
+                .inspect(|CTup3(addr, _, buf)| reads.push((addr.address(), buf.len())));
+            MemOps::with_raw(inp, data.out, data.out_fail, |data| {
+                mem.phys_read_raw_iter(data)
+            })
+        }
+
+        fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+            self.mem.phys_write_raw_iter(data)
+        }
+
+        fn metadata(&self) -> PhysicalMemoryMetadata {
+            self.mem.metadata()
+        }
+    }
+
+    fn counting_mem(size: usize) -> CountingMemory {
+        let mut mem = DummyMemory::new(size);
+        let data = (0..size).map(|i| i as u8).collect::<Vec<_>>();
+        mem.phys_write(0.into(), data.as_slice()).unwrap();
+        CountingMemory { mem, reads: vec![] }
+    }
+
+    #[test]
+    fn merge_adjacent() {
+        let mut mem = CoalescingPhysicalMemory::new(counting_mem(size::kb(64)), 0);
+
+        let (mut a, mut b, mut c, mut d) = ([0u8; 4], [0u8; 4], [0u8; 6], [0u8; 2]);
+        mem.phys_view()
+            .read_raw_list(&mut [
+                CTup2(Address::from(0x1004), (&mut b[..]).into()),
+                CTup2(Address::from(0x1000), (&mut a[..]).into()),
+                CTup2(Address::from(0x1002), (&mut c[..]).into()),
+                CTup2(Address::from(0x2000), (&mut d[..]).into()),
+            ])
+            .unwrap();
+
+        assert_eq!(a, [0x00, 0x01, 0x02, 0x03]);
+        assert_eq!(b, [0x04, 0x05, 0x06, 0x07]);
+        assert_eq!(c, [0x02, 0x03, 0x04, 0x05, 0x06, 0x07]);
+        assert_eq!(d, [0x00, 0x01]);
+
+        let reads = &mem.into_inner().reads;
+        assert_eq!(reads.len(), 2);
+        assert!(reads.contains(&(Address::from(0x1000), 8)));
+        assert!(reads.contains(&(Address::from(0x2000), 2)));
+    }
+
+    #[test]
+    fn merge_gaps() {
+        let mut mem = CoalescingPhysicalMemory::new(counting_mem(size::kb(64)), 0x10);
+
+        let (mut a, mut b) = ([0u8; 4], [0u8; 4]);
+        mem.phys_view()
+            .read_raw_list(&mut [
+                CTup2(Address::from(0x1000), (&mut a[..]).into()),
+                CTup2(Address::from(0x1010), (&mut b[..]).into()),
+            ])
+            .unwrap();
+
+        assert_eq!(b, [0x10, 0x11, 0x12, 0x13]);
+        assert_eq!(mem.into_inner().reads, vec![(Address::from(0x1000), 0x14)]);
+    }
+
+    #[test]
+    fn retry_partial() {
+        let mut mem = CoalescingPhysicalMemory::new(counting_mem(size::kb(64)), 0);
+
+        let (mut a, mut b) = ([0u8; 4], [0xffu8; 4]);
+        let res = mem.phys_view().read_raw_list(&mut [
+            CTup2(Address::from(0xfffc), (&mut a[..]).into()),
+            CTup2(Address::from(0x10000), (&mut b[..]).into()),
+        ]);
+
+        assert!(res.is_err());
+        assert_eq!(a, [0xfc, 0xfd, 0xfe, 0xff]);
+        assert_eq!(b, [0; 4]);
+    }
+}
@@ -4,10 +4,10 @@ Basic connector which works on file i/o operations (`Seek`, `Read`, `Write`).
 
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::mem::{
-    opt_call, MemoryMap, PhysicalMemory, PhysicalMemoryMetadata, PhysicalReadMemOps,
-    PhysicalWriteMemOps,
+    opt_call, MemoryMap, PhysicalMemory, PhysicalMemoryMetadata, PhysicalMemoryRun,
+    PhysicalMemoryRunCallback, PhysicalReadMemOps, PhysicalWriteMemOps,
 };
-use crate::types::{umem, Address};
+use crate::types::{umem, Address, PageType};
 
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom, Write};
@@ -196,6 +196,19 @@ impl<T: Seek + Read + Write + Send> PhysicalMemory for FileIoMemory<T> {
             ideal_batch_size: u32::MAX,
         }
     }
+
+    fn memory_runs(&self, mut out: PhysicalMemoryRunCallback) {
+        for mapping in self.mem_map.iter() {
+            if !out.call(PhysicalMemoryRun {
+                base: mapping.base(),
+                size: mapping.output().1,
+                page_type: PageType::UNKNOWN,
+                readonly: false,
+            }) {
+                break;
+            }
+        }
+    }
 }
 
 cglue_impl_group!(
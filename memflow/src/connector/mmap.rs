@@ -4,10 +4,10 @@ Basic connector which works on mapped memory.
 
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::mem::{
-    opt_call, MemoryMap, PhysicalMemory, PhysicalMemoryMetadata, PhysicalReadMemOps,
-    PhysicalWriteMemOps,
+    opt_call, MemoryMap, PhysicalMemory, PhysicalMemoryMapped, PhysicalMemoryMetadata,
+    PhysicalMemoryRun, PhysicalMemoryRunCallback, PhysicalReadMemOps, PhysicalWriteMemOps,
 };
-use crate::types::{umem, Address};
+use crate::types::{umem, Address, PageType};
 
 use crate::cglue::*;
 
@@ -136,6 +136,19 @@ impl<'a, F: AsRef<MemoryMap<&'a mut [u8]>> + Send> PhysicalMemory
             ideal_batch_size: u32::MAX,
         }
     }
+
+    fn memory_runs(&self, mut out: PhysicalMemoryRunCallback) {
+        for mapping in self.info.as_ref().iter() {
+            if !out.call(PhysicalMemoryRun {
+                base: mapping.base(),
+                size: mapping.output().len() as umem,
+                page_type: PageType::UNKNOWN,
+                readonly: false,
+            }) {
+                break;
+            }
+        }
+    }
 }
 
 #[allow(clippy::needless_option_as_deref)]
@@ -157,6 +170,11 @@ impl<'a, F: AsRef<MemoryMap<&'a [u8]>> + Send> PhysicalMemory
             .log_error("target mapping is not writeable"))
     }
 
+    #[inline]
+    fn as_mapped(&self) -> Option<&dyn PhysicalMemoryMapped> {
+        Some(self)
+    }
+
     fn metadata(&self) -> PhysicalMemoryMetadata {
         let max_address = self
             .info
@@ -178,6 +196,30 @@ impl<'a, F: AsRef<MemoryMap<&'a [u8]>> + Send> PhysicalMemory
             ideal_batch_size: u32::MAX,
         }
     }
+
+    fn memory_runs(&self, mut out: PhysicalMemoryRunCallback) {
+        for mapping in self.info.as_ref().iter() {
+            if !out.call(PhysicalMemoryRun {
+                base: mapping.base(),
+                size: mapping.output().len() as umem,
+                page_type: PageType::UNKNOWN,
+                readonly: true,
+            }) {
+                break;
+            }
+        }
+    }
+}
+
+// writeable mappings do not provide direct views, since the slices could be
+// modified through the connector while they are borrowed
+impl<'a, F: AsRef<MemoryMap<&'a [u8]>>> PhysicalMemoryMapped for MappedPhysicalMemory<&'a [u8], F> {
+    fn phys_mapped(&self, addr: Address, len: usize) -> Option<&[u8]> {
+        let mapping = self.info.as_ref().mapping_at(addr)?;
+        let buf: &'a [u8] = *mapping.output();
+        let start = (addr.to_umem() - mapping.base().to_umem()) as usize;
+        buf.get(start..start.checked_add(len)?)
+    }
 }
 
 #[cfg(feature = "plugins")]
@@ -192,3 +234,36 @@ cglue_impl_group!(
     crate::plugins::ConnectorInstance,
     {}
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::size;
+
+    #[test]
+    fn dump_skips_holes() {
+        let mut low = vec![1u8; size::kb(64)];
+        let mut high = vec![2u8; size::kb(64)];
+
+        let mut map = MemoryMap::new();
+        map.push(Address::null(), &mut low[..]);
+        map.push(Address::from(size::mb(1)), &mut high[..]);
+        let mut mem = MappedPhysicalMemory::with_info(map);
+
+        let runs = mem.memory_run_list();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].base, Address::from(size::mb(1)));
+        assert_eq!(runs[1].size, size::kb(64) as umem);
+
+        let mut dumped = vec![];
+        mem.phys_dump(size::kb(16), |addr, chunk| {
+            dumped.push((addr, chunk[0]));
+            true
+        })
+        .unwrap();
+
+        assert_eq!(dumped.len(), 8);
+        assert_eq!(dumped[3], (Address::from(size::kb(48)), 1));
+        assert_eq!(dumped[4], (Address::from(size::mb(1)), 2));
+    }
+}
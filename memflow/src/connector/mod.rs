@@ -27,6 +27,42 @@ pub mod mmap;
 #[doc(hidden)]
 pub use mmap::MappedPhysicalMemory;
 
+pub mod aligned;
+#[doc(hidden)]
+pub use aligned::AlignedPhysicalMemory;
+
+pub mod coalesce;
+#[doc(hidden)]
+pub use coalesce::CoalescingPhysicalMemory;
+
+pub mod circuit_breaker;
+#[doc(hidden)]
+pub use circuit_breaker::CircuitBreaker;
+
+#[cfg(feature = "std")]
+pub mod pool;
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub use pool::{ConnectorPool, PooledConnector};
+
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[doc(hidden)]
+#[cfg(feature = "parallel")]
+pub use parallel::ParallelMemory;
+
+pub mod replay;
+#[doc(hidden)]
+pub use replay::{ReadRecorder, ReadRecording, ReplayMemory};
+
+pub mod stealth;
+#[doc(hidden)]
+pub use stealth::{StealthMemory, StealthProfile};
+
+pub mod verify;
+#[doc(hidden)]
+pub use verify::VerifiedPhysicalMemory;
+
 pub mod cpu_state;
 #[doc(hidden)]
 pub use cpu_state::{ConnectorCpuState, ConnectorCpuStateInner, CpuState};
@@ -0,0 +1,212 @@
+/*!
+Connector wrapper that splits large read batches across multiple threads.
+
+High bandwidth backends, like PCIe FPGA devices, are rarely saturated by a single thread
+issuing requests. The [`ParallelMemory`] wrapper holds several clones of a connector and
+distributes the requests of large batches across them using rayon. Since the virtual memory
+layer issues all physical reads of a `read_raw_list` call as one batch, large virtual batches
+are parallelized as well.
+
+Each clone is used by at most one thread at a time. Note that caches inside of the wrapped
+connector are not shared between the clones, a page cache should be put in front of this wrapper.
+*/
+
+use std::prelude::v1::*;
+
+use crate::cglue::*;
+use crate::error::Result;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+
+use rayon::prelude::*;
+
+/// Wraps a value to move it to another thread.
+///
+/// Requests carry mutable slices into the buffers of the caller. The buffers of all requests
+/// within a batch are disjoint and every request is only accessed by a single thread.
+struct Unshared<T>(T);
+
+unsafe impl<T> Send for Unshared<T> {}
+
+/// Distributes large read batches across multiple clones of a connector.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::ParallelMemory;
+/// use memflow::mem::{MemoryView, PhysicalMemory};
+/// use memflow::types::{size, Address};
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// let mut mem = ParallelMemory::new(mem, 4).min_split(16);
+///
+/// let mut pages = vec![[0u8; 0x1000]; 64];
+/// let mut view = mem.phys_view();
+/// let mut batcher = view.batcher();
+/// for (i, page) in pages.iter_mut().enumerate() {
+///     batcher.read_into(Address::from(i as u64 * 0x1000), page);
+/// }
+/// batcher.commit_rw().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ParallelMemory<T> {
+    instances: Vec<T>,
+    min_split: usize,
+}
+
+impl<T: PhysicalMemory + Clone> ParallelMemory<T> {
+    /// Creates a new wrapper that distributes requests across `threads` instances of `mem`.
+    pub fn new(mem: T, threads: usize) -> Self {
+        let mut instances = (1..threads).map(|_| mem.clone()).collect::<Vec<_>>();
+        instances.insert(0, mem);
+        Self {
+            instances,
+            min_split: 0x100,
+        }
+    }
+}
+
+impl<T: PhysicalMemory> ParallelMemory<T> {
+    /// Sets the minimum amount of requests a batch has to contain for it to be split.
+    ///
+    /// Smaller batches are read by the first instance on the calling thread,
+    /// since distributing them costs more than it gains.
+    pub fn min_split(mut self, min_split: usize) -> Self {
+        self.min_split = min_split;
+        self
+    }
+
+    /// Returns the amount of connector instances used by this wrapper.
+    pub fn threads(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Consumes the wrapper and returns the original connector.
+    pub fn into_inner(mut self) -> T {
+        self.instances.swap_remove(0)
+    }
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for ParallelMemory<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let reqs = inp.collect::<Vec<_>>();
+        let threads = self.instances.len();
+
+        if threads < 2 || reqs.len() < self.min_split {
+            let mem = &mut self.instances[0];
+            return MemOps::with_raw(
+                reqs.into_iter(),
+                out.as_deref_mut(),
+                out_fail.as_deref_mut(),
+                |data| mem.phys_read_raw_iter(data),
+            );
+        }
+
+        let per_thread = (reqs.len() + threads - 1) / threads;
+        let mut reqs = reqs.into_iter();
+        let chunks = (0..threads)
+            .map(|_| Unshared(reqs.by_ref().take(per_thread).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        let results = self
+            .instances
+            .par_iter_mut()
+            .zip(chunks.into_par_iter())
+            .map(|(mem, chunk)| {
+                let (mut done, mut failed) = (vec![], vec![]);
+                let res = {
+                    let mut done_cb: ReadCallback = (&mut done).into();
+                    let mut failed_cb: ReadCallback = (&mut failed).into();
+                    MemOps::with_raw(
+                        chunk.0.into_iter(),
+                        Some(&mut done_cb),
+                        Some(&mut failed_cb),
+                        |data| mem.phys_read_raw_iter(data),
+                    )
+                };
+                Unshared((res, done, failed))
+            })
+            .collect::<Vec<_>>();
+
+        // the callbacks of the caller are not thread safe, invoke them after all threads are done
+        let mut ret = Ok(());
+        for Unshared((res, done, failed)) in results {
+            if res.is_err() {
+                ret = res;
+            }
+            for data in done {
+                opt_call(out.as_deref_mut(), data);
+            }
+            for data in failed {
+                opt_call(out_fail.as_deref_mut(), data);
+            }
+        }
+        ret
+    }
+
+    fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+        self.instances[0].phys_write_raw_iter(data)
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.instances[0].metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.instances[0].memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.instances
+            .iter_mut()
+            .for_each(|mem| mem.set_mem_map(mem_map))
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    ParallelMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::MemoryView;
+    use crate::types::{size, Address};
+
+    #[test]
+    fn split_batch() {
+        let mut dummy = DummyMemory::new(size::mb(1));
+        for i in 0..0x100u64 {
+            dummy.phys_write((i * 0x1000).into(), &i).unwrap();
+        }
+
+        let mut mem = ParallelMemory::new(dummy, 4).min_split(8);
+        assert_eq!(mem.threads(), 4);
+
+        let mut values = vec![0u64; 0x100];
+        {
+            let mut view = mem.phys_view();
+            let mut batcher = view.batcher();
+            for (i, value) in values.iter_mut().enumerate() {
+                batcher.read_into(Address::from(i as u64 * 0x1000), value);
+            }
+            batcher.commit_rw().unwrap();
+        }
+
+        assert!(values.iter().enumerate().all(|(i, &v)| v == i as u64));
+    }
+}
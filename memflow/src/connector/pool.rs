@@ -0,0 +1,178 @@
+/*!
+Pool of warm connector instances.
+
+Creating a connector can be expensive (e.g. the qemu connector has to scan for the qemu process
+and its memory mappings). Short-lived tasks, like request handlers of a server, should not
+recreate the connector each time. The [`ConnectorPool`] hands out clones of a template
+connector and takes them back once they are dropped, so their caches stay warm for the next task.
+*/
+
+use std::prelude::v1::*;
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// A pool of cloned connector instances.
+///
+/// The pool is not limited to connectors, it works with any object implementing `Clone`.
+/// Instances are cloned from the template whenever no idle instance is available.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::ConnectorPool;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// let pool = ConnectorPool::new(mem, 4);
+///
+/// {
+///     let mut conn = pool.get();
+///     let mut value = 0u64;
+///     conn.phys_read_into(0x1000.into(), &mut value).unwrap();
+/// } // `conn` is returned to the pool here
+///
+/// assert_eq!(pool.idle_count(), 1);
+/// ```
+pub struct ConnectorPool<T> {
+    template: Mutex<T>,
+    idle: Mutex<Vec<T>>,
+    max_idle: usize,
+}
+
+impl<T: Clone> ConnectorPool<T> {
+    /// Creates a new pool that clones instances from `template`.
+    ///
+    /// At most `max_idle` instances are kept around, additional instances are dropped when they are returned.
+    pub fn new(template: T, max_idle: usize) -> Self {
+        Self {
+            template: Mutex::new(template),
+            idle: Mutex::new(Vec::with_capacity(max_idle)),
+            max_idle,
+        }
+    }
+
+    /// Creates `count` instances upfront so the first tasks do not have to pay the cost of cloning.
+    pub fn prewarm(&self, count: usize) {
+        let count = std::cmp::min(count, self.max_idle);
+        let missing = count.saturating_sub(self.idle_count());
+        let instances = (0..missing)
+            .map(|_| self.new_instance())
+            .collect::<Vec<_>>();
+        self.idle.lock().unwrap().extend(instances);
+    }
+
+    /// Retrieves an instance from the pool.
+    ///
+    /// If no idle instance is available a new one is cloned from the template.
+    /// The instance is returned to the pool when the returned guard is dropped.
+    pub fn get(&self) -> PooledConnector<'_, T> {
+        let instance = self.idle.lock().unwrap().pop();
+        PooledConnector {
+            pool: self,
+            instance: Some(instance.unwrap_or_else(|| self.new_instance())),
+        }
+    }
+
+    /// Returns the amount of idle instances currently held by the pool.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Drops all idle instances.
+    ///
+    /// This can be used to get rid of instances with stale caches, e.g. after the target rebooted.
+    pub fn clear(&self) {
+        self.idle.lock().unwrap().clear();
+    }
+
+    fn new_instance(&self) -> T {
+        self.template.lock().unwrap().clone()
+    }
+
+    fn release(&self, instance: T) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < self.max_idle {
+            idle.push(instance);
+        }
+    }
+}
+
+/// An instance that has been taken out of a [`ConnectorPool`].
+///
+/// The instance is returned to the pool when this guard is dropped.
+pub struct PooledConnector<'a, T: Clone> {
+    pool: &'a ConnectorPool<T>,
+    instance: Option<T>,
+}
+
+impl<'a, T: Clone> PooledConnector<'a, T> {
+    /// Removes the instance from the pool permanently.
+    pub fn detach(mut self) -> T {
+        self.instance.take().unwrap()
+    }
+
+    /// Drops the instance instead of returning it to the pool.
+    ///
+    /// This should be used when the instance ended up in a bad state.
+    pub fn discard(mut self) {
+        self.instance.take();
+    }
+}
+
+impl<'a, T: Clone> Deref for PooledConnector<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.instance.as_ref().unwrap()
+    }
+}
+
+impl<'a, T: Clone> DerefMut for PooledConnector<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.instance.as_mut().unwrap()
+    }
+}
+
+impl<'a, T: Clone> Drop for PooledConnector<'a, T> {
+    fn drop(&mut self) {
+        if let Some(instance) = self.instance.take() {
+            self.pool.release(instance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn reuse_instances() {
+        let pool = ConnectorPool::new(DummyMemory::new(size::kb(64)), 2);
+        pool.prewarm(4);
+        assert_eq!(pool.idle_count(), 2);
+
+        {
+            let mut a = pool.get();
+            let _b = pool.get();
+            let _c = pool.get();
+            assert_eq!(pool.idle_count(), 0);
+
+            a.phys_write(0x1000.into(), &0xdeadu32).unwrap();
+        }
+
+        // the third instance exceeds `max_idle` and is dropped
+        assert_eq!(pool.idle_count(), 2);
+
+        pool.get().discard();
+        assert_eq!(pool.idle_count(), 1);
+
+        let conn = pool.get().detach();
+        drop(conn);
+        assert_eq!(pool.idle_count(), 0);
+    }
+}
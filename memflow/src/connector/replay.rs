@@ -0,0 +1,506 @@
+/*!
+Recording and deterministic replay of physical memory reads.
+
+Issues like "kernel initialization fails on my target" are hard to reproduce without access
+to the target itself. The [`ReadRecorder`] wraps a connector and stores all data that has
+been read into a [`ReadRecording`]. The recording can be written to a compact file and
+later be loaded into a [`ReplayMemory`], which serves the exact same data again without
+requiring the original target.
+
+# Examples
+
+```
+use memflow::connector::{ReadRecorder, ReplayMemory};
+use memflow::mem::PhysicalMemory;
+use memflow::types::size;
+# use memflow::dummy::DummyMemory;
+# let mut mem = DummyMemory::new(size::mb(2));
+# mem.phys_write(0x1000.into(), &0x1234u64).unwrap();
+
+// record a session
+let mut recorder = ReadRecorder::new(mem);
+let mut value = 0u64;
+recorder.phys_read_into(0x1000.into(), &mut value).unwrap();
+
+// store the recording and replay it
+let mut bundle = vec![];
+recorder.recording().save(&mut bundle).unwrap();
+
+let mut replay = ReplayMemory::load(bundle.as_slice()).unwrap();
+let mut replayed = 0u64;
+replay.phys_read_into(0x1000.into(), &mut replayed).unwrap();
+assert_eq!(value, replayed);
+```
+*/
+
+use std::collections::BTreeMap;
+use std::prelude::v1::*;
+
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{umem, Address};
+
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "std")]
+const RECORDING_MAGIC: [u8; 4] = *b"MFRR";
+#[cfg(feature = "std")]
+const RECORDING_VERSION: u32 = 1;
+
+/// Sparse store of all physical memory contents that were observed during a session.
+#[derive(Clone)]
+pub struct ReadRecording {
+    metadata: PhysicalMemoryMetadata,
+    /// Disjoint and non-adjacent ranges of recorded data keyed by their start address.
+    ranges: BTreeMap<umem, Vec<u8>>,
+}
+
+impl ReadRecording {
+    /// Creates an empty recording of a connector with the given metadata.
+    pub fn new(metadata: PhysicalMemoryMetadata) -> Self {
+        Self {
+            metadata,
+            ranges: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the metadata of the connector this recording was taken from.
+    pub fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.metadata
+    }
+
+    /// Returns the number of contiguous ranges of recorded data.
+    pub fn range_count(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Stores `data` at the given address.
+    ///
+    /// The data is merged with all overlapping and adjacent ranges that have been recorded before.
+    pub fn record(&mut self, addr: Address, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let start = addr.to_umem();
+        let end = start + data.len() as umem;
+
+        // fast path: the data is contained in a single existing range
+        if let Some((&base, buf)) = self.ranges.range_mut(..=start).next_back() {
+            if base + buf.len() as umem >= end {
+                let offset = (start - base) as usize;
+                buf[offset..offset + data.len()].copy_from_slice(data);
+                return;
+            }
+        }
+
+        let first = self
+            .ranges
+            .range(..=start)
+            .next_back()
+            .filter(|(&base, buf)| base + buf.len() as umem >= start)
+            .map(|(&base, _)| base)
+            .unwrap_or(start);
+        let merged = self
+            .ranges
+            .range(first..=end)
+            .map(|(&base, _)| base)
+            .collect::<Vec<_>>();
+
+        let new_start = std::cmp::min(start, first);
+        let new_end = merged
+            .last()
+            .map(|base| base + self.ranges[base].len() as umem)
+            .map_or(end, |last_end| std::cmp::max(end, last_end));
+
+        let mut buf = vec![0; (new_end - new_start) as usize];
+        for base in merged {
+            let old = self.ranges.remove(&base).unwrap();
+            let offset = (base - new_start) as usize;
+            buf[offset..offset + old.len()].copy_from_slice(&old);
+        }
+        let offset = (start - new_start) as usize;
+        buf[offset..offset + data.len()].copy_from_slice(data);
+
+        self.ranges.insert(new_start, buf);
+    }
+
+    /// Copies the recorded data at the given address into `out`.
+    ///
+    /// Returns `false` and leaves `out` untouched if any of the bytes have not been recorded.
+    pub fn read_into(&self, addr: Address, out: &mut [u8]) -> bool {
+        if out.is_empty() {
+            return true;
+        }
+
+        let start = addr.to_umem();
+        match self.ranges.range(..=start).next_back() {
+            Some((&base, buf)) if base + buf.len() as umem >= start + out.len() as umem => {
+                let offset = (start - base) as usize;
+                out.copy_from_slice(&buf[offset..offset + out.len()]);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Writes the recording into the given writer.
+    ///
+    /// The format consists of a small header containing the connector metadata
+    /// followed by all recorded ranges, each prefixed with its address and length.
+    #[cfg(feature = "std")]
+    pub fn save(&self, mut writer: impl Write) -> Result<()> {
+        let mut write = |buf: &[u8]| {
+            writer.write_all(buf).map_err(|err| {
+                Error(ErrorOrigin::Connector, ErrorKind::UnableToWriteFile).log_error(err)
+            })
+        };
+
+        write(&RECORDING_MAGIC)?;
+        write(&RECORDING_VERSION.to_le_bytes())?;
+        write(&(self.metadata.max_address.to_umem() as u64).to_le_bytes())?;
+        write(&(self.metadata.real_size as u64).to_le_bytes())?;
+        write(&[self.metadata.readonly as u8])?;
+        write(&self.metadata.ideal_batch_size.to_le_bytes())?;
+        write(&(self.ranges.len() as u64).to_le_bytes())?;
+
+        for (base, buf) in self.ranges.iter() {
+            write(&(*base as u64).to_le_bytes())?;
+            write(&(buf.len() as u64).to_le_bytes())?;
+            write(buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads a recording that has previously been stored with [`ReadRecording::save`].
+    #[cfg(feature = "std")]
+    pub fn load(mut reader: impl Read) -> Result<Self> {
+        let mut read = |buf: &mut [u8]| {
+            reader.read_exact(buf).map_err(|err| {
+                Error(ErrorOrigin::Connector, ErrorKind::UnableToReadFile).log_error(err)
+            })
+        };
+
+        let mut magic = [0u8; 4];
+        read(&mut magic)?;
+        if magic != RECORDING_MAGIC {
+            return Err(Error(ErrorOrigin::Connector, ErrorKind::Encoding)
+                .log_error("the file is not a memflow read recording"));
+        }
+
+        let mut u8_buf = [0u8; 1];
+        let mut u32_buf = [0u8; 4];
+        let mut u64_buf = [0u8; 8];
+
+        read(&mut u32_buf)?;
+        if u32::from_le_bytes(u32_buf) != RECORDING_VERSION {
+            return Err(Error(ErrorOrigin::Connector, ErrorKind::VersionMismatch)
+                .log_error("unsupported read recording version"));
+        }
+
+        read(&mut u64_buf)?;
+        let max_address = Address::from(u64::from_le_bytes(u64_buf));
+        read(&mut u64_buf)?;
+        let real_size = u64::from_le_bytes(u64_buf) as umem;
+        read(&mut u8_buf)?;
+        let readonly = u8_buf[0] != 0;
+        read(&mut u32_buf)?;
+        let ideal_batch_size = u32::from_le_bytes(u32_buf);
+
+        let mut recording = Self::new(PhysicalMemoryMetadata {
+            max_address,
+            real_size,
+            readonly,
+            ideal_batch_size,
+        });
+
+        let max_address = max_address.to_umem() as u64;
+
+        read(&mut u64_buf)?;
+        for _ in 0..u64::from_le_bytes(u64_buf) {
+            read(&mut u64_buf)?;
+            let base = u64::from_le_bytes(u64_buf);
+            read(&mut u64_buf)?;
+            let len = u64::from_le_bytes(u64_buf);
+
+            // reject entries outside of the recorded address space before allocating their buffer
+            if len != 0 && !matches!(base.checked_add(len - 1), Some(end) if end <= max_address) {
+                return Err(Error(ErrorOrigin::Connector, ErrorKind::Encoding)
+                    .log_error("read recording entry exceeds the physical address space"));
+            }
+
+            let base = Address::from(base);
+            let mut buf = vec![0; len as usize];
+            read(&mut buf)?;
+
+            recording.record(base, &buf);
+        }
+
+        Ok(recording)
+    }
+}
+
+/// Wraps a [`PhysicalMemory`] object and records all data that has been read successfully.
+#[derive(Clone)]
+pub struct ReadRecorder<T> {
+    mem: T,
+    recording: ReadRecording,
+}
+
+impl<T: PhysicalMemory> ReadRecorder<T> {
+    /// Constructs a new `ReadRecorder` around the given connector.
+    pub fn new(mem: T) -> Self {
+        let recording = ReadRecording::new(mem.metadata());
+        Self { mem, recording }
+    }
+
+    /// Returns the data that has been recorded so far.
+    pub fn recording(&self) -> &ReadRecording {
+        &self.recording
+    }
+
+    /// Consumes the recorder and returns the underlying connector and the recording.
+    pub fn into_inner(self) -> (T, ReadRecording) {
+        (self.mem, self.recording)
+    }
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for ReadRecorder<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        // the metadata address is replaced by the index of the request,
+        // so the physical address is known when the data arrives
+        let mut addrs = vec![];
+        let reqs = inp
+            .enumerate()
+            .map(|(i, CTup3(addr, meta_addr, buf))| {
+                addrs.push((addr.address(), meta_addr));
+                CTup3(addr, Address::from(i as umem), buf)
+            })
+            .collect::<Vec<_>>();
+
+        let recording = &mut self.recording;
+        let callback = &mut |CTup2(idx, buf): CTup2<Address, _>| {
+            let buf: CSliceMut<u8> = buf;
+            let (addr, meta_addr) = addrs[idx.to_umem() as usize];
+            recording.record(addr, &buf);
+            opt_call(out.as_deref_mut(), CTup2(meta_addr, buf))
+        };
+        let mut callback = callback.into();
+
+        let fail_callback = &mut |CTup2(idx, buf): CTup2<Address, _>| {
+            let (_, meta_addr) = addrs[idx.to_umem() as usize];
+            opt_call(out_fail.as_deref_mut(), CTup2(meta_addr, buf))
+        };
+        let mut fail_callback = fail_callback.into();
+
+        let mem = &mut self.mem;
+        MemOps::with_raw(
+            reqs.into_iter(),
+            Some(&mut callback),
+            Some(&mut fail_callback),
+            |data| mem.phys_read_raw_iter(data),
+        )
+    }
+
+    #[inline]
+    fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+        self.mem.phys_write_raw_iter(data)
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+/// Serves physical memory reads from a [`ReadRecording`].
+///
+/// Reads of data that has not been recorded fail. Writes are applied to the recording,
+/// so subsequent reads observe them just like they would on the original target.
+#[derive(Clone)]
+pub struct ReplayMemory {
+    recording: ReadRecording,
+}
+
+impl ReplayMemory {
+    /// Constructs a new `ReplayMemory` serving the given recording.
+    pub fn new(recording: ReadRecording) -> Self {
+        Self { recording }
+    }
+
+    /// Loads a stored recording and constructs a `ReplayMemory` from it.
+    #[cfg(feature = "std")]
+    pub fn load(reader: impl Read) -> Result<Self> {
+        ReadRecording::load(reader).map(Self::new)
+    }
+
+    /// Consumes this object and returns the underlying recording.
+    pub fn into_inner(self) -> ReadRecording {
+        self.recording
+    }
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl PhysicalMemory for ReplayMemory {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        for CTup3(addr, meta_addr, mut buf) in inp {
+            if self.recording.read_into(addr.address(), &mut buf) {
+                opt_call(out.as_deref_mut(), CTup2(meta_addr, buf));
+            } else {
+                opt_call(out_fail.as_deref_mut(), CTup2(meta_addr, buf));
+            }
+        }
+        Ok(())
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps { inp, mut out, .. }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        for CTup3(addr, meta_addr, buf) in inp {
+            self.recording.record(addr.address(), &buf);
+            opt_call(out.as_deref_mut(), CTup2(meta_addr, buf));
+        }
+        Ok(())
+    }
+
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.recording.metadata()
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    ReadRecorder<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(ReplayMemory, crate::plugins::ConnectorInstance, {});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::{size, PhysicalAddress};
+
+    #[test]
+    fn unrecorded_reads_fail() {
+        let mut mem = DummyMemory::new(size::mb(1));
+        mem.phys_write(0x1ffc.into(), &[1u8, 2, 3, 4, 5, 6, 7, 8])
+            .unwrap();
+
+        let mut recorder = ReadRecorder::new(mem);
+        let mut buf = [0u8; 4];
+        recorder.phys_read_into(0x1ffc.into(), &mut buf).unwrap();
+
+        let mut replay = ReplayMemory::new(recorder.into_inner().1);
+        assert_eq!(replay.recording.range_count(), 1);
+
+        let mut out = [0u8; 4];
+        replay.phys_read_into(0x1ffc.into(), &mut out).unwrap();
+        assert_eq!(out, [1, 2, 3, 4]);
+
+        let mut failed = false;
+        let mut out = [0xffu8; 8];
+        MemOps::with(
+            std::iter::once((PhysicalAddress::from(0x1ffc), CSliceMut::from(&mut out[..]))),
+            None,
+            Some(
+                &mut (&mut |_: ReadData| {
+                    failed = true;
+                    true
+                })
+                    .into(),
+            ),
+            |data| replay.phys_read_raw_iter(data),
+        )
+        .unwrap();
+        assert!(failed);
+    }
+
+    #[test]
+    fn merge_ranges() {
+        let mut recording = ReadRecording::new(DummyMemory::new(size::mb(1)).metadata());
+        recording.record(0x1000.into(), &[1, 2, 3, 4]);
+        recording.record(0x1008.into(), &[9, 10]);
+        assert_eq!(recording.range_count(), 2);
+
+        // bridges the gap between both ranges and overwrites parts of them
+        recording.record(0x1003.into(), &[5, 6, 7, 8, 0]);
+        assert_eq!(recording.range_count(), 1);
+
+        let mut out = [0u8; 10];
+        assert!(recording.read_into(0x1000.into(), &mut out));
+        assert_eq!(out, [1, 2, 3, 5, 6, 7, 8, 0, 9, 10]);
+
+        // adjacent data is merged as well
+        recording.record(0x100a.into(), &[11]);
+        assert_eq!(recording.range_count(), 1);
+        assert!(!recording.read_into(0x100a.into(), &mut out[..2]));
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut recording = ReadRecording::new(DummyMemory::new(size::mb(1)).metadata());
+        recording.record(0x1000.into(), &[1, 2, 3]);
+        recording.record(0x5fff.into(), &[4, 5]);
+
+        let mut bundle = vec![];
+        recording.save(&mut bundle).unwrap();
+        let loaded = ReadRecording::load(bundle.as_slice()).unwrap();
+
+        assert_eq!(loaded.range_count(), 2);
+        let mut out = [0u8; 2];
+        assert!(loaded.read_into(0x5fff.into(), &mut out));
+        assert_eq!(out, [4, 5]);
+        assert!(!loaded.read_into(0x1002.into(), &mut out));
+    }
+
+    #[test]
+    fn load_rejects_oversized_entry() {
+        let mut recording = ReadRecording::new(DummyMemory::new(size::mb(1)).metadata());
+        recording.record(0x1000.into(), &[1, 2, 3]);
+
+        let mut bundle = vec![];
+        recording.save(&mut bundle).unwrap();
+
+        // patch the length of the only entry
+        let len_offset = bundle.len() - 3 - 8;
+        bundle[len_offset..(len_offset + 8)].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        assert_eq!(
+            ReadRecording::load(bundle.as_slice()).err(),
+            Some(Error(ErrorOrigin::Connector, ErrorKind::Encoding))
+        );
+    }
+}
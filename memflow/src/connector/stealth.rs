@@ -0,0 +1,335 @@
+/*!
+Helper connector that keeps the footprint of memory accesses low.
+
+Some targets run integrity monitoring that looks for signs of introspection, e.g. by watching
+for modified pages, by measuring access latencies or by looking for sequential sweeps over
+physical memory. The [`StealthMemory`] wrapper applies a [`StealthProfile`] to all requests:
+writes are rejected, the rate of read requests is limited and the order of the
+requests in a batch is randomized.
+
+Repeated reads of the same (hot) pages are best avoided by putting a long lived page cache
+in front of the wrapper, see [`StealthProfile::build_cached`].
+*/
+
+use std::prelude::v1::*;
+
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::mem::{
+    mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata, PhysicalMemoryRun,
+};
+
+#[cfg(feature = "std")]
+use crate::architecture::ArchitectureObj;
+#[cfg(feature = "std")]
+use crate::mem::CachedPhysicalMemory;
+#[cfg(feature = "std")]
+use crate::types::{cache::TimedCacheValidator, PageType};
+#[cfg(feature = "std")]
+use coarsetime::{Duration, Instant};
+
+/// Describes how cautious a [`StealthMemory`] accesses the target.
+///
+/// The default profile rejects all writes, shuffles read batches, limits
+/// the amount of read requests to 1000 per second and keeps cached pages valid for 10 seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct StealthProfile {
+    allow_writes: bool,
+    shuffle: bool,
+    seed: u64,
+    #[cfg(feature = "std")]
+    max_reads_per_sec: Option<usize>,
+    #[cfg(feature = "std")]
+    cache_validity: Duration,
+}
+
+impl Default for StealthProfile {
+    fn default() -> Self {
+        Self {
+            allow_writes: false,
+            shuffle: true,
+            #[cfg(feature = "std")]
+            seed: Instant::now().as_ticks(),
+            #[cfg(not(feature = "std"))]
+            seed: 0x2545_f491_4f6c_dd1d,
+            #[cfg(feature = "std")]
+            max_reads_per_sec: Some(1000),
+            #[cfg(feature = "std")]
+            cache_validity: Duration::from_secs(10),
+        }
+    }
+}
+
+impl StealthProfile {
+    /// Creates the default profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows write requests to pass through to the backend.
+    pub fn allow_writes(mut self, allow_writes: bool) -> Self {
+        self.allow_writes = allow_writes;
+        self
+    }
+
+    /// Enables or disables randomizing the order of requests within a batch.
+    pub fn shuffle(mut self, shuffle: bool) -> Self {
+        self.shuffle = shuffle;
+        self
+    }
+
+    /// Sets the seed used to randomize the order of requests.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Limits the amount of read requests that are issued per second.
+    ///
+    /// Batches exceeding the limit are delayed until the next second starts.
+    /// `None` disables the limit.
+    #[cfg(feature = "std")]
+    pub fn max_reads_per_sec(mut self, max_reads_per_sec: Option<usize>) -> Self {
+        self.max_reads_per_sec = max_reads_per_sec;
+        self
+    }
+
+    /// Sets for how long pages stay valid in the cache created by [`StealthProfile::build_cached`].
+    #[cfg(feature = "std")]
+    pub fn cache_validity(mut self, cache_validity: Duration) -> Self {
+        self.cache_validity = cache_validity;
+        self
+    }
+
+    /// Wraps the given backend with this profile.
+    pub fn build<T: PhysicalMemory>(self, mem: T) -> StealthMemory<T> {
+        StealthMemory::new(mem, self)
+    }
+
+    /// Wraps the given backend with this profile and puts a page cache in front of it.
+    ///
+    /// Unlike the default cache configuration, all pages are cached, regardless of their type.
+    #[cfg(feature = "std")]
+    pub fn build_cached<'a, T: PhysicalMemory>(
+        self,
+        mem: T,
+        arch: impl Into<ArchitectureObj>,
+    ) -> Result<CachedPhysicalMemory<'a, StealthMemory<T>, TimedCacheValidator>> {
+        CachedPhysicalMemory::builder(self.build(mem))
+            .arch(arch)
+            .validator(TimedCacheValidator::new(self.cache_validity))
+            .page_type_mask(PageType::all())
+            .build()
+    }
+}
+
+/// Wraps a [`PhysicalMemory`] backend and applies a [`StealthProfile`] to all requests.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::StealthProfile;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// let mut mem = StealthProfile::new().build(mem);
+///
+/// let mut value = 0u64;
+/// mem.phys_read_into(0x1000.into(), &mut value).unwrap();
+/// assert!(mem.phys_write(0x1000.into(), &value).is_err());
+/// ```
+#[derive(Clone)]
+pub struct StealthMemory<T> {
+    mem: T,
+    profile: StealthProfile,
+    rng: u64,
+    #[cfg(feature = "std")]
+    window_start: Instant,
+    #[cfg(feature = "std")]
+    window_reads: usize,
+}
+
+impl<T: PhysicalMemory> StealthMemory<T> {
+    /// Constructs a new `StealthMemory` with the given profile.
+    pub fn new(mem: T, profile: StealthProfile) -> Self {
+        Self {
+            mem,
+            profile,
+            // xorshift must not be seeded with 0
+            rng: profile.seed | 1,
+            #[cfg(feature = "std")]
+            window_start: Instant::now(),
+            #[cfg(feature = "std")]
+            window_reads: 0,
+        }
+    }
+
+    /// Returns the profile used by this connector.
+    pub fn profile(&self) -> &StealthProfile {
+        &self.profile
+    }
+
+    /// Consumes the wrapper and returns the underlying backend.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn shuffle<D>(&mut self, data: &mut [D]) {
+        for i in (1..data.len()).rev() {
+            let j = (self.next_random() % (i as u64 + 1)) as usize;
+            data.swap(i, j);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn throttle(&mut self, count: usize) {
+        let limit = match self.profile.max_reads_per_sec {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let window = Duration::from_secs(1);
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= window {
+            self.window_start = Instant::now();
+            self.window_reads = 0;
+        } else if self.window_reads > 0 && self.window_reads + count > limit {
+            std::thread::sleep(std::time::Duration::from_millis(
+                (window - elapsed).as_millis(),
+            ));
+            self.window_start = Instant::now();
+            self.window_reads = 0;
+        }
+
+        self.window_reads += count;
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn throttle(&mut self, _count: usize) {}
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for StealthMemory<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let mut reqs = inp.collect::<Vec<_>>();
+        if reqs.is_empty() {
+            return Ok(());
+        }
+
+        if self.profile.shuffle {
+            self.shuffle(&mut reqs);
+        }
+        self.throttle(reqs.len());
+
+        MemOps::with_raw(
+            reqs.into_iter(),
+            out.as_deref_mut(),
+            out_fail.as_deref_mut(),
+            |data| self.mem.phys_read_raw_iter(data),
+        )
+    }
+
+    fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+        if !self.profile.allow_writes {
+            return Err(Error(ErrorOrigin::Connector, ErrorKind::ReadOnly)
+                .log_trace("write rejected by stealth profile"));
+        }
+        self.mem.phys_write_raw_iter(data)
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        let mut metadata = self.mem.metadata();
+        metadata.readonly |= !self.profile.allow_writes;
+        metadata
+    }
+
+    fn memory_runs(&self, mut out: PhysicalMemoryRunCallback) {
+        let readonly = !self.profile.allow_writes;
+        self.mem.memory_runs(
+            (&mut |mut run: PhysicalMemoryRun| {
+                run.readonly |= readonly;
+                out.call(run)
+            })
+                .into(),
+        )
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    StealthMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::MemoryView;
+    use crate::types::{size, Address};
+
+    #[test]
+    fn shuffled_reads() {
+        let mut dummy = DummyMemory::new(size::kb(64));
+        for i in 0..16u64 {
+            dummy.phys_write((i * 0x1000).into(), &i).unwrap();
+        }
+
+        let mut mem = StealthProfile::new()
+            .seed(0x1234)
+            .max_reads_per_sec(None)
+            .build(dummy);
+        assert!(mem.metadata().readonly);
+
+        let mut values = [0u64; 16];
+        {
+            let mut view = mem.phys_view();
+            let mut batch = view.batcher();
+            for (i, value) in values.iter_mut().enumerate() {
+                batch.read_into(Address::from(i as u64 * 0x1000), value);
+            }
+            batch.commit_rw().unwrap();
+        }
+
+        assert!(values.iter().enumerate().all(|(i, &v)| v == i as u64));
+        assert_eq!(
+            mem.phys_write(0.into(), &0u64),
+            Err(Error(ErrorOrigin::Connector, ErrorKind::ReadOnly))
+        );
+    }
+
+    #[test]
+    fn writes_allowed() {
+        let mut mem = StealthProfile::new()
+            .allow_writes(true)
+            .build(DummyMemory::new(size::kb(64)));
+        assert!(!mem.metadata().readonly);
+
+        mem.phys_write(0x1000.into(), &0xdeadu32).unwrap();
+        let mut value = 0u32;
+        mem.phys_read_into(0x1000.into(), &mut value).unwrap();
+        assert_eq!(value, 0xdead);
+    }
+}
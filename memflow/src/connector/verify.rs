@@ -0,0 +1,312 @@
+/*!
+Helper connector that verifies writes by reading them back.
+
+On unreliable links (e.g. some DMA devices) writes can get dropped silently.
+The [`VerifiedPhysicalMemory`] wrapper reads back every successfully written range
+and compares it with the data that was supposed to be written.
+*/
+
+use std::prelude::v1::*;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{umem, Address};
+
+/// Wraps a [`PhysicalMemory`] backend and verifies all writes by reading them back.
+///
+/// Reads are passed through unmodified. Every written range is read back after the write completed.
+/// Ranges that could not be read back or contain different data are reported through the
+/// `out_fail` callback and the write returns an error of kind [`ErrorKind::WriteVerificationFailed`].
+///
+/// When requests within a single batch overlap, the last request wins and every request
+/// is only compared against the bytes that were not overwritten by a later one.
+///
+/// # Examples
+/// ```
+/// use memflow::connector::VerifiedPhysicalMemory;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+///
+/// let mut mem = VerifiedPhysicalMemory::new(mem);
+/// mem.phys_write(0x1000.into(), &0xdead_beefu32).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct VerifiedPhysicalMemory<T> {
+    mem: T,
+    readback: Vec<u8>,
+}
+
+impl<T: PhysicalMemory> VerifiedPhysicalMemory<T> {
+    /// Constructs a new `VerifiedPhysicalMemory` around the given backend.
+    pub fn new(mem: T) -> Self {
+        Self {
+            mem,
+            readback: vec![],
+        }
+    }
+
+    /// Consumes this wrapper and returns the underlying backend.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+}
+
+/// Computes the bytes every successfully written request still owns after the whole batch was applied.
+///
+/// Returns `None` for requests that were not overwritten by a later request at all, otherwise
+/// the owned ranges relative to the start of the request.
+fn owned_ranges(reqs: &[PhysicalWriteData], written: &[bool]) -> Vec<Option<Vec<Range<usize>>>> {
+    let mut owned = vec![None; reqs.len()];
+    // disjoint ranges that were written by later requests, indexed by their start address
+    let mut claimed = BTreeMap::<umem, umem>::new();
+
+    for (i, CTup3(addr, _, data)) in reqs.iter().enumerate().rev() {
+        if !written[i] || data.is_empty() {
+            continue;
+        }
+
+        let start = addr.to_umem();
+        let end = start + data.len() as umem;
+
+        let mut overlapping = vec![];
+        if let Some((&s, &e)) = claimed.range(..start).next_back() {
+            if e > start {
+                overlapping.push((s, e));
+            }
+        }
+        overlapping.extend(claimed.range(start..end).map(|(&s, &e)| (s, e)));
+
+        if !overlapping.is_empty() {
+            let mut ranges = vec![];
+            let mut pos = start;
+            for &(s, e) in &overlapping {
+                if s > pos {
+                    ranges.push((pos - start) as usize..(s - start) as usize);
+                }
+                pos = core::cmp::max(pos, e);
+            }
+            if pos < end {
+                ranges.push((pos - start) as usize..(end - start) as usize);
+            }
+            owned[i] = Some(ranges);
+        }
+
+        let (mut claim_start, mut claim_end) = (start, end);
+        for (s, e) in overlapping {
+            claimed.remove(&s);
+            claim_start = core::cmp::min(claim_start, s);
+            claim_end = core::cmp::max(claim_end, e);
+        }
+        claimed.insert(claim_start, claim_end);
+    }
+
+    owned
+}
+
+#[allow(clippy::needless_option_as_deref)]
+impl<T: PhysicalMemory> PhysicalMemory for VerifiedPhysicalMemory<T> {
+    #[inline]
+    fn phys_read_raw_iter(&mut self, data: PhysicalReadMemOps) -> Result<()> {
+        self.mem.phys_read_raw_iter(data)
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        let reqs = inp.collect::<Vec<_>>();
+
+        if reqs.is_empty() {
+            return Ok(());
+        }
+
+        // the metadata address is replaced by the index of the request
+        let mut written = vec![false; reqs.len()];
+        {
+            let iter = reqs.iter().enumerate().map(|(i, CTup3(addr, _, data))| {
+                CTup3(*addr, Address::from(i as umem), CSliceRef::from(&**data))
+            });
+
+            let callback = &mut |CTup2(idx, _): WriteData| {
+                written[idx.to_umem() as usize] = true;
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(iter, Some(&mut callback), None, |data| {
+                self.mem.phys_write_raw_iter(data)
+            })?;
+        }
+
+        let owned = owned_ranges(&reqs, &written);
+
+        let mut verified = vec![false; reqs.len()];
+        {
+            let mem = &mut self.mem;
+
+            self.readback.clear();
+            self.readback.resize(
+                reqs.iter()
+                    .zip(written.iter())
+                    .filter(|(_, written)| **written)
+                    .map(|(CTup3(_, _, data), _)| data.len())
+                    .sum(),
+                0,
+            );
+
+            let mut readback = self.readback.as_mut_slice();
+            let iter = reqs
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| written[*i])
+                .map(|(i, CTup3(addr, _, data))| {
+                    let (buf, rest) = std::mem::take(&mut readback).split_at_mut(data.len());
+                    readback = rest;
+                    CTup3(*addr, Address::from(i as umem), CSliceMut::from(buf))
+                })
+                .collect::<Vec<_>>();
+
+            let callback = &mut |CTup2(idx, buf): ReadData| {
+                let idx = idx.to_umem() as usize;
+                let data = &*reqs[idx].2;
+                verified[idx] = match &owned[idx] {
+                    None => *buf == *data,
+                    Some(ranges) => ranges.iter().all(|r| buf[r.clone()] == data[r.clone()]),
+                };
+                true
+            };
+            let mut callback = callback.into();
+
+            MemOps::with_raw(iter.into_iter(), Some(&mut callback), None, |data| {
+                mem.phys_read_raw_iter(data)
+            })?;
+        }
+
+        let mut mismatch = false;
+
+        for ((CTup3(_, meta_addr, data), written), verified) in
+            reqs.into_iter().zip(written).zip(verified)
+        {
+            if verified {
+                opt_call(out.as_deref_mut(), CTup2(meta_addr, data));
+            } else {
+                mismatch = mismatch || written;
+                opt_call(out_fail.as_deref_mut(), CTup2(meta_addr, data));
+            }
+        }
+
+        if mismatch {
+            Err(Error(
+                ErrorOrigin::Connector,
+                ErrorKind::WriteVerificationFailed,
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    VerifiedPhysicalMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    /// Backend that silently drops every write.
+    struct DroppingMemory(DummyMemory);
+
+    impl PhysicalMemory for DroppingMemory {
+        fn phys_read_raw_iter(&mut self, data: PhysicalReadMemOps) -> Result<()> {
+            self.0.phys_read_raw_iter(data)
+        }
+
+        fn phys_write_raw_iter(&mut self, mut data: PhysicalWriteMemOps) -> Result<()> {
+            for CTup3(_, meta_addr, buf) in data.inp {
+                opt_call(data.out.as_deref_mut(), CTup2(meta_addr, buf));
+            }
+            Ok(())
+        }
+
+        fn metadata(&self) -> PhysicalMemoryMetadata {
+            self.0.metadata()
+        }
+    }
+
+    #[test]
+    fn verified_write() {
+        let mut mem = VerifiedPhysicalMemory::new(DummyMemory::new(size::kb(64)));
+        mem.phys_write(0x1000.into(), &[1u8, 2, 3, 4]).unwrap();
+
+        let mut out = [0u8; 4];
+        mem.phys_read_into(0x1000.into(), &mut out).unwrap();
+        assert_eq!(out, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn overlapping_writes() {
+        let mut mem = VerifiedPhysicalMemory::new(DummyMemory::new(size::kb(64)));
+
+        let writes = vec![
+            (0x1000.into(), [1u8; 8].as_ref()),
+            (0x1002.into(), [2u8; 4].as_ref()),
+            (0x1004.into(), [3u8; 8].as_ref()),
+        ];
+        MemOps::with(
+            writes
+                .into_iter()
+                .map(|(addr, data)| (addr, CSliceRef::from(data))),
+            None,
+            None,
+            |data| mem.phys_write_raw_iter(data),
+        )
+        .unwrap();
+
+        let mut out = [0u8; 12];
+        mem.phys_read_into(0x1000.into(), &mut out).unwrap();
+        assert_eq!(out, [1, 1, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn dropped_write() {
+        let mut mem = VerifiedPhysicalMemory::new(DroppingMemory(DummyMemory::new(size::kb(64))));
+        assert_eq!(
+            mem.phys_write(0x1000.into(), &[1u8, 2, 3, 4]),
+            Err(Error(
+                ErrorOrigin::Connector,
+                ErrorKind::WriteVerificationFailed
+            ))
+        );
+    }
+}
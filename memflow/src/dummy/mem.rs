@@ -65,12 +65,21 @@ impl PhysicalMemory for DummyMemory {
         self.mem.metadata()
     }
 
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
     #[inline]
     fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
         self.mem.set_mem_map(mem_map)
     }
 }
 
+/// Parses the `size` argument of the dummy connector.
+///
+/// Unlike [`Args::get_size`] the number is read as hex and a unit is required,
+/// so `size=10m` is 16 MiB. This format is kept for compatibility with existing setups.
 pub fn parse_size(args: &Args) -> Result<usize> {
     let (size, size_mul) = {
         let size = args.get("size").unwrap_or("2m");
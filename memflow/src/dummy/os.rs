@@ -272,6 +272,10 @@ impl DummyOs {
                 command_line: "/some/dummy --dummyarg".into(),
                 sys_arch: x64::ARCH.ident(),
                 proc_arch: x64::ARCH.ident(),
+                parent_pid: 0,
+                session_id: 0,
+                create_time: 0,
+                exit_time: 0,
             },
             dtb,
             map_size,
@@ -599,6 +603,7 @@ impl PhysicalMemory for DummyOs {
 #[no_mangle]
 pub static MEMFLOW_OS_DUMMY: OsDescriptor = OsDescriptor {
     plugin_version: MEMFLOW_PLUGIN_VERSION,
+    compatible_version: MEMFLOW_PLUGIN_VERSION,
     input_layout: <<LoadableOs as Loadable>::CInputArg as ::abi_stable::StableAbi>::LAYOUT,
     output_layout: <<LoadableOs as Loadable>::Instance as ::abi_stable::StableAbi>::LAYOUT,
     name: CSliceRef::from_str("dummy"),
@@ -606,6 +611,7 @@ pub static MEMFLOW_OS_DUMMY: OsDescriptor = OsDescriptor {
     description: CSliceRef::from_str("Dummy testing OS"),
     help_callback: None, // TODO: add dummy help string
     target_list_callback: None,
+    arg_list_callback: None,
     create: mf_create,
 };
 
@@ -231,6 +231,8 @@ pub enum ErrorKind {
     ImportNotFound,
     SectionNotFound,
 
+    WriteVerificationFailed,
+    TargetUnhealthy,
     Unknown,
 }
 
@@ -292,6 +294,8 @@ impl ErrorKind {
             ErrorKind::ImportNotFound => "import not found",
             ErrorKind::SectionNotFound => "section not found",
 
+            ErrorKind::WriteVerificationFailed => "written memory could not be verified",
+            ErrorKind::TargetUnhealthy => "target is unhealthy and rejects requests",
             ErrorKind::Unknown => "unknown error",
         }
     }
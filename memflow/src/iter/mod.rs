@@ -54,6 +54,44 @@ pub trait FlowIters: Iterator {
     {
         DoublePeekingIterator::<Self>::new(self)
     }
+
+    /// Remove the parts of address-buffer pairs that fall into any of the given holes
+    ///
+    /// Chunks that lie partially inside of a hole are split, only the parts outside of the holes
+    /// are yielded. Both the chunks and the holes have to be sorted in ascending order, and the
+    /// holes must not overlap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::prelude::{FlowIters, PageChunks, Address};
+    ///
+    /// let buffer = vec![0u8; 0x400];
+    /// let holes = [Address::from(0x180)..Address::from(0x280)];
+    ///
+    /// let chunks = buffer
+    ///     .page_chunks(0.into(), 0x100)
+    ///     .skip_holes(&holes)
+    ///     .map(|(addr, chunk)| (addr, chunk.len()))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// assert_eq!(
+    ///     chunks,
+    ///     vec![
+    ///         (Address::from(0x000), 0x100),
+    ///         (Address::from(0x100), 0x80),
+    ///         (Address::from(0x280), 0x80),
+    ///         (Address::from(0x300), 0x100),
+    ///     ]
+    /// );
+    /// ```
+    fn skip_holes<T>(self, holes: &[core::ops::Range<Address>]) -> SkipHoles<'_, Self, T>
+    where
+        Self: Iterator<Item = (Address, T)> + Sized,
+        T: SplitAtIndex,
+    {
+        SkipHoles::new(self, holes)
+    }
 }
 
 impl<T> FlowIters for T where T: Iterator {}
@@ -104,6 +142,10 @@ pub trait PageChunks {
         self.mem_chunks(start_address, page_size as umem)
     }
 
+    /// Create a chunk iterator with arbitrary alignment
+    ///
+    /// This is the same function as `page_chunks`, but the chunk size does not have to be a
+    /// page size. Useful for splitting buffers at large page, cache line or any other boundaries.
     fn mem_chunks(
         self,
         start_address: Address,
@@ -184,14 +226,60 @@ pub trait PageChunks {
     {
         PageChunkIterator::new(self, start_address, mem_size, split_fn)
     }
+
+    /// Create an aligned chunk iterator with overlapping windows
+    ///
+    /// Every chunk is extended by up to `overlap` bytes of the following chunks. This is useful
+    /// for scanning memory for patterns that may straddle a chunk boundary: with an overlap of
+    /// the pattern length minus one every match is fully contained in one of the windows.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_address` - starting address of the buffer
+    /// * `mem_size` - size of a single chunk
+    /// * `overlap` - number of bytes each window extends into the next chunk
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::prelude::{PageChunks, Address};
+    ///
+    /// let buffer = [0u8; 0x300];
+    ///
+    /// let windows = (&buffer[..])
+    ///     .mem_chunks_overlapping(0x80.into(), 0x100, 0x10)
+    ///     .map(|(addr, window)| (addr, window.len()))
+    ///     .collect::<Vec<_>>();
+    ///
+    /// assert_eq!(
+    ///     windows,
+    ///     vec![
+    ///         (Address::from(0x80), 0x90),
+    ///         (Address::from(0x100), 0x110),
+    ///         (Address::from(0x200), 0x110),
+    ///         (Address::from(0x300), 0x80),
+    ///     ]
+    /// );
+    /// ```
+    fn mem_chunks_overlapping(
+        self,
+        start_address: Address,
+        mem_size: umem,
+        overlap: umem,
+    ) -> OverlappingChunkIterator<Self>
+    where
+        Self: SplitAtIndex + Clone + Sized,
+    {
+        OverlappingChunkIterator::new(self, start_address, mem_size, overlap)
+    }
 }
 
 impl<T> PageChunks for T where T: SplitAtIndex {}
 
 #[cfg(test)]
 mod tests {
-    use crate::iter::PageChunks;
-    use crate::types::Address;
+    use crate::iter::{FlowIters, PageChunks};
+    use crate::types::{umem, Address};
 
     const PAGE_SIZE: usize = 97;
     const OFF: usize = 26;
@@ -325,6 +413,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pc_check_overlapping() {
+        let arr = [0_u8; 0x1000];
+        let overlap = 7;
+
+        let windows = (&arr[..])
+            .mem_chunks_overlapping(OFF.into(), PAGE_SIZE as umem, overlap)
+            .collect::<Vec<_>>();
+        let chunks = arr.page_chunks(OFF.into(), PAGE_SIZE).collect::<Vec<_>>();
+
+        assert_eq!(windows.len(), chunks.len());
+
+        for ((addr, window), (chunk_addr, chunk)) in windows.iter().zip(chunks.iter()) {
+            assert_eq!(addr, chunk_addr);
+            assert_eq!(window.as_ptr(), chunk.as_ptr());
+
+            let remaining = OFF + arr.len() - addr.to_umem() as usize;
+            assert_eq!(
+                window.len(),
+                core::cmp::min(chunk.len() + overlap as usize, remaining)
+            );
+        }
+    }
+
+    #[test]
+    fn pc_check_skip_holes() {
+        let arr = [0_u8; 0x1000];
+        let holes = [
+            Address::from(0x0)..Address::from(0x10),
+            Address::from(0x150)..Address::from(0x350),
+            Address::from(0x400)..Address::from(0x401),
+            Address::from(0xff0)..Address::from(0x2000),
+        ];
+
+        let chunks = arr
+            .page_chunks(Address::null(), 0x100)
+            .skip_holes(&holes)
+            .collect::<Vec<_>>();
+
+        for (addr, chunk) in chunks.iter() {
+            let end = *addr + chunk.len();
+            assert!(!chunk.is_empty());
+            assert!(holes.iter().all(|h| end <= h.start || h.end <= *addr));
+            assert_eq!(
+                addr.as_page_aligned(0x100),
+                (end - 1_usize).as_page_aligned(0x100)
+            );
+        }
+
+        let total = chunks.iter().map(|(_, c)| c.len()).sum::<usize>();
+        assert_eq!(total, 0x1000 - 0x10 - 0x200 - 0x1 - 0x10);
+    }
+
     #[test]
     fn pc_check_empty() {
         let arr = [0_u8; 0];
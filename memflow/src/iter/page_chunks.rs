@@ -1,6 +1,7 @@
 use crate::cglue::{CSliceMut, CSliceRef, CTup2, CTup3};
 use crate::types::{clamp_to_usize, imem, umem, Address};
 use core::convert::TryInto;
+use core::ops::Range;
 use std::iter::*;
 
 pub trait SplitAtIndex {
@@ -487,3 +488,101 @@ impl<T: SplitAtIndex, FS: FnMut(Address, &T, Option<&T>) -> bool> Iterator
         }
     }
 }
+
+/// Iterator over chunks that extend into the following chunk.
+///
+/// Created by [`mem_chunks_overlapping`](crate::iter::PageChunks::mem_chunks_overlapping).
+pub struct OverlappingChunkIterator<T> {
+    buf: T,
+    start_address: Address,
+    mem_size: umem,
+    overlap: umem,
+    cur_off: umem,
+}
+
+impl<T: SplitAtIndex> OverlappingChunkIterator<T> {
+    pub fn new(buf: T, start_address: Address, mem_size: umem, overlap: umem) -> Self {
+        Self {
+            buf,
+            start_address,
+            mem_size,
+            overlap,
+            cur_off: 0,
+        }
+    }
+}
+
+impl<T: SplitAtIndex + Clone> Iterator for OverlappingChunkIterator<T> {
+    type Item = (Address, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cur_off >= self.buf.length() {
+            return None;
+        }
+
+        let address = self.start_address + self.cur_off;
+        let chunk_len = self.mem_size - address.to_umem() % self.mem_size;
+
+        let (_, rest) = self.buf.clone().split_at(self.cur_off);
+        let (window, _) = rest?.split_at(chunk_len.saturating_add(self.overlap));
+
+        self.cur_off += chunk_len;
+
+        window.map(|window| (address, window))
+    }
+}
+
+/// Iterator adaptor that removes the parts of chunks which fall into holes.
+///
+/// Created by [`skip_holes`](crate::iter::FlowIters::skip_holes).
+pub struct SkipHoles<'a, I, T> {
+    iter: I,
+    holes: &'a [Range<Address>],
+    pending: Option<(Address, T)>,
+}
+
+impl<'a, I, T> SkipHoles<'a, I, T> {
+    pub fn new(iter: I, holes: &'a [Range<Address>]) -> Self {
+        debug_assert!(holes.windows(2).all(|w| w[0].end <= w[1].start));
+        Self {
+            iter,
+            holes,
+            pending: None,
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = (Address, T)>, T: SplitAtIndex> Iterator for SkipHoles<'a, I, T> {
+    type Item = (Address, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (address, buf) = match self.pending.take() {
+                Some(pending) => pending,
+                None => self.iter.next()?,
+            };
+            let end = address + buf.length();
+
+            // chunks are in ascending order, holes before the current chunk are not needed anymore
+            while matches!(self.holes.first(), Some(hole) if hole.end <= address) {
+                self.holes = &self.holes[1..];
+            }
+
+            let hole = match self.holes.first() {
+                Some(hole) if hole.start < end => hole.clone(),
+                _ => return Some((address, buf)),
+            };
+
+            if hole.start <= address {
+                let (_, rest) = buf.split_at((hole.end - address) as umem);
+                self.pending = rest.map(|rest| (hole.end, rest));
+            } else {
+                let (head, rest) = buf.split_at((hole.start - address) as umem);
+                self.pending = rest.map(|rest| (hole.start, rest));
+                if let Some(head) = head {
+                    return Some((address, head));
+                }
+            }
+        }
+    }
+}
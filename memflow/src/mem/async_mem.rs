@@ -0,0 +1,214 @@
+/*!
+Asynchronous variants of the physical and virtual memory traits.
+
+Connectors that talk to a remote host or to hardware spend most of their time waiting for
+responses. Implementing [`AsyncPhysicalMemory`] (or [`AsyncMemoryView`]) allows them to keep
+many requests in flight at the same time, e.g. by joining the futures of several reads.
+
+Requests take owned buffers and are issued through shared references, so a single connector
+can be used from multiple futures concurrently. Synchronous connectors can be used through
+the [`SyncAdapter`], which serializes all requests.
+*/
+
+use std::prelude::v1::*;
+
+use crate::dataview::Pod;
+use crate::error::{PartialResultExt, Result};
+use crate::mem::{MemoryView, PhysicalMemory, PhysicalMemoryMetadata};
+use crate::types::{Address, PhysicalAddress};
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+/// A boxed future as returned by the async memory traits.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Asynchronous access to physical memory.
+pub trait AsyncPhysicalMemory: Send + Sync {
+    /// Reads `buf.len()` bytes at `addr` and returns the filled buffer.
+    fn phys_read_raw_async(
+        &self,
+        addr: PhysicalAddress,
+        buf: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Vec<u8>>>;
+
+    /// Writes `data` to `addr`.
+    fn phys_write_raw_async(
+        &self,
+        addr: PhysicalAddress,
+        data: Vec<u8>,
+    ) -> BoxFuture<'_, Result<()>>;
+
+    /// Retrieves metadata about the physical memory.
+    fn metadata(&self) -> PhysicalMemoryMetadata;
+
+    /// Reads a value of type `T` at `addr`.
+    fn phys_read_async<T: Pod + Default + Send>(
+        &self,
+        addr: PhysicalAddress,
+    ) -> BoxFuture<'_, Result<T>>
+    where
+        Self: Sized,
+    {
+        let read = self.phys_read_raw_async(addr, vec![0; std::mem::size_of::<T>()]);
+        Box::pin(async move {
+            let buf = read.await?;
+            let mut out = T::default();
+            out.as_bytes_mut().copy_from_slice(&buf);
+            Ok(out)
+        })
+    }
+}
+
+/// Asynchronous access to a virtual address space.
+pub trait AsyncMemoryView: Send + Sync {
+    /// Reads `buf.len()` bytes at `addr` and returns the filled buffer.
+    ///
+    /// Parts that could not be read are zero filled.
+    fn read_raw_async(&self, addr: Address, buf: Vec<u8>) -> BoxFuture<'_, Result<Vec<u8>>>;
+
+    /// Writes `data` to `addr`.
+    fn write_raw_async(&self, addr: Address, data: Vec<u8>) -> BoxFuture<'_, Result<()>>;
+
+    /// Reads a value of type `T` at `addr`.
+    fn read_async<T: Pod + Default + Send>(&self, addr: Address) -> BoxFuture<'_, Result<T>>
+    where
+        Self: Sized,
+    {
+        let read = self.read_raw_async(addr, vec![0; std::mem::size_of::<T>()]);
+        Box::pin(async move {
+            let buf = read.await?;
+            let mut out = T::default();
+            out.as_bytes_mut().copy_from_slice(&buf);
+            Ok(out)
+        })
+    }
+}
+
+/// Exposes a synchronous connector or memory view through the async traits.
+///
+/// Requests are executed one after another while the future is polled, so this adapter does
+/// not overlap any requests. It allows writing code against the async traits that works
+/// with all existing connectors.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::async_mem::{AsyncPhysicalMemory, SyncAdapter};
+///
+/// async fn read_header(mem: &impl AsyncPhysicalMemory) -> u64 {
+///     mem.phys_read_async::<u64>(0x1000.into())
+///         .await
+///         .unwrap()
+/// }
+/// # use memflow::types::size;
+/// # let mem = SyncAdapter::new(memflow::dummy::DummyMemory::new(size::mb(2)));
+/// # let _ = read_header(&mem);
+/// ```
+pub struct SyncAdapter<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> SyncAdapter<T> {
+    /// Wraps the given connector.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Consumes the adapter and returns the wrapped connector.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut inner)
+    }
+}
+
+impl<T: PhysicalMemory> AsyncPhysicalMemory for SyncAdapter<T> {
+    fn phys_read_raw_async(
+        &self,
+        addr: PhysicalAddress,
+        mut buf: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Vec<u8>>> {
+        Box::pin(async move {
+            self.with(|mem| mem.phys_read_into(addr, buf.as_mut_slice()))?;
+            Ok(buf)
+        })
+    }
+
+    fn phys_write_raw_async(
+        &self,
+        addr: PhysicalAddress,
+        data: Vec<u8>,
+    ) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.with(|mem| mem.phys_write(addr, data.as_slice())) })
+    }
+
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.with(|mem| mem.metadata())
+    }
+}
+
+impl<T: MemoryView> AsyncMemoryView for SyncAdapter<T> {
+    fn read_raw_async(&self, addr: Address, mut buf: Vec<u8>) -> BoxFuture<'_, Result<Vec<u8>>> {
+        Box::pin(async move {
+            self.with(|mem| mem.read_raw_into(addr, buf.as_mut_slice()))
+                .data_part()?;
+            Ok(buf)
+        })
+    }
+
+    fn write_raw_async(&self, addr: Address, data: Vec<u8>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            self.with(|mem| mem.write_raw(addr, data.as_slice()))
+                .data_part()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    // the futures of the adapter never wait, so a single poll is sufficient
+    fn poll_once<F: Future>(fut: F) -> F::Output {
+        fn noop_raw() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                noop_raw()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(noop_raw()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(out) => out,
+            Poll::Pending => panic!("future is not ready"),
+        }
+    }
+
+    #[test]
+    fn sync_adapter() {
+        let mem = SyncAdapter::new(DummyMemory::new(size::kb(64)));
+
+        poll_once(mem.phys_write_raw_async(0x1000.into(), vec![1, 2, 3, 4])).unwrap();
+        let value = poll_once(mem.phys_read_async::<u32>(0x1000.into())).unwrap();
+        assert_eq!(value, 0x0403_0201);
+
+        let view = SyncAdapter::new(mem.into_inner().into_phys_view());
+        let buf = poll_once(view.read_raw_async(0x1001.into(), vec![0; 2])).unwrap();
+        assert_eq!(buf, vec![2, 3]);
+    }
+}
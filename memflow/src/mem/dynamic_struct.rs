@@ -0,0 +1,271 @@
+/*!
+Structures with field offsets that are only known at runtime.
+
+Kernel structures like `_EPROCESS` change their layout between builds of the operating system.
+Instead of compiling a `#[repr(C)]` definition for every build, a [`StructLayout`] maps the
+field names to their offsets and is filled in at runtime, e.g. from a PDB or from an
+[`OffsetTable`] stored in a TOML file:
+
+```toml
+[structs._EPROCESS]
+size = 0xa40
+
+[structs._EPROCESS.fields]
+UniqueProcessId = { offset = 0x440, size = 8 }
+ImageFileName = { offset = 0x5a8, size = 15 }
+```
+
+A [`DynamicStruct`] binds a layout to an address and reads or writes single fields by name.
+*/
+
+use std::prelude::v1::*;
+
+use crate::dataview::Pod;
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+use std::collections::BTreeMap;
+
+/// The location of a single field within a structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FieldLayout {
+    /// Offset of the field from the start of the structure.
+    pub offset: umem,
+    /// Size of the field in bytes. When set, accesses with a differently sized type fail.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub size: Option<usize>,
+}
+
+/// The field offsets of a single structure.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct StructLayout {
+    /// Total size of the structure in bytes, 0 if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub size: umem,
+    /// All known fields, indexed by their name.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fields: BTreeMap<String, FieldLayout>,
+}
+
+impl StructLayout {
+    /// Creates an empty layout for a structure of `size` bytes.
+    pub fn new(size: umem) -> Self {
+        Self {
+            size,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a field at `offset` whose size is not checked.
+    pub fn field(mut self, name: &str, offset: umem) -> Self {
+        self.fields
+            .insert(name.to_string(), FieldLayout { offset, size: None });
+        self
+    }
+
+    /// Adds a field of `size` bytes at `offset`.
+    pub fn sized_field(mut self, name: &str, offset: umem, size: usize) -> Self {
+        self.fields.insert(
+            name.to_string(),
+            FieldLayout {
+                offset,
+                size: Some(size),
+            },
+        );
+        self
+    }
+
+    /// Looks up a field and verifies that it can be accessed as `size` bytes.
+    pub fn get(&self, name: &str, size: usize) -> Result<&FieldLayout> {
+        let field = self.fields.get(name).ok_or_else(|| {
+            Error(ErrorOrigin::Memory, ErrorKind::NotFound)
+                .log_trace(format!("field {} not found in struct layout", name))
+        })?;
+
+        match field.size {
+            Some(field_size) if field_size != size => {
+                Err(
+                    Error(ErrorOrigin::Memory, ErrorKind::InvalidArgument).log_trace(format!(
+                        "field {} is {} bytes long, but was accessed with {} bytes",
+                        name, field_size, size
+                    )),
+                )
+            }
+            _ => Ok(field),
+        }
+    }
+}
+
+/// A set of structure layouts, indexed by the name of the structure.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct OffsetTable {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub structs: BTreeMap<String, StructLayout>,
+}
+
+impl OffsetTable {
+    /// Parses an offset table from a TOML string.
+    #[cfg(all(feature = "toml", feature = "serde"))]
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        ::toml::from_str(contents).map_err(|err| {
+            Error(ErrorOrigin::Memory, ErrorKind::Configuration)
+                .log_error(format!("unable to parse the offset table: {}", err))
+        })
+    }
+
+    /// Returns the layout of the structure `name`.
+    pub fn get(&self, name: &str) -> Result<&StructLayout> {
+        self.structs.get(name).ok_or_else(|| {
+            Error(ErrorOrigin::Memory, ErrorKind::NotFound)
+                .log_trace(format!("struct {} not found in offset table", name))
+        })
+    }
+
+    /// Binds the layout of the structure `name` to the given address.
+    pub fn bind(&self, name: &str, base: Address) -> Result<DynamicStruct<'_>> {
+        Ok(DynamicStruct::new(self.get(name)?, base))
+    }
+}
+
+/// A structure at a fixed address whose fields are accessed by name.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::dynamic_struct::{DynamicStruct, StructLayout};
+/// use memflow::mem::{MemoryView, PhysicalMemory};
+/// use memflow::types::Address;
+/// # use memflow::dummy::DummyMemory;
+/// # use memflow::types::size;
+/// # let mut mem = DummyMemory::new(size::mb(2));
+/// let mut view = mem.phys_view();
+///
+/// // the offsets would usually be loaded from a file or a PDB
+/// let layout = StructLayout::new(0xa40)
+///     .sized_field("UniqueProcessId", 0x440, 8)
+///     .sized_field("ImageFileName", 0x5a8, 15);
+///
+/// let eprocess = DynamicStruct::new(&layout, Address::from(0x1000));
+/// eprocess.write_field(&mut view, "UniqueProcessId", &4u64).unwrap();
+///
+/// let pid: u64 = eprocess.read_field(&mut view, "UniqueProcessId").unwrap();
+/// assert_eq!(pid, 4);
+///
+/// // accessing a field with the wrong size fails
+/// assert!(eprocess.read_field::<u32, _>(&mut view, "UniqueProcessId").is_err());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicStruct<'a> {
+    layout: &'a StructLayout,
+    base: Address,
+}
+
+impl<'a> DynamicStruct<'a> {
+    /// Binds `layout` to the structure located at `base`.
+    pub fn new(layout: &'a StructLayout, base: Address) -> Self {
+        Self { layout, base }
+    }
+
+    /// Returns the address of the structure.
+    pub fn base(&self) -> Address {
+        self.base
+    }
+
+    /// Returns the layout of the structure.
+    pub fn layout(&self) -> &'a StructLayout {
+        self.layout
+    }
+
+    /// Returns the address of the field `name`.
+    pub fn field_address(&self, name: &str) -> Result<Address> {
+        self.layout
+            .fields
+            .get(name)
+            .map(|field| self.base + field.offset)
+            .ok_or_else(|| {
+                Error(ErrorOrigin::Memory, ErrorKind::NotFound)
+                    .log_trace(format!("field {} not found in struct layout", name))
+            })
+    }
+
+    /// Reads the field `name` as a value of type `T`.
+    pub fn read_field<T: Pod + Sized, M: MemoryView>(&self, mem: &mut M, name: &str) -> Result<T> {
+        let field = self.layout.get(name, std::mem::size_of::<T>())?;
+        mem.read(self.base + field.offset).data()
+    }
+
+    /// Writes `data` into the field `name`.
+    pub fn write_field<T: Pod + ?Sized, M: MemoryView>(
+        &self,
+        mem: &mut M,
+        name: &str,
+        data: &T,
+    ) -> Result<()> {
+        let field = self.layout.get(name, std::mem::size_of_val(data))?;
+        mem.write(self.base + field.offset, data).data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn read_unsized_field() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        let layout = StructLayout::new(0x20).field("Flags", 0x10);
+        let s = DynamicStruct::new(&layout, Address::from(0x2000));
+
+        view.write(Address::from(0x2010), &0x1234_5678u32).unwrap();
+        assert_eq!(
+            s.read_field::<u32, _>(&mut view, "Flags").unwrap(),
+            0x1234_5678
+        );
+        assert_eq!(s.read_field::<u16, _>(&mut view, "Flags").unwrap(), 0x5678);
+        assert_eq!(s.field_address("Flags").unwrap(), Address::from(0x2010));
+        assert!(s.read_field::<u32, _>(&mut view, "Missing").is_err());
+    }
+
+    #[cfg(all(feature = "toml", feature = "serde"))]
+    #[test]
+    fn parse_offset_table() {
+        let table = OffsetTable::from_toml_str(
+            r#"
+[structs._EPROCESS]
+size = 0xa40
+
+[structs._EPROCESS.fields]
+UniqueProcessId = { offset = 0x440, size = 8 }
+ImageFileName = { offset = 0x5a8 }
+"#,
+        )
+        .unwrap();
+
+        let eprocess = table.get("_EPROCESS").unwrap();
+        assert_eq!(eprocess.size, 0xa40);
+        assert_eq!(
+            eprocess.fields["UniqueProcessId"],
+            FieldLayout {
+                offset: 0x440,
+                size: Some(8)
+            }
+        );
+        assert_eq!(eprocess.fields["ImageFileName"].size, None);
+
+        let bound = table.bind("_EPROCESS", Address::from(0x1000)).unwrap();
+        assert_eq!(
+            bound.field_address("ImageFileName").unwrap(),
+            Address::from(0x15a8)
+        );
+        assert!(table.get("_KPROCESS").is_err());
+    }
+}
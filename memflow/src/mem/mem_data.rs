@@ -1,5 +1,6 @@
 //! Generic address and buffer association structure.
 
+use crate::mem::phys_mem::PhysicalMemoryRun;
 use crate::types::{umem, Address, PageType, PhysicalAddress};
 use cglue::callback::{Callbackable, OpaqueCallback};
 use cglue::iter::CIterator;
@@ -51,6 +52,8 @@ pub type WriteCallback<'a, 'b> = OpaqueCallback<'a, WriteData<'b>>;
 
 pub type MemoryRangeCallback<'a> = OpaqueCallback<'a, MemoryRange>;
 
+pub type PhysicalMemoryRunCallback<'a> = OpaqueCallback<'a, PhysicalMemoryRun>;
+
 /// Data needed to perform memory operations.
 ///
 /// `inp` is an iterator containing
@@ -33,6 +33,8 @@
 use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 
 use super::MemoryView;
+use crate::error::PartialResultExt;
+use crate::os::ModuleInfo;
 use crate::types::{umem, Address};
 
 /// MemoryCursor implments a Cursor around the [`VirtualMemory`] trait.
@@ -360,6 +362,157 @@ impl<T: MemoryView> Seek for MemoryCursor<T> {
     }
 }
 
+/// A cursor over a fixed range of an address space, like a single module.
+///
+/// In contrast to [`MemoryCursor`], positions are relative to the start of the range and
+/// reads stop at its end. This lets parsers that expect a file (e.g. pelite or goblin) consume
+/// a module directly from the target. Parts of the range that can not be read, like paged out
+/// sections, are returned as zeroes instead of failing the entire read.
+///
+/// # Examples:
+///
+/// ```
+/// use std::io::{self, Read, Seek, SeekFrom};
+///
+/// use memflow::dummy::DummyOs;
+/// use memflow::mem::VirtualMemoryCursor;
+/// use memflow::os::Process;
+/// use memflow::types::size;
+///
+/// fn main() -> io::Result<()> {
+///     let proc = DummyOs::quick_process(size::mb(2), b"MZ");
+///     let base = proc.info().address;
+///
+///     let mut cursor = VirtualMemoryCursor::new(proc, base, size::kb(8) as _);
+///
+///     let mut magic = [0u8; 2];
+///     cursor.read_exact(&mut magic)?;
+///     assert_eq!(&magic, b"MZ");
+///
+///     // the whole range can be consumed like a file
+///     cursor.seek(SeekFrom::Start(0))?;
+///     let mut image = vec![];
+///     cursor.read_to_end(&mut image)?;
+///     assert_eq!(image.len(), size::kb(8));
+///
+///     Ok(())
+/// }
+/// ```
+pub struct VirtualMemoryCursor<T> {
+    mem: T,
+    base: Address,
+    size: umem,
+    pos: umem,
+}
+
+impl<T: MemoryView> VirtualMemoryCursor<T> {
+    /// Creates a new cursor over the range `base..base + size`.
+    ///
+    /// Cursor initial position is `0`, which corresponds to `base`.
+    pub fn new(mem: T, base: Address, size: umem) -> Self {
+        Self {
+            mem,
+            base,
+            size,
+            pos: 0,
+        }
+    }
+
+    /// Creates a new cursor over the image of the given module.
+    pub fn from_module(mem: T, module: &ModuleInfo) -> Self {
+        Self::new(mem, module.base, module.size)
+    }
+
+    /// Consumes this cursor, returning the underlying [`MemoryView`] object.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+
+    /// Gets a reference to the underlying [`MemoryView`] object in this cursor.
+    pub fn get_ref(&self) -> &T {
+        &self.mem
+    }
+
+    /// Gets a mutable reference to the underlying [`MemoryView`] object in this cursor.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.mem
+    }
+
+    /// Returns the start address of the range.
+    pub fn base(&self) -> Address {
+        self.base
+    }
+
+    /// Returns the size of the range in bytes.
+    pub fn size(&self) -> umem {
+        self.size
+    }
+
+    /// Returns the address the cursor currently points to.
+    pub fn address(&self) -> Address {
+        self.base + self.pos
+    }
+
+    /// Returns the amount of bytes that can be accessed from the current position.
+    fn remaining(&self, len: usize) -> usize {
+        std::cmp::min(len as umem, self.size.saturating_sub(self.pos)) as usize
+    }
+}
+
+impl<T: MemoryView> Read for VirtualMemoryCursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let len = self.remaining(buf.len());
+        self.mem
+            .read_raw_into(self.address(), &mut buf[..len])
+            .data_part()
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        self.pos += len as umem;
+        Ok(len)
+    }
+}
+
+impl<T: MemoryView> Write for VirtualMemoryCursor<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let len = self.remaining(buf.len());
+        self.mem
+            .write_raw(self.address(), &buf[..len])
+            .map_err(|err| Error::new(ErrorKind::Other, err))?;
+        self.pos += len as umem;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: MemoryView> Seek for VirtualMemoryCursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let target_pos = match pos {
+            SeekFrom::Start(offs) => Some(offs as umem),
+            SeekFrom::End(offs) => apply_offset(self.size, offs),
+            SeekFrom::Current(offs) => apply_offset(self.pos, offs),
+        }
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        self.pos = target_pos;
+        Ok(target_pos as u64)
+    }
+}
+
+fn apply_offset(pos: umem, offs: i64) -> Option<umem> {
+    if offs >= 0 {
+        pos.checked_add(offs as umem)
+    } else {
+        pos.checked_sub(offs.unsigned_abs() as umem)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -505,4 +658,26 @@ mod tests {
         assert_eq!(cursor.read(&mut read_buf).unwrap(), 4); // read 4 bytes from the 512th byte
         assert_eq!(read_buf, write_buf); // compare buffers
     }
+
+    #[test]
+    fn virtual_range_read() {
+        let (mut virt_mem, virt_base) = dummy_virt_mem();
+        virt_mem
+            .write(virt_base + 0x10usize, &0xdead_beef_u32)
+            .unwrap();
+
+        let mut cursor = VirtualMemoryCursor::new(virt_mem, virt_base + 0x10usize, 0x20);
+
+        let mut read_buf = [0u8; 4];
+        cursor.read_exact(&mut read_buf).unwrap();
+        assert_eq!(u32::from_le_bytes(read_buf), 0xdead_beef);
+
+        // reads are clamped to the end of the range
+        assert_eq!(cursor.seek(SeekFrom::End(-2)).unwrap(), 0x1e);
+        assert_eq!(cursor.read(&mut read_buf).unwrap(), 2);
+        assert_eq!(cursor.read(&mut read_buf).unwrap(), 0);
+
+        assert!(cursor.seek(SeekFrom::Current(-0x21)).is_err());
+        assert_eq!(cursor.address(), virt_base + 0x30usize);
+    }
 }
@@ -2,12 +2,14 @@ use super::mem_data::*;
 
 use crate::prelude::v1::{Result, *};
 
-use std::mem::MaybeUninit;
+use smallvec::SmallVec;
 use std::prelude::v1::*;
 
 pub mod arch_overlay;
 pub mod batcher;
 pub mod remap_view;
+pub mod transaction;
+pub mod validity;
 
 #[cfg(feature = "std")]
 pub mod cursor;
@@ -15,9 +17,18 @@ pub mod cursor;
 pub use arch_overlay::ArchOverlayView;
 pub use batcher::MemoryViewBatcher;
 pub use remap_view::RemapView;
+pub use transaction::{QueuedWrite, WriteTransaction};
+pub use validity::ReadValidity;
 
 #[cfg(feature = "std")]
-pub use cursor::MemoryCursor;
+pub use cursor::{MemoryCursor, VirtualMemoryCursor};
+
+/// Scratch buffer used by the string helpers.
+///
+/// Most strings read from the target are short, so they are read into a stack buffer
+/// and only spill into a heap allocation once they exceed it. The decoded string is written
+/// into a caller provided `String` by the `_into` variants of the helpers.
+type StringBuffer = SmallVec<[u8; 64]>;
 
 /// The `MemoryView` trait implements generic access to memory, no matter if it is a process
 /// virtual memory, or machine's physical memory.
@@ -181,6 +192,65 @@ pub trait MemoryView: Send {
         self.read_raw_into(addr, &mut *buf).map_data(|_| buf)
     }
 
+    /// Reads `out.len()` bytes at `addr` and reports which parts of the buffer were filled.
+    ///
+    /// In contrast to [`MemoryView::read_raw_into`] a failure to read parts of the range is not
+    /// reported as an error. Instead the returned [`ReadValidity`] holds the exact ranges that were
+    /// read, all other bytes of `out` are zeroed. This is useful when dumping large ranges
+    /// that contain paged out sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn dump(mem: &mut impl MemoryView, base: Address, size: usize) -> Vec<u8> {
+    ///     let mut buf = vec![0u8; size];
+    ///     let validity = mem.read_raw_partial(base, &mut buf).unwrap();
+    ///     for (addr, len) in validity.invalid_ranges() {
+    ///         println!("unable to read {:x} bytes at {:x}", len, addr);
+    ///     }
+    ///     # assert_eq!(validity.valid_bytes(), 0x1000);
+    ///     buf
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let virt_base = proc.info().address;
+    /// # dump(&mut proc, virt_base + size::mb(2) - 0x1000usize, 0x2000);
+    /// ```
+    #[skip_func]
+    fn read_raw_partial(&mut self, addr: Address, out: &mut [u8]) -> Result<ReadValidity>
+    where
+        Self: Sized,
+    {
+        let len = out.len() as umem;
+        let mut valid = vec![];
+
+        let on_read = &mut |CTup2(addr, d): ReadData| {
+            valid.push((addr, d.len() as umem));
+            true
+        };
+
+        let on_fail = &mut |CTup2(_, mut d): ReadData| {
+            for v in d.iter_mut() {
+                *v = 0;
+            }
+            true
+        };
+
+        MemOps::with_raw(
+            std::iter::once(CTup3(addr, addr, out.into())),
+            Some(&mut on_read.into()),
+            Some(&mut on_fail.into()),
+            |data| self.read_raw_iter(data),
+        )?;
+
+        Ok(ReadValidity::new(addr, len, valid))
+    }
+
     #[skip_func]
     fn read_into<T: Pod + ?Sized>(&mut self, addr: Address, out: &mut T) -> PartialResult<()>
     where
@@ -189,24 +259,71 @@ pub trait MemoryView: Send {
         self.read_raw_into(addr, out.as_bytes_mut())
     }
 
+    /// Reads a `T` into a value on the stack.
+    ///
+    /// Bytes that could not be read are left zeroed.
     #[skip_func]
-    #[allow(clippy::uninit_assumed_init)]
     fn read<T: Pod + Sized>(&mut self, addr: Address) -> PartialResult<T>
     where
         Self: Sized,
     {
-        let mut obj: T = unsafe { MaybeUninit::uninit().assume_init() };
-        // TODO: zero out on partial
+        let mut obj = T::zeroed();
         self.read_into(addr, &mut obj).map_data(|_| obj)
     }
 
+    /// Reads a `T` stored in the byte order of the target.
+    ///
+    /// The byte order is taken from [`MemoryView::metadata`], the value is byte swapped if it
+    /// differs from the byte order memflow runs on. Use [`MemoryView::read`] to read the raw
+    /// bytes instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::mem::{MemoryView, PhysicalMemory};
+    /// use memflow::types::Address;
+    /// use memflow::derive::{ByteSwap, Pod};
+    /// # use memflow::dummy::DummyMemory;
+    /// # use memflow::types::size;
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone, Copy, Pod, ByteSwap)]
+    /// struct Header {
+    ///     magic: u32,
+    ///     len: u32,
+    /// }
+    ///
+    /// # let mut mem = DummyMemory::new(size::mb(1));
+    /// // treat the memory as a big-endian target
+    /// let mut mem = mem.phys_view().into_overlay_arch_parts(32, false);
+    /// mem.write_raw(Address::from(0x1000), &[0xfe, 0xed, 0xfa, 0xce, 0, 0, 0, 0x10])
+    ///     .unwrap();
+    ///
+    /// let header: Header = mem.read_endian(Address::from(0x1000)).unwrap();
+    /// assert_eq!(header.magic, 0xfeedface);
+    /// assert_eq!(header.len, 0x10);
+    /// ```
+    #[skip_func]
+    fn read_endian<T: Pod + ByteSwap + Sized>(&mut self, addr: Address) -> PartialResult<T>
+    where
+        Self: Sized,
+    {
+        let swap = needs_byte_swap(self.metadata().little_endian);
+        self.read::<T>(addr).map_data(|mut obj| {
+            if swap {
+                obj.byte_swap();
+            }
+            obj
+        })
+    }
+
     // TODO: allow cglue to somehow pass MaybeUninit to the IntError
     #[skip_func]
     fn read_addr32(&mut self, addr: Address) -> PartialResult<Address>
     where
         Self: Sized,
     {
-        self.read::<u32>(addr).map_data(|d| d.into())
+        self.read_endian::<u32>(addr).map_data(|d| d.into())
     }
 
     #[skip_func]
@@ -214,17 +331,23 @@ pub trait MemoryView: Send {
     where
         Self: Sized,
     {
-        self.read::<u64>(addr).map_data(|d| d.into())
+        self.read_endian::<u64>(addr).map_data(|d| d.into())
     }
 
+    /// Reads a pointer sized address of `arch`, in the byte order of `arch`.
     #[skip_func]
     fn read_addr_arch(&mut self, arch: ArchitectureObj, addr: Address) -> PartialResult<Address>
     where
         Self: Sized,
     {
+        let swap = needs_byte_swap(arch.endianess() == Endianess::LittleEndian);
         match arch.bits() {
-            64 => self.read_addr64(addr),
-            32 => self.read_addr32(addr),
+            64 => self
+                .read::<u64>(addr)
+                .map_data(|d| if swap { d.swap_bytes() } else { d }.into()),
+            32 => self
+                .read::<u32>(addr)
+                .map_data(|d| if swap { d.swap_bytes() } else { d }.into()),
             _ => Err(PartialError::Error(Error(
                 ErrorOrigin::VirtualMemory,
                 ErrorKind::InvalidArchitecture,
@@ -366,6 +489,57 @@ pub trait MemoryView: Send {
         self.write_raw(addr, data.as_bytes())
     }
 
+    /// Writes multiple values of the same type with a single batched request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn reset_ammo(mem: &mut impl MemoryView, weapons: &[Address]) {
+    ///     let writes = weapons.iter().map(|&w| (w + 0x10usize, 30u32)).collect::<Vec<_>>();
+    ///     mem.write_list(&writes).unwrap();
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let virt_base = proc.info().address;
+    /// # reset_ammo(&mut proc, &[virt_base, virt_base + 0x2000usize]);
+    /// # assert_eq!(proc.read::<u32>(virt_base + 0x2010usize).unwrap(), 30);
+    /// ```
+    #[skip_func]
+    fn write_list<T: Pod>(&mut self, data: &[(Address, T)]) -> PartialResult<()>
+    where
+        Self: Sized,
+    {
+        let list = data
+            .iter()
+            .map(|(addr, value)| CTup2(*addr, value.as_bytes().into()))
+            .collect::<Vec<_>>();
+        self.write_raw_list(&list)
+    }
+
+    /// Writes `data` in the byte order of the target.
+    ///
+    /// This is the counterpart of [`MemoryView::read_endian`].
+    #[skip_func]
+    fn write_endian<T: Pod + ByteSwap + Copy>(
+        &mut self,
+        addr: Address,
+        data: &T,
+    ) -> PartialResult<()>
+    where
+        Self: Sized,
+    {
+        let mut data = *data;
+        if needs_byte_swap(self.metadata().little_endian) {
+            data.byte_swap();
+        }
+        self.write(addr, &data)
+    }
+
     #[skip_func]
     fn write_ptr<U: PrimitiveAddress, T: Pod + ?Sized>(
         &mut self,
@@ -387,12 +561,102 @@ pub trait MemoryView: Send {
     /// If no null terminator is found the resulting string is exactly `len` characters long.
     #[skip_func]
     fn read_char_array(&mut self, addr: Address, len: usize) -> PartialResult<String> {
-        let mut buf = vec![0; len];
+        let mut out = String::new();
+        self.read_char_array_into(addr, len, &mut out)
+            .map_data(|_| out)
+    }
+
+    /// Reads a fixed length string from the target into `out`.
+    ///
+    /// Behaves like [`read_char_array`](Self::read_char_array), but reuses the allocation of `out`.
+    #[skip_func]
+    fn read_char_array_into(
+        &mut self,
+        addr: Address,
+        len: usize,
+        out: &mut String,
+    ) -> PartialResult<()> {
+        let mut buf = StringBuffer::from_elem(0, len);
         self.read_raw_into(addr, &mut buf).data_part()?;
-        if let Some((n, _)) = buf.iter().enumerate().find(|(_, c)| **c == 0_u8) {
-            buf.truncate(n);
+        decode_char_array(&buf, out);
+        Ok(())
+    }
+
+    /// Reads a list of fixed length strings from the target in a single batch.
+    ///
+    /// The strings are read into `scratch` and decoded into `out`, which receives one string
+    /// per address. Both buffers keep their allocations, so reusing them across calls avoids
+    /// allocating while iterating over large lists of objects.
+    ///
+    /// Strings that could not be read are returned empty and the function returns
+    /// `PartialError::PartialVirtualRead`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn print_names(mem: &mut impl MemoryView, names: &[Address]) {
+    ///     let mut scratch = vec![];
+    ///     let mut out = vec![];
+    ///
+    ///     for _ in 0..3 {
+    ///         mem.read_char_array_list(names, 16, &mut scratch, &mut out).unwrap();
+    ///         for name in &out {
+    ///             println!("{}", name);
+    ///             # assert_eq!(name, "explorer.exe");
+    ///         }
+    ///     }
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), b"explorer.exe\0");
+    /// # let virt_base = proc.info().address;
+    /// # print_names(&mut proc, &[virt_base, virt_base]);
+    /// ```
+    #[skip_func]
+    fn read_char_array_list(
+        &mut self,
+        addrs: &[Address],
+        len: usize,
+        scratch: &mut Vec<u8>,
+        out: &mut Vec<String>,
+    ) -> PartialResult<()>
+    where
+        Self: Sized,
+    {
+        let mut result = Ok(());
+
+        scratch.clear();
+        scratch.resize(addrs.len() * len, 0);
+
+        if len > 0 {
+            let callback = &mut |CTup2(_, mut d): ReadData| {
+                result = Err(PartialError::PartialVirtualRead(()));
+                for v in d.iter_mut() {
+                    *v = 0;
+                }
+                true
+            };
+
+            let iter = addrs
+                .iter()
+                .zip(scratch.chunks_mut(len))
+                .map(|(&addr, buf)| CTup3(addr, addr, buf.into()));
+
+            MemOps::with_raw(iter, None, Some(&mut callback.into()), |data| {
+                self.read_raw_iter(data)
+            })?;
         }
-        Ok(String::from_utf8_lossy(&buf).to_string())
+
+        out.resize_with(addrs.len(), String::new);
+        for (i, s) in out.iter_mut().enumerate() {
+            decode_char_array(&scratch[(i * len)..((i + 1) * len)], s);
+        }
+
+        result
     }
 
     /// Reads a variable length string with a length of up to specified amount from the target.
@@ -410,7 +674,22 @@ pub trait MemoryView: Send {
     /// For reading fixed-size char arrays the [`read_char_array`](Self::read_char_array) should be used.
     #[skip_func]
     fn read_char_string_n(&mut self, addr: Address, n: usize) -> PartialResult<String> {
-        let mut buf = vec![0; std::cmp::min(32, n)];
+        let mut out = String::new();
+        self.read_char_string_n_into(addr, n, &mut out)
+            .map_data(|_| out)
+    }
+
+    /// Reads a variable length string with a length of up to specified amount from the target into `out`.
+    ///
+    /// Behaves like [`read_char_string_n`](Self::read_char_string_n), but reuses the allocation of `out`.
+    #[skip_func]
+    fn read_char_string_n_into(
+        &mut self,
+        addr: Address,
+        n: usize,
+        out: &mut String,
+    ) -> PartialResult<()> {
+        let mut buf = StringBuffer::from_elem(0, std::cmp::min(32, n));
 
         let mut last_n = 0;
 
@@ -419,8 +698,8 @@ pub trait MemoryView: Send {
 
             self.read_raw_into(addr + last_n, right).data_part()?;
             if let Some((n, _)) = right.iter().enumerate().find(|(_, c)| **c == 0_u8) {
-                buf.truncate(last_n + n);
-                return Ok(String::from_utf8_lossy(&buf).to_string());
+                decode_char_array(&buf[..(last_n + n)], out);
+                return Ok(());
             }
             if buf.len() >= n {
                 break;
@@ -446,6 +725,248 @@ pub trait MemoryView: Send {
         self.read_char_string_n(addr, 4096)
     }
 
+    /// Reads a fixed length UTF-16 string from the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - target address to read from
+    /// * `len` - length of the string in UTF-16 code units
+    ///
+    /// # Remarks:
+    ///
+    /// Characters are expected to be little-endian, as it is the case on Windows.
+    /// If a null terminator is found the string is truncated to the terminator.
+    /// Invalid surrogates are replaced with `U+FFFD`.
+    #[skip_func]
+    fn read_wide_char_array(&mut self, addr: Address, len: usize) -> PartialResult<String> {
+        let mut out = String::new();
+        self.read_wide_char_array_into(addr, len, &mut out)
+            .map_data(|_| out)
+    }
+
+    /// Reads a fixed length UTF-16 string from the target into `out`.
+    ///
+    /// Behaves like [`read_wide_char_array`](Self::read_wide_char_array), but reuses the allocation of `out`.
+    #[skip_func]
+    fn read_wide_char_array_into(
+        &mut self,
+        addr: Address,
+        len: usize,
+        out: &mut String,
+    ) -> PartialResult<()> {
+        let mut buf = StringBuffer::from_elem(0, len * 2);
+        self.read_raw_into(addr, &mut buf).data_part()?;
+        decode_utf16_le(&buf, out);
+        Ok(())
+    }
+
+    /// Reads a null-terminated UTF-16 string with a length of up to `n` code units from the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - target address to read from
+    /// * `n` - maximum number of UTF-16 code units to read
+    ///
+    /// # Remarks:
+    ///
+    /// Characters are expected to be little-endian, as it is the case on Windows.
+    /// If no null terminator is found the this function will return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn read_name(mem: &mut impl MemoryView, addr: Address) -> String {
+    ///     mem.read_wide_string_n(addr, 260).unwrap()
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let name = "notepad.exe\0".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>();
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &name);
+    /// # let virt_base = proc.info().address;
+    /// # assert_eq!(read_name(&mut proc, virt_base), "notepad.exe");
+    /// ```
+    #[skip_func]
+    fn read_wide_string_n(&mut self, addr: Address, n: usize) -> PartialResult<String> {
+        let mut out = String::new();
+        self.read_wide_string_n_into(addr, n, &mut out)
+            .map_data(|_| out)
+    }
+
+    /// Reads a null-terminated UTF-16 string with a length of up to `n` code units from the target into `out`.
+    ///
+    /// Behaves like [`read_wide_string_n`](Self::read_wide_string_n), but reuses the allocation of `out`.
+    #[skip_func]
+    fn read_wide_string_n_into(
+        &mut self,
+        addr: Address,
+        n: usize,
+        out: &mut String,
+    ) -> PartialResult<()> {
+        let mut buf = StringBuffer::from_elem(0, std::cmp::min(32, n) * 2);
+
+        let mut last_n = 0;
+
+        loop {
+            let (_, right) = buf.split_at_mut(last_n);
+
+            self.read_raw_into(addr + last_n, right).data_part()?;
+            if let Some(n) = right.chunks_exact(2).position(|c| c == [0, 0]) {
+                decode_utf16_le(&buf[..(last_n + n * 2)], out);
+                return Ok(());
+            }
+            if buf.len() >= n * 2 {
+                break;
+            }
+            last_n = buf.len();
+
+            buf.extend((0..buf.len()).map(|_| 0));
+        }
+
+        Err(PartialError::Error(Error(
+            ErrorOrigin::VirtualMemory,
+            ErrorKind::OutOfBounds,
+        )))
+    }
+
+    /// Reads a null-terminated UTF-16 string with up to 4096 code units from the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - target address to read from
+    #[skip_func]
+    fn read_wide_string(&mut self, addr: Address) -> PartialResult<String> {
+        self.read_wide_string_n(addr, 4096)
+    }
+
+    /// Reads a Windows `_UNICODE_STRING` structure and the string it points to.
+    ///
+    /// The layout of the structure depends on the pointer width of `arch`. The string
+    /// does not have to be null-terminated, its length is taken from the structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn read_image_name(mem: &mut impl MemoryView, unicode_string: Address) -> String {
+    ///     mem.read_unicode_string(x64::ARCH, unicode_string).unwrap()
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let virt_base = proc.info().address;
+    /// # let name = "ntdll.dll".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>();
+    /// # proc.write(virt_base, &(name.len() as u16)).unwrap();
+    /// # proc.write(virt_base + 8usize, &(virt_base + 0x10usize).to_umem()).unwrap();
+    /// # proc.write(virt_base + 0x10usize, name.as_slice()).unwrap();
+    /// # assert_eq!(read_image_name(&mut proc, virt_base), "ntdll.dll");
+    /// ```
+    #[skip_func]
+    fn read_unicode_string(&mut self, arch: ArchitectureObj, addr: Address) -> PartialResult<String>
+    where
+        Self: Sized,
+    {
+        let len: u16 = self.read(addr).data_part()?;
+        let buffer = self
+            .read_addr_arch(arch, addr + arch.size_addr())
+            .data_part()?;
+
+        let mut buf = StringBuffer::from_elem(0, len as usize);
+        self.read_raw_into(buffer, &mut buf).data_part()?;
+        let mut out = String::new();
+        decode_utf16_le(&buf, &mut out);
+        Ok(out)
+    }
+
+    /// Streams a large memory range through a fixed size buffer.
+    ///
+    /// The range is read in chunks of up to `chunk_size` bytes. Each chunk is passed
+    /// to `callback` together with its address, so arbitrarily large ranges can be processed
+    /// without allocating a buffer for the entire range.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - target address to start reading from
+    /// * `len` - number of bytes to read
+    /// * `chunk_size` - maximum size of a single chunk
+    /// * `callback` - called for every chunk, returning `false` stops the stream
+    ///
+    /// # Remarks:
+    ///
+    /// Parts of a chunk that could not be read are zeroed out and the function
+    /// returns `PartialError::PartialVirtualRead` after the stream finished.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Address;
+    /// use memflow::mem::MemoryView;
+    ///
+    /// fn checksum(mem: &mut impl MemoryView, addr: Address) -> u64 {
+    ///     let mut sum = 0u64;
+    ///     mem.read_stream(addr, 0x10000, 0x1000, |_, chunk| {
+    ///         sum += chunk.iter().map(|&b| b as u64).sum::<u64>();
+    ///         true
+    ///     })
+    ///     .unwrap();
+    ///     sum
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::types::size;
+    /// # use memflow::os::Process;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let virt_base = proc.info().address;
+    /// # checksum(&mut proc, virt_base);
+    /// ```
+    #[skip_func]
+    fn read_stream<F>(
+        &mut self,
+        addr: Address,
+        len: umem,
+        chunk_size: usize,
+        mut callback: F,
+    ) -> PartialResult<()>
+    where
+        Self: Sized,
+        F: FnMut(Address, &[u8]) -> bool,
+    {
+        let chunk_size = std::cmp::min(std::cmp::max(chunk_size, 1) as umem, len) as usize;
+        let mut buf = vec![0u8; chunk_size];
+
+        let mut partial = false;
+        let mut offset: umem = 0;
+
+        while offset < len {
+            let chunk_len = std::cmp::min(chunk_size as umem, len - offset) as usize;
+            let chunk = &mut buf[..chunk_len];
+
+            match self.read_raw_into(addr + offset, chunk) {
+                Ok(_) => {}
+                Err(PartialError::PartialVirtualRead(_)) => partial = true,
+                Err(err) => return Err(err),
+            }
+
+            if !callback(addr + offset, chunk) {
+                break;
+            }
+
+            offset += chunk_len as umem;
+        }
+
+        if partial {
+            Err(PartialError::PartialVirtualRead(()))
+        } else {
+            Ok(())
+        }
+    }
+
     // TODO: batcher
 
     #[cfg(feature = "std")]
@@ -485,13 +1006,21 @@ pub trait MemoryView: Send {
     }
 
     #[skip_func]
-    fn batcher(&mut self) -> MemoryViewBatcher<Self>
+    fn batcher(&mut self) -> MemoryViewBatcher<'_, Self>
     where
         Self: Sized,
     {
         MemoryViewBatcher::new(self)
     }
 
+    #[skip_func]
+    fn transaction(&mut self) -> WriteTransaction<'_, Self>
+    where
+        Self: Sized,
+    {
+        WriteTransaction::new(self)
+    }
+
     #[skip_func]
     fn into_overlay_arch(self, arch: ArchitectureObj) -> ArchOverlayView<Self>
     where
@@ -556,3 +1085,27 @@ pub struct MemoryViewMetadata {
     pub little_endian: bool,
     pub arch_bits: u8,
 }
+
+/// Returns whether data of a target with the given byte order has to be swapped.
+fn needs_byte_swap(little_endian: bool) -> bool {
+    little_endian != cfg!(target_endian = "little")
+}
+
+/// Decodes a char array up to the first null character into `out`.
+fn decode_char_array(buf: &[u8], out: &mut String) {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    out.clear();
+    out.push_str(&String::from_utf8_lossy(&buf[..len]));
+}
+
+/// Decodes little-endian UTF-16 up to the first null character into `out`.
+fn decode_utf16_le(buf: &[u8], out: &mut String) {
+    let units = buf
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&c| c != 0);
+    out.clear();
+    out.extend(
+        std::char::decode_utf16(units).map(|c| c.unwrap_or(std::char::REPLACEMENT_CHARACTER)),
+    );
+}
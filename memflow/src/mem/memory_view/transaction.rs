@@ -0,0 +1,194 @@
+//! Writes that are applied to the target all at once, or not at all.
+use std::prelude::v1::*;
+
+use super::*;
+use crate::dataview::Pod;
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::types::Address;
+
+/// A single write that is queued in a [`WriteTransaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedWrite {
+    /// Target address of the write.
+    pub address: Address,
+    /// The bytes that will be written.
+    pub data: Vec<u8>,
+}
+
+/// Queues writes and applies them to the target in a single batch.
+///
+/// Patches that span multiple locations, like a hook and its trampoline, must never be
+/// applied partially to a live target. A transaction collects all writes first. They can be
+/// inspected, reordered or removed before [`commit`](Self::commit) sends them out as one write
+/// list. If any part of the commit fails, the previous contents of all written locations
+/// are restored.
+///
+/// Dropping a transaction without committing it discards all queued writes.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::MemoryView;
+/// # use memflow::dummy::DummyOs;
+/// # use memflow::os::Process;
+/// # use memflow::types::size;
+/// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+/// # let base = proc.info().address;
+///
+/// let mut tx = proc.transaction();
+/// tx.write(base, &0xe9u8).write(base + 1usize, &0x1000u32);
+///
+/// // the writes can be inspected before they reach the target
+/// assert_eq!(tx.writes().len(), 2);
+/// tx.commit().unwrap();
+///
+/// assert_eq!(proc.read::<u8>(base).unwrap(), 0xe9);
+/// ```
+pub struct WriteTransaction<'a, T: MemoryView> {
+    mem: &'a mut T,
+    writes: Vec<QueuedWrite>,
+}
+
+impl<'a, T: MemoryView> WriteTransaction<'a, T> {
+    /// Starts a new and empty transaction on `mem`.
+    pub fn new(mem: &'a mut T) -> Self {
+        Self {
+            mem,
+            writes: vec![],
+        }
+    }
+
+    /// Queues a write of the raw bytes in `data` to `addr`.
+    pub fn write_raw(&mut self, addr: Address, data: &[u8]) -> &mut Self {
+        self.writes.push(QueuedWrite {
+            address: addr,
+            data: data.to_vec(),
+        });
+        self
+    }
+
+    /// Queues a write of `data` to `addr`.
+    pub fn write<F: Pod + ?Sized>(&mut self, addr: Address, data: &F) -> &mut Self {
+        self.write_raw(addr, data.as_bytes())
+    }
+
+    /// Returns all queued writes in the order they will be issued.
+    pub fn writes(&self) -> &[QueuedWrite] {
+        &self.writes
+    }
+
+    /// Returns the queue for modification.
+    ///
+    /// Writes can be edited, reordered or removed until the transaction is committed.
+    pub fn writes_mut(&mut self) -> &mut Vec<QueuedWrite> {
+        &mut self.writes
+    }
+
+    /// Sorts the queued writes by their address.
+    ///
+    /// Writes to the same address keep their relative order.
+    pub fn sort_by_address(&mut self) -> &mut Self {
+        self.writes.sort_by_key(|w| w.address);
+        self
+    }
+
+    /// Returns the number of queued writes.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    /// Returns `true` if no writes are queued.
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Applies all queued writes as a single write list.
+    ///
+    /// The current contents of all written locations are read first. If they can not be read
+    /// completely, nothing is written. If the writes only succeed partially, the previous
+    /// contents are written back and an error is returned.
+    pub fn commit(self) -> Result<()> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+
+        let mut originals = self
+            .writes
+            .iter()
+            .map(|w| vec![0u8; w.data.len()])
+            .collect::<Vec<_>>();
+
+        {
+            let mut list = self
+                .writes
+                .iter()
+                .zip(originals.iter_mut())
+                .map(|(w, buf)| CTup2(w.address, buf.as_mut_slice().into()))
+                .collect::<Vec<_>>();
+            self.mem.read_raw_list(&mut list).data().map_err(|_| {
+                Error(ErrorOrigin::Memory, ErrorKind::PartialData)
+                    .log_error("unable to back up the written memory, transaction aborted")
+            })?;
+        }
+
+        let list = self
+            .writes
+            .iter()
+            .map(|w| CTup2(w.address, w.data.as_slice().into()))
+            .collect::<Vec<_>>();
+
+        if self.mem.write_raw_list(&list).is_ok() {
+            return Ok(());
+        }
+
+        // restore in reverse order so overlapping writes end up with their oldest contents
+        let list = self
+            .writes
+            .iter()
+            .zip(originals.iter())
+            .rev()
+            .map(|(w, buf)| CTup2(w.address, buf.as_slice().into()))
+            .collect::<Vec<_>>();
+        let restored = self.mem.write_raw_list(&list).is_ok();
+
+        Err(
+            Error(ErrorOrigin::Memory, ErrorKind::PartialData).log_error(if restored {
+                "transaction failed, the previous memory contents were restored"
+            } else {
+                "transaction failed and the previous memory contents could not be restored"
+            }),
+        )
+    }
+
+    /// Discards all queued writes without touching the target.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyOs;
+    use crate::os::Process;
+    use crate::types::size;
+
+    #[test]
+    fn restore_on_partial_write() {
+        let mut proc = DummyOs::quick_process(size::mb(2), &[0xaau8; 0x10]);
+        let base = proc.info().address;
+
+        let mut tx = proc.transaction();
+        tx.write(base + 8usize, &0x1111u16)
+            .write(base, &0x2222u16)
+            .sort_by_address();
+        assert_eq!(tx.writes()[0].address, base);
+        tx.rollback();
+        assert_eq!(proc.read::<u16>(base).unwrap(), 0xaaaa);
+
+        // the second write targets unmapped memory, so the first one has to be undone
+        let mut tx = proc.transaction();
+        tx.write(base, &0x3333u16)
+            .write(base + size::mb(4), &0x4444u16);
+        assert!(tx.commit().is_err());
+        assert_eq!(proc.read::<u16>(base).unwrap(), 0xaaaa);
+    }
+}
@@ -0,0 +1,153 @@
+//! Tracking of the successfully read parts of a request.
+use crate::types::{umem, Address};
+
+use std::prelude::v1::*;
+
+/// Describes which parts of a read request were filled with data.
+///
+/// Reading a large range, like a whole module, frequently touches pages that are paged out or
+/// not mapped at all. Instead of rejecting the whole read, [`MemoryView::read_raw_partial`]
+/// reports the ranges that were read successfully. All other bytes of the buffer are zeroed.
+///
+/// [`MemoryView::read_raw_partial`]: super::MemoryView::read_raw_partial
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadValidity {
+    base: Address,
+    len: umem,
+    valid: Vec<(Address, umem)>,
+}
+
+impl ReadValidity {
+    /// Creates a new validity map for the request `base..base + len`.
+    ///
+    /// `valid` holds the `(address, length)` pairs of all successfully read chunks,
+    /// they do not need to be sorted. Adjacent chunks get merged.
+    pub fn new(base: Address, len: umem, mut valid: Vec<(Address, umem)>) -> Self {
+        valid.retain(|&(_, len)| len != 0);
+        valid.sort_by_key(|&(addr, _)| addr);
+
+        let mut merged: Vec<(Address, umem)> = Vec::with_capacity(valid.len());
+        for (addr, len) in valid {
+            match merged.last_mut() {
+                Some((last, last_len)) if *last + *last_len >= addr => {
+                    let end = std::cmp::max(*last + *last_len, addr + len);
+                    *last_len = (end - *last) as umem;
+                }
+                _ => merged.push((addr, len)),
+            }
+        }
+
+        Self {
+            base,
+            len,
+            valid: merged,
+        }
+    }
+
+    /// Returns the start address of the request.
+    pub fn base(&self) -> Address {
+        self.base
+    }
+
+    /// Returns the length of the request in bytes.
+    pub fn len(&self) -> umem {
+        self.len
+    }
+
+    /// Returns `true` if the request was empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the sorted `(address, length)` pairs of all ranges that were read.
+    pub fn valid_ranges(&self) -> &[(Address, umem)] {
+        &self.valid
+    }
+
+    /// Returns the sorted `(address, length)` pairs of all ranges that could not be read.
+    pub fn invalid_ranges(&self) -> Vec<(Address, umem)> {
+        let end = self.base + self.len;
+        let mut ret = vec![];
+        let mut cur = self.base;
+
+        for &(addr, len) in self.valid.iter() {
+            if addr > cur {
+                ret.push((cur, (addr - cur) as umem));
+            }
+            cur = addr + len;
+        }
+
+        if cur < end {
+            ret.push((cur, (end - cur) as umem));
+        }
+
+        ret
+    }
+
+    /// Returns the amount of bytes that were read.
+    pub fn valid_bytes(&self) -> umem {
+        self.valid.iter().map(|&(_, len)| len).sum()
+    }
+
+    /// Returns `true` if the whole request was read.
+    pub fn is_complete(&self) -> bool {
+        self.valid_bytes() == self.len
+    }
+
+    /// Returns `true` if the byte at `addr` was read.
+    pub fn is_valid(&self, addr: Address) -> bool {
+        match self.valid.binary_search_by_key(&addr, |&(a, _)| a) {
+            Ok(_) => true,
+            Err(0) => false,
+            Err(i) => {
+                let (start, len) = self.valid[i - 1];
+                addr < start + len
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyOs;
+    use crate::mem::MemoryView;
+    use crate::os::Process;
+    use crate::types::size;
+
+    #[test]
+    fn merge_ranges() {
+        let validity = ReadValidity::new(
+            Address::from(0x1000),
+            0x4000,
+            vec![
+                (Address::from(0x2000), 0x1000),
+                (Address::from(0x1000), 0x800),
+                (Address::from(0x1800), 0x800),
+                (Address::from(0x4000), 0),
+            ],
+        );
+
+        assert_eq!(validity.valid_ranges(), &[(Address::from(0x1000), 0x2000)]);
+        assert_eq!(
+            validity.invalid_ranges(),
+            vec![(Address::from(0x3000), 0x2000)]
+        );
+        assert!(validity.is_valid(Address::from(0x2fff)));
+        assert!(!validity.is_valid(Address::from(0x3000)));
+        assert!(!validity.is_complete());
+    }
+
+    #[test]
+    fn read_unmapped_tail() {
+        let mut proc = DummyOs::quick_process(size::mb(2), &[0xffu8; 0x10]);
+        let addr = proc.info().address + size::mb(2) - 0x800usize;
+
+        let mut buf = vec![0xaau8; 0x1000];
+        let validity = proc.read_raw_partial(addr, &mut buf).unwrap();
+
+        assert_eq!(validity.valid_ranges(), &[(addr, 0x800)]);
+        assert_eq!(validity.invalid_ranges(), vec![(addr + 0x800usize, 0x800)]);
+        assert!(buf[0x800..].iter().all(|&b| b == 0));
+    }
+}
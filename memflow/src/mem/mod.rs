@@ -7,26 +7,42 @@
 //!
 //! TODO: more documentation
 
+#[cfg(feature = "async")]
+pub mod async_mem;
+pub mod dynamic_struct;
 pub mod mem_data;
-pub mod mem_map;
+pub use crate::types::mem_map;
 pub mod memory_view;
 pub mod phys_mem;
+pub mod scan;
+pub mod snapshot;
 pub mod virt_mem;
 pub mod virt_translate;
+pub mod watch;
 
 pub use mem_map::{MemoryMap, PhysicalMemoryMapping};
-pub use phys_mem::{CachedPhysicalMemory, PhysicalMemory, PhysicalMemoryMetadata};
-pub use virt_mem::VirtualDma;
+pub use phys_mem::{
+    AccessHeatmap, AccessRecorder, CachedPhysicalMemory, PageAccess, PhysicalMemory,
+    PhysicalMemoryMapped, PhysicalMemoryMetadata, PhysicalMemoryRun,
+};
+#[cfg(feature = "filemap")]
+pub use phys_mem::{PersistentCachedMemory, PersistentCachedMemoryBuilder};
+pub use virt_mem::{VirtualDma, VirtualDmaStats};
 //#[doc(hidden)]
 //pub use virt_mem_batcher::VirtualMemoryBatcher;
 pub use virt_translate::{
-    CachedVirtualTranslate, DirectTranslate, VirtualTranslate, VirtualTranslate2,
-    VirtualTranslate3, VtopFailureCallback, VtopOutputCallback,
+    CachedVirtualTranslate, DirectTranslate, NestedTranslate, PageTableDump, PageTableNode,
+    PageTableNodeEntry, TranslationDecision, TranslationFailure, TranslationFailureReason,
+    TranslationStep, TranslationTrace, VirtualTranslate, VirtualTranslate2, VirtualTranslate3,
+    VtopFailureCallback, VtopOutputCallback,
 };
 
-pub use memory_view::{MemoryView, MemoryViewMetadata};
+pub use memory_view::{MemoryView, MemoryViewMetadata, ReadValidity, WriteTransaction};
 
 #[cfg(feature = "std")]
-pub use memory_view::MemoryCursor;
+pub use memory_view::{MemoryCursor, VirtualMemoryCursor};
 
 pub use mem_data::*;
+
+#[cfg(feature = "async")]
+pub use async_mem::{AsyncMemoryView, AsyncPhysicalMemory, SyncAdapter};
@@ -0,0 +1,290 @@
+//! Recording of physical memory access patterns.
+//!
+//! The [`AccessRecorder`] wraps a [`PhysicalMemory`] object and counts how often each page is read.
+//! The resulting [`AccessHeatmap`] can be stored between sessions and later be used to
+//! prefetch the hottest pages right after connecting to a target, which
+//! warms up caches (e.g. a [`CachedPhysicalMemory`](super::CachedPhysicalMemory))
+//! before the first real request is issued.
+//!
+//! # Examples
+//!
+//! ```
+//! use memflow::architecture::x86::x64;
+//! use memflow::mem::{AccessRecorder, CachedPhysicalMemory, PhysicalMemory};
+//! use memflow::types::size;
+//! # use memflow::dummy::DummyMemory;
+//! # let mem = DummyMemory::new(size::mb(2));
+//!
+//! // record the accesses of a session
+//! let mut recorder = AccessRecorder::new(mem, size::kb(4));
+//! let mut value = 0u64;
+//! recorder.phys_read_into(0x1000.into(), &mut value).unwrap();
+//! let (mem, heatmap) = recorder.into_inner();
+//!
+//! // warm up the cache of the next session
+//! let mut cache = CachedPhysicalMemory::builder(mem)
+//!     .arch(x64::ARCH)
+//!     .build()
+//!     .unwrap();
+//! heatmap.prefetch(&mut cache, 64).unwrap();
+//! ```
+
+use std::prelude::v1::*;
+
+use crate::cglue::*;
+use crate::error::Result;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{umem, Address, PageType, PhysicalAddress};
+
+use std::collections::BTreeMap;
+
+/// The amount of pages that are prefetched in a single batch.
+const PREFETCH_BATCH_SIZE: usize = 64;
+
+/// Access statistics of a single page.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PageAccess {
+    /// Base address of the page.
+    pub page_base: Address,
+    /// Page type of the last access.
+    pub page_type: PageType,
+    /// Number of accesses to this page.
+    pub count: u64,
+}
+
+/// Per-page access counters that were collected by an [`AccessRecorder`].
+#[derive(Debug, Clone)]
+pub struct AccessHeatmap {
+    page_size: umem,
+    pages: BTreeMap<Address, PageAccess>,
+}
+
+impl AccessHeatmap {
+    /// Creates a new and empty heatmap with the given page granularity.
+    pub fn new(page_size: usize) -> Self {
+        Self {
+            page_size: page_size as umem,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    /// Reconstructs a heatmap from a previously stored list of pages.
+    pub fn from_vec(page_size: usize, pages: Vec<PageAccess>) -> Self {
+        Self {
+            page_size: page_size as umem,
+            pages: pages.into_iter().map(|p| (p.page_base, p)).collect(),
+        }
+    }
+
+    /// Returns all recorded pages sorted by their access count in descending order.
+    ///
+    /// The returned list can be serialized and later be loaded again via [`AccessHeatmap::from_vec`].
+    pub fn to_vec(&self) -> Vec<PageAccess> {
+        let mut pages = self.pages.values().copied().collect::<Vec<_>>();
+        pages.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.page_base.cmp(&b.page_base))
+        });
+        pages
+    }
+
+    /// Returns the page granularity of this heatmap.
+    pub fn page_size(&self) -> usize {
+        self.page_size as usize
+    }
+
+    /// Returns the number of distinct pages in this heatmap.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns `true` if no accesses have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Adds the accesses of another heatmap to this one.
+    ///
+    /// This can be used to accumulate access patterns over multiple sessions.
+    pub fn merge(&mut self, other: &AccessHeatmap) {
+        for page in other.pages.values() {
+            self.pages
+                .entry(page.page_base)
+                .and_modify(|p| p.count += page.count)
+                .or_insert(*page);
+        }
+    }
+
+    /// Records an access of `len` bytes at the given address.
+    pub fn record(&mut self, addr: PhysicalAddress, len: umem) {
+        if len == 0 {
+            return;
+        }
+
+        let start = addr.address().as_mem_aligned(self.page_size);
+        let end = addr.address() + (len - 1);
+
+        let mut page_base = start;
+        while page_base <= end {
+            self.pages
+                .entry(page_base)
+                .and_modify(|p| {
+                    p.count += 1;
+                    p.page_type = addr.page_type();
+                })
+                .or_insert(PageAccess {
+                    page_base,
+                    page_type: addr.page_type(),
+                    count: 1,
+                });
+            page_base += self.page_size;
+        }
+    }
+
+    /// Reads the `max_pages` most frequently accessed pages through the given memory object.
+    ///
+    /// The data that is read is discarded. This function is intended to warm up
+    /// the caches of `mem` before it is being used.
+    /// Pages that fail to be read are ignored.
+    pub fn prefetch(&self, mem: &mut impl PhysicalMemory, max_pages: usize) -> Result<()> {
+        let pages = self.to_vec();
+        let mut buf = vec![0u8; self.page_size as usize * PREFETCH_BATCH_SIZE];
+
+        for batch in pages
+            .into_iter()
+            .take(max_pages)
+            .collect::<Vec<_>>()
+            .chunks(PREFETCH_BATCH_SIZE)
+        {
+            let iter = batch
+                .iter()
+                .zip(buf.chunks_mut(self.page_size as usize))
+                .map(|(page, chunk)| {
+                    (
+                        PhysicalAddress::with_page(page.page_base, page.page_type, self.page_size),
+                        CSliceMut::from(chunk),
+                    )
+                });
+
+            MemOps::with(iter, None, None, |data| mem.phys_read_raw_iter(data))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Wraps a [`PhysicalMemory`] object and records an [`AccessHeatmap`] of all reads.
+#[derive(Clone)]
+pub struct AccessRecorder<T> {
+    mem: T,
+    heatmap: AccessHeatmap,
+}
+
+impl<T: PhysicalMemory> AccessRecorder<T> {
+    /// Constructs a new `AccessRecorder` that records accesses with the given page granularity.
+    pub fn new(mem: T, page_size: usize) -> Self {
+        Self {
+            mem,
+            heatmap: AccessHeatmap::new(page_size),
+        }
+    }
+
+    /// Returns the heatmap that has been recorded so far.
+    pub fn heatmap(&self) -> &AccessHeatmap {
+        &self.heatmap
+    }
+
+    /// Clears all recorded accesses.
+    pub fn reset(&mut self) {
+        self.heatmap = AccessHeatmap::new(self.heatmap.page_size());
+    }
+
+    /// Consumes the recorder and returns the underlying memory object and the recorded heatmap.
+    pub fn into_inner(self) -> (T, AccessHeatmap) {
+        (self.mem, self.heatmap)
+    }
+}
+
+impl<T: PhysicalMemory> PhysicalMemory for AccessRecorder<T> {
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps { inp, out, out_fail }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let heatmap = &mut self.heatmap;
+        let inp =
+            &mut inp.inspect(|CTup3(addr, _, data)| heatmap.record(*addr, data.len() as umem));
+        let inp = inp.into();
+
+        self.mem.phys_read_raw_iter(MemOps { inp, out, out_fail })
+    }
+
+    #[inline]
+    fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+        self.mem.phys_write_raw_iter(data)
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    AccessRecorder<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    #[test]
+    fn record_pages() {
+        let mut recorder = AccessRecorder::new(DummyMemory::new(size::mb(1)), size::kb(4));
+
+        let mut buf = [0u8; 0x10];
+        recorder.phys_read_into(0x1000.into(), &mut buf).unwrap();
+        recorder.phys_read_into(0x1ff8.into(), &mut buf).unwrap();
+        recorder.phys_read_into(0x5000.into(), &mut buf).unwrap();
+
+        let pages = recorder.heatmap().to_vec();
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].page_base, Address::from(0x1000));
+        assert_eq!(pages[0].count, 2);
+        assert_eq!(pages[1].page_base, Address::from(0x2000));
+        assert_eq!(pages[1].count, 1);
+        assert_eq!(pages[2].page_base, Address::from(0x5000));
+    }
+
+    #[test]
+    fn merge_and_restore() {
+        let mut a = AccessHeatmap::new(size::kb(4));
+        a.record(0x1000.into(), 8);
+
+        let mut b = AccessHeatmap::from_vec(size::kb(4), a.to_vec());
+        b.record(0x3000.into(), 8);
+        b.merge(&a);
+
+        let pages = b.to_vec();
+        assert_eq!(pages[0].page_base, Address::from(0x1000));
+        assert_eq!(pages[0].count, 2);
+        assert_eq!(pages[1].count, 1);
+    }
+}
@@ -27,23 +27,39 @@
 //! }
 //! ```
 
+use std::prelude::v1::*;
+
+mod negative_cache;
 mod page_cache;
+#[cfg(feature = "filemap")]
+mod persistent;
+
+#[cfg(feature = "filemap")]
+pub use persistent::{PersistentCachedMemory, PersistentCachedMemoryBuilder};
 
 use crate::architecture::ArchitectureObj;
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::iter::PageChunks;
 use crate::mem::{
-    MemOps, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata, PhysicalReadMemOps,
+    opt_call, MemOps, PhysicalMemory, PhysicalMemoryMapped, PhysicalMemoryMapping,
+    PhysicalMemoryMetadata, PhysicalMemoryRun, PhysicalMemoryRunCallback, PhysicalReadMemOps,
     PhysicalWriteMemOps,
 };
+use cglue::slice::{CSliceMut, CSliceRef};
 use cglue::tuple::*;
+use negative_cache::NegativeCache;
 use page_cache::{PageCache, PageValidity};
 
-use crate::types::cache::{CacheValidator, DefaultCacheValidator};
+#[cfg(feature = "std")]
+use crate::types::cache::StatsLogger;
+use crate::types::cache::{CacheStats, CacheValidator, DefaultCacheValidator};
+
+use crate::types::{size, umem, PageType, PhysicalAddress};
 
-use crate::types::{size, PageType};
+use bumpalo::{collections::Vec as BumpVec, Bump};
 
-use bumpalo::Bump;
+#[cfg(feature = "std")]
+use coarsetime::Duration;
 
 /// The cache object that can use as a drop-in replacement for any Connector.
 ///
@@ -53,6 +69,9 @@ pub struct CachedPhysicalMemory<'a, T, Q> {
     mem: T,
     cache: PageCache<'a, Q>,
     arena: Bump,
+    failures: Option<NegativeCache<DefaultCacheValidator>>,
+    #[cfg(feature = "std")]
+    stats_logger: Option<StatsLogger>,
 }
 
 impl<'a, T, Q> Clone for CachedPhysicalMemory<'a, T, Q>
@@ -65,6 +84,9 @@ where
             mem: self.mem.clone(),
             cache: self.cache.clone(),
             arena: Bump::new(),
+            failures: self.failures.clone(),
+            #[cfg(feature = "std")]
+            stats_logger: self.stats_logger.clone(),
         }
     }
 }
@@ -81,6 +103,57 @@ impl<'a, T: PhysicalMemory, Q: CacheValidator> CachedPhysicalMemory<'a, T, Q> {
             mem,
             cache,
             arena: Bump::new(),
+            failures: None,
+            #[cfg(feature = "std")]
+            stats_logger: None,
+        }
+    }
+
+    /// Returns the hit and miss counters of the cache.
+    ///
+    /// # Examples
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{PhysicalMemory, CachedPhysicalMemory, MemoryView};
+    /// use memflow::types::PageType;
+    ///
+    /// fn read_twice<T: PhysicalMemory>(mem: T) {
+    ///     let mut cache = CachedPhysicalMemory::builder(mem)
+    ///         .arch(x64::ARCH)
+    ///         .page_type_mask(PageType::UNKNOWN)
+    ///         .build()
+    ///         .unwrap();
+    ///
+    ///     let _: u64 = cache.phys_view().read(0x1000.into()).unwrap();
+    ///     let _: u64 = cache.phys_view().read(0x1000.into()).unwrap();
+    ///
+    ///     let stats = cache.stats();
+    ///     assert_eq!(stats.hits, 1);
+    ///     assert_eq!(stats.misses, 1);
+    ///     println!("{}", stats);
+    /// }
+    /// # use memflow::dummy::DummyMemory;
+    /// # use memflow::types::size;
+    /// # read_twice(DummyMemory::new(size::mb(4)));
+    /// ```
+    pub fn stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Resets the counters of the cache to zero.
+    pub fn reset_stats(&mut self) {
+        self.cache.reset_stats()
+    }
+
+    /// Forgets all pages that were remembered as unreadable.
+    ///
+    /// This should be called whenever the memory layout of the target may have changed,
+    /// e.g. after memory was hot-plugged. Writes through the cache already drop the
+    /// written pages. Has no effect if negative caching is disabled,
+    /// see [`CachedPhysicalMemoryBuilder::negative_cache`].
+    pub fn invalidate_failures(&mut self) {
+        if let Some(failures) = &mut self.failures {
+            failures.clear();
         }
     }
 
@@ -134,23 +207,53 @@ impl<'a, T: PhysicalMemory, Q: CacheValidator> PhysicalMemory for CachedPhysical
         //data: PhysicalReadMemOps,
         data: PhysicalReadMemOps,
     ) -> Result<()> {
+        // reads from a mapped backend are a plain copy, caching them would only add another one
+        if self.mem.as_mapped().is_some() {
+            return self.mem.phys_read_raw_iter(data);
+        }
+
         self.cache.validator.update_validity();
         self.arena.reset();
-        self.cache.cached_read(&mut self.mem, data, &self.arena)
+
+        let ret = match &mut self.failures {
+            Some(failures) => {
+                failures.validator.update_validity();
+                negative_cached_read(&mut self.mem, &mut self.cache, failures, data, &self.arena)
+            }
+            None => self.cache.cached_read(&mut self.mem, data, &self.arena),
+        };
+
+        #[cfg(feature = "std")]
+        if let Some(logger) = &mut self.stats_logger {
+            logger.tick(&self.cache.stats());
+        }
+
+        ret
     }
 
     fn phys_write_raw_iter(
         &mut self,
-        MemOps { inp, out, out_fail }: PhysicalWriteMemOps,
+        MemOps {
+            inp,
+            out,
+            mut out_fail,
+        }: PhysicalWriteMemOps,
     ) -> Result<()> {
         self.cache.validator.update_validity();
 
         let mem = &mut self.mem;
         let cache = &mut self.cache;
+        let failures = &mut self.failures;
+        let page_size = cache.page_size();
 
-        let inp = inp.map(move |CTup3(addr, meta_addr, data)| {
-            if cache.is_cached_page_type(addr.page_type()) {
-                for (paddr, data_chunk) in data.page_chunks(addr.address(), cache.page_size()) {
+        let mut written = Vec::new();
+        let mut failed = Vec::new();
+
+        let ret = {
+            // the same physical page may have been read through a mapping of a different type,
+            // so all cached pages are updated regardless of the page type of the write
+            let inp = inp.map(|CTup3(addr, meta_addr, data)| {
+                for (paddr, data_chunk) in data.page_chunks(addr.address(), page_size) {
                     let mut cached_page = cache.cached_page_mut(paddr, false);
                     if let PageValidity::Valid(buf) = &mut cached_page.validity {
                         // write-back into still valid cache pages
@@ -160,13 +263,45 @@ impl<'a, T: PhysicalMemory, Q: CacheValidator> PhysicalMemory for CachedPhysical
 
                     cache.put_entry(cached_page);
                 }
+                if let Some(failures) = failures.as_mut() {
+                    failures.invalidate_range(addr.address(), data.len() as umem);
+                }
+                written.push((meta_addr, addr.address(), data.len() as umem));
+                CTup3(addr, meta_addr, data)
+            });
+
+            let mut out = out.map(|o| move |data| o.call(data));
+            let mut out = out.as_mut().map(<_>::into);
+            let out = out.as_mut();
+
+            let fail_cb = &mut |CTup2(fail_addr, buf): CTup2<_, _>| {
+                let buf: CSliceRef<u8> = buf;
+                failed.push((fail_addr, buf.len() as umem));
+                opt_call(out_fail.as_deref_mut(), CTup2(fail_addr, buf))
+            };
+            let mut fail_cb = fail_cb.into();
+
+            MemOps::with_raw(inp, out, Some(&mut fail_cb), |data| {
+                mem.phys_write_raw_iter(data)
+            })
+        };
+
+        // the cached pages were already updated, drop the ones that did not reach the target
+        for (fail_addr, fail_len) in failed {
+            for &(meta_addr, addr, len) in written.iter().filter(|&&(meta_addr, _, len)| {
+                fail_addr >= meta_addr && fail_addr < meta_addr + len
+            }) {
+                let start = addr + (fail_addr - meta_addr) as umem;
+                let end = start + std::cmp::min(fail_len, len);
+                let mut page = start.as_page_aligned(page_size);
+                while page < end {
+                    cache.invalidate_cached_page(page);
+                    page += page_size;
+                }
             }
-            CTup3(addr, meta_addr, data)
-        });
+        }
 
-        MemOps::with_raw(inp, out, out_fail, move |data| {
-            mem.phys_write_raw_iter(data)
-        })
+        ret
     }
 
     #[inline]
@@ -174,10 +309,108 @@ impl<'a, T: PhysicalMemory, Q: CacheValidator> PhysicalMemory for CachedPhysical
         self.mem.metadata()
     }
 
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
     #[inline]
     fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
         self.mem.set_mem_map(mem_map)
     }
+
+    #[inline]
+    fn as_mapped(&self) -> Option<&dyn PhysicalMemoryMapped> {
+        self.mem.as_mapped()
+    }
+}
+
+/// Reads through the page cache, but fails pages that recently failed without reading them.
+fn negative_cached_read<'a, 'b, T: PhysicalMemory, Q: CacheValidator>(
+    mem: &mut T,
+    cache: &mut PageCache<'a, Q>,
+    failures: &mut NegativeCache<DefaultCacheValidator>,
+    MemOps {
+        inp,
+        out,
+        mut out_fail,
+    }: PhysicalReadMemOps,
+    arena: &'b Bump,
+) -> Result<()> {
+    let page_size = cache.page_size();
+
+    // the buffers of all chunks are disjoint, which allows mapping a failed buffer back to its page
+    let mut chunks = BumpVec::new_in(arena);
+    let mut skipped = BumpVec::new_in(arena);
+    let mut failed = BumpVec::new_in(arena);
+
+    let ret = {
+        let mut inp = inp
+            .flat_map(|CTup3(addr, meta_addr, data)| {
+                (meta_addr, data)
+                    .page_chunks(addr.address(), page_size)
+                    .map(move |(paddr, (meta_addr, chunk))| {
+                        CTup3(
+                            PhysicalAddress::with_page(
+                                paddr,
+                                addr.page_type(),
+                                addr.page_size() as umem,
+                            ),
+                            meta_addr,
+                            chunk,
+                        )
+                    })
+            })
+            .filter_map(|CTup3(addr, meta_addr, chunk)| {
+                if failures.contains(addr.address()) {
+                    skipped.push(CTup2(meta_addr, chunk));
+                    None
+                } else {
+                    chunks.push((chunk.as_ptr() as usize, chunk.len(), addr.address()));
+                    Some(CTup3(addr, meta_addr, chunk))
+                }
+            });
+
+        let mut out = out.map(|o| move |data| o.call(data));
+        let mut out = out.as_mut().map(<_>::into);
+        let out = out.as_mut();
+
+        let fail_cb = &mut |CTup2(meta_addr, buf): CTup2<_, _>| {
+            let buf: CSliceMut<u8> = buf;
+            failed.push(buf.as_ptr() as usize);
+            opt_call(out_fail.as_deref_mut(), CTup2(meta_addr, buf))
+        };
+        let mut fail_cb = fail_cb.into();
+
+        cache.cached_read(
+            mem,
+            MemOps {
+                inp: (&mut inp).into(),
+                out,
+                out_fail: Some(&mut fail_cb),
+            },
+            arena,
+        )
+    };
+
+    chunks.sort_unstable_by_key(|&(ptr, _, _)| ptr);
+    for ptr in failed {
+        let idx = match chunks.binary_search_by_key(&ptr, |&(ptr, _, _)| ptr) {
+            Ok(idx) => idx,
+            Err(0) => continue,
+            Err(idx) => idx - 1,
+        };
+        let (start, len, addr) = chunks[idx];
+        if ptr < start + std::cmp::max(len, 1) {
+            failures.insert(addr + (ptr - start));
+        }
+    }
+
+    for data in skipped {
+        opt_call(out_fail.as_deref_mut(), data);
+    }
+
+    ret
 }
 
 /// The builder interface for constructing a `CachedPhysicalMemory` object.
@@ -187,6 +420,10 @@ pub struct CachedPhysicalMemoryBuilder<T, Q> {
     page_size: Option<usize>,
     cache_size: usize,
     page_type_mask: PageType,
+    prefetch_pages: usize,
+    negative_cache: Option<(usize, DefaultCacheValidator)>,
+    #[cfg(feature = "std")]
+    log_interval: Option<Duration>,
 }
 
 impl<T: PhysicalMemory> CachedPhysicalMemoryBuilder<T, DefaultCacheValidator> {
@@ -268,6 +505,10 @@ impl<T: PhysicalMemory> CachedPhysicalMemoryBuilder<T, DefaultCacheValidator> {
             page_size: None,
             cache_size: size::mb(2),
             page_type_mask: PageType::PAGE_TABLE | PageType::READ_ONLY,
+            prefetch_pages: 0,
+            negative_cache: None,
+            #[cfg(feature = "std")]
+            log_interval: None,
         }
     }
 }
@@ -275,18 +516,42 @@ impl<T: PhysicalMemory> CachedPhysicalMemoryBuilder<T, DefaultCacheValidator> {
 impl<T: PhysicalMemory, Q: CacheValidator> CachedPhysicalMemoryBuilder<T, Q> {
     /// Builds the `CachedPhysicalMemory` object or returns an error if the page size is not set.
     pub fn build<'a>(self) -> Result<CachedPhysicalMemory<'a, T, Q>> {
-        Ok(CachedPhysicalMemory::new(
-            self.mem,
-            PageCache::with_page_size(
-                self.page_size.ok_or_else(|| {
-                    Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
-                        .log_error("page_size must be initialized")
-                })?,
-                self.cache_size,
-                self.page_type_mask,
-                self.validator,
-            ),
-        ))
+        let page_size = self.page_size.ok_or_else(|| {
+            Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
+                .log_error("page_size must be initialized")
+        })?;
+        let mut cache = PageCache::with_page_size(
+            page_size,
+            self.cache_size,
+            self.page_type_mask,
+            self.validator,
+        );
+        cache.set_prefetch_pages(self.prefetch_pages);
+
+        // regions tagged as uncacheable by the connector (e.g. MMIO) are never cached
+        let mut uncached = Vec::new();
+        self.mem.memory_runs(
+            (&mut |run: PhysicalMemoryRun| {
+                if !run.page_type.is_cacheable() {
+                    uncached.push(run.base..(run.base + run.size));
+                }
+                true
+            })
+                .into(),
+        );
+        cache.set_uncached_ranges(uncached);
+
+        let mut mem = CachedPhysicalMemory::new(self.mem, cache);
+        mem.failures = self
+            .negative_cache
+            .map(|(entries, validator)| NegativeCache::new(entries, page_size, validator));
+
+        #[cfg(feature = "std")]
+        if let Some(interval) = self.log_interval {
+            mem.stats_logger = Some(StatsLogger::new("page", interval));
+        }
+
+        Ok(mem)
     }
 
     /// Sets a custom validator for the cache.
@@ -327,6 +592,10 @@ impl<T: PhysicalMemory, Q: CacheValidator> CachedPhysicalMemoryBuilder<T, Q> {
             page_size: self.page_size,
             cache_size: self.cache_size,
             page_type_mask: self.page_type_mask,
+            prefetch_pages: self.prefetch_pages,
+            negative_cache: self.negative_cache,
+            #[cfg(feature = "std")]
+            log_interval: self.log_interval,
         }
     }
 
@@ -454,6 +723,108 @@ impl<T: PhysicalMemory, Q: CacheValidator> CachedPhysicalMemoryBuilder<T, Q> {
         self.page_type_mask = page_type_mask;
         self
     }
+
+    /// Enables reading ahead of sequential accesses.
+    ///
+    /// Once consecutive pages have been read, the following `pages` pages are read into the
+    /// cache with a single batched request. This speeds up linear reads, like dumping a module,
+    /// over connectors with a high latency per request. Only pages matching the
+    /// [`page_type_mask`](Self::page_type_mask) are prefetched.
+    ///
+    /// The default setting is 0, which disables prefetching.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{PhysicalMemory, CachedPhysicalMemory};
+    ///
+    /// fn build<T: PhysicalMemory>(mem: T) {
+    ///     let cache = CachedPhysicalMemory::builder(mem)
+    ///         .arch(x64::ARCH)
+    ///         .prefetch(16)
+    ///         .build()
+    ///         .unwrap();
+    /// }
+    /// # use memflow::dummy::DummyMemory;
+    /// # use memflow::types::size;
+    /// # let mut mem = DummyMemory::new(size::mb(4));
+    /// # build(mem);
+    /// ```
+    pub fn prefetch(mut self, pages: usize) -> Self {
+        self.prefetch_pages = pages;
+        self
+    }
+
+    /// Remembers pages that failed to be read.
+    ///
+    /// Reads of a page that failed within the lifetime given by `validator` fail immediately
+    /// instead of being forwarded to the connector. This avoids hammering slow connectors when
+    /// unmapped regions are probed repeatedly, e.g. by a scanner. `entries` is the amount of
+    /// pages that can be remembered at once.
+    ///
+    /// Since memory can be mapped at any time, the lifetime should be short. Writes drop the
+    /// written pages and [`CachedPhysicalMemory::invalidate_failures`] drops all of them.
+    ///
+    /// By default failed reads are not cached.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{PhysicalMemory, CachedPhysicalMemory};
+    /// use memflow::types::DefaultCacheValidator;
+    ///
+    /// fn build<T: PhysicalMemory>(mem: T) {
+    ///     let cache = CachedPhysicalMemory::builder(mem)
+    ///         .arch(x64::ARCH)
+    ///         .negative_cache(1024, DefaultCacheValidator::new(Duration::from_millis(100).into()))
+    ///         .build()
+    ///         .unwrap();
+    /// }
+    /// # use memflow::dummy::DummyMemory;
+    /// # use memflow::types::size;
+    /// # let mut mem = DummyMemory::new(size::mb(4));
+    /// # build(mem);
+    /// ```
+    pub fn negative_cache(mut self, entries: usize, validator: DefaultCacheValidator) -> Self {
+        self.negative_cache = Some((entries, validator));
+        self
+    }
+
+    /// Periodically logs the cache statistics.
+    ///
+    /// The statistics are written with the `info` log level at most once per `interval`.
+    /// By default no statistics are logged, they can still be queried with
+    /// [`CachedPhysicalMemory::stats`].
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{PhysicalMemory, CachedPhysicalMemory};
+    ///
+    /// fn build<T: PhysicalMemory>(mem: T) {
+    ///     let cache = CachedPhysicalMemory::builder(mem)
+    ///         .arch(x64::ARCH)
+    ///         .log_stats(Duration::from_secs(10).into())
+    ///         .build()
+    ///         .unwrap();
+    /// }
+    /// # use memflow::dummy::DummyMemory;
+    /// # use memflow::types::size;
+    /// # let mut mem = DummyMemory::new(size::mb(4));
+    /// # build(mem);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn log_stats(mut self, interval: Duration) -> Self {
+        self.log_interval = Some(interval);
+        self
+    }
 }
 
 #[cfg(feature = "plugins")]
@@ -0,0 +1,73 @@
+use std::prelude::v1::*;
+
+use crate::types::{cache::CacheValidator, umem, Address};
+
+/// Remembers pages that recently failed to be read.
+///
+/// Scanners frequently probe the same unmapped regions over and over again. Every probe is
+/// forwarded to the connector and fails there, which is costly on backends with a high latency.
+/// Failed pages are stored in a direct mapped table, the validator decides how long a failure
+/// is remembered. It should be a lot shorter than the lifetime of regular cache entries,
+/// since memory can get mapped in at any time.
+#[derive(Clone)]
+pub struct NegativeCache<Q> {
+    pages: Box<[Address]>,
+    page_size: usize,
+    pub validator: Q,
+}
+
+impl<Q: CacheValidator> NegativeCache<Q> {
+    pub fn new(entries: usize, page_size: usize, mut validator: Q) -> Self {
+        let entries = std::cmp::max(entries, 1);
+        validator.allocate_slots(entries);
+
+        Self {
+            pages: vec![Address::INVALID; entries].into_boxed_slice(),
+            page_size,
+            validator,
+        }
+    }
+
+    #[inline]
+    fn slot(&self, page: Address) -> usize {
+        ((page.to_umem() / self.page_size as umem) % self.pages.len() as umem) as usize
+    }
+
+    /// Returns `true` if reading the page containing `addr` failed recently.
+    #[inline]
+    pub fn contains(&self, addr: Address) -> bool {
+        let page = addr.as_page_aligned(self.page_size);
+        let slot = self.slot(page);
+        self.pages[slot] == page && self.validator.is_slot_valid(slot)
+    }
+
+    /// Remembers the page containing `addr` as unreadable.
+    pub fn insert(&mut self, addr: Address) {
+        let page = addr.as_page_aligned(self.page_size);
+        let slot = self.slot(page);
+        self.pages[slot] = page;
+        self.validator.validate_slot(slot);
+    }
+
+    /// Forgets all failures within `addr..addr + len`.
+    pub fn invalidate_range(&mut self, addr: Address, len: umem) {
+        let end = addr + len;
+        let mut page = addr.as_page_aligned(self.page_size);
+        while page < end {
+            let slot = self.slot(page);
+            if self.pages[slot] == page {
+                self.pages[slot] = Address::INVALID;
+                self.validator.invalidate_slot(slot);
+            }
+            page += self.page_size;
+        }
+    }
+
+    /// Forgets all failures.
+    pub fn clear(&mut self) {
+        for (slot, page) in self.pages.iter_mut().enumerate() {
+            *page = Address::INVALID;
+            self.validator.invalidate_slot(slot);
+        }
+    }
+}
@@ -3,9 +3,13 @@ use crate::error::Result;
 use crate::iter::PageChunks;
 use crate::mem::mem_data::*;
 use crate::mem::phys_mem::*;
-use crate::types::{cache::CacheValidator, umem, Address, PageType, PhysicalAddress};
+use crate::types::{
+    cache::{CacheStats, CacheValidator},
+    umem, Address, PageType, PhysicalAddress,
+};
 
 use std::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+use std::ops::Range;
 
 use bumpalo::{collections::Vec as BumpVec, Bump};
 
@@ -27,12 +31,54 @@ impl<'a> CacheEntry<'a> {
     }
 }
 
+/// The amount of consecutive pages that have to be read before pages get prefetched.
+const PREFETCH_MIN_RUN: usize = 2;
+
+/// Tracks sequential accesses to cached pages.
+#[derive(Clone, Copy)]
+struct Prefetcher {
+    pages: usize,
+    next: Address,
+    run: usize,
+    page_type: PageType,
+}
+
+impl Prefetcher {
+    fn new(pages: usize) -> Self {
+        Self {
+            pages,
+            next: Address::INVALID,
+            run: 0,
+            page_type: PageType::UNKNOWN,
+        }
+    }
+
+    fn track(&mut self, page: Address, page_size: usize, page_type: PageType) {
+        if page == self.next {
+            self.run += 1;
+            self.next = page + page_size;
+        } else if page + page_size != self.next {
+            self.run = 1;
+            self.next = page + page_size;
+        }
+        self.page_type = page_type;
+    }
+
+    fn is_sequential(&self) -> bool {
+        self.pages > 0 && self.run >= PREFETCH_MIN_RUN
+    }
+}
+
 pub struct PageCache<'a, T> {
     address: Box<[Address]>,
     page_refs: Box<[Option<&'a mut [u8]>]>,
     address_once_validated: Box<[Address]>,
     page_size: usize,
     page_type_mask: PageType,
+    /// sorted physical ranges that must never be cached
+    uncached_ranges: Vec<Range<Address>>,
+    prefetcher: Prefetcher,
+    stats: CacheStats,
     pub validator: T,
     cache_ptr: *mut u8,
     cache_layout: Layout,
@@ -76,12 +122,38 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
             address_once_validated: vec![Address::INVALID; cache_entries].into_boxed_slice(),
             page_size,
             page_type_mask,
+            uncached_ranges: vec![],
+            prefetcher: Prefetcher::new(0),
+            stats: CacheStats::default(),
             validator,
             cache_ptr,
             cache_layout: layout,
         }
     }
 
+    /// Sets the amount of pages that are read ahead once sequential accesses are detected.
+    ///
+    /// A value of 0 disables prefetching.
+    pub fn set_prefetch_pages(&mut self, pages: usize) {
+        self.prefetcher = Prefetcher::new(pages);
+    }
+
+    /// Sets the physical memory ranges that must never be cached, regardless of their page type.
+    pub fn set_uncached_ranges(&mut self, mut ranges: Vec<Range<Address>>) {
+        ranges.sort_unstable_by_key(|r| r.start);
+        self.uncached_ranges = ranges;
+    }
+
+    /// Returns the counters collected since the cache was created or last reset.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets all counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
     fn page_index(&self, addr: Address) -> usize {
         ((addr.as_page_aligned(self.page_size).to_umem() / self.page_size as umem)
             % (self.address.len() as umem)) as usize
@@ -123,7 +195,15 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
     }
 
     pub fn is_cached_page_type(&self, page_type: PageType) -> bool {
-        self.page_type_mask.contains(page_type)
+        page_type.is_cacheable() && self.page_type_mask.contains(page_type)
+    }
+
+    fn is_uncached_range(&self, addr: Address, len: usize) -> bool {
+        let idx = self.uncached_ranges.partition_point(|r| r.end <= addr);
+        self.uncached_ranges
+            .get(idx)
+            .map(|r| r.start < addr + len)
+            .unwrap_or(false)
     }
 
     pub fn cached_page_mut(&mut self, addr: Address, skip_validator: bool) -> CacheEntry<'a> {
@@ -162,6 +242,9 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
 
     pub fn validate_page(&mut self, addr: Address, page_buf: &'a mut [u8]) {
         let idx = self.page_index(addr);
+        if self.address[idx] != Address::INVALID && self.address[idx] != addr {
+            self.stats.evictions += 1;
+        }
         self.address[idx] = addr;
         self.address_once_validated[idx] = Address::INVALID;
         self.validator.validate_slot(idx);
@@ -175,8 +258,19 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
         self.address_once_validated[idx] = Address::INVALID;
     }
 
+    /// Invalidates the page containing `addr` if it is cached, regardless of its page type.
+    pub fn invalidate_cached_page(&mut self, addr: Address) {
+        if self.address[self.page_index(addr)] == addr.as_page_aligned(self.page_size) {
+            self.stats.invalidations += 1;
+            self.invalidate_page_raw(addr)
+        }
+    }
+
     pub fn invalidate_page(&mut self, addr: Address, page_type: PageType) {
         if self.page_type_mask.contains(page_type) {
+            if self.address[self.page_index(addr)] == addr.as_page_aligned(self.page_size) {
+                self.stats.invalidations += 1;
+            }
             self.invalidate_page_raw(addr)
         }
     }
@@ -216,7 +310,9 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
             let mut wlistcache = BumpVec::new_in(arena);
 
             while let Some(CTup3(addr, meta_addr, out)) = next {
-                if self.is_cached_page_type(addr.page_type()) {
+                if self.is_cached_page_type(addr.page_type())
+                    && !self.is_uncached_range(addr.address(), out.len())
+                {
                     (meta_addr, out)
                         .page_chunks(addr.address(), page_size)
                         .for_each(|(paddr, (meta_addr, chunk))| {
@@ -231,6 +327,15 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
                             );
 
                             let cached_page = self.cached_page_mut(prd.0.address(), false);
+                            self.prefetcher
+                                .track(cached_page.address, page_size, addr.page_type());
+
+                            if let PageValidity::Valid(_) = cached_page.validity {
+                                self.stats.hits += 1;
+                                self.stats.bytes_served += prd.2.len() as umem;
+                            } else {
+                                self.stats.misses += 1;
+                            }
 
                             match cached_page.validity {
                                 PageValidity::Valid(buf) => {
@@ -330,9 +435,80 @@ impl<'a, T: CacheValidator> PageCache<'a, T> {
                     }
                 }
             }
+        }
 
-            Ok(())
+        if self.prefetcher.is_sequential() {
+            self.prefetch(mem, arena)?;
         }
+
+        Ok(())
+    }
+
+    /// Speculatively reads the pages following a sequential access into the cache.
+    ///
+    /// Pages that are still valid in the cache are skipped, all other pages are read
+    /// with a single batched request. Failed reads are silently discarded.
+    fn prefetch<F: PhysicalMemory>(&mut self, mem: &mut F, arena: &Bump) -> Result<()> {
+        let page_size = self.page_size;
+        let Prefetcher {
+            pages,
+            next,
+            page_type,
+            ..
+        } = self.prefetcher;
+
+        let mut list = BumpVec::new_in(arena);
+
+        for i in 0..pages {
+            let cached_page = self.cached_page_mut(next + i * page_size, false);
+            match cached_page.validity {
+                PageValidity::Valid(buf) => self.put_page(cached_page.address, buf),
+                PageValidity::Validatable(buf) => {
+                    list.push(CTup3(
+                        PhysicalAddress::with_page(
+                            cached_page.address,
+                            page_type,
+                            page_size as umem,
+                        ),
+                        cached_page.address,
+                        buf.into(),
+                    ));
+                    self.mark_page_for_validation(cached_page.address);
+                }
+                _ => {}
+            }
+        }
+
+        if list.is_empty() {
+            return Ok(());
+        }
+
+        {
+            let mut iter = list
+                .iter_mut()
+                .map(|CTup3(addr, _, buf): &mut PhysicalReadData| {
+                    CTup3(*addr, addr.address(), buf.into())
+                });
+
+            let callback = &mut |CTup2(addr, buf): ReadData<'a>| {
+                self.validate_page(addr, buf.into());
+                true
+            };
+
+            let mut callback = callback.into();
+
+            mem.phys_read_raw_iter(MemOps {
+                inp: (&mut iter).into(),
+                out: Some(&mut callback),
+                out_fail: None,
+            })?;
+        }
+
+        list.into_iter().for_each(|CTup3(addr, _, buf)| {
+            self.cancel_page_validation(addr.address(), buf.into());
+        });
+
+        Ok(())
     }
 }
 
@@ -343,6 +519,7 @@ where
     fn clone(&self) -> Self {
         let page_size = self.page_size;
         let page_type_mask = self.page_type_mask;
+        let prefetcher = Prefetcher::new(self.prefetcher.pages);
         let validator = self.validator.clone();
 
         let cache_entries = self.address.len();
@@ -371,6 +548,9 @@ where
             address_once_validated: vec![Address::INVALID; cache_entries].into_boxed_slice(),
             page_size,
             page_type_mask,
+            uncached_ranges: self.uncached_ranges.clone(),
+            prefetcher,
+            stats: CacheStats::default(),
             validator,
             cache_ptr,
             cache_layout: layout,
@@ -392,7 +572,7 @@ mod tests {
     use crate::architecture::x86;
     use crate::cglue::ForwardMut;
     use crate::dummy::{DummyMemory, DummyOs};
-    use crate::mem::{CachedPhysicalMemory, MemoryView, VirtualDma};
+    use crate::mem::{AccessRecorder, CachedPhysicalMemory, MemoryView, VirtualDma};
     use crate::types::{cache::TimedCacheValidator, size, Address, PhysicalAddress};
 
     use coarsetime::Duration;
@@ -839,4 +1019,172 @@ mod tests {
         virt_mem.read_into(virt_base, buf_3.as_mut_slice()).unwrap();
         assert_eq!(buf_2, buf_3);
     }
+
+    #[test]
+    fn writeback_other_page_type() {
+        let mut dummy_mem = DummyMemory::new(size::mb(2));
+
+        let cache = PageCache::new(
+            x86::x64::ARCH,
+            size::mb(2),
+            PageType::PAGE_TABLE | PageType::READ_ONLY,
+            TimedCacheValidator::new(Duration::from_secs(100)),
+        );
+        let mut mem_cache = CachedPhysicalMemory::new(dummy_mem.forward_mut(), cache);
+
+        let pt_addr = PhysicalAddress::with_page(0x3008.into(), PageType::PAGE_TABLE, 0x1000);
+        let mut value = 0u64;
+        mem_cache.phys_read_into(pt_addr, &mut value).unwrap();
+        assert_eq!(value, 0);
+
+        // patching the page table through an untyped physical write
+        mem_cache.phys_write(0x3008.into(), &0x1234u64).unwrap();
+
+        mem_cache.phys_read_into(pt_addr, &mut value).unwrap();
+        assert_eq!(value, 0x1234);
+        assert_eq!(mem_cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn negative_cache() {
+        let recorder = AccessRecorder::new(DummyMemory::new(size::mb(1)), size::kb(4));
+        let mut mem_cache = CachedPhysicalMemory::builder(recorder)
+            .arch(x86::x64::ARCH)
+            .page_type_mask(PageType::UNKNOWN)
+            .negative_cache(16, TimedCacheValidator::new(Duration::from_secs(100)))
+            .build()
+            .unwrap();
+
+        let unmapped = PhysicalAddress::from(size::mb(2) as umem);
+        assert!(mem_cache
+            .phys_view()
+            .read_into(unmapped.address(), &mut 0u64)
+            .is_err());
+        assert!(mem_cache
+            .phys_view()
+            .read_into(unmapped.address(), &mut 0u64)
+            .is_err());
+
+        mem_cache.invalidate_failures();
+        assert!(mem_cache
+            .phys_view()
+            .read_into(unmapped.address(), &mut 0u64)
+            .is_err());
+
+        // only the first read and the one after the invalidation reached the connector
+        let (_, heatmap) = mem_cache.into_inner().into_inner();
+        assert_eq!(heatmap.to_vec()[0].page_base, unmapped.address());
+        assert_eq!(heatmap.to_vec()[0].count, 2);
+    }
+
+    #[test]
+    fn uncacheable_pages() {
+        let recorder = AccessRecorder::new(DummyMemory::new(size::mb(1)), size::kb(4));
+        let mut mem_cache = CachedPhysicalMemory::builder(recorder)
+            .arch(x86::x64::ARCH)
+            .page_type_mask(PageType::all())
+            .build()
+            .unwrap();
+
+        let page_size = size::kb(4) as umem;
+        let cached = PhysicalAddress::with_page(Address::NULL, PageType::UNKNOWN, page_size);
+        let mmio = PhysicalAddress::with_page(
+            Address::from(page_size),
+            PageType::UNKNOWN | PageType::UNCACHEABLE,
+            page_size,
+        );
+
+        for _ in 0..2 {
+            mem_cache.phys_read_into(cached, &mut 0u64).unwrap();
+            mem_cache.phys_read_into(mmio, &mut 0u64).unwrap();
+        }
+
+        // every read of the uncacheable page has to reach the connector
+        let (_, heatmap) = mem_cache.into_inner().into_inner();
+        let pages = heatmap.to_vec();
+        assert_eq!(pages[0].page_base, mmio.address());
+        assert_eq!(pages[0].count, 2);
+        assert_eq!(pages[1].page_base, cached.address());
+        assert_eq!(pages[1].count, 1);
+    }
+
+    #[test]
+    fn uncached_ranges_cloned() {
+        let mut cache = PageCache::new(
+            x86::x64::ARCH,
+            size::kb(64),
+            PageType::all(),
+            TimedCacheValidator::new(Duration::from_secs(100)),
+        );
+        cache.set_uncached_ranges(vec![Address::from(0x1000)..Address::from(0x2000)]);
+
+        let cloned = cache.clone();
+        assert!(cloned.is_uncached_range(Address::from(0x1800), 8));
+        assert!(!cloned.is_uncached_range(Address::from(0x2000), 8));
+    }
+
+    #[test]
+    fn prefetch_sequential() {
+        use std::sync::{Arc, Mutex};
+
+        /// Records the addresses of all reads that reach the backend.
+        struct Recorder {
+            mem: DummyMemory,
+            reads: Arc<Mutex<Vec<Address>>>,
+        }
+
+        impl PhysicalMemory for Recorder {
+            fn phys_read_raw_iter(
+                &mut self,
+                MemOps { inp, out, out_fail }: PhysicalReadMemOps,
+            ) -> Result<()> {
+                let (mem, reads) = (&mut self.mem, &self.reads);
+                let inp = inp.inspect(|CTup3(addr, _, _)| {
+                    reads.lock().unwrap().push(addr.address());
+                });
+                MemOps::with_raw(inp, out, out_fail, |data| mem.phys_read_raw_iter(data))
+            }
+
+            fn phys_write_raw_iter(&mut self, data: PhysicalWriteMemOps) -> Result<()> {
+                self.mem.phys_write_raw_iter(data)
+            }
+
+            fn metadata(&self) -> PhysicalMemoryMetadata {
+                self.mem.metadata()
+            }
+        }
+
+        let reads = Arc::new(Mutex::new(vec![]));
+        let recorder = Recorder {
+            mem: DummyMemory::new(size::mb(1)),
+            reads: reads.clone(),
+        };
+
+        let mut cache = PageCache::new(
+            x86::x64::ARCH,
+            size::kb(64),
+            PageType::UNKNOWN,
+            TimedCacheValidator::new(Duration::from_secs(100)),
+        );
+        cache.set_prefetch_pages(4);
+
+        let mut mem_cache = CachedPhysicalMemory::new(recorder, cache);
+
+        let mut buf = [0u8; 0x10];
+        for page in 0..2u64 {
+            mem_cache
+                .phys_read_into(Address::from(page * 0x1000).into(), &mut buf)
+                .unwrap();
+        }
+
+        // pages 2..6 are read ahead after the second page
+        assert!((2..6u64).all(|p| reads.lock().unwrap().contains(&Address::from(p * 0x1000))));
+        reads.lock().unwrap().clear();
+
+        // a random access does not trigger any further reads
+        mem_cache
+            .phys_read_into(Address::from(0x3000).into(), &mut buf)
+            .unwrap();
+        assert!(reads.lock().unwrap().is_empty());
+    }
 }
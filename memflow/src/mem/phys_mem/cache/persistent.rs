@@ -0,0 +1,569 @@
+//! A page cache that is stored in a memory mapped file.
+//!
+//! Connectors with a high latency per request, like network or FPGA based ones, take a long time
+//! to warm up an in-memory cache. The [`PersistentCachedMemory`] keeps all pages it has read in a
+//! file, so a later session against the same target starts with a warm cache.
+//!
+//! The cache file is keyed by the identity of the connector and the boot id of the target.
+//! Since the target can change the contents of the cached pages behind the back of the cache
+//! between two sessions of the same boot, only pages that are not expected to change should be
+//! cached. By default this is limited to read-only pages.
+//!
+//! The file is split into a fixed amount of slots, every page can only be stored in a single
+//! slot. Pages that map to the same slot replace each other.
+
+use std::prelude::v1::*;
+
+use crate::architecture::ArchitectureObj;
+use crate::cglue::*;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::iter::PageChunks;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMapping, PhysicalMemoryMetadata};
+use crate::types::{
+    cache::CacheStats, size, umem, util::fnv1a, Address, PageType, PhysicalAddress,
+};
+
+use memmap::{MmapMut, MmapOptions};
+
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"MFPGCACH";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 0x40;
+
+/// Layout of the cache file.
+///
+/// ```text
+/// header | tags (8 bytes per slot) | padding up to the page size | pages
+/// ```
+///
+/// A tag holds the address of the page in the slot with the lowest bit set,
+/// or 0 if the slot is empty.
+struct PageFile {
+    file: File,
+    map: MmapMut,
+    page_size: usize,
+    slots: usize,
+    data_offset: usize,
+}
+
+impl PageFile {
+    fn open(path: &Path, page_size: usize, slots: usize, key: u64) -> Result<Self> {
+        let data_offset = (HEADER_SIZE + slots * 8 + page_size - 1) / page_size * page_size;
+        let file_size = (data_offset + slots * page_size) as u64;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|err| {
+                Error(ErrorOrigin::Cache, ErrorKind::UnableToWriteFile).log_error(err)
+            })?;
+
+        let mut header = [0u8; HEADER_SIZE];
+        header[..8].copy_from_slice(MAGIC);
+        header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&(page_size as u32).to_le_bytes());
+        header[16..24].copy_from_slice(&(slots as u64).to_le_bytes());
+        header[24..32].copy_from_slice(&key.to_le_bytes());
+
+        let reuse = file
+            .metadata()
+            .map(|m| m.len() == file_size)
+            .unwrap_or(false);
+        if !reuse {
+            // truncating first zeroes all tags of a previous cache with a different layout
+            file.set_len(0)
+                .and_then(|_| file.set_len(file_size))
+                .map_err(|err| {
+                    Error(ErrorOrigin::Cache, ErrorKind::UnableToWriteFile).log_error(err)
+                })?;
+        }
+
+        let mut page_file = Self::map(file, page_size, slots, data_offset)?;
+
+        if page_file.map[..HEADER_SIZE] != header[..] {
+            if reuse {
+                log::info!("discarding stale page cache {:?}", path);
+            }
+            page_file.map[..data_offset].iter_mut().for_each(|b| *b = 0);
+            page_file.map[..HEADER_SIZE].copy_from_slice(&header);
+        }
+
+        Ok(page_file)
+    }
+
+    fn map(file: File, page_size: usize, slots: usize, data_offset: usize) -> Result<Self> {
+        let map = unsafe {
+            MmapOptions::new().map_mut(&file).map_err(|err| {
+                Error(ErrorOrigin::Cache, ErrorKind::UnableToMapFile).log_error(err)
+            })?
+        };
+
+        Ok(Self {
+            file,
+            map,
+            page_size,
+            slots,
+            data_offset,
+        })
+    }
+
+    #[inline]
+    fn slot(&self, page: Address) -> usize {
+        ((page.to_umem() / self.page_size as umem) % self.slots as umem) as usize
+    }
+
+    #[inline]
+    fn tag(&self, slot: usize) -> u64 {
+        let off = HEADER_SIZE + slot * 8;
+        u64::from_le_bytes(self.map[off..off + 8].try_into().unwrap())
+    }
+
+    #[inline]
+    fn set_tag(&mut self, slot: usize, tag: u64) {
+        let off = HEADER_SIZE + slot * 8;
+        self.map[off..off + 8].copy_from_slice(&tag.to_le_bytes());
+    }
+
+    #[inline]
+    fn page_mut(&mut self, slot: usize) -> &mut [u8] {
+        let off = self.data_offset + slot * self.page_size;
+        &mut self.map[off..off + self.page_size]
+    }
+
+    /// Returns the contents of `page` if it is stored in the file.
+    fn get(&mut self, page: Address) -> Option<&mut [u8]> {
+        let slot = self.slot(page);
+        if self.tag(slot) == page.to_umem() as u64 | 1 {
+            Some(self.page_mut(slot))
+        } else {
+            None
+        }
+    }
+
+    /// Stores `data` as the contents of `page`, returns `true` if another page was replaced.
+    fn put(&mut self, page: Address, data: &[u8]) -> bool {
+        let slot = self.slot(page);
+        let tag = self.tag(slot);
+        self.page_mut(slot).copy_from_slice(data);
+        self.set_tag(slot, page.to_umem() as u64 | 1);
+        tag != 0 && tag != page.to_umem() as u64 | 1
+    }
+}
+
+impl Clone for PageFile {
+    /// Maps the cache file a second time.
+    ///
+    /// # Panics
+    ///
+    /// If the file can not be duplicated or mapped.
+    fn clone(&self) -> Self {
+        Self::map(
+            self.file.try_clone().unwrap(),
+            self.page_size,
+            self.slots,
+            self.data_offset,
+        )
+        .unwrap()
+    }
+}
+
+/// Wraps a [`PhysicalMemory`] object and stores all cached pages in a file.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::architecture::x86::x64;
+/// use memflow::mem::{MemoryView, PersistentCachedMemory, PhysicalMemory};
+/// use memflow::types::size;
+/// # use memflow::dummy::DummyMemory;
+/// # let mem = DummyMemory::new(size::mb(2));
+/// # let dir = std::env::temp_dir().join("memflow-persistent-doctest");
+///
+/// let mut cache = PersistentCachedMemory::builder(mem)
+///     .arch(x64::ARCH)
+///     .directory(&dir)
+///     .identity("qemu:win10", "c0ffee")
+///     .cache_size(size::mb(16))
+///     .build()
+///     .unwrap();
+///
+/// let value: u64 = cache.phys_view().read(0x1000.into()).unwrap();
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct PersistentCachedMemory<T> {
+    mem: T,
+    file: PageFile,
+    page_type_mask: PageType,
+    stats: CacheStats,
+}
+
+impl<T: PhysicalMemory> PersistentCachedMemory<T> {
+    /// Returns a new builder for this cache with default settings.
+    pub fn builder(mem: T) -> PersistentCachedMemoryBuilder<T> {
+        PersistentCachedMemoryBuilder::new(mem)
+    }
+
+    /// Returns the hit and miss counters of this session.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Writes all modified pages of the cache file back to disk.
+    ///
+    /// The operating system writes the pages back on its own, this function only has to be
+    /// called to make sure the file is complete in case the process is terminated abruptly.
+    pub fn flush(&self) -> Result<()> {
+        self.file
+            .map
+            .flush()
+            .map_err(|err| Error(ErrorOrigin::Cache, ErrorKind::UnableToWriteFile).log_error(err))
+    }
+
+    /// Consumes the cache and returns the underlying memory object.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+}
+
+impl<T: PhysicalMemory> PhysicalMemory for PersistentCachedMemory<T> {
+    #[allow(clippy::needless_option_as_deref)]
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            mut out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let page_size = self.file.page_size;
+
+        let mut forward = vec![];
+        let mut missed = vec![];
+
+        for CTup3(addr, meta_addr, data) in inp {
+            if !self.page_type_mask.contains(addr.page_type()) {
+                forward.push(CTup3(addr, meta_addr, data));
+                continue;
+            }
+
+            for (paddr, (meta_addr, mut chunk)) in
+                (meta_addr, data).page_chunks(addr.address(), page_size)
+            {
+                let page = paddr.as_page_aligned(page_size);
+                let start = (paddr - page) as usize;
+
+                if let Some(buf) = self.file.get(page) {
+                    let len = chunk.len();
+                    chunk.copy_from_slice(&buf[start..start + len]);
+                    self.stats.hits += 1;
+                    self.stats.bytes_served += chunk.len() as umem;
+                    opt_call(out.as_deref_mut(), CTup2(meta_addr, chunk));
+                } else {
+                    self.stats.misses += 1;
+                    missed.push((
+                        PhysicalAddress::with_page(paddr, addr.page_type(), page_size as umem),
+                        meta_addr,
+                        chunk,
+                    ));
+                }
+            }
+        }
+
+        if !missed.is_empty() {
+            // read the missed pages as a whole, so they can be stored in the file
+            let mut pages = missed
+                .iter()
+                .map(|(addr, _, _)| {
+                    PhysicalAddress::with_page(
+                        addr.address().as_page_aligned(page_size),
+                        addr.page_type(),
+                        page_size as umem,
+                    )
+                })
+                .collect::<Vec<_>>();
+            pages.sort_by_key(|p| p.address());
+            pages.dedup_by_key(|p| p.address());
+
+            let mut buf = vec![0u8; pages.len() * page_size];
+            let mut done = vec![];
+            {
+                let mut done_cb: ReadCallback = (&mut done).into();
+                let iter = pages
+                    .iter()
+                    .zip(buf.chunks_mut(page_size))
+                    .map(|(&page, chunk)| CTup3(page, page.address(), chunk.into()));
+                MemOps::with_raw(iter, Some(&mut done_cb), None, |data| {
+                    self.mem.phys_read_raw_iter(data)
+                })?;
+            }
+            let mut done = done
+                .into_iter()
+                .map(|CTup2(page, _)| page)
+                .collect::<Vec<_>>();
+            done.sort();
+
+            for (i, page) in pages.iter().enumerate() {
+                if done.binary_search(&page.address()).is_ok()
+                    && self
+                        .file
+                        .put(page.address(), &buf[i * page_size..(i + 1) * page_size])
+                {
+                    self.stats.evictions += 1;
+                }
+            }
+
+            for (addr, meta_addr, mut chunk) in missed {
+                let page = addr.address().as_page_aligned(page_size);
+                match pages.binary_search_by_key(&page, |p| p.address()) {
+                    Ok(i) if done.binary_search(&page).is_ok() => {
+                        let start = i * page_size + (addr.address() - page) as usize;
+                        let len = chunk.len();
+                        chunk.copy_from_slice(&buf[start..start + len]);
+                        opt_call(out.as_deref_mut(), CTup2(meta_addr, chunk));
+                    }
+                    // the page could not be read as a whole, let the connector handle the chunk
+                    _ => forward.push(CTup3(addr, meta_addr, chunk)),
+                }
+            }
+        }
+
+        if forward.is_empty() {
+            return Ok(());
+        }
+
+        let mem = &mut self.mem;
+        MemOps::with_raw(
+            forward.into_iter(),
+            out.as_deref_mut(),
+            out_fail.as_deref_mut(),
+            |data| mem.phys_read_raw_iter(data),
+        )
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps { inp, out, out_fail }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        let file = &mut self.file;
+        let page_size = file.page_size;
+
+        // keep the stored pages in sync with the target
+        let inp = inp.map(move |CTup3(addr, meta_addr, data)| {
+            for (paddr, chunk) in data.page_chunks(addr.address(), page_size) {
+                let page = paddr.as_page_aligned(page_size);
+                if let Some(buf) = file.get(page) {
+                    let start = (paddr - page) as usize;
+                    buf[start..start + chunk.len()].copy_from_slice(chunk.into());
+                }
+            }
+            CTup3(addr, meta_addr, data)
+        });
+
+        let mem = &mut self.mem;
+        MemOps::with_raw(inp, out, out_fail, |data| mem.phys_write_raw_iter(data))
+    }
+
+    #[inline]
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+
+    #[inline]
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        self.mem.memory_runs(out)
+    }
+
+    #[inline]
+    fn set_mem_map(&mut self, mem_map: &[PhysicalMemoryMapping]) {
+        self.mem.set_mem_map(mem_map)
+    }
+}
+
+#[cfg(feature = "plugins")]
+cglue_impl_group!(
+    PersistentCachedMemory<T: PhysicalMemory>,
+    crate::plugins::ConnectorInstance,
+    {}
+);
+
+/// The builder interface for constructing a [`PersistentCachedMemory`] object.
+pub struct PersistentCachedMemoryBuilder<T> {
+    mem: T,
+    directory: Option<PathBuf>,
+    identity: Option<(String, String)>,
+    page_size: Option<usize>,
+    cache_size: usize,
+    page_type_mask: PageType,
+}
+
+impl<T: PhysicalMemory> PersistentCachedMemoryBuilder<T> {
+    fn new(mem: T) -> Self {
+        Self {
+            mem,
+            directory: None,
+            identity: None,
+            page_size: None,
+            cache_size: size::mb(64),
+            page_type_mask: PageType::READ_ONLY,
+        }
+    }
+
+    /// Opens or creates the cache file and builds the cache.
+    ///
+    /// Returns an error if the directory, the identity or the page size is not set,
+    /// or if the cache file can not be created.
+    pub fn build(self) -> Result<PersistentCachedMemory<T>> {
+        let directory = self.directory.ok_or_else(|| {
+            Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
+                .log_error("directory must be initialized")
+        })?;
+        let (connector, boot_id) = self.identity.ok_or_else(|| {
+            Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
+                .log_error("identity must be initialized")
+        })?;
+        let page_size = self.page_size.ok_or_else(|| {
+            Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
+                .log_error("page_size must be initialized")
+        })?;
+
+        if !page_size.is_power_of_two() || self.cache_size < page_size {
+            return Err(Error(ErrorOrigin::Cache, ErrorKind::InvalidArgument)
+                .log_error("page size must be a power of two and fit into the cache size"));
+        }
+
+        std::fs::create_dir_all(&directory).map_err(|err| {
+            Error(ErrorOrigin::Cache, ErrorKind::UnableToCreateDirectory).log_error(err)
+        })?;
+
+        let key = fnv1a(
+            connector
+                .bytes()
+                .chain(std::iter::once(0))
+                .chain(boot_id.bytes()),
+        );
+        let path = directory.join(format!("{:016x}.pagecache", key));
+
+        Ok(PersistentCachedMemory {
+            mem: self.mem,
+            file: PageFile::open(&path, page_size, self.cache_size / page_size, key)?,
+            page_type_mask: self.page_type_mask,
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Sets the directory the cache files are stored in.
+    ///
+    /// The directory is created if it does not exist yet.
+    pub fn directory(mut self, directory: impl AsRef<Path>) -> Self {
+        self.directory = Some(directory.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the identity of the target.
+    ///
+    /// `connector` has to uniquely identify the connector and its target,
+    /// e.g. the connector name and the name of the virtual machine. `boot_id` has to change
+    /// whenever the target reboots. A different identity results in a different cache file.
+    pub fn identity(mut self, connector: &str, boot_id: &str) -> Self {
+        self.identity = Some((connector.to_string(), boot_id.to_string()));
+        self
+    }
+
+    /// Sets the page size of the cache to the page size of the given architecture.
+    pub fn arch(mut self, arch: impl Into<ArchitectureObj>) -> Self {
+        self.page_size = Some(arch.into().page_size());
+        self
+    }
+
+    /// Changes the page size of the cache.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Sets the size of the cache file in bytes, excluding its header.
+    ///
+    /// The default size is 64 megabytes.
+    pub fn cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Sets the types of pages that are stored in the cache.
+    ///
+    /// The default is `PageType::READ_ONLY`. Since the cache file outlives a session,
+    /// pages that can be modified by the target should not be stored.
+    pub fn page_type_mask(mut self, page_type_mask: PageType) -> Self {
+        self.page_type_mask = page_type_mask;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::MemoryView;
+
+    fn build(mem: DummyMemory, dir: &Path, boot_id: &str) -> PersistentCachedMemory<DummyMemory> {
+        PersistentCachedMemory::builder(mem)
+            .directory(dir)
+            .identity("dummy", boot_id)
+            .page_size(size::kb(4))
+            .cache_size(size::kb(64))
+            .page_type_mask(PageType::UNKNOWN)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn reuse_across_sessions() {
+        let dir = std::env::temp_dir().join(format!("memflow-pagecache-{}", std::process::id()));
+
+        let mut mem = DummyMemory::new(size::mb(1));
+        mem.phys_write(0x1000.into(), &0x1234u64).unwrap();
+
+        let mut cache = build(mem.clone(), &dir, "boot-1");
+        assert_eq!(
+            cache.phys_view().read::<u64>(0x1000.into()).unwrap(),
+            0x1234
+        );
+        assert_eq!(cache.stats().misses, 1);
+        drop(cache);
+
+        // the target changes behind the back of the cache
+        mem.phys_write(0x1000.into(), &0x5678u64).unwrap();
+
+        let mut cache = build(mem.clone(), &dir, "boot-1");
+        assert_eq!(
+            cache.phys_view().read::<u64>(0x1000.into()).unwrap(),
+            0x1234
+        );
+        assert_eq!(cache.stats().hits, 1);
+
+        // writes through the cache update the stored page
+        cache.phys_write(0x1008.into(), &0xabcdu64).unwrap();
+        assert_eq!(
+            cache.phys_view().read::<u64>(0x1008.into()).unwrap(),
+            0xabcd
+        );
+        drop(cache);
+
+        // a reboot starts with an empty cache
+        let mut cache = build(mem, &dir, "boot-2");
+        assert_eq!(
+            cache.phys_view().read::<u64>(0x1000.into()).unwrap(),
+            0x5678
+        );
+        assert_eq!(cache.stats().hits, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
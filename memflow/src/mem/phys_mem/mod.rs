@@ -1,7 +1,7 @@
 use crate::cglue::*;
 use crate::dataview::Pod;
-use crate::error::Result;
-use crate::types::{umem, Address, PhysicalAddress};
+use crate::error::{PartialError, PartialResult, Result};
+use crate::types::{umem, Address, PageType, PhysicalAddress};
 
 use super::mem_data::*;
 use super::PhysicalMemoryMapping;
@@ -10,8 +10,10 @@ use std::prelude::v1::*;
 
 use crate::mem::memory_view::*;
 
+pub mod access_recorder;
 pub mod cache;
 
+pub use access_recorder::{AccessHeatmap, AccessRecorder, PageAccess};
 pub use cache::*;
 
 // TODO:
@@ -159,6 +161,118 @@ pub trait PhysicalMemory: Send {
     #[inline]
     fn set_mem_map(&mut self, _mem_map: &[PhysicalMemoryMapping]) {}
 
+    /// Reports the runs of physical memory that are backed by actual memory.
+    ///
+    /// The physical address space of most targets contains holes, e.g. for memory mapped
+    /// devices. Reading from a hole fails at best and has side effects on the target at worst.
+    /// Connectors that know the memory layout of their target report every run separately.
+    ///
+    /// By default a single run spanning from 0 to `max_address` is reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::mem::{MemoryMap, PhysicalMemory};
+    /// use memflow::types::{size, Address};
+    /// # use memflow::dummy::DummyMemory;
+    /// # let mut mem = DummyMemory::new(size::mb(1));
+    ///
+    /// for run in mem.memory_run_list() {
+    ///     println!("{:x} - {:x}", run.base, run.base + run.size);
+    /// }
+    /// ```
+    fn memory_runs(&self, out: PhysicalMemoryRunCallback) {
+        let mut out = out;
+        let metadata = self.metadata();
+        let _ = out.call(PhysicalMemoryRun {
+            base: Address::null(),
+            size: metadata.max_address.to_umem() + 1,
+            page_type: PageType::UNKNOWN,
+            readonly: metadata.readonly,
+        });
+    }
+
+    /// Returns all runs reported by [`memory_runs`](Self::memory_runs) sorted by their address.
+    #[skip_func]
+    fn memory_run_list(&self) -> Vec<PhysicalMemoryRun>
+    where
+        Self: Sized,
+    {
+        let mut runs = vec![];
+        self.memory_runs((&mut runs).into());
+        runs.sort_by_key(|run: &PhysicalMemoryRun| run.base);
+        runs
+    }
+
+    /// Reads all of physical memory in chunks of `chunk_size` bytes.
+    ///
+    /// Only the runs reported by [`memory_runs`](Self::memory_runs) are read, holes in the
+    /// physical address space are skipped. The callback receives the address and the contents
+    /// of every chunk, returning `false` stops the dump. Chunks that could not be read completely
+    /// are zero filled and a [`PartialError`] is returned in the end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::mem::PhysicalMemory;
+    /// use memflow::types::size;
+    /// # use memflow::dummy::DummyMemory;
+    /// # let mut mem = DummyMemory::new(size::mb(1));
+    ///
+    /// let mut dumped = 0;
+    /// mem.phys_dump(size::kb(64), |_, chunk| {
+    ///     dumped += chunk.len();
+    ///     true
+    /// })
+    /// .unwrap();
+    /// assert_eq!(dumped, size::mb(1));
+    /// ```
+    #[skip_func]
+    fn phys_dump<F>(&mut self, chunk_size: usize, mut callback: F) -> PartialResult<()>
+    where
+        Self: Sized,
+        F: FnMut(Address, &[u8]) -> bool,
+    {
+        let mut partial = false;
+
+        for run in self.memory_run_list() {
+            let mut proceed = true;
+            let mut view = self.phys_view();
+
+            match view.read_stream(run.base, run.size, chunk_size, |addr, chunk| {
+                proceed = callback(addr, chunk);
+                proceed
+            }) {
+                Ok(_) => {}
+                Err(PartialError::PartialVirtualRead(_)) => partial = true,
+                Err(err) => return Err(err),
+            }
+
+            if !proceed {
+                break;
+            }
+        }
+
+        if partial {
+            Err(PartialError::PartialVirtualRead(()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the zero-copy interface of this backend, if it has one.
+    ///
+    /// Backends implementing [`PhysicalMemoryMapped`] should return `Some(self)` here,
+    /// which allows wrapping layers like the page cache or the page table walker
+    /// to access the memory without intermediate copies.
+    ///
+    /// By default this returns `None`.
+    #[skip_func]
+    #[inline]
+    fn as_mapped(&self) -> Option<&dyn PhysicalMemoryMapped> {
+        None
+    }
+
     #[skip_func]
     fn phys_read_into<T: Pod + ?Sized>(&mut self, addr: PhysicalAddress, out: &mut T) -> Result<()>
     where
@@ -211,6 +325,19 @@ pub trait PhysicalMemory: Send {
     }
 }
 
+/// Optional extension for backends that can hand out direct views of physical memory.
+///
+/// Backends that keep the physical memory mapped into the address space of the current process,
+/// like memory dumps opened with `mmap`, can provide slices of the mapped memory instead of copying
+/// it into the buffers of the caller. Wrapping layers discover the extension through
+/// [`PhysicalMemory::as_mapped`].
+pub trait PhysicalMemoryMapped {
+    /// Returns a view of the `len` bytes at `addr`.
+    ///
+    /// Returns `None` if the range is not fully mapped in one piece.
+    fn phys_mapped(&self, addr: Address, len: usize) -> Option<&[u8]>;
+}
+
 #[repr(C)]
 #[derive(Clone)]
 #[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
@@ -309,3 +436,19 @@ pub struct PhysicalMemoryMetadata {
     pub readonly: bool,
     pub ideal_batch_size: u32,
 }
+
+/// A contiguous run of physical memory, as reported by [`PhysicalMemory::memory_runs`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct PhysicalMemoryRun {
+    /// Physical address of the first byte of the run.
+    pub base: Address,
+    /// Length of the run in bytes.
+    pub size: umem,
+    /// Type of the memory in this run, `PageType::UNKNOWN` if the connector can not tell.
+    pub page_type: PageType,
+    /// Whether writes to this run are rejected.
+    pub readonly: bool,
+}
@@ -0,0 +1,215 @@
+/*!
+Brute-force search for x86-64 page table roots.
+
+OS layers usually locate the directory table base (DTB) of the kernel through structures at
+well known locations, like the low stub on windows. On targets where this fails, the DTB can
+still be recovered by scanning physical memory for pages that look like a top-level (PML4) page
+table.
+
+Every page is rated by the following heuristics:
+- all present entries have to point into physical memory and must not have the large page bit set
+- at least one present entry has to map the upper (kernel) half of the address space
+- an entry pointing back to the page itself (self-referencing entry, as used by windows) is a
+  strong indication of a real DTB
+*/
+
+use std::prelude::v1::*;
+
+use super::SCAN_CHUNK_SIZE;
+use crate::error::{PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+use std::convert::TryInto;
+
+const PML4_ENTRIES: usize = 512;
+const PML4_SIZE: usize = PML4_ENTRIES * 8;
+
+const PRESENT_BIT: u64 = 1;
+const USER_BIT: u64 = 1 << 2;
+const LARGE_PAGE_BIT: u64 = 1 << 7;
+const ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// A page that looks like a top-level page table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DtbCandidate {
+    /// Physical address of the page.
+    pub dtb: Address,
+    /// Index of the entry pointing back to the page itself.
+    pub self_ref_index: Option<usize>,
+    /// Number of present entries in the upper half of the address space.
+    pub kernel_entries: usize,
+    /// Number of present entries in the lower half of the address space.
+    pub user_entries: usize,
+    /// Rating of the candidate, higher is more likely.
+    pub score: u32,
+}
+
+impl DtbCandidate {
+    /// Rates a single page, returns `None` if it can not be a page table root.
+    ///
+    /// `max_address` is the highest valid physical address.
+    pub fn from_page(dtb: Address, page: &[u8], max_address: Address) -> Option<Self> {
+        if page.len() < PML4_SIZE {
+            return None;
+        }
+
+        let mut candidate = DtbCandidate {
+            dtb,
+            self_ref_index: None,
+            kernel_entries: 0,
+            user_entries: 0,
+            score: 0,
+        };
+
+        // kernel entries with the user bit cleared are rated higher
+        let mut supervisor_entries = 0;
+
+        for (index, entry) in page[..PML4_SIZE]
+            .chunks_exact(8)
+            .map(|e| u64::from_le_bytes(e.try_into().unwrap()))
+            .enumerate()
+        {
+            if entry & PRESENT_BIT == 0 {
+                continue;
+            }
+
+            let addr = Address::from(entry & ADDRESS_MASK);
+            if entry & LARGE_PAGE_BIT != 0 || addr > max_address {
+                return None;
+            }
+
+            if addr == dtb {
+                candidate.self_ref_index = Some(index);
+            }
+
+            if index >= PML4_ENTRIES / 2 {
+                candidate.kernel_entries += 1;
+                if entry & USER_BIT == 0 {
+                    supervisor_entries += 1;
+                }
+            } else {
+                candidate.user_entries += 1;
+            }
+        }
+
+        if candidate.kernel_entries == 0 {
+            return None;
+        }
+
+        let self_ref_score = if candidate.self_ref_index.is_some() {
+            1000
+        } else {
+            0
+        };
+        candidate.score = self_ref_score
+            + (candidate.kernel_entries + supervisor_entries) as u32
+            + std::cmp::min(candidate.user_entries, 16) as u32;
+
+        Some(candidate)
+    }
+}
+
+/// Scans the physical range `start..start + len` for x86-64 page table roots.
+///
+/// `mem` has to be a view of physical memory, e.g. [`PhysicalMemory::phys_view`]. The start is
+/// rounded down to the page boundary. Candidates are returned sorted by their score, the most
+/// likely DTB first.
+///
+/// [`PhysicalMemory::phys_view`]: crate::mem::PhysicalMemory::phys_view
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::scan::dtb::scan_dtb_x64;
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::{umem, Address};
+/// # use memflow::dummy::{DummyMemory, DummyOs};
+/// # use memflow::types::size;
+/// # let mem = DummyMemory::new(size::mb(16));
+/// # let mut os = DummyOs::new(mem);
+/// # let dtb = os.alloc_dtb_const_base(Address::from(0x1000_0000_0000u64), size::kb(4), &[]);
+/// # os.alloc_mem_to_dtb(dtb, Address::from(0xffff_8000_0000_0000u64), size::kb(4), &[]);
+/// # let mut mem = os.into_inner();
+///
+/// let mut view = mem.phys_view();
+/// let candidates = scan_dtb_x64(&mut view, Address::null(), size::mb(16) as umem).unwrap();
+///
+/// for candidate in candidates.iter() {
+///     println!("{:x}: score {}", candidate.dtb, candidate.score);
+/// }
+/// # assert_eq!(candidates[0].dtb, dtb);
+/// ```
+pub fn scan_dtb_x64<M: MemoryView>(
+    mem: &mut M,
+    start: Address,
+    len: umem,
+) -> Result<Vec<DtbCandidate>> {
+    let aligned = start.as_page_aligned(PML4_SIZE);
+    let len = len + (start - aligned) as umem;
+    let max_address = mem.metadata().max_address;
+
+    let mut candidates = vec![];
+
+    mem.read_stream(aligned, len, SCAN_CHUNK_SIZE * 16, |addr, chunk| {
+        candidates.extend(
+            chunk
+                .chunks_exact(PML4_SIZE)
+                .enumerate()
+                .filter_map(|(i, page)| {
+                    DtbCandidate::from_page(addr + i * PML4_SIZE, page, max_address)
+                }),
+        );
+        true
+    })
+    .data_part()?;
+
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then(a.dtb.cmp(&b.dtb)));
+
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::{DummyMemory, DummyOs};
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn find_self_referencing_dtb() {
+        let mem = DummyMemory::new(size::mb(16));
+        let mut os = DummyOs::new(mem);
+        let (user_base, kernel_base) = (
+            Address::from(0x1000_0000_0000u64),
+            Address::from(0xffff_8000_0000_0000u64),
+        );
+
+        let dtb = os.alloc_dtb_const_base(user_base, size::kb(4), &[]);
+        let other = os.alloc_dtb_const_base(user_base, size::kb(4), &[]);
+        os.alloc_mem_to_dtb(dtb, kernel_base, size::kb(4), &[]);
+        os.alloc_mem_to_dtb(other, kernel_base, size::kb(4), &[]);
+
+        let mut mem = os.into_inner();
+        let mut view = mem.phys_view();
+
+        // self-referencing entry at the same index windows 10 uses
+        let self_ref = dtb.to_umem() as u64 | 0x63;
+        view.write(dtb + 0x1edusize * 8, &self_ref).unwrap();
+
+        let candidates = scan_dtb_x64(&mut view, Address::null(), size::mb(16) as umem).unwrap();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].dtb, dtb);
+        assert_eq!(candidates[0].self_ref_index, Some(0x1ed));
+        assert_eq!(candidates[0].kernel_entries, 2);
+        assert_eq!(candidates[1].dtb, other);
+        assert_eq!(candidates[1].self_ref_index, None);
+
+        // entries pointing outside of physical memory rule the page out
+        view.write(other + 8usize, &(size::gb(1) as u64 | 0x3))
+            .unwrap();
+        let candidates = scan_dtb_x64(&mut view, Address::null(), size::mb(16) as umem).unwrap();
+        assert_eq!(candidates.len(), 1);
+    }
+}
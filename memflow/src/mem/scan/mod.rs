@@ -0,0 +1,298 @@
+/*!
+Byte pattern scanning over physical and virtual memory.
+
+The scanner works on any [`MemoryView`], so physical memory can be scanned through
+[`PhysicalMemory::phys_view`](crate::mem::PhysicalMemory::phys_view) and the address space of a
+process through the process itself. Memory is read in page sized chunks and matches that
+span the boundary between two chunks are found as well.
+
+The [`dtb`] module searches physical memory for page table roots.
+*/
+
+use std::prelude::v1::*;
+
+pub mod dtb;
+pub mod multi;
+pub mod pattern;
+
+pub use dtb::{scan_dtb_x64, DtbCandidate};
+pub use multi::PatternSet;
+pub use pattern::Pattern;
+
+use crate::error::{PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+use cglue::callback::OpaqueCallback;
+use cglue::tuple::CTup2;
+
+/// The default amount of bytes that are read at once.
+pub const SCAN_CHUNK_SIZE: usize = 0x1000;
+
+/// Callback receiving the address of every match.
+pub type ScanCallback<'a> = OpaqueCallback<'a, Address>;
+
+/// Callback receiving the index of the matched pattern within a [`PatternSet`] and the address of every match.
+pub type MultiScanCallback<'a> = OpaqueCallback<'a, CTup2<usize, Address>>;
+
+/// Scans the range `start..start + len` for the given pattern.
+///
+/// The callback is called with the address of every match, returning `false` stops the scan.
+/// Parts of the range that can not be read are zero filled and are therefore only matched
+/// by patterns consisting of zeroes and wildcards.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::scan::{scan, Pattern};
+/// use memflow::mem::MemoryView;
+/// use memflow::types::{umem, Address};
+///
+/// fn find_all(mem: &mut impl MemoryView, start: Address, len: umem) -> Vec<Address> {
+///     let pattern = "48 8B 05 ?? ?? ?? ??".parse::<Pattern>().unwrap();
+///
+///     let mut matches = vec![];
+///     scan(mem, start, len, &pattern, (&mut |addr: Address| {
+///         matches.push(addr);
+///         true
+///     }).into())
+///     .unwrap();
+///     matches
+/// }
+/// # use memflow::dummy::DummyOs;
+/// # use memflow::types::size;
+/// # use memflow::os::Process;
+/// # let mut proc = DummyOs::quick_process(size::mb(2), &[0x48, 0x8b, 0x05, 0, 0, 0, 0]);
+/// # let virt_base = proc.info().address;
+/// # assert_eq!(find_all(&mut proc, virt_base, size::kb(4) as umem), vec![virt_base]);
+/// ```
+pub fn scan<M: MemoryView>(
+    mem: &mut M,
+    start: Address,
+    len: umem,
+    pattern: &Pattern,
+    callback: ScanCallback,
+) -> Result<()> {
+    scan_chunked(mem, start, len, pattern, SCAN_CHUNK_SIZE, callback)
+}
+
+/// Scans the range `start..start + len` for the given pattern, reading `chunk_size` bytes at once.
+///
+/// See [`scan`] for details.
+pub fn scan_chunked<M: MemoryView>(
+    mem: &mut M,
+    start: Address,
+    len: umem,
+    pattern: &Pattern,
+    chunk_size: usize,
+    mut callback: ScanCallback,
+) -> Result<()> {
+    scan_windows(
+        mem,
+        start,
+        len,
+        chunk_size,
+        pattern.len() - 1,
+        |base, window, tail| {
+            pattern
+                .find_iter(window)
+                .filter(|offset| offset + pattern.len() > tail)
+                .all(|offset| callback.call(base + offset as umem))
+        },
+    )
+}
+
+/// Scans the range `start..start + len` for all patterns of the set in a single pass.
+///
+/// The callback is called with the index of the pattern and the address of every match,
+/// returning `false` stops the scan. Matches are reported roughly in ascending order of
+/// their addresses, see [`PatternSet::find`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::cglue::CTup2;
+/// use memflow::mem::scan::{scan_set, PatternSet};
+/// use memflow::mem::MemoryView;
+/// use memflow::types::{umem, Address};
+///
+/// fn sweep(mem: &mut impl MemoryView, start: Address, len: umem) -> Vec<(usize, Address)> {
+///     let iocs = PatternSet::from_strs(&["4D 5A 90 00", "E8 ?? ?? ?? ?? 5D C3"]).unwrap();
+///
+///     let mut matches = vec![];
+///     scan_set(mem, start, len, &iocs, (&mut |CTup2(idx, addr): CTup2<usize, Address>| {
+///         matches.push((idx, addr));
+///         true
+///     }).into())
+///     .unwrap();
+///     matches
+/// }
+/// # use memflow::dummy::DummyOs;
+/// # use memflow::types::size;
+/// # use memflow::os::Process;
+/// # let mut proc = DummyOs::quick_process(size::mb(2), &[0x4d, 0x5a, 0x90, 0]);
+/// # let virt_base = proc.info().address;
+/// # assert_eq!(sweep(&mut proc, virt_base, size::kb(4) as umem), vec![(0, virt_base)]);
+/// ```
+pub fn scan_set<M: MemoryView>(
+    mem: &mut M,
+    start: Address,
+    len: umem,
+    patterns: &PatternSet,
+    mut callback: MultiScanCallback,
+) -> Result<()> {
+    scan_windows(
+        mem,
+        start,
+        len,
+        SCAN_CHUNK_SIZE,
+        patterns.max_len() - 1,
+        |base, window, tail| {
+            patterns.find(window, |idx, offset| {
+                // matches that end within the tail have been reported with the previous window
+                offset + patterns.patterns()[idx].len() <= tail
+                    || callback.call(CTup2(idx, base + offset as umem))
+            })
+        },
+    )
+}
+
+/// Streams the range in chunks and calls `find` with the base address of a window,
+/// the window itself and the length of the tail that was carried over from the previous chunk.
+///
+/// The last `overlap` bytes of every chunk are kept in front of the next chunk,
+/// so matches spanning two chunks are not missed.
+fn scan_windows<M, F>(
+    mem: &mut M,
+    start: Address,
+    len: umem,
+    chunk_size: usize,
+    overlap: usize,
+    mut find: F,
+) -> Result<()>
+where
+    M: MemoryView,
+    F: FnMut(Address, &[u8], usize) -> bool,
+{
+    let mut window = Vec::with_capacity(overlap + chunk_size);
+
+    mem.read_stream(start, len, chunk_size, |addr, chunk| {
+        let tail = window.len();
+        window.extend_from_slice(chunk);
+
+        if !find(addr - tail as umem, &window, tail) {
+            return false;
+        }
+
+        let keep = std::cmp::min(overlap, window.len());
+        window.drain(..window.len() - keep);
+        true
+    })
+    .data_part()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn parse_pattern() {
+        let pattern: Pattern = "48 8b ? ?? 05".parse().unwrap();
+        assert_eq!(pattern.to_string(), "48 8B ?? ?? 05");
+        assert!(pattern.matches(&[0x48, 0x8b, 0, 0xff, 0x05, 0xaa]));
+        assert!(!pattern.matches(&[0x48, 0x8b, 0, 0xff, 0x06]));
+        assert!(!pattern.matches(&[0x48, 0x8b]));
+
+        assert!("".parse::<Pattern>().is_err());
+        assert!("?? ??".parse::<Pattern>().is_err());
+        assert!("48 8".parse::<Pattern>().is_err());
+        assert!("48 zz".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn scan_across_chunks() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        let needle = [0xde, 0xad, 0xbe, 0xef];
+        view.write_raw(0x0ffe.into(), &needle).unwrap();
+        view.write_raw(0x3000.into(), &needle).unwrap();
+
+        let pattern = "DE AD ?? EF".parse::<Pattern>().unwrap();
+
+        let mut matches = vec![];
+        scan(
+            &mut view,
+            Address::NULL,
+            size::kb(64) as umem,
+            &pattern,
+            (&mut |addr: Address| {
+                matches.push(addr);
+                true
+            })
+                .into(),
+        )
+        .unwrap();
+        assert_eq!(matches, vec![Address::from(0x0ffe), Address::from(0x3000)]);
+
+        // stops after the first match
+        let mut count = 0;
+        scan_chunked(
+            &mut view,
+            Address::NULL,
+            size::kb(64) as umem,
+            &pattern,
+            0x100,
+            (&mut |_: Address| {
+                count += 1;
+                false
+            })
+                .into(),
+        )
+        .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn scan_pattern_set() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        // the short pattern ends up in the tail that is carried over to the next chunk
+        view.write_raw(0x0ffc.into(), &[0xaa, 0xbb]).unwrap();
+        view.write_raw(0x1ffe.into(), &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66])
+            .unwrap();
+        view.write_raw(0x5000.into(), &[0xaa, 0xbb]).unwrap();
+
+        let set = PatternSet::from_strs(&["AA BB", "11 ?? 33 44 ?? 66", "22 33"]).unwrap();
+        assert_eq!(set.max_len(), 6);
+
+        let mut matches = vec![];
+        scan_set(
+            &mut view,
+            Address::NULL,
+            size::kb(64) as umem,
+            &set,
+            (&mut |CTup2(idx, addr): CTup2<usize, Address>| {
+                matches.push((idx, addr));
+                true
+            })
+                .into(),
+        )
+        .unwrap();
+
+        matches.sort_by_key(|&(_, addr)| addr);
+        assert_eq!(
+            matches,
+            vec![
+                (0, Address::from(0x0ffc)),
+                (1, Address::from(0x1ffe)),
+                (2, Address::from(0x1fff)),
+                (0, Address::from(0x5000)),
+            ]
+        );
+    }
+}
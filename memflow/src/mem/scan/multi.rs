@@ -0,0 +1,194 @@
+use std::prelude::v1::*;
+
+use super::Pattern;
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+
+/// A set of patterns that is matched in a single pass over memory.
+///
+/// Every pattern is anchored on its longest run of non-wildcard bytes. The anchors of all
+/// patterns are compiled into an Aho-Corasick automaton, so the cost of a scan mostly depends on
+/// the amount of scanned memory and not on the number of patterns. Candidates found
+/// by the automaton are verified against the full pattern afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::scan::PatternSet;
+///
+/// let set = PatternSet::from_strs(&["DE AD BE EF", "CA FE ?? BA BE"]).unwrap();
+///
+/// let buf = [0u8, 0xca, 0xfe, 0x00, 0xba, 0xbe, 0xde, 0xad, 0xbe, 0xef];
+/// let mut matches = vec![];
+/// set.find(&buf, |pattern, offset| {
+///     matches.push((pattern, offset));
+///     true
+/// });
+/// assert_eq!(matches, vec![(1, 1), (0, 6)]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    patterns: Vec<Pattern>,
+    /// offset of the anchor within each pattern and its length
+    anchors: Vec<(usize, usize)>,
+    /// sorted goto transitions of every state of the trie
+    goto: Vec<Vec<(u8, u32)>>,
+    /// full transition table of the root state
+    root: [u32; 256],
+    fail: Vec<u32>,
+    /// indices of all patterns whose anchor ends at a given state
+    out: Vec<Vec<usize>>,
+    max_len: usize,
+}
+
+impl PatternSet {
+    /// Compiles the given patterns into a set.
+    pub fn new(patterns: Vec<Pattern>) -> Result<Self> {
+        if patterns.is_empty() {
+            return Err(Error(ErrorOrigin::Memory, ErrorKind::InvalidArgument)
+                .log_error("pattern set must contain at least one pattern"));
+        }
+
+        let anchors = patterns.iter().map(longest_run).collect::<Vec<_>>();
+        let max_len = patterns.iter().map(Pattern::len).max().unwrap_or(0);
+
+        let mut set = Self {
+            patterns,
+            anchors,
+            goto: vec![vec![]],
+            root: [0; 256],
+            fail: vec![0],
+            out: vec![vec![]],
+            max_len,
+        };
+        set.build();
+        Ok(set)
+    }
+
+    /// Parses all signature strings and compiles them into a set.
+    ///
+    /// See [`Pattern`] for the format of the strings.
+    pub fn from_strs(patterns: &[&str]) -> Result<Self> {
+        Self::new(
+            patterns
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<Vec<_>>>()?,
+        )
+    }
+
+    /// Returns the patterns of this set, the index of a pattern in this slice
+    /// is the index reported for its matches.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Returns the length of the longest pattern in the set.
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Finds all matches in `buf` and calls `callback` with the pattern index and the offset of each match.
+    ///
+    /// Matches are reported in the order in which their anchors appear in `buf`.
+    /// Returning `false` from the callback stops the search.
+    /// Returns `false` if the search has been stopped.
+    pub fn find<F: FnMut(usize, usize) -> bool>(&self, buf: &[u8], mut callback: F) -> bool {
+        let mut state = 0;
+        for (i, &b) in buf.iter().enumerate() {
+            state = self.next_state(state, b);
+            for &idx in self.out[state as usize].iter() {
+                let (anchor_offset, anchor_len) = self.anchors[idx];
+                let pattern = &self.patterns[idx];
+
+                let start = match (i + 1).checked_sub(anchor_len + anchor_offset) {
+                    Some(start) => start,
+                    None => continue,
+                };
+
+                if pattern.matches(&buf[start..]) && !callback(idx, start) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn next_state(&self, mut state: u32, b: u8) -> u32 {
+        loop {
+            if state == 0 {
+                return self.root[b as usize];
+            }
+            let goto = &self.goto[state as usize];
+            if let Ok(i) = goto.binary_search_by_key(&b, |&(k, _)| k) {
+                return goto[i].1;
+            }
+            state = self.fail[state as usize];
+        }
+    }
+
+    fn build(&mut self) {
+        // build the trie out of all anchors
+        for (idx, (pattern, &(offset, len))) in
+            self.patterns.iter().zip(self.anchors.iter()).enumerate()
+        {
+            let mut state = 0usize;
+            for &b in pattern.bytes()[offset..offset + len].iter() {
+                state = match self.goto[state].binary_search_by_key(&b, |&(k, _)| k) {
+                    Ok(i) => self.goto[state][i].1 as usize,
+                    Err(i) => {
+                        let next = self.goto.len();
+                        self.goto[state].insert(i, (b, next as u32));
+                        self.goto.push(vec![]);
+                        self.fail.push(0);
+                        self.out.push(vec![]);
+                        next
+                    }
+                };
+            }
+            self.out[state].push(idx);
+        }
+
+        for &(b, next) in self.goto[0].iter() {
+            self.root[b as usize] = next;
+        }
+
+        // compute the failure links in breadth-first order
+        let mut queue = self.goto[0]
+            .iter()
+            .map(|&(_, s)| s)
+            .collect::<std::collections::VecDeque<_>>();
+
+        while let Some(state) = queue.pop_front() {
+            for i in 0..self.goto[state as usize].len() {
+                let (b, next) = self.goto[state as usize][i];
+                let fail = self.next_state(self.fail[state as usize], b);
+                self.fail[next as usize] = fail;
+
+                let inherited = self.out[fail as usize].clone();
+                self.out[next as usize].extend(inherited);
+
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Returns the offset and length of the longest run of non-wildcard bytes in the pattern.
+fn longest_run(pattern: &Pattern) -> (usize, usize) {
+    let mut best = (0, 0);
+    let mut start = 0;
+    for (i, &m) in pattern
+        .mask()
+        .iter()
+        .chain(std::iter::once(&false))
+        .enumerate()
+    {
+        if !m {
+            if i - start > best.1 {
+                best = (start, i - start);
+            }
+            start = i + 1;
+        }
+    }
+    best
+}
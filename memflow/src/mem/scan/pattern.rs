@@ -0,0 +1,135 @@
+use std::prelude::v1::*;
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+
+use std::fmt;
+
+/// A byte pattern with optional wildcards.
+///
+/// Patterns are usually parsed from IDA-style signature strings where every byte is
+/// written as two hex digits and wildcards are written as `?` or `??`:
+///
+/// ```
+/// use memflow::mem::scan::Pattern;
+///
+/// let pattern: Pattern = "48 8B ?? ?? 05".parse().unwrap();
+/// assert_eq!(pattern.len(), 5);
+/// assert!(pattern.matches(&[0x48, 0x8b, 0x12, 0x34, 0x05]));
+/// assert_eq!(pattern.to_string(), "48 8B ?? ?? 05");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<bool>,
+}
+
+impl Pattern {
+    /// Creates a new pattern from the given bytes and mask.
+    ///
+    /// Bytes where the mask is `false` are treated as wildcards.
+    pub fn new(bytes: Vec<u8>, mask: Vec<bool>) -> Result<Self> {
+        if bytes.is_empty() || bytes.len() != mask.len() || !mask.iter().any(|&m| m) {
+            return Err(Error(ErrorOrigin::Memory, ErrorKind::InvalidArgument)
+                .log_error("pattern must contain at least one non-wildcard byte"));
+        }
+        Ok(Self { bytes, mask })
+    }
+
+    /// Creates a pattern without wildcards.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::new(bytes.to_vec(), vec![true; bytes.len()])
+    }
+
+    /// Returns the bytes of the pattern, wildcards are set to 0.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the mask of the pattern, wildcards are `false`.
+    pub fn mask(&self) -> &[bool] {
+        &self.mask
+    }
+
+    /// Returns the length of the pattern in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns `true` if the pattern is empty.
+    ///
+    /// Patterns created through [`Pattern::new`] are never empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns `true` if the start of `buf` matches this pattern.
+    pub fn matches(&self, buf: &[u8]) -> bool {
+        buf.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(self.mask.iter())
+                .zip(buf.iter())
+                .all(|((&b, &m), &v)| !m || b == v)
+    }
+
+    /// Returns an iterator over the offsets of all matches in `buf`.
+    ///
+    /// Overlapping matches are reported as well.
+    pub fn find_iter<'a>(&'a self, buf: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        // anchor the search on the first non-wildcard byte to skip most candidates quickly
+        let anchor = self.mask.iter().position(|&m| m).unwrap_or(0);
+        let anchor_byte = self.bytes[anchor];
+        let end = (buf.len() + 1).saturating_sub(self.len());
+
+        (0..end).filter(move |&i| buf[i + anchor] == anchor_byte && self.matches(&buf[i..]))
+    }
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = crate::error::Error;
+
+    /// Parses an IDA-style signature string like `48 8B ?? ?? 05`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut bytes = vec![];
+        let mut mask = vec![];
+
+        for token in s.split_whitespace() {
+            if token == "?" || token == "??" {
+                bytes.push(0);
+                mask.push(false);
+            } else if token.len() == 2 {
+                let byte = u8::from_str_radix(token, 16).map_err(|_| {
+                    Error(ErrorOrigin::Memory, ErrorKind::InvalidArgument)
+                        .log_error(format!("invalid byte in pattern: {}", token))
+                })?;
+                bytes.push(byte);
+                mask.push(true);
+            } else {
+                return Err(Error(ErrorOrigin::Memory, ErrorKind::InvalidArgument)
+                    .log_error(format!("invalid token in pattern: {}", token)));
+            }
+        }
+
+        Self::new(bytes, mask)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tokens = self
+            .bytes
+            .iter()
+            .zip(self.mask.iter())
+            .map(|(b, &m)| {
+                if m {
+                    format!("{:02X}", b)
+                } else {
+                    "??".to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", tokens.join(" "))
+    }
+}
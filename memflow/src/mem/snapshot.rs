@@ -0,0 +1,234 @@
+/*!
+Snapshots of memory regions and the difference between two of them.
+
+A [`Snapshot`] holds a copy of a set of regions together with a hash of every block in them.
+Diffing two snapshots only compares the bytes of blocks whose hashes differ, so taking
+many snapshots of large regions and looking for the few bytes that changed stays cheap.
+
+# Examples
+
+```
+use memflow::mem::snapshot::Snapshot;
+use memflow::mem::{MemoryView, PhysicalMemory};
+use memflow::types::{size, umem, Address};
+# use memflow::dummy::DummyMemory;
+# let mut mem = DummyMemory::new(size::mb(2));
+let mut view = mem.phys_view();
+
+let regions = [(Address::from(0x1000), size::kb(8) as umem)];
+let before = Snapshot::capture(&mut view, &regions).unwrap();
+
+view.write(Address::from(0x1804), &0xdeadu16).unwrap();
+
+let after = Snapshot::capture(&mut view, &regions).unwrap();
+let changes = before.diff(&after);
+
+assert_eq!(changes.len(), 1);
+assert_eq!(changes[0].addr, Address::from(0x1804));
+assert_eq!(changes[0].before, vec![0, 0]);
+assert_eq!(changes[0].after, vec![0xad, 0xde]);
+```
+*/
+
+use std::prelude::v1::*;
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, util::fnv1a, Address};
+
+use cglue::tuple::CTup2;
+
+use core::convert::TryFrom;
+
+/// The size of the blocks that are hashed individually.
+pub const SNAPSHOT_BLOCK_SIZE: usize = 0x1000;
+
+/// A copy of a single memory region.
+#[derive(Debug, Clone)]
+pub struct SnapshotRegion {
+    base: Address,
+    data: Vec<u8>,
+    hashes: Vec<u64>,
+}
+
+impl SnapshotRegion {
+    /// Returns the start address of the region.
+    pub fn base(&self) -> Address {
+        self.base
+    }
+
+    /// Returns the captured contents of the region.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the hashes of all blocks of the region.
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// Returns the length of the region in bytes.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the region is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// A range of memory that differs between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub addr: Address,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// A hashed copy of a set of memory regions.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    regions: Vec<SnapshotRegion>,
+}
+
+impl Snapshot {
+    /// Captures the given `(address, length)` regions with a single batched read.
+    ///
+    /// Parts of the regions that can not be read are zero filled.
+    pub fn capture<M: MemoryView>(mem: &mut M, regions: &[(Address, umem)]) -> Result<Self> {
+        let mut regions = regions
+            .iter()
+            .map(|&(base, len)| {
+                let len = usize::try_from(len).map_err(|_| {
+                    Error(ErrorOrigin::Memory, ErrorKind::InvalidMemorySize)
+                        .log_error("snapshot region does not fit into host memory")
+                })?;
+                Ok(SnapshotRegion {
+                    base,
+                    data: vec![0; len],
+                    hashes: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        regions.sort_by_key(|r| r.base);
+
+        {
+            let mut list = regions
+                .iter_mut()
+                .map(|r| CTup2(r.base, r.data.as_mut_slice().into()))
+                .collect::<Vec<_>>();
+            mem.read_raw_list(&mut list).data_part()?;
+        }
+
+        for region in regions.iter_mut() {
+            region.hashes = region
+                .data
+                .chunks(SNAPSHOT_BLOCK_SIZE)
+                .map(|block| fnv1a(block.iter().copied()))
+                .collect();
+        }
+
+        Ok(Self { regions })
+    }
+
+    /// Returns all regions of the snapshot, sorted by their base address.
+    pub fn regions(&self) -> &[SnapshotRegion] {
+        &self.regions
+    }
+
+    /// Returns all ranges that changed between this snapshot and `other`.
+    ///
+    /// Regions are matched by their base address, regions that only exist in one of
+    /// the snapshots are ignored. Adjacent changed bytes are merged into a single range.
+    pub fn diff(&self, other: &Snapshot) -> Vec<MemoryChange> {
+        let mut changes = vec![];
+
+        for before in self.regions.iter() {
+            let after = match other.regions.binary_search_by_key(&before.base, |r| r.base) {
+                Ok(i) => &other.regions[i],
+                Err(_) => continue,
+            };
+
+            let len = std::cmp::min(before.len(), after.len());
+            let mut current: Option<MemoryChange> = None;
+
+            for (block, (h1, h2)) in before.hashes.iter().zip(after.hashes.iter()).enumerate() {
+                let start = block * SNAPSHOT_BLOCK_SIZE;
+                let end = std::cmp::min(start + SNAPSHOT_BLOCK_SIZE, len);
+
+                // the last block differs in size if the regions have different lengths
+                if h1 == h2 && end - start == SNAPSHOT_BLOCK_SIZE {
+                    changes.extend(current.take());
+                    continue;
+                }
+
+                for i in start..end {
+                    let (b, a) = (before.data[i], after.data[i]);
+                    if a == b {
+                        changes.extend(current.take());
+                        continue;
+                    }
+
+                    let change = current.get_or_insert_with(|| MemoryChange {
+                        addr: before.base + i as umem,
+                        before: vec![],
+                        after: vec![],
+                    });
+                    change.before.push(b);
+                    change.after.push(a);
+                }
+            }
+
+            changes.extend(current.take());
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn diff_across_blocks() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        let regions = [
+            (Address::from(0x4000), 0x800),
+            (Address::from(0x1000), 0x2000),
+        ];
+        let before = Snapshot::capture(&mut view, &regions).unwrap();
+        assert_eq!(before.regions()[0].base(), Address::from(0x1000));
+        assert_eq!(before.regions()[0].hashes().len(), 2);
+
+        view.write(Address::from(0x1ffe), &[1u8, 2, 3, 4]).unwrap();
+        view.write(Address::from(0x4010), &0xffu8).unwrap();
+        // outside of all regions
+        view.write(Address::from(0x8000), &0xffu8).unwrap();
+
+        let after = Snapshot::capture(&mut view, &regions).unwrap();
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                MemoryChange {
+                    addr: Address::from(0x1ffe),
+                    before: vec![0; 4],
+                    after: vec![1, 2, 3, 4],
+                },
+                MemoryChange {
+                    addr: Address::from(0x4010),
+                    before: vec![0],
+                    after: vec![0xff],
+                },
+            ]
+        );
+
+        assert!(after.diff(&after).is_empty());
+    }
+}
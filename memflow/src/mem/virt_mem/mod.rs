@@ -1,4 +1,4 @@
 pub mod virtual_dma;
 
 #[doc(hidden)]
-pub use virtual_dma::VirtualDma;
+pub use virtual_dma::{VirtualDma, VirtualDmaStats};
@@ -1,14 +1,14 @@
 use std::prelude::v1::*;
 
 use crate::architecture::{ArchitectureObj, Endianess};
-use crate::error::{Error, Result, *};
+use crate::error::{Result, *};
 use crate::mem::memory_view::*;
 use crate::mem::{
     mem_data::*,
     virt_translate::{
-        DirectTranslate, VirtualTranslate, VirtualTranslate2, VirtualTranslate3,
-        VirtualTranslation, VirtualTranslationCallback, VirtualTranslationFail,
-        VirtualTranslationFailCallback,
+        DirectTranslate, PageTableDump, TranslationFailure, TranslationFailureReason,
+        VirtualTranslate, VirtualTranslate2, VirtualTranslate3, VirtualTranslation,
+        VirtualTranslationCallback, VirtualTranslationFail, VirtualTranslationFailCallback,
     },
     MemoryView, PhysicalMemory, PhysicalMemoryMetadata,
 };
@@ -28,6 +28,39 @@ pub struct VirtualDma<T, V, D> {
     proc_arch: ArchitectureObj,
     translator: D,
     arena: Bump,
+    stats: VirtualDmaStats,
+}
+
+/// Operation counters collected by a [`VirtualDma`] instance.
+///
+/// The counters are accumulated over the lifetime of the object (or until
+/// [`VirtualDma::reset_stats`] is called) and allow callers to judge how efficient
+/// their access patterns are without having to wrap the underlying layers.
+///
+/// Translation cache hits are only counted if the translation layer is a
+/// [`CachedVirtualTranslate`](crate::mem::virt_translate::CachedVirtualTranslate), its
+/// detailed counters can be accessed through [`VirtualDma::vat`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct VirtualDmaStats {
+    /// Number of virtual ranges that were successfully translated into physical ranges.
+    pub translations: umem,
+    /// Number of virtual ranges that could not be translated.
+    pub translation_failures: umem,
+    /// Number of pages whose translation was answered from the translation cache.
+    pub cache_hits: umem,
+    /// Number of physical chunks that were passed to the physical read function.
+    pub phys_reads: umem,
+    /// Number of physical chunks that were passed to the physical write function.
+    pub phys_writes: umem,
+    /// Total number of bytes requested from physical memory.
+    pub bytes_read: umem,
+    /// Total number of bytes passed to physical memory.
+    pub bytes_written: umem,
+    /// Number of physical chunks that the underlying memory failed to read or write.
+    pub partial_failures: umem,
 }
 
 impl<T: PhysicalMemory, D: VirtualTranslate3> VirtualDma<T, DirectTranslate, D> {
@@ -73,6 +106,7 @@ impl<T: PhysicalMemory, D: VirtualTranslate3> VirtualDma<T, DirectTranslate, D>
             proc_arch: arch.into(),
             translator,
             arena: Bump::new(),
+            stats: VirtualDmaStats::default(),
         }
     }
 }
@@ -117,6 +151,7 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> VirtualDma<T
             proc_arch: arch.into(),
             translator,
             arena: Bump::new(),
+            stats: VirtualDmaStats::default(),
         }
     }
 
@@ -135,19 +170,196 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> VirtualDma<T
         &self.translator
     }
 
-    /// A wrapper around `read_addr64` and `read_addr32` that will use the pointer size of this context's process.
-    /// TODO: do this in virt mem
+    /// Reads a pointer with the pointer size of this context's process.
+    ///
+    /// For emulated processes (e.g. WoW64) this reads 32-bit pointers even though the system is 64-bit.
+    /// Use [`VirtualDma::read_addr_sys`] to read pointers of structures owned by the system instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::{x32, x64};
+    /// use memflow::mem::{MemoryView, VirtualDma};
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[0xff; 16]);
+    ///
+    /// // a 32-bit process running on a 64-bit system
+    /// let mut virt_mem = VirtualDma::new(os.into_inner(), x32::ARCH, x64::new_translator(dtb));
+    ///
+    /// assert_eq!(virt_mem.read_addr(virt_base).unwrap().to_umem(), 0xffff_ffff);
+    /// assert_eq!(virt_mem.read_addr_sys(virt_base).unwrap().to_umem(), 0xffff_ffff_ffff_ffff);
+    /// ```
     pub fn read_addr(&mut self, addr: Address) -> PartialResult<Address> {
-        match self.proc_arch.bits() {
-            64 => self.read_addr64(addr),
-            32 => self.read_addr32(addr),
-            _ => Err(PartialError::Error(Error(
-                ErrorOrigin::VirtualMemory,
-                ErrorKind::InvalidArchitecture,
-            ))),
+        self.read_addr_arch(self.proc_arch, addr)
+    }
+
+    /// Reads a pointer with the pointer size of the system architecture.
+    pub fn read_addr_sys(&mut self, addr: Address) -> PartialResult<Address> {
+        self.read_addr_arch(self.sys_arch(), addr)
+    }
+
+    /// Dereferences a multi-level pointer path.
+    ///
+    /// Starting at `base`, the pointer at the current address is read and the next offset is
+    /// added to it, until all offsets have been applied. Pointers are read with the pointer
+    /// size of this context's process.
+    ///
+    /// An error is returned if one of the pointers can not be read or is null.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{MemoryView, VirtualDma};
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[]);
+    ///
+    /// let mut virt_mem = VirtualDma::new(os.into_inner(), x64::ARCH, x64::new_translator(dtb));
+    ///
+    /// // [[virt_base] + 0x8] + 0x10
+    /// virt_mem.write(virt_base, &(virt_base + 0x100usize).to_umem()).unwrap();
+    /// virt_mem.write(virt_base + 0x108usize, &(virt_base + 0x200usize).to_umem()).unwrap();
+    ///
+    /// assert_eq!(
+    ///     virt_mem.read_ptr_chain(virt_base, &[0x8, 0x10]).unwrap(),
+    ///     virt_base + 0x210usize
+    /// );
+    /// assert_eq!(
+    ///     virt_mem.read_ptr_chain_trace(virt_base, &[0x8, 0x10]).unwrap(),
+    ///     vec![virt_base, virt_base + 0x108usize, virt_base + 0x210usize]
+    /// );
+    ///
+    /// // [[[virt_base] + 0x8] + 0x10] is null, the trace stops at the broken level
+    /// let (trace, _) = virt_mem
+    ///     .read_ptr_chain_trace(virt_base, &[0x8, 0x10, 0x0])
+    ///     .unwrap_err();
+    /// assert_eq!(trace, vec![virt_base, virt_base + 0x108usize, virt_base + 0x210usize]);
+    /// ```
+    pub fn read_ptr_chain(&mut self, base: Address, offsets: &[umem]) -> Result<Address> {
+        self.walk_ptr_chain(base, offsets, |_| {})
+    }
+
+    /// Dereferences a multi-level pointer path and returns every address along the path.
+    ///
+    /// The first element is `base`, the last one is the final address returned by
+    /// [`VirtualDma::read_ptr_chain`]. This is mainly useful to find out which level
+    /// of a broken pointer path went wrong.
+    ///
+    /// If the path is broken the addresses up to the one whose pointer could not be
+    /// dereferenced are returned together with the error.
+    pub fn read_ptr_chain_trace(
+        &mut self,
+        base: Address,
+        offsets: &[umem],
+    ) -> std::result::Result<Vec<Address>, (Vec<Address>, Error)> {
+        let mut addrs = Vec::with_capacity(offsets.len() + 1);
+        addrs.push(base);
+        match self.walk_ptr_chain(base, offsets, |addr| addrs.push(addr)) {
+            Ok(_) => Ok(addrs),
+            Err(err) => Err((addrs, err)),
+        }
+    }
+
+    fn walk_ptr_chain(
+        &mut self,
+        base: Address,
+        offsets: &[umem],
+        mut visit: impl FnMut(Address),
+    ) -> Result<Address> {
+        let mut addr = base;
+        for &offset in offsets {
+            let ptr = self.read_addr(addr).data()?;
+            if ptr.is_null() {
+                return Err(Error(ErrorOrigin::VirtualMemory, ErrorKind::NotFound)
+                    .log_trace(format!("null pointer in pointer chain at {:x}", addr)));
+            }
+            addr = ptr + offset;
+            visit(addr);
+        }
+        Ok(addr)
+    }
+
+    /// Explains why `addr` can not be translated.
+    ///
+    /// Returns `None` if the address translates fine. Otherwise the page walk is repeated
+    /// step by step and the page table level, the raw entry and the reason it stopped at are
+    /// returned. This bypasses all translation caches and is meant to debug incomplete reads,
+    /// e.g. by calling it for the invalid ranges of [`MemoryView::read_raw_partial`].
+    ///
+    /// An error is returned if the translator does not support tracing page walks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{TranslationFailureReason, VirtualDma};
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[]);
+    ///
+    /// let mut virt_mem = VirtualDma::new(os.into_inner(), x64::ARCH, x64::new_translator(dtb));
+    ///
+    /// assert_eq!(virt_mem.translate_diag(virt_base).unwrap(), None);
+    ///
+    /// let failure = virt_mem.translate_diag(virt_base - 1usize).unwrap().unwrap();
+    /// assert_eq!(failure.reason, TranslationFailureReason::NotPresent);
+    /// println!("{}", failure);
+    /// ```
+    pub fn translate_diag(&mut self, addr: Address) -> Result<Option<TranslationFailure>> {
+        match self.translator.virt_to_phys_trace(&mut self.phys_mem, addr) {
+            Ok(trace) => Ok(trace.failure()),
+            Err(Error(_, ErrorKind::OutOfMemoryRange)) => Ok(Some(TranslationFailure {
+                virt_addr: addr,
+                level: 0,
+                entry: 0,
+                reason: TranslationFailureReason::NonCanonical,
+            })),
+            Err(err) => Err(err),
         }
     }
 
+    /// Dumps all page tables of the address space.
+    ///
+    /// See [`VirtualTranslate3::dump_page_tables`] for details.
+    pub fn dump_page_tables(&mut self) -> Result<PageTableDump> {
+        self.translator.dump_page_tables(&mut self.phys_mem)
+    }
+
+    /// Returns the operation counters collected by this object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{MemoryView, VirtualDma};
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[0; 16]);
+    ///
+    /// let mut virt_mem = VirtualDma::new(os.into_inner(), x64::ARCH, x64::new_translator(dtb));
+    ///
+    /// let mut buf = [0u8; 16];
+    /// virt_mem.read_raw_into(virt_base, &mut buf).unwrap();
+    ///
+    /// let stats = virt_mem.stats();
+    /// assert_eq!(stats.bytes_read, 16);
+    /// assert_eq!(stats.translation_failures, 0);
+    /// ```
+    pub fn stats(&self) -> VirtualDmaStats {
+        self.stats
+    }
+
+    /// Resets all operation counters to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = VirtualDmaStats::default();
+    }
+
     /// Consumes this VirtualDma object, returning the underlying memory and vat objects
     pub fn into_inner(self) -> (T, V) {
         (self.phys_mem, self.vat)
@@ -183,6 +395,7 @@ where
             proc_arch: self.proc_arch,
             translator: self.translator.clone(),
             arena: Bump::new(),
+            stats: self.stats,
         }
     }
 }
@@ -202,47 +415,146 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> MemoryView
         self.arena.reset();
         let mut translation = BumpVec::with_capacity_in(inp.size_hint().0, &self.arena);
 
+        let mut translation_failures = 0;
+        let cache_hits = self.vat.cache_stats().map(|s| s.hits);
+
         self.vat.virt_to_phys_iter(
             &mut self.phys_mem,
             &self.translator,
             inp,
             &mut translation.from_extend(),
             &mut (&mut |(_, CTup3(_, meta, buf)): (_, _)| {
+                translation_failures += 1;
                 opt_call(out_fail.as_deref_mut(), CTup2(meta, buf))
             })
                 .into(),
         );
 
-        MemOps::with_raw(translation.into_iter(), out, out_fail, |data| {
-            self.phys_mem.phys_read_raw_iter(data)
-        })
+        self.stats.cache_hits += cache_hits_since(&self.vat, cache_hits);
+        let stats = &mut self.stats;
+        stats.translations += translation.len() as umem;
+        stats.translation_failures += translation_failures;
+        stats.phys_reads += translation.len() as umem;
+        stats.bytes_read += translation
+            .iter()
+            .map(|CTup3(_, _, buf)| buf.len() as umem)
+            .sum::<umem>();
+
+        let mut partial_failures = 0;
+
+        let ret = {
+            let mut out = out.map(|o| move |data| o.call(data));
+            let mut out = out.as_mut().map(<_>::into);
+            let out = out.as_mut();
+
+            let fail_cb = &mut |CTup2(addr, buf): CTup2<_, _>| {
+                partial_failures += 1;
+                opt_call(out_fail.as_deref_mut(), CTup2(addr, buf))
+            };
+            let mut fail_cb = fail_cb.into();
+            let phys_mem = &mut self.phys_mem;
+
+            MemOps::with_raw(translation.into_iter(), out, Some(&mut fail_cb), |data| {
+                phys_mem.phys_read_raw_iter(data)
+            })
+        };
+
+        self.stats.partial_failures += partial_failures;
+
+        ret
     }
 
     fn write_raw_iter(
         &mut self,
         MemOps {
             inp,
-            out,
+            mut out,
             mut out_fail,
         }: WriteRawMemOps,
     ) -> Result<()> {
         self.arena.reset();
         let mut translation = BumpVec::with_capacity_in(inp.size_hint().0, &self.arena);
 
+        let mut translation_failures = 0;
+        let cache_hits = self.vat.cache_stats().map(|s| s.hits);
+
         self.vat.virt_to_phys_iter(
             &mut self.phys_mem,
             &self.translator,
             inp,
             &mut translation.from_extend(),
             &mut (&mut |(_, CTup3(_, meta, buf)): (_, _)| {
+                translation_failures += 1;
                 opt_call(out_fail.as_deref_mut(), CTup2(meta, buf))
             })
                 .into(),
         );
 
-        MemOps::with_raw(translation.into_iter(), out, out_fail, |data| {
-            self.phys_mem.phys_write_raw_iter(data)
-        })
+        self.stats.cache_hits += cache_hits_since(&self.vat, cache_hits);
+
+        // the original chunks are kept to report them to the callbacks
+        let chunks = BumpVec::from_iter_in(
+            translation
+                .into_iter()
+                .map(|CTup3(addr, meta, buf)| (addr, meta, <&[u8]>::from(buf))),
+            &self.arena,
+        );
+        let writes = coalesce_writes(&chunks, &self.arena);
+
+        let stats = &mut self.stats;
+        stats.translations += chunks.len() as umem;
+        stats.translation_failures += translation_failures;
+        stats.phys_writes += writes.len() as umem;
+        stats.bytes_written += chunks
+            .iter()
+            .map(|(_, _, buf)| buf.len() as umem)
+            .sum::<umem>();
+
+        let mut failed = BumpVec::new_in(&self.arena);
+
+        let ret = {
+            let fail_cb = &mut |CTup2(meta, buf): WriteData| {
+                failed.push((meta, buf.len() as umem));
+                true
+            };
+            let mut fail_cb = fail_cb.into();
+            let phys_mem = &mut self.phys_mem;
+
+            MemOps::with_raw(writes.into_iter(), None, Some(&mut fail_cb), |data| {
+                phys_mem.phys_write_raw_iter(data)
+            })
+        };
+
+        // merge the failed ranges so every chunk can be matched with a single lookup
+        failed.sort_unstable_by_key(|&(addr, _)| addr);
+        let mut merged = BumpVec::<(Address, Address)>::with_capacity_in(failed.len(), &self.arena);
+        for &(addr, len) in failed.iter() {
+            let end = addr + len;
+            match merged.last_mut() {
+                Some((_, last_end)) if addr <= *last_end => {
+                    *last_end = std::cmp::max(*last_end, end)
+                }
+                _ => merged.push((addr, end)),
+            }
+        }
+
+        let mut partial_failures = 0;
+
+        for &(_, meta, buf) in chunks.iter() {
+            let end = meta + buf.len();
+            let data = CTup2(meta, buf.into());
+            let idx = merged.partition_point(|&(addr, _)| addr < end);
+            if idx > 0 && meta < merged[idx - 1].1 {
+                partial_failures += 1;
+                opt_call(out_fail.as_deref_mut(), data);
+            } else {
+                opt_call(out.as_deref_mut(), data);
+            }
+        }
+
+        self.stats.partial_failures += partial_failures;
+
+        ret
     }
 
     fn metadata(&self) -> MemoryViewMetadata {
@@ -272,6 +584,10 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> VirtualTrans
         mut out: VirtualTranslationCallback,
         mut out_fail: VirtualTranslationFailCallback,
     ) {
+        let mut translations = 0;
+        let mut translation_failures = 0;
+        let cache_hits = self.vat.cache_stats().map(|s| s.hits);
+
         self.vat.virt_to_phys_iter(
             &mut self.phys_mem,
             &self.translator,
@@ -279,6 +595,7 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> VirtualTrans
                 .iter()
                 .map(|&CTup2(address, size)| CTup3(address, address, size)),
             &mut (&mut |CTup3(a, b, c): CTup3<PhysicalAddress, Address, umem>| {
+                translations += 1;
                 out.call(VirtualTranslation {
                     in_virtual: b,
                     size: c,
@@ -287,9 +604,69 @@ impl<T: PhysicalMemory, V: VirtualTranslate2, D: VirtualTranslate3> VirtualTrans
             })
                 .into(),
             &mut (&mut |(_e, CTup3(from, _, size))| {
+                translation_failures += 1;
                 out_fail.call(VirtualTranslationFail { from, size })
             })
                 .into(),
-        )
+        );
+
+        self.stats.cache_hits += cache_hits_since(&self.vat, cache_hits);
+        self.stats.translations += translations;
+        self.stats.translation_failures += translation_failures;
+    }
+}
+
+/// Returns the number of translation cache hits since `hits_before` was sampled.
+fn cache_hits_since(vat: &impl VirtualTranslate2, hits_before: Option<umem>) -> umem {
+    match (hits_before, vat.cache_stats()) {
+        (Some(before), Some(after)) => after.hits.saturating_sub(before),
+        _ => 0,
+    }
+}
+
+/// Merges consecutive writes that are contiguous in both physical and virtual memory.
+///
+/// Writes spanning multiple pages get split up during translation. If the pages are
+/// physically contiguous, their data is copied into a single buffer so the connector
+/// receives one request instead of one per page.
+fn coalesce_writes<'a, 'b: 'a>(
+    chunks: &[(PhysicalAddress, Address, &'b [u8])],
+    arena: &'a Bump,
+) -> BumpVec<'a, PhysicalWriteData<'a>> {
+    let mut writes = BumpVec::with_capacity_in(chunks.len(), arena);
+
+    let mut start = 0;
+    while start < chunks.len() {
+        let mut end = start + 1;
+        while end < chunks.len() {
+            let (prev_addr, prev_meta, prev_buf) = chunks[end - 1];
+            let (addr, meta, _) = chunks[end];
+            if prev_addr.address() + prev_buf.len() != addr.address()
+                || prev_meta + prev_buf.len() != meta
+                || prev_addr.page_type() != addr.page_type()
+            {
+                break;
+            }
+            end += 1;
+        }
+
+        let (addr, meta, buf) = chunks[start];
+        if end - start == 1 {
+            writes.push(CTup3(addr, meta, buf.into()));
+        } else {
+            let run = &chunks[start..end];
+            let merged = arena.alloc_slice_fill_copy(run.iter().map(|(_, _, b)| b.len()).sum(), 0);
+            let mut offset = 0;
+            for (_, _, b) in run {
+                merged[offset..offset + b.len()].copy_from_slice(b);
+                offset += b.len();
+            }
+            let merged: &'a [u8] = merged;
+            writes.push(CTup3(addr, meta, merged.into()));
+        }
+
+        start = end;
     }
+
+    writes
 }
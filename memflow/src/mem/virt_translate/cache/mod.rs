@@ -6,7 +6,9 @@ use crate::architecture::ArchitectureObj;
 use crate::iter::{PageChunks, SplitAtIndex};
 use crate::mem::virt_translate::VirtualTranslate2;
 use crate::mem::PhysicalMemory;
-use crate::types::cache::{CacheValidator, DefaultCacheValidator};
+#[cfg(feature = "std")]
+use crate::types::cache::StatsLogger;
+use crate::types::cache::{CacheStats, CacheValidator, DefaultCacheValidator};
 use crate::types::{umem, Address};
 use cglue::tuple::*;
 use tlb_cache::TlbCache;
@@ -17,6 +19,9 @@ use cglue::callback::FromExtend;
 
 use bumpalo::{collections::Vec as BumpVec, Bump};
 
+#[cfg(feature = "std")]
+use coarsetime::Duration;
+
 /// CachedVirtualTranslate trasnaparently caches virtual addresss translations.
 ///
 /// Using a VAT cache can provide significant speedups, since page table walks perform a number
@@ -102,8 +107,12 @@ pub struct CachedVirtualTranslate<V, Q> {
     tlb: TlbCache<Q>,
     arch: ArchitectureObj,
     arena: Bump,
+    enabled: bool,
+    cache_failures: bool,
     pub hitc: umem,
     pub misc: umem,
+    #[cfg(feature = "std")]
+    stats_logger: Option<StatsLogger>,
 }
 
 impl<V: VirtualTranslate2, Q: CacheValidator> CachedVirtualTranslate<V, Q> {
@@ -113,10 +122,81 @@ impl<V: VirtualTranslate2, Q: CacheValidator> CachedVirtualTranslate<V, Q> {
             tlb,
             arch,
             arena: Bump::new(),
+            enabled: true,
+            cache_failures: true,
             hitc: 0,
             misc: 0,
+            #[cfg(feature = "std")]
+            stats_logger: None,
         }
     }
+
+    /// Enables or disables the cache.
+    ///
+    /// While the cache is disabled, all translations are forwarded to the underlying translator
+    /// and the cached entries are neither used nor updated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{CachedVirtualTranslate, DirectTranslate, VirtualDma};
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[]);
+    ///
+    /// let vat = CachedVirtualTranslate::builder(DirectTranslate::new())
+    ///     .arch(x64::ARCH)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let mut virt_mem =
+    ///     VirtualDma::with_vat(os.into_inner(), x64::ARCH, x64::new_translator(dtb), vat);
+    ///
+    /// // the page tables of this context change too frequently to be cached
+    /// virt_mem.vat().set_enabled(false);
+    /// ```
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns `true` if the cache is used for translations.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Drops all cached translations.
+    ///
+    /// This has to be called when the page tables of the target were modified in a way that
+    /// the validator of the cache does not pick up in time.
+    pub fn flush(&mut self) {
+        self.tlb.flush();
+    }
+
+    /// Drops all cached translation failures.
+    ///
+    /// Failed translations are cached just like successful ones. When an address that was
+    /// unmapped may have been mapped since, e.g. after a process allocated memory,
+    /// this function makes sure the next access walks the page tables again.
+    pub fn invalidate_failures(&mut self) {
+        self.tlb.invalidate_failures();
+    }
+
+    /// Returns the hit and miss counters of the translation cache.
+    ///
+    /// Hits and misses are counted per page, `bytes_served` is the amount of bytes whose
+    /// translation was answered from the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.tlb.stats
+    }
+
+    /// Resets the counters of the translation cache to zero.
+    pub fn reset_stats(&mut self) {
+        self.tlb.stats = CacheStats::default();
+        self.hitc = 0;
+        self.misc = 0;
+    }
 }
 
 impl<V: VirtualTranslate2> CachedVirtualTranslate<V, DefaultCacheValidator> {
@@ -134,8 +214,12 @@ impl<V: VirtualTranslate2 + Clone, Q: CacheValidator + Clone> Clone
             tlb: self.tlb.clone(),
             arch: self.arch,
             arena: Bump::new(),
+            enabled: self.enabled,
+            cache_failures: self.cache_failures,
             hitc: self.hitc,
             misc: self.misc,
+            #[cfg(feature = "std")]
+            stats_logger: self.stats_logger.clone(),
         }
     }
 }
@@ -154,6 +238,12 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
         D: VirtualTranslate3,
         VI: Iterator<Item = CTup3<Address, Address, B>>,
     {
+        if !self.enabled {
+            self.vat
+                .virt_to_phys_iter(phys_mem, translator, addrs, out, out_fail);
+            return;
+        }
+
         self.tlb.validator.update_validity();
         self.arena.reset();
 
@@ -165,8 +255,10 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
 
         let mut hitc = 0;
         let mut misc = 0;
+        let mut bytes_served = 0;
 
         let arch = self.arch;
+        let page_size = arch.page_size();
         let mut addrs = addrs
             .filter_map(|CTup3(addr, meta_addr, buf)| {
                 if tlb.is_read_too_long(arch, buf.length() as umem) {
@@ -177,7 +269,7 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
                 }
             })
             .flat_map(|(addr, meta_addr, buf)| {
-                (meta_addr, buf).page_chunks_by(addr, arch.page_size(), |addr, (_, split), _| {
+                (meta_addr, buf).page_chunks_by(addr, page_size, |addr, (_, split), _| {
                     tlb.try_entry(translator, addr + split.length(), arch)
                         .is_some()
                         || tlb.try_entry(translator, addr, arch).is_some()
@@ -186,7 +278,8 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
             .filter_map(|(addr, (meta_addr, buf))| {
                 if let Some(entry) = tlb.try_entry(translator, addr, arch) {
                     hitc += 1;
-                    debug_assert!(buf.length() <= arch.page_size() as umem);
+                    bytes_served += buf.length() as umem;
+                    debug_assert!(buf.length() <= page_size as umem);
                     // TODO: handle case
                     let _ = match entry {
                         Ok(entry) => out.call(CTup3(entry.phys_addr, meta_addr, buf)),
@@ -194,7 +287,7 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
                     };
                     None
                 } else {
-                    misc += core::cmp::max(1, buf.length() / arch.page_size() as umem);
+                    misc += core::cmp::max(1, buf.length() / page_size as umem);
                     Some(CTup3(addr, meta_addr, (addr, buf)))
                 }
             })
@@ -225,15 +318,32 @@ impl<V: VirtualTranslate2, Q: CacheValidator> VirtualTranslate2 for CachedVirtua
                 }),
         );
 
+        let cache_failures = self.cache_failures;
         out_fail.extend(uncached_out_fail.into_iter().map(
             |(err, CTup3(vaddr, meta_addr, (_, buf)))| {
-                tlb.cache_invalid_if_uncached(translator, vaddr, buf.length() as umem, arch);
+                if cache_failures {
+                    tlb.cache_invalid_if_uncached(translator, vaddr, buf.length() as umem, arch);
+                }
                 (err, CTup3(vaddr, meta_addr, buf))
             },
         ));
 
         self.hitc += hitc;
         self.misc += misc;
+
+        let stats = &mut self.tlb.stats;
+        stats.hits += hitc;
+        stats.misses += misc;
+        stats.bytes_served += bytes_served;
+
+        #[cfg(feature = "std")]
+        if let Some(logger) = &mut self.stats_logger {
+            logger.tick(stats);
+        }
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(self.tlb.stats)
     }
 }
 
@@ -241,7 +351,12 @@ pub struct CachedVirtualTranslateBuilder<V, Q> {
     vat: V,
     validator: Q,
     entries: Option<usize>,
+    ways: usize,
+    enabled: bool,
+    cache_failures: bool,
     arch: Option<ArchitectureObj>,
+    #[cfg(feature = "std")]
+    log_interval: Option<Duration>,
 }
 
 impl<V: VirtualTranslate2> CachedVirtualTranslateBuilder<V, DefaultCacheValidator> {
@@ -250,27 +365,45 @@ impl<V: VirtualTranslate2> CachedVirtualTranslateBuilder<V, DefaultCacheValidato
             vat,
             validator: DefaultCacheValidator::default(),
             entries: Some(2048),
+            ways: 1,
+            enabled: true,
+            cache_failures: true,
             arch: None,
+            #[cfg(feature = "std")]
+            log_interval: None,
         }
     }
 }
 
 impl<V: VirtualTranslate2, Q: CacheValidator> CachedVirtualTranslateBuilder<V, Q> {
     pub fn build(self) -> Result<CachedVirtualTranslate<V, Q>> {
-        Ok(CachedVirtualTranslate::new(
+        let entries = self.entries.ok_or_else(|| {
+            Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
+                .log_error("entries must be initialized")
+        })?;
+
+        if self.ways == 0 || entries < self.ways {
+            return Err(Error(ErrorOrigin::Cache, ErrorKind::InvalidArgument)
+                .log_error("associativity must be between 1 and the number of entries"));
+        }
+
+        let mut vat = CachedVirtualTranslate::new(
             self.vat,
-            TlbCache::new(
-                self.entries.ok_or_else(|| {
-                    Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
-                        .log_error("entries must be initialized")
-                })?,
-                self.validator,
-            ),
+            TlbCache::with_geometry(entries, self.ways, self.validator),
             self.arch.ok_or_else(|| {
                 Error(ErrorOrigin::Cache, ErrorKind::Uninitialized)
                     .log_error("arch must be initialized")
             })?,
-        ))
+        );
+        vat.set_enabled(self.enabled);
+        vat.cache_failures = self.cache_failures;
+
+        #[cfg(feature = "std")]
+        if let Some(interval) = self.log_interval {
+            vat.stats_logger = Some(StatsLogger::new("tlb", interval));
+        }
+
+        Ok(vat)
     }
 
     pub fn validator<QN: CacheValidator>(
@@ -281,7 +414,12 @@ impl<V: VirtualTranslate2, Q: CacheValidator> CachedVirtualTranslateBuilder<V, Q
             vat: self.vat,
             validator,
             entries: self.entries,
+            ways: self.ways,
+            enabled: self.enabled,
+            cache_failures: self.cache_failures,
             arch: self.arch,
+            #[cfg(feature = "std")]
+            log_interval: self.log_interval,
         }
     }
 
@@ -290,10 +428,76 @@ impl<V: VirtualTranslate2, Q: CacheValidator> CachedVirtualTranslateBuilder<V, Q
         self
     }
 
+    /// Sets the associativity of the cache.
+    ///
+    /// The entries are split into sets of `ways` entries, a page can be cached in any entry of
+    /// its set. Higher associativity reduces conflicts between pages at the cost of slower
+    /// lookups. The default of 1 creates a direct mapped cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{CachedVirtualTranslate, DirectTranslate};
+    ///
+    /// let vat = CachedVirtualTranslate::builder(DirectTranslate::new())
+    ///     .arch(x64::ARCH)
+    ///     .entries(4096)
+    ///     .associativity(4)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn associativity(mut self, ways: usize) -> Self {
+        self.ways = ways;
+        self
+    }
+
+    /// Sets whether the cache is enabled initially.
+    ///
+    /// See [`CachedVirtualTranslate::set_enabled`] for details.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Sets whether failed translations are cached.
+    ///
+    /// Caching failures avoids walking the page tables again and again when unmapped memory is
+    /// probed, at the cost of missing memory that gets mapped while the failure is cached.
+    /// See [`CachedVirtualTranslate::invalidate_failures`] for dropping them explicitly.
+    ///
+    /// The default setting is `true`.
+    pub fn cache_failures(mut self, cache_failures: bool) -> Self {
+        self.cache_failures = cache_failures;
+        self
+    }
+
     pub fn arch(mut self, arch: impl Into<ArchitectureObj>) -> Self {
         self.arch = Some(arch.into());
         self
     }
+
+    /// Logs the statistics of the cache with the `info` level at most once per `interval`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::{CachedVirtualTranslate, DirectTranslate};
+    ///
+    /// let vat = CachedVirtualTranslate::builder(DirectTranslate::new())
+    ///     .arch(x64::ARCH)
+    ///     .log_stats(Duration::from_secs(30).into())
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn log_stats(mut self, interval: Duration) -> Self {
+        self.log_interval = Some(interval);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -303,9 +507,9 @@ mod tests {
     use crate::dummy::{DummyMemory, DummyOs};
     use crate::error::PartialResultExt;
     use crate::mem::{DirectTranslate, PhysicalMemory};
-    use crate::mem::{MemoryView, VirtualDma};
+    use crate::mem::{MemoryView, VirtualDma, VirtualTranslate};
     use crate::types::cache::timed_validator::TimedCacheValidator;
-    use crate::types::{size, Address};
+    use crate::types::{mem, size, Address};
 
     use coarsetime::Duration;
 
@@ -381,4 +585,98 @@ mod tests {
             .unwrap();
         assert!(read_into == buffer);
     }
+
+    #[test]
+    fn associative_geometry() {
+        let buffer = standard_buffer(size::kb(64));
+        let mem = DummyMemory::new(buffer.len() + size::mb(2));
+        let (os, dtb, virt_base) = DummyOs::new_and_dtb(mem, buffer.len(), &buffer);
+        let translator = x86::x64::new_translator(dtb);
+
+        assert!(CachedVirtualTranslate::builder(DirectTranslate::new())
+            .arch(x86::x64::ARCH)
+            .entries(2)
+            .associativity(4)
+            .build()
+            .is_err());
+
+        // 2 sets of 4 entries, the pages 0, 2, 4 and 6 all land in the first set
+        let vat = CachedVirtualTranslate::builder(DirectTranslate::new())
+            .arch(x86::x64::ARCH)
+            .validator(TimedCacheValidator::new(Duration::from_secs(100)))
+            .entries(8)
+            .associativity(4)
+            .build()
+            .unwrap();
+        let mut vmem = VirtualDma::with_vat(os.into_inner(), x86::x64::ARCH, translator, vat);
+
+        let pages = [0, 2, 4, 6].iter().map(|i| virt_base + i * size::kb(4));
+        for addr in pages.clone().chain(pages.clone()) {
+            vmem.read::<u64>(addr).unwrap();
+        }
+        assert_eq!(vmem.vat().misc, 4);
+        assert_eq!(vmem.vat().hitc, 4);
+
+        let stats = vmem.vat().stats();
+        assert_eq!(stats.hits, 4);
+        assert_eq!(stats.misses, 4);
+        assert_eq!(stats.evictions, 0);
+        assert_eq!(stats.bytes_served, 4 * 8);
+        assert_eq!(vmem.stats().cache_hits, 4);
+
+        // while disabled, the cache is bypassed entirely
+        vmem.vat().set_enabled(false);
+        for addr in pages {
+            vmem.read::<u64>(addr).unwrap();
+        }
+        assert_eq!(vmem.vat().misc, 4);
+        assert_eq!(vmem.vat().hitc, 4);
+
+        // a fifth page in the same set evicts one of the others
+        vmem.vat().set_enabled(true);
+        vmem.read::<u64>(virt_base + 8 * size::kb(4)).unwrap();
+        assert_eq!(vmem.vat().stats().evictions, 1);
+    }
+
+    #[test]
+    fn large_page_single_entry() {
+        let mut dummy_mem = DummyMemory::new(size::mb(8));
+        let mut phys_view = dummy_mem.phys_view();
+        // 2mb page at 2mb, mapped to 4mb
+        for &(addr, entry) in &[
+            (0x1000u64, 0x2003u64),
+            (0x2000, 0x3003),
+            (0x3008, 0x40_0083),
+            (0x40_1000, 0x1234),
+            (0x5f_f008, 0x5678),
+        ] {
+            phys_view.write(Address::from(addr), &entry).unwrap();
+        }
+
+        let vat = CachedVirtualTranslate::builder(DirectTranslate::new())
+            .arch(x86::x64::ARCH)
+            .validator(TimedCacheValidator::new(Duration::from_secs(100)))
+            .entries(1)
+            .build()
+            .unwrap();
+        let translator = x86::x64::new_translator(Address::from(0x1000));
+        let mut vmem = VirtualDma::with_vat(dummy_mem, x86::x64::ARCH, translator, vat);
+
+        assert_eq!(vmem.read::<u64>(Address::from(0x20_1000)).unwrap(), 0x1234);
+        assert_eq!(vmem.read::<u64>(Address::from(0x3f_f008)).unwrap(), 0x5678);
+
+        let stats = vmem.vat().stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.evictions, 0);
+
+        let paddr = vmem.virt_to_phys(Address::from(0x3f_f008)).unwrap();
+        assert_eq!(paddr.address(), Address::from(0x5f_f008));
+        assert_eq!(paddr.page_size(), mem::mb(2));
+        assert_eq!(vmem.vat().stats().hits, 2);
+
+        let stats = vmem.stats();
+        assert_eq!(stats.cache_hits, 2);
+        assert_eq!(stats.translations, 3);
+    }
 }
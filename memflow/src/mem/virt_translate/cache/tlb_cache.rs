@@ -3,7 +3,12 @@ use std::prelude::v1::*;
 use super::VirtualTranslate3;
 use crate::architecture::ArchitectureObj;
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
-use crate::types::{cache::CacheValidator, umem, Address, PhysicalAddress};
+use crate::types::{
+    cache::{CacheStats, CacheValidator},
+    umem, Address, PhysicalAddress,
+};
+
+use smallvec::SmallVec;
 
 #[derive(Clone, Copy)]
 pub struct TlbEntry {
@@ -40,22 +45,82 @@ impl CachedEntry {
 #[derive(Clone)]
 pub struct TlbCache<T> {
     entries: Box<[CachedEntry]>,
+    ways: usize,
+    /// Sizes of the large pages that were cached so far.
+    large_page_sizes: SmallVec<[umem; 2]>,
+    next_way: usize,
     pub validator: T,
+    pub stats: CacheStats,
 }
 
 impl<T: CacheValidator> TlbCache<T> {
-    pub fn new(size: usize, mut validator: T) -> Self {
+    /// Creates a direct mapped cache with `size` entries.
+    pub fn new(size: usize, validator: T) -> Self {
+        Self::with_geometry(size, 1, validator)
+    }
+
+    /// Creates a cache with `size` entries, split into sets of `ways` entries each.
+    ///
+    /// `size` gets rounded down to a multiple of `ways`.
+    pub fn with_geometry(size: usize, ways: usize, mut validator: T) -> Self {
+        let ways = std::cmp::max(ways, 1);
+        let size = std::cmp::max(size / ways, 1) * ways;
+
         validator.allocate_slots(size);
 
         Self {
             entries: vec![CachedEntry::INVALID; size].into_boxed_slice(),
+            ways,
+            large_page_sizes: SmallVec::new(),
+            next_way: 0,
             validator,
+            stats: CacheStats::default(),
         }
     }
 
     #[inline]
-    fn get_cache_index(&self, page_addr: Address, page_size: usize) -> usize {
-        ((page_addr.to_umem() / page_size as umem) % (self.entries.len() as umem)) as usize
+    fn get_set_start(&self, page_addr: Address, page_size: usize) -> usize {
+        let sets = (self.entries.len() / self.ways) as umem;
+        ((page_addr.to_umem() / page_size as umem) % sets) as usize * self.ways
+    }
+
+    /// Returns the index of the entry holding `page_addr`.
+    #[inline]
+    fn find_entry(&self, pt_index: umem, page_addr: Address, page_size: usize) -> Option<usize> {
+        let start = self.get_set_start(page_addr, page_size);
+        (start..start + self.ways).find(|&idx| {
+            let entry = &self.entries[idx];
+            entry.pt_index == pt_index && entry.virt_page == page_addr
+        })
+    }
+
+    /// Returns the index of an entry in the set of `page_addr` that is free to use.
+    ///
+    /// Entries holding failed translations are considered free.
+    #[inline]
+    fn find_free_entry(&self, page_addr: Address, page_size: usize) -> Option<usize> {
+        let start = self.get_set_start(page_addr, page_size);
+        (start..start + self.ways).find(|&idx| {
+            let entry = &self.entries[idx];
+            entry.pt_index == !0
+                || !entry.phys_page.is_valid()
+                || !self.validator.is_slot_valid(idx)
+        })
+    }
+
+    /// Returns the index of the large page entry containing `addr`.
+    ///
+    /// Large pages are stored in a single entry, at the address of the large page.
+    #[inline]
+    fn find_large_entry(&self, pt_index: umem, addr: Address, page_size: usize) -> Option<usize> {
+        self.large_page_sizes.iter().find_map(|&large_size| {
+            self.find_entry(
+                pt_index,
+                addr.as_page_aligned(large_size as usize),
+                page_size,
+            )
+            .filter(|&idx| self.entries[idx].phys_page.page_size() == large_size)
+        })
     }
 
     #[inline]
@@ -73,29 +138,28 @@ impl<T: CacheValidator> TlbCache<T> {
         let pt_index = translator.translation_table_id(addr);
         let page_size = arch.page_size();
         let page_address = addr.as_page_aligned(page_size);
-        let idx = self.get_cache_index(page_address, page_size);
+        let idx = self
+            .find_entry(pt_index, page_address, page_size)
+            .or_else(|| self.find_large_entry(pt_index, addr, page_size))
+            .filter(|&idx| self.validator.is_slot_valid(idx))?;
         let entry = self.entries[idx];
-        if entry.pt_index == pt_index
-            && entry.virt_page == page_address
-            && self.validator.is_slot_valid(idx)
-        {
-            if entry.phys_page.is_valid() && entry.phys_page.has_page() {
-                Some(Ok(TlbEntry {
-                    pt_index,
-                    virt_addr: addr,
-                    // TODO: this should be aware of huge pages
-                    phys_addr: PhysicalAddress::with_page(
-                        entry.phys_page.address().as_page_aligned(page_size)
-                            + (addr - page_address),
-                        entry.phys_page.page_type(),
-                        page_size as umem,
-                    ),
-                }))
-            } else {
-                Some(Err(Error(ErrorOrigin::TlbCache, ErrorKind::NotFound)))
-            }
+        if entry.phys_page.is_valid() && entry.phys_page.has_page() {
+            let entry_size = std::cmp::max(entry.phys_page.page_size(), page_size as umem);
+            Some(Ok(TlbEntry {
+                pt_index,
+                virt_addr: addr,
+                phys_addr: PhysicalAddress::with_page(
+                    entry
+                        .phys_page
+                        .address()
+                        .as_page_aligned(entry_size as usize)
+                        + (addr - entry.virt_page),
+                    entry.phys_page.page_type(),
+                    entry_size,
+                ),
+            }))
         } else {
-            None
+            Some(Err(Error(ErrorOrigin::TlbCache, ErrorKind::NotFound)))
         }
     }
 
@@ -109,15 +173,65 @@ impl<T: CacheValidator> TlbCache<T> {
     ) {
         let pt_index = translator.translation_table_id(in_addr);
         let page_size = arch.page_size();
-        let idx = self.get_cache_index(in_addr.as_page_aligned(page_size), page_size);
+
+        // large pages occupy a single entry instead of one for every contained page
+        let page_addr = if out_page.has_page() && out_page.page_size() > page_size as umem {
+            let large_size = out_page.page_size();
+            if !self.large_page_sizes.contains(&large_size) {
+                self.large_page_sizes.push(large_size);
+            }
+            in_addr.as_page_aligned(large_size as usize)
+        } else {
+            in_addr.as_page_aligned(page_size)
+        };
+
+        let idx = match self
+            .find_entry(pt_index, page_addr, page_size)
+            .or_else(|| self.find_free_entry(page_addr, page_size))
+        {
+            Some(idx) => idx,
+            None => {
+                // evict the entries of a full set in a round robin fashion
+                self.next_way = (self.next_way + 1) % self.ways;
+                self.get_set_start(page_addr, page_size) + self.next_way
+            }
+        };
+
+        let old = &self.entries[idx];
+        if old.phys_page.is_valid()
+            && self.validator.is_slot_valid(idx)
+            && (old.pt_index != pt_index || old.virt_page != page_addr)
+        {
+            self.stats.evictions += 1;
+        }
+
         self.entries[idx] = CachedEntry {
             pt_index,
-            virt_page: in_addr.as_page_aligned(page_size),
+            virt_page: page_addr,
             phys_page: out_page,
         };
         self.validator.validate_slot(idx);
     }
 
+    /// Drops all cached translations.
+    pub fn flush(&mut self) {
+        for (idx, entry) in self.entries.iter_mut().enumerate() {
+            *entry = CachedEntry::INVALID;
+            self.validator.invalidate_slot(idx);
+        }
+    }
+
+    /// Drops all cached translation failures, keeping the successful translations.
+    pub fn invalidate_failures(&mut self) {
+        for (idx, entry) in self.entries.iter_mut().enumerate() {
+            if entry.pt_index != !0 && !entry.phys_page.is_valid() {
+                *entry = CachedEntry::INVALID;
+                self.validator.invalidate_slot(idx);
+                self.stats.invalidations += 1;
+            }
+        }
+    }
+
     #[inline]
     pub fn cache_invalid_if_uncached<D: VirtualTranslate3>(
         &mut self,
@@ -136,13 +250,21 @@ impl<T: CacheValidator> TlbCache<T> {
             .take(self.entries.len())
         {
             let cur_page = Address::from(i);
-            let idx = self.get_cache_index(cur_page, page_size);
 
-            let entry = &mut self.entries[idx];
-            if entry.pt_index == !0
-                || !entry.phys_page.is_valid()
-                || !self.validator.is_slot_valid(idx)
-            {
+            // never evict valid translations in favour of failed ones
+            let idx = match self.find_entry(pt_index, cur_page, page_size) {
+                Some(idx)
+                    if self.entries[idx].phys_page.is_valid()
+                        && self.validator.is_slot_valid(idx) =>
+                {
+                    continue
+                }
+                Some(idx) => Some(idx),
+                None => self.find_free_entry(cur_page, page_size),
+            };
+
+            if let Some(idx) = idx {
+                let entry = &mut self.entries[idx];
                 entry.pt_index = pt_index;
                 entry.virt_page = cur_page;
                 entry.phys_page = PhysicalAddress::INVALID;
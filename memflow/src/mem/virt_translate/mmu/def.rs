@@ -35,12 +35,21 @@ pub struct ArchMmuDef {
     pub addr_size: u8,
     /// size of an individual page table entry in bytes.
     pub pte_size: usize,
+    /// number of bits the address inside a PTE has to be shifted left by (0 on most
+    /// architectures, 2 on RISC-V where the physical page number starts at bit 10).
+    pub pte_addr_shift: u8,
     /// index of a bit in PTE defining whether the page is present or not.
     pub present_bit: fn(Address) -> bool,
     /// index of a bit in PTE defining if the page is writeable.
     pub writeable_bit: fn(Address, bool) -> bool,
     /// index of a bit in PTE defining if the page is non-executable.
     pub nx_bit: fn(Address, bool) -> bool,
+    /// index of a bit in PTE defining if the page is only accessible from supervisor mode.
+    pub supervisor_bit: fn(Address, bool) -> bool,
+    /// index of a bit in PTE defining if the page was accessed.
+    pub accessed_bit: fn(Address) -> bool,
+    /// index of a bit in PTE defining if the page was written to.
+    pub dirty_bit: fn(Address) -> bool,
     /// function for checking a bit in PTE to see if the PTE points to a large page.
     pub large_page_bit: fn(Address) -> bool,
 }
@@ -75,7 +84,7 @@ impl ArchMmuDef {
                 self.pte_size.to_le().trailing_zeros() as u8
             };
         let mask = Address::bit_mask(min..=max);
-        pte_addr.to_umem() & umem::from_le(mask.to_umem())
+        (pte_addr.to_umem() << self.pte_addr_shift) & umem::from_le(mask.to_umem())
     }
 
     pub(crate) const fn virt_addr_bit_range(&self, step: usize) -> (u8, u8) {
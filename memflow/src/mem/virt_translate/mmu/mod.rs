@@ -6,9 +6,9 @@ use super::VtopFailureCallback;
 use crate::iter::SplitAtIndex;
 use crate::types::{umem, Address};
 use cglue::tuple::*;
-pub(crate) use def::ArchMmuDef;
+pub use def::ArchMmuDef;
 pub(crate) use fixed_slice_vec::FixedSliceVec as MVec;
-pub(crate) use spec::ArchMmuSpec;
+pub use spec::ArchMmuSpec;
 pub(crate) use translate_data::FlagsType;
 use translate_data::{TranslateDataVec, TranslateVec, TranslationChunk};
 
@@ -1,3 +1,5 @@
+use std::prelude::v1::*;
+
 use crate::architecture::Endianess;
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 use crate::iter::FlowIters;
@@ -6,7 +8,10 @@ use crate::mem::{PhysicalMemory, PhysicalReadData};
 use crate::types::{umem, Address, PageType, PhysicalAddress, UMEM_BITS};
 use cglue::tuple::*;
 
-use super::super::{VtopFailureCallback, VtopOutputCallback};
+use super::super::{
+    PageTableDump, PageTableNode, PageTableNodeEntry, TranslationDecision, TranslationStep,
+    TranslationTrace, VtopFailureCallback, VtopOutputCallback,
+};
 use super::translate_data::{
     FlagsType, TranslateData, TranslateDataVec, TranslateVec, TranslationChunk,
 };
@@ -103,7 +108,7 @@ impl ArchMmuSpec {
     }
 
     pub fn pte_addr_mask(&self, pte_addr: Address, step: usize) -> umem {
-        pte_addr.to_umem() & umem::from_le(self.pte_addr_masks[step])
+        (pte_addr.to_umem() << self.def.pte_addr_shift) & umem::from_le(self.pte_addr_masks[step])
     }
 
     /// Filter out the input virtual address range to be in bounds
@@ -293,7 +298,13 @@ impl ArchMmuSpec {
                 .noexec((self.def.nx_bit)(
                     pte_addr,
                     prev_flags.contains(FlagsType::NX),
-                )),
+                ))
+                .user(!(self.def.supervisor_bit)(
+                    pte_addr,
+                    prev_flags.contains(FlagsType::SUPERVISOR),
+                ))
+                .accessed((self.def.accessed_bit)(pte_addr))
+                .dirty((self.def.dirty_bit)(pte_addr)),
             self.page_size_step(step),
         )
     }
@@ -324,6 +335,338 @@ impl ArchMmuSpec {
             || ((self.def.large_page_bit)(pte_addr) && self.valid_final_page_steps[step])
     }
 
+    /// Check if the virtual address lies within the translatable address space
+    ///
+    /// This is the single address equivalent of the range checks in `virt_addr_filter`.
+    pub fn is_canonical(&self, virt_addr: Address) -> bool {
+        let addr = virt_addr.to_umem();
+        let arch_bit_range: umem = (!0) >> (UMEM_BITS - self.def.addr_size * 8);
+        if addr & !arch_bit_range != 0 {
+            return false;
+        }
+
+        let virt_bit_range = self.virt_addr_bit_ranges[0].1;
+        let virt_range: umem = 1 << (virt_bit_range - 1);
+        if addr < virt_range {
+            return true;
+        }
+
+        // The upper half has to be all negative (all bits set), including the sign bit
+        let lhs = Address::bit_mask((virt_bit_range - 1)..=(self.def.addr_size * 8 - 1)).to_umem();
+        ((addr & lhs) ^ lhs) == 0
+    }
+
+    /// Perform a virtual to physical translation of a single address, recording each page walk step
+    ///
+    /// This mirrors the steps taken by `virt_to_phys_iter`, but reads one page table entry
+    /// at a time. Failures during the walk are recorded in the trace, only non-canonical
+    /// addresses result in an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `mem` - physical memory to read the page tables from
+    /// * `dtb` - translation base of the address space
+    /// * `virt_addr` - the virtual address to translate
+    pub(crate) fn virt_to_phys_trace<T, D>(
+        &self,
+        mem: &mut T,
+        dtb: D,
+        virt_addr: Address,
+    ) -> Result<TranslationTrace>
+    where
+        T: PhysicalMemory,
+        D: MmuTranslationBase,
+    {
+        if !self.is_canonical(virt_addr) {
+            return Err(Error(ErrorOrigin::Mmu, ErrorKind::OutOfMemoryRange));
+        }
+
+        let pte_size = self.def.pte_size;
+        let mut trace = TranslationTrace {
+            virt_addr,
+            steps: vec![],
+            phys_addr: None,
+        };
+
+        // the first level may be split between multiple page tables (e.g. TTBR0/TTBR1 on arm)
+        let index = self.virt_addr_to_pte_offset(virt_addr, 0) / pte_size as umem;
+        let (mut table, _) = dtb.get_pt_by_index(index as usize);
+        let mut prev_flags = FlagsType::NONE;
+
+        for step in 0..self.split_count() - 1 {
+            let entry_addr = self.vtop_step(table, virt_addr, step);
+
+            let mut buf = [0u8; 8];
+            let read = mem.phys_read_into(
+                PhysicalAddress::with_page(
+                    entry_addr,
+                    PageType::PAGE_TABLE,
+                    self.pt_leaf_size(step) as umem,
+                ),
+                &mut buf[..pte_size],
+            );
+            let entry = match (read, self.def.endianess, pte_size) {
+                (Err(_), _, _) => None,
+                (Ok(_), Endianess::LittleEndian, 8) => Some(u64::from_le_bytes(buf) as umem),
+                (Ok(_), Endianess::LittleEndian, 4) => {
+                    Some(u32::from_le_bytes(buf[..4].try_into().unwrap()) as umem)
+                }
+                (Ok(_), Endianess::BigEndian, 8) => Some(u64::from_be_bytes(buf) as umem),
+                (Ok(_), Endianess::BigEndian, 4) => {
+                    Some(u32::from_be_bytes(buf[..4].try_into().unwrap()) as umem)
+                }
+                (Ok(_), _, _) => Some(0),
+            };
+
+            let mut record = TranslationStep {
+                step,
+                table,
+                entry_addr,
+                entry: entry.unwrap_or_default(),
+                decision: TranslationDecision::ReadFailed,
+            };
+
+            let entry = match entry {
+                Some(entry) => Address::from(entry),
+                None => {
+                    trace.steps.push(record);
+                    break;
+                }
+            };
+
+            prev_flags = FlagsType::NONE
+                .writeable((self.def.writeable_bit)(
+                    entry,
+                    prev_flags.contains(FlagsType::WRITEABLE),
+                ))
+                .nx((self.def.nx_bit)(entry, prev_flags.contains(FlagsType::NX)))
+                .supervisor((self.def.supervisor_bit)(
+                    entry,
+                    prev_flags.contains(FlagsType::SUPERVISOR),
+                ));
+
+            // the entry read in this step determines the layout of the next step
+            let next_step = step + 1;
+
+            if !self.check_entry(entry, next_step + 1) {
+                record.decision = TranslationDecision::NotPresent;
+                trace.steps.push(record);
+                break;
+            } else if self.is_final_mapping(entry, next_step) {
+                record.decision = TranslationDecision::Page;
+                trace.steps.push(record);
+                trace.phys_addr = Some(self.get_phys_page(entry, virt_addr, next_step, prev_flags));
+                break;
+            }
+
+            record.decision = TranslationDecision::NextTable;
+            trace.steps.push(record);
+            table = entry;
+        }
+
+        Ok(trace)
+    }
+
+    /// Walk the whole page table hierarchy of a translation base
+    ///
+    /// Every non-zero entry gets recorded, along with the decision the page walk would make for
+    /// it. Tables that can not be read are recorded as `TranslationDecision::ReadFailed` on the
+    /// entry pointing to them, only an unreadable top-level table results in an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `mem` - physical memory to read the page tables from
+    /// * `dtb` - translation base of the address space
+    pub(crate) fn dump_page_tables<T, D>(&self, mem: &mut T, dtb: D) -> Result<PageTableDump>
+    where
+        T: PhysicalMemory,
+        D: MmuTranslationBase,
+    {
+        let pte_size = self.def.pte_size;
+
+        // the first level may be split between multiple page tables (e.g. TTBR0/TTBR1 on arm)
+        let mut roots: Vec<(Address, Vec<usize>)> = vec![];
+        for index in 0..(1usize << self.def.virtual_address_splits[0]) {
+            let (table, _) = dtb.get_pt_by_index(index);
+            match roots.iter_mut().find(|(t, _)| *t == table) {
+                Some((_, indices)) => indices.push(index),
+                None => roots.push((table, vec![index])),
+            }
+        }
+
+        let mut dump = PageTableDump { roots: vec![] };
+
+        for (table, indices) in roots {
+            let base = Address::from(self.pte_addr_mask(table, 0));
+            let raw = self.read_pt_entries(mem, base, 0)?;
+
+            let mut node = PageTableNode {
+                step: 0,
+                address: base,
+                entries: vec![],
+            };
+
+            for index in indices {
+                let virt_addr = self.sign_extend(Address::from(
+                    (index as umem) << self.virt_addr_bit_ranges[0].0,
+                ));
+                let entry_addr = self.vtop_step(table, virt_addr, 0);
+                let entry = raw[((entry_addr - base) as usize) / pte_size];
+                if entry != 0 {
+                    node.entries.push(self.dump_entry(
+                        mem,
+                        CTup3(index, entry_addr, entry),
+                        virt_addr,
+                        0,
+                        FlagsType::NONE,
+                    ));
+                }
+            }
+
+            dump.roots.push(node);
+        }
+
+        Ok(dump)
+    }
+
+    fn dump_table<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        table: Address,
+        step: usize,
+        virt_base: Address,
+        prev_flags: FlagsType,
+    ) -> Result<PageTableNode> {
+        let base = Address::from(self.pte_addr_mask(table, step));
+        let raw = self.read_pt_entries(mem, base, step)?;
+
+        let entries = raw
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, entry)| entry != 0)
+            .map(|(index, entry)| {
+                let entry_addr = base + index * self.def.pte_size;
+                let virt_addr =
+                    virt_base.to_umem() | ((index as umem) << self.virt_addr_bit_ranges[step].0);
+                self.dump_entry(
+                    mem,
+                    CTup3(index, entry_addr, entry),
+                    virt_addr.into(),
+                    step,
+                    prev_flags,
+                )
+            })
+            .collect();
+
+        Ok(PageTableNode {
+            step,
+            address: base,
+            entries,
+        })
+    }
+
+    fn dump_entry<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        CTup3(index, entry_addr, entry): CTup3<usize, Address, umem>,
+        virt_addr: Address,
+        step: usize,
+        prev_flags: FlagsType,
+    ) -> PageTableNodeEntry {
+        let entry_ptr = Address::from(entry);
+
+        let flags = FlagsType::NONE
+            .writeable((self.def.writeable_bit)(
+                entry_ptr,
+                prev_flags.contains(FlagsType::WRITEABLE),
+            ))
+            .nx((self.def.nx_bit)(
+                entry_ptr,
+                prev_flags.contains(FlagsType::NX),
+            ))
+            .supervisor((self.def.supervisor_bit)(
+                entry_ptr,
+                prev_flags.contains(FlagsType::SUPERVISOR),
+            ));
+
+        let mut node = PageTableNodeEntry {
+            index,
+            entry_addr,
+            entry,
+            virt_addr,
+            page_type: PageType::default()
+                .write(flags.contains(FlagsType::WRITEABLE))
+                .noexec(flags.contains(FlagsType::NX))
+                .user(!flags.contains(FlagsType::SUPERVISOR)),
+            decision: TranslationDecision::NotPresent,
+            table: None,
+            phys_addr: None,
+        };
+
+        // the entry read in this step determines the layout of the next step
+        let next_step = step + 1;
+
+        if !self.check_entry(entry_ptr, next_step + 1) {
+            return node;
+        } else if self.is_final_mapping(entry_ptr, next_step) {
+            node.decision = TranslationDecision::Page;
+            node.phys_addr = Some(self.get_phys_page(entry_ptr, virt_addr, next_step, flags));
+            return node;
+        }
+
+        match self.dump_table(mem, entry_ptr, next_step, virt_addr, flags) {
+            Ok(table) => {
+                node.decision = TranslationDecision::NextTable;
+                node.table = Some(table);
+            }
+            Err(_) => node.decision = TranslationDecision::ReadFailed,
+        }
+
+        node
+    }
+
+    fn read_pt_entries<T: PhysicalMemory>(
+        &self,
+        mem: &mut T,
+        base: Address,
+        step: usize,
+    ) -> Result<Vec<umem>> {
+        let size = self.pt_leaf_size(step);
+        let mut buf = vec![0u8; size];
+        mem.phys_read_into(
+            PhysicalAddress::with_page(base, PageType::PAGE_TABLE, size as umem),
+            buf.as_mut_slice(),
+        )?;
+
+        Ok(buf
+            .chunks_exact(self.def.pte_size)
+            .map(|chunk| match (self.def.endianess, chunk.len()) {
+                (Endianess::LittleEndian, 8) => {
+                    u64::from_le_bytes(chunk.try_into().unwrap()) as umem
+                }
+                (Endianess::LittleEndian, 4) => {
+                    u32::from_le_bytes(chunk.try_into().unwrap()) as umem
+                }
+                (Endianess::BigEndian, 8) => u64::from_be_bytes(chunk.try_into().unwrap()) as umem,
+                (Endianess::BigEndian, 4) => u32::from_be_bytes(chunk.try_into().unwrap()) as umem,
+                _ => 0,
+            })
+            .collect())
+    }
+
+    /// Extend the sign of a virtual address in the upper half of the address space
+    fn sign_extend(&self, virt_addr: Address) -> Address {
+        let virt_bit_range = self.virt_addr_bit_ranges[0].1;
+        let addr_bits = self.def.addr_size * 8;
+        if virt_bit_range >= addr_bits || !virt_addr.bit_at(virt_bit_range - 1) {
+            virt_addr
+        } else {
+            Address::from(
+                virt_addr.to_umem() | Address::bit_mask(virt_bit_range..=(addr_bits - 1)).to_umem(),
+            )
+        }
+    }
+
     /// This function will do a virtual to physical memory translation for the `ArchMmuSpec` in
     /// `MmuTranslationBase` scope, over multiple elements.
     pub(crate) fn virt_to_phys_iter<T, B, D, VI>(
@@ -535,6 +878,22 @@ impl ArchMmuSpec {
     {
         let pte_size = self.def.pte_size;
 
+        // Decode the entries straight from the mapping if the backend has one
+        if let Some(mapped) = mem.as_mapped() {
+            if chunks
+                .iter()
+                .all(|c| mapped.phys_mapped(c.pt_addr, pte_size).is_some())
+            {
+                for chunk in chunks.iter_mut() {
+                    if let Some(buf) = mapped.phys_mapped(chunk.pt_addr, pte_size) {
+                        chunk.pt_addr = buf_to_addr(buf);
+                        chunk.update_flags(&self.def);
+                    }
+                }
+                return Ok(());
+            }
+        }
+
         // Create temporary read bufs.
         // We need extra bytes for alignment
         let (pt_buf_bytes, slice) = slice.split_at_mut(chunks.len() * pte_size + 8);
@@ -125,6 +125,8 @@ bitflags! {
         const WRITEABLE = 0b01;
         // Maps MMUDef's nx_bit
         const NX = 0b10;
+        // Maps MMUDef's supervisor_bit
+        const SUPERVISOR = 0b100;
     }
 }
 
@@ -149,6 +151,15 @@ impl FlagsType {
         }
     }
 
+    pub fn supervisor(mut self, flag: bool) -> Self {
+        self &= !(FlagsType::SUPERVISOR);
+        if flag {
+            self | FlagsType::SUPERVISOR
+        } else {
+            self
+        }
+    }
+
     pub fn writeable(mut self, flag: bool) -> Self {
         self &= !(FlagsType::WRITEABLE);
         if flag {
@@ -169,6 +180,10 @@ impl TranslationChunk<Address> {
             .nx((mmu_def.nx_bit)(
                 self.pt_addr,
                 self.prev_flags.contains(FlagsType::NX),
+            ))
+            .supervisor((mmu_def.supervisor_bit)(
+                self.pt_addr,
+                self.prev_flags.contains(FlagsType::SUPERVISOR),
             ));
     }
 }
@@ -65,6 +65,7 @@ use std::prelude::v1::*;
 use super::{MemoryRange, MemoryRangeCallback, VtopRange};
 
 use std::cmp::*;
+use std::fmt;
 
 use cglue::prelude::v1::*;
 use itertools::Itertools;
@@ -83,13 +84,18 @@ pub mod cache;
 
 pub use cache::*;
 
+pub mod nested;
+pub use nested::NestedTranslate;
+
 #[cfg(test)]
 mod tests;
 
 use crate::error::{Result, *};
 
 use crate::mem::PhysicalMemory;
-use crate::types::{imem, umem, Address, Page, PhysicalAddress};
+use crate::types::{
+    imem, umem, Address, CacheStats, Page, PageType, PhysicalAddress, VirtualAddress,
+};
 
 /// Translates virtual addresses into physical ones.
 ///
@@ -262,6 +268,10 @@ pub trait VirtualTranslate: Send {
     ///
     /// Given negative gap size, they will not be removed.
     ///
+    /// Each range carries the [`PageType`] of its pages. Gaps of up to `gap_size` bytes are only
+    /// filled in if the ranges on both sides of the gap have identical page types, ranges with
+    /// differing attributes are always reported separately.
+    ///
     /// # Example:
     ///
     /// ```
@@ -346,6 +356,90 @@ pub trait VirtualTranslate: Send {
         out
     }
 
+    /// Translate the start of many virtual address ranges in a single pass.
+    ///
+    /// All ranges are passed to [`virt_to_phys_list`](Self::virt_to_phys_list) at once, thus nearby
+    /// addresses share the page table reads. The result contains one entry for every range in
+    /// `addrs`, in the same order - the physical address of the start of the range, or `None` if
+    /// any part of the range is unmapped. Ranges of length 0 are treated as being 1 byte long.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use memflow::prelude::v1::*;
+    /// # use memflow::dummy::DummyOs;
+    ///
+    /// // Find out which values point to 8 bytes of mapped memory
+    /// fn classify(mem: &mut impl VirtualTranslate, values: &[Address]) -> Vec<bool> {
+    ///     let ranges = values.iter().map(|&v| CTup2(v, 8)).collect::<Vec<_>>();
+    ///     mem.virt_translate_list(&ranges)
+    ///         .into_iter()
+    ///         .map(|paddr| paddr.is_some())
+    ///         .collect()
+    /// }
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let addr = proc.info().address;
+    /// # let values = [addr + 8, Address::null(), addr + size::mb(2) - 4];
+    /// # assert_eq!(classify(&mut proc.mem, &values), vec![true, false, false]);
+    /// ```
+    #[skip_func]
+    fn virt_translate_list(&mut self, addrs: &[VtopRange]) -> Vec<Option<PhysicalAddress>> {
+        let mut translated = vec![];
+        let mut failed = vec![];
+
+        let ranges = addrs
+            .iter()
+            .map(|&CTup2(addr, size)| CTup2(addr, std::cmp::max(size, 1)))
+            .collect::<Vec<_>>();
+
+        self.virt_to_phys_list(&ranges, (&mut translated).into(), (&mut failed).into());
+
+        translated.sort_unstable_by_key(|t: &VirtualTranslation| t.in_virtual);
+        failed.sort_unstable_by_key(|f: &VirtualTranslationFail| f.from);
+
+        // overlapping input ranges fail in overlapping ranges, merge them so they stay sorted
+        // by their end as well
+        let failed = failed
+            .into_iter()
+            .coalesce(|a, b| {
+                if b.from <= a.from + a.size {
+                    let end = std::cmp::max(a.from + a.size, b.from + b.size);
+                    Ok(VirtualTranslationFail {
+                        from: a.from,
+                        size: end.to_umem() - a.from.to_umem(),
+                    })
+                } else {
+                    Err((a, b))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        ranges
+            .into_iter()
+            .map(|CTup2(addr, size)| {
+                let idx = failed.partition_point(|f| f.from + f.size <= addr);
+                if matches!(failed.get(idx), Some(f) if f.from < addr + size) {
+                    return None;
+                }
+
+                // translations never cross page boundaries, so the last translation starting
+                // before the address is the only one that can be within the same page
+                let idx = translated.partition_point(|t| t.in_virtual <= addr);
+                let t = &translated[idx.checked_sub(1)?];
+                let page_size = t.out_physical.page_size() as usize;
+                if t.in_virtual.as_page_aligned(page_size) != addr.as_page_aligned(page_size) {
+                    return None;
+                }
+
+                Some(PhysicalAddress::with_page(
+                    t.out_physical.address() + (addr - t.in_virtual),
+                    t.out_physical.page_type(),
+                    t.out_physical.page_size(),
+                ))
+            })
+            .collect()
+    }
+
     /// Retrieve page information at virtual address.
     ///
     /// This function is equivalent to calling
@@ -373,6 +467,43 @@ pub trait VirtualTranslate: Send {
         Ok(paddr.containing_page())
     }
 
+    /// Translate a [`VirtualAddress`] into its physical counterpart.
+    ///
+    /// This is the typed equivalent of [`virt_to_phys`](Self::virt_to_phys).
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use memflow::prelude::v1::*;
+    /// # use memflow::dummy::DummyOs;
+    ///
+    /// fn vtop(mem: &mut impl VirtualTranslate, addr: VirtualAddress) {
+    ///     let paddr = mem.virt_addr_to_phys(addr).unwrap();
+    ///     assert!(paddr.is_valid());
+    /// }
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let addr = proc.info().address;
+    /// # vtop(&mut proc.mem, VirtualAddress::new(addr));
+    /// ```
+    #[skip_func]
+    fn virt_addr_to_phys(&mut self, address: impl Into<VirtualAddress>) -> Result<PhysicalAddress>
+    where
+        Self: Sized,
+    {
+        self.virt_to_phys(address.into().address())
+    }
+
+    /// Retrieve page information at a [`VirtualAddress`].
+    ///
+    /// This is the typed equivalent of [`virt_page_info`](Self::virt_page_info).
+    #[skip_func]
+    fn virt_addr_page_info(&mut self, address: impl Into<VirtualAddress>) -> Result<Page>
+    where
+        Self: Sized,
+    {
+        self.virt_page_info(address.into().address())
+    }
+
     /// Retrieve a vector of physical pages within given range.
     ///
     /// This is equivalent to calling [`virt_page_map_range`](Self::virt_page_map_range) with a
@@ -570,6 +701,9 @@ pub trait VirtualTranslate: Send {
 
     /// Retrieves all mapped virtual pages.
     ///
+    /// This walks the page tables directly, so it works without any knowledge of the OS
+    /// (like VADs) and without probing the address space with reads.
+    ///
     /// The [`virt_page_map`](Self::virt_page_map) function is a convenience wrapper for calling
     /// [`virt_page_map_range`](Self::virt_page_map_range)`(gap_size, Address::null(), Address::invalid(), out)`.
     ///
@@ -830,6 +964,11 @@ where
         );
         output.map(Ok).unwrap_or_else(|| Err(output_err.unwrap()))
     }
+
+    /// Returns the counters of the translation cache, if this layer caches translations.
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
 }
 
 // forward impls
@@ -854,6 +993,11 @@ where
     {
         (**self).virt_to_phys_iter(phys_mem, translator, addrs, out, out_fail)
     }
+
+    #[inline]
+    fn cache_stats(&self) -> Option<CacheStats> {
+        (**self).cache_stats()
+    }
 }
 
 /// Translates virtual memory to physical using internal translation base (usually a process' dtb)
@@ -942,6 +1086,80 @@ pub trait VirtualTranslate3: Clone + Copy + Send {
         tmp_buf: &mut [std::mem::MaybeUninit<u8>],
     );
 
+    /// Translate a single virtual address while recording every page table entry that was read
+    ///
+    /// This is a slow path meant for diagnosing translation failures. Unlike `virt_to_phys` it
+    /// does not batch reads and returns the full page walk, including the step at which the
+    /// walk stopped.
+    ///
+    /// The default implementation returns `ErrorKind::NotSupported`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// use memflow::mem::{TranslationDecision, VirtualTranslate3};
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::types::size;
+    ///
+    /// # let mem = DummyMemory::new(size::mb(16));
+    /// # let mut os = DummyOs::new(mem);
+    /// # let (dtb, virtual_base) = os.alloc_dtb(size::mb(2), &[]);
+    /// # let mut mem = os.into_inner();
+    /// let translator = x64::new_translator(dtb);
+    ///
+    /// let trace = translator.virt_to_phys_trace(&mut mem, virtual_base).unwrap();
+    /// for step in trace.steps.iter() {
+    ///     println!("{}", step);
+    /// }
+    ///
+    /// assert_eq!(trace.steps.last().unwrap().decision, TranslationDecision::Page);
+    /// assert!(trace.phys_addr.is_some());
+    /// ```
+    fn virt_to_phys_trace<T: PhysicalMemory>(
+        &self,
+        _mem: &mut T,
+        _addr: Address,
+    ) -> Result<TranslationTrace> {
+        Err(Error(
+            ErrorOrigin::VirtualTranslate,
+            ErrorKind::NotSupported,
+        ))
+    }
+
+    /// Read every page table of the address space into a tree of entries
+    ///
+    /// This walks the whole translation hierarchy, starting at the translation base. It is meant
+    /// for page table forensics and debugging translation issues, since it reads every
+    /// present table one by one.
+    ///
+    /// The default implementation returns `ErrorKind::NotSupported`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// use memflow::mem::VirtualTranslate3;
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::types::size;
+    ///
+    /// # let mem = DummyMemory::new(size::mb(16));
+    /// # let mut os = DummyOs::new(mem);
+    /// # let (dtb, virtual_base) = os.alloc_dtb(size::mb(2), &[]);
+    /// # let mut mem = os.into_inner();
+    /// let translator = x64::new_translator(dtb);
+    ///
+    /// let dump = translator.dump_page_tables(&mut mem).unwrap();
+    /// println!("{}", dump);
+    ///
+    /// let pages = dump.pages();
+    /// assert_eq!(pages.first().unwrap().virt_addr, virtual_base);
+    /// ```
+    fn dump_page_tables<T: PhysicalMemory>(&self, _mem: &mut T) -> Result<PageTableDump> {
+        Err(Error(
+            ErrorOrigin::VirtualTranslate,
+            ErrorKind::NotSupported,
+        ))
+    }
+
     fn translation_table_id(&self, address: Address) -> umem;
 
     fn arch(&self) -> ArchitectureObj;
@@ -949,3 +1167,233 @@ pub trait VirtualTranslate3: Clone + Copy + Send {
 
 pub type VtopOutputCallback<'a, B> = OpaqueCallback<'a, CTup3<PhysicalAddress, Address, B>>;
 pub type VtopFailureCallback<'a, B> = OpaqueCallback<'a, (Error, CTup3<Address, Address, B>)>;
+
+/// The outcome of a single step of a page walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum TranslationDecision {
+    /// The entry points to the page table of the next level.
+    NextTable,
+    /// The entry maps a (potentially large) page, the walk ends here.
+    Page,
+    /// The entry is not present, the translation fails.
+    NotPresent,
+    /// The entry could not be read from physical memory.
+    ReadFailed,
+}
+
+/// A single page table entry that was read during a page walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TranslationStep {
+    /// The step of the page walk, 0 being the top-level page table.
+    pub step: usize,
+    /// The physical address of the page table.
+    pub table: Address,
+    /// The physical address of the entry within the page table.
+    pub entry_addr: Address,
+    /// The raw value of the entry.
+    pub entry: umem,
+    /// What the page walk did with this entry.
+    pub decision: TranslationDecision,
+}
+
+impl fmt::Display for TranslationStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step {}: table {:x} entry {:x} = {:x} ({:?})",
+            self.step, self.table, self.entry_addr, self.entry, self.decision
+        )
+    }
+}
+
+/// The full page walk of a single virtual address, as returned by
+/// [`VirtualTranslate3::virt_to_phys_trace`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TranslationTrace {
+    /// The virtual address that was translated.
+    pub virt_addr: Address,
+    /// All page table entries that were read, in order.
+    pub steps: Vec<TranslationStep>,
+    /// The resulting physical address, `None` if the translation failed.
+    pub phys_addr: Option<PhysicalAddress>,
+}
+
+impl TranslationTrace {
+    /// Returns the reason the translation failed, `None` if it succeeded.
+    pub fn failure(&self) -> Option<TranslationFailure> {
+        if self.phys_addr.is_some() {
+            return None;
+        }
+
+        let last = self.steps.last()?;
+        let (entry, reason) = match last.decision {
+            TranslationDecision::NotPresent if last.entry == 0 => {
+                (last.entry, TranslationFailureReason::NotPresent)
+            }
+            // the present bit is cleared, but the os stores information in the entry
+            // (e.g. the pagefile offset of a paged out page)
+            TranslationDecision::NotPresent => (last.entry, TranslationFailureReason::PagedOut),
+            // the previous entry points to a table outside of physical memory
+            TranslationDecision::ReadFailed if last.step > 0 => (
+                self.steps[last.step - 1].entry,
+                TranslationFailureReason::InvalidEntry,
+            ),
+            TranslationDecision::ReadFailed => (0, TranslationFailureReason::ReadFailed),
+            TranslationDecision::NextTable | TranslationDecision::Page => return None,
+        };
+
+        Some(TranslationFailure {
+            virt_addr: self.virt_addr,
+            level: last.step,
+            entry,
+            reason,
+        })
+    }
+}
+
+/// The page tables of an address space, as returned by
+/// [`VirtualTranslate3::dump_page_tables`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PageTableDump {
+    /// The top-level page tables. Most architectures use a single one, arm splits the address
+    /// space into two.
+    pub roots: Vec<PageTableNode>,
+}
+
+impl PageTableDump {
+    /// Returns all entries that map a page, sorted by virtual address.
+    pub fn pages(&self) -> Vec<&PageTableNodeEntry> {
+        fn collect<'a>(node: &'a PageTableNode, out: &mut Vec<&'a PageTableNodeEntry>) {
+            for entry in node.entries.iter() {
+                match &entry.table {
+                    Some(table) => collect(table, out),
+                    None if entry.decision == TranslationDecision::Page => out.push(entry),
+                    None => {}
+                }
+            }
+        }
+
+        let mut out = vec![];
+        self.roots.iter().for_each(|root| collect(root, &mut out));
+        out.sort_by_key(|entry| entry.virt_addr);
+        out
+    }
+}
+
+impl fmt::Display for PageTableDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn fmt_node(node: &PageTableNode, f: &mut fmt::Formatter) -> fmt::Result {
+            let indent = node.step * 2;
+            writeln!(f, "{:indent$}table {:x}", "", node.address, indent = indent)?;
+            for entry in node.entries.iter() {
+                write!(
+                    f,
+                    "{:indent$}[{:>3}] {:x} = {:x} -> {:x} {:?} ({:?})",
+                    "",
+                    entry.index,
+                    entry.entry_addr,
+                    entry.entry,
+                    entry.virt_addr,
+                    entry.decision,
+                    entry.page_type,
+                    indent = indent + 2
+                )?;
+                match (&entry.table, entry.phys_addr) {
+                    (Some(table), _) => {
+                        writeln!(f)?;
+                        fmt_node(table, f)?;
+                    }
+                    (None, Some(phys_addr)) => writeln!(
+                        f,
+                        " {:x} ({:x} bytes)",
+                        phys_addr.address(),
+                        phys_addr.page_size()
+                    )?,
+                    (None, None) => writeln!(f)?,
+                }
+            }
+            Ok(())
+        }
+
+        self.roots.iter().try_for_each(|root| fmt_node(root, f))
+    }
+}
+
+/// A single page table within a [`PageTableDump`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PageTableNode {
+    /// The step of the page walk this table is used in, 0 being the top-level page table.
+    pub step: usize,
+    /// The physical address of the table.
+    pub address: Address,
+    /// All non-zero entries of the table.
+    pub entries: Vec<PageTableNodeEntry>,
+}
+
+/// A single non-zero page table entry within a [`PageTableDump`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PageTableNodeEntry {
+    /// The index of the entry within its table.
+    pub index: usize,
+    /// The physical address of the entry.
+    pub entry_addr: Address,
+    /// The raw value of the entry.
+    pub entry: umem,
+    /// The first virtual address translated through this entry.
+    pub virt_addr: Address,
+    /// The effective page attributes, including the ones inherited from the upper levels.
+    pub page_type: PageType,
+    /// What a page walk does with this entry.
+    pub decision: TranslationDecision,
+    /// The page table of the next level, if the entry points to one.
+    pub table: Option<PageTableNode>,
+    /// The mapped page, if the entry maps one.
+    pub phys_addr: Option<PhysicalAddress>,
+}
+
+/// The reason a virtual address could not be translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum TranslationFailureReason {
+    /// The address is not canonical for the architecture.
+    NonCanonical,
+    /// The entry is empty, the address was never mapped.
+    NotPresent,
+    /// The entry is not present but holds data, the page is most likely in the pagefile.
+    PagedOut,
+    /// The entry points to a page table that can not be read.
+    InvalidEntry,
+    /// The top-level page table can not be read.
+    ReadFailed,
+}
+
+/// Structured information about a failed translation, as returned by
+/// [`VirtualDma::translate_diag`](crate::mem::VirtualDma::translate_diag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct TranslationFailure {
+    /// The virtual address that was translated.
+    pub virt_addr: Address,
+    /// The step of the page walk at which the translation stopped, 0 being the top-level table.
+    pub level: usize,
+    /// The raw value of the offending page table entry.
+    pub entry: umem,
+    /// Why the translation failed.
+    pub reason: TranslationFailureReason,
+}
+
+impl fmt::Display for TranslationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unable to translate {:x}: {:?} at level {} (entry {:x})",
+            self.virt_addr, self.reason, self.level, self.entry
+        )
+    }
+}
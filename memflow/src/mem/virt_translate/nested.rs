@@ -0,0 +1,227 @@
+/*!
+Two-stage address translation for nested virtualization.
+
+Hypervisors translate guest virtual addresses in two steps. The guest page tables map them to
+guest physical addresses, which are in turn mapped to host physical addresses by the second
+stage tables of the hypervisor (EPT on Intel, NPT on AMD). The guest page tables themselves
+also live in guest physical memory, so every entry read during the guest page walk has to go
+through the second stage as well.
+
+[`NestedTranslate`] chains two translators this way. Combined with the host's physical memory
+it can be used like any other translator, e.g. to create a [`VirtualDma`](crate::mem::VirtualDma)
+for a process inside of a nested VM.
+*/
+
+use std::prelude::v1::*;
+
+use super::{VirtualTranslate3, VtopFailureCallback, VtopOutputCallback};
+use crate::architecture::ArchitectureObj;
+use crate::error::Result;
+use crate::iter::SplitAtIndex;
+use crate::mem::{mem_data::*, PhysicalMemory, PhysicalMemoryMetadata};
+use crate::types::{umem, Address, PageType, PhysicalAddress};
+
+use cglue::tuple::*;
+
+/// Size of the stack buffer [`VirtualTranslate3::virt_to_phys`] translates a single address with.
+const MIN_STAGE_BUF_SIZE: usize = 512;
+
+/// Translates addresses with a guest translator followed by a host (second stage) translator.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::architecture::x86::{x64, x64_ept};
+/// use memflow::mem::{NestedTranslate, VirtualDma};
+/// # use memflow::dummy::DummyMemory;
+/// # use memflow::types::{size, Address};
+/// # let host_mem = DummyMemory::new(size::mb(2));
+/// # let (guest_dtb, eptp) = (Address::from(0x1000), Address::from(0x2000));
+///
+/// let translator = NestedTranslate::new(
+///     x64::new_translator(guest_dtb),
+///     x64_ept::new_translator(eptp),
+/// );
+///
+/// let guest_process = VirtualDma::new(host_mem, x64::ARCH, translator);
+/// ```
+#[derive(Clone, Copy)]
+pub struct NestedTranslate<G, H> {
+    guest: G,
+    host: H,
+}
+
+impl<G: VirtualTranslate3, H: VirtualTranslate3> NestedTranslate<G, H> {
+    /// Chains the `guest` translator with the `host` translator of the second stage.
+    pub fn new(guest: G, host: H) -> Self {
+        Self { guest, host }
+    }
+
+    /// Returns the translator from guest virtual to guest physical addresses.
+    pub fn guest(&self) -> &G {
+        &self.guest
+    }
+
+    /// Returns the translator from guest physical to host physical addresses.
+    pub fn host(&self) -> &H {
+        &self.host
+    }
+}
+
+impl<G: VirtualTranslate3, H: VirtualTranslate3> VirtualTranslate3 for NestedTranslate<G, H> {
+    fn virt_to_phys_iter<
+        T: PhysicalMemory + ?Sized,
+        B: SplitAtIndex,
+        VI: Iterator<Item = CTup3<Address, Address, B>>,
+    >(
+        &self,
+        mem: &mut T,
+        addrs: VI,
+        out: &mut VtopOutputCallback<B>,
+        out_fail: &mut VtopFailureCallback<B>,
+        tmp_buf: &mut [std::mem::MaybeUninit<u8>],
+    ) {
+        // each stage needs at least as much space as a single address translation uses
+        let mut heap_buf;
+        let tmp_buf = if tmp_buf.len() < 2 * MIN_STAGE_BUF_SIZE {
+            heap_buf = vec![std::mem::MaybeUninit::new(0); 2 * MIN_STAGE_BUF_SIZE];
+            &mut heap_buf[..]
+        } else {
+            tmp_buf
+        };
+
+        let mut guest_phys = vec![];
+
+        {
+            // the guest page walk keeps using its half of the buffer while the second stage
+            // translates the reads of the guest page tables with the other half
+            let (guest_buf, host_buf) = tmp_buf.split_at_mut(tmp_buf.len() / 2);
+            let mut guest_mem = GuestPhysicalMemory {
+                mem: &mut *mem,
+                host: self.host,
+                tmp_buf: host_buf,
+            };
+
+            self.guest.virt_to_phys_iter(
+                &mut guest_mem,
+                addrs,
+                &mut (&mut guest_phys).into(),
+                out_fail,
+                guest_buf,
+            );
+        }
+
+        // the page properties of both stages are combined, so every guest page is translated
+        // on its own
+        for CTup3(guest_addr, meta, buf) in guest_phys {
+            let mut cont = true;
+            let host_out =
+                &mut |CTup3(host_addr, meta, buf): CTup3<PhysicalAddress, Address, B>| {
+                    cont = out.call(CTup3(combine_pages(guest_addr, host_addr), meta, buf));
+                    cont
+                };
+
+            self.host.virt_to_phys_iter(
+                mem,
+                std::iter::once(CTup3(guest_addr.address(), meta, buf)),
+                &mut host_out.into(),
+                out_fail,
+                tmp_buf,
+            );
+
+            if !cont {
+                break;
+            }
+        }
+    }
+
+    fn translation_table_id(&self, address: Address) -> umem {
+        self.guest.translation_table_id(address)
+    }
+
+    fn arch(&self) -> ArchitectureObj {
+        self.guest.arch()
+    }
+}
+
+/// Restricts the guest page by the permissions and the size of the host page.
+fn combine_pages(guest: PhysicalAddress, host: PhysicalAddress) -> PhysicalAddress {
+    let (guest_type, host_type) = (guest.page_type(), host.page_type());
+
+    let page_type = guest_type
+        .write(guest_type.contains(PageType::WRITEABLE) && host_type.contains(PageType::WRITEABLE))
+        .noexec(guest_type.contains(PageType::NOEXEC) || host_type.contains(PageType::NOEXEC));
+
+    PhysicalAddress::with_page(
+        host.address(),
+        page_type,
+        std::cmp::min(guest.page_size(), host.page_size()),
+    )
+}
+
+/// Guest physical memory, accessed through the second translation stage.
+struct GuestPhysicalMemory<'a, T: ?Sized, H> {
+    mem: &'a mut T,
+    host: H,
+    tmp_buf: &'a mut [std::mem::MaybeUninit<u8>],
+}
+
+impl<'a, T: PhysicalMemory + ?Sized, H: VirtualTranslate3> PhysicalMemory
+    for GuestPhysicalMemory<'a, T, H>
+{
+    fn phys_read_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            out,
+            mut out_fail,
+        }: PhysicalReadMemOps,
+    ) -> Result<()> {
+        let mut translated = vec![];
+
+        self.host.virt_to_phys_iter(
+            &mut *self.mem,
+            inp.map(|CTup3(addr, meta, buf)| CTup3(addr.address(), meta, buf)),
+            &mut (&mut translated).into(),
+            &mut (&mut |(_, CTup3(_, meta, buf)): (_, _)| {
+                opt_call(out_fail.as_deref_mut(), CTup2(meta, buf))
+            })
+                .into(),
+            self.tmp_buf,
+        );
+
+        MemOps::with_raw(translated.into_iter(), out, out_fail, |data| {
+            self.mem.phys_read_raw_iter(data)
+        })
+    }
+
+    fn phys_write_raw_iter(
+        &mut self,
+        MemOps {
+            inp,
+            out,
+            mut out_fail,
+        }: PhysicalWriteMemOps,
+    ) -> Result<()> {
+        let mut translated = vec![];
+
+        self.host.virt_to_phys_iter(
+            &mut *self.mem,
+            inp.map(|CTup3(addr, meta, buf)| CTup3(addr.address(), meta, buf)),
+            &mut (&mut translated).into(),
+            &mut (&mut |(_, CTup3(_, meta, buf)): (_, _)| {
+                opt_call(out_fail.as_deref_mut(), CTup2(meta, buf))
+            })
+                .into(),
+            self.tmp_buf,
+        );
+
+        MemOps::with_raw(translated.into_iter(), out, out_fail, |data| {
+            self.mem.phys_write_raw_iter(data)
+        })
+    }
+
+    fn metadata(&self) -> PhysicalMemoryMetadata {
+        self.mem.metadata()
+    }
+}
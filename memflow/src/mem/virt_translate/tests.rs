@@ -1,11 +1,16 @@
-use crate::architecture::x86::x64;
+use crate::architecture::arm::{aarch64_16k, aarch64_64k};
+use crate::architecture::riscv::{new_translator_satp, sv39};
+use crate::architecture::x86::{
+    detect_x64_arch, detect_x86_arch, x32, x32_pae, x64, x64_arch_from_cr4, x64_ept, x64_la57,
+    x86_arch_from_cr4,
+};
 use crate::cglue::ForwardMut;
 use crate::dummy::{DummyMemory, DummyOs};
 use crate::mem::{
-    DirectTranslate, MemoryView, PhysicalMemory, VirtualDma, VirtualTranslate, VirtualTranslate2,
-    VirtualTranslate3,
+    DirectTranslate, MemoryView, NestedTranslate, PhysicalMemory, TranslationDecision,
+    TranslationFailureReason, VirtualDma, VirtualTranslate, VirtualTranslate2, VirtualTranslate3,
 };
-use crate::types::{mem, size, PageType};
+use crate::types::{imem, mem, size, umem, Address, PageType};
 use cglue::tuple::*;
 
 #[test]
@@ -98,6 +103,83 @@ fn test_virt_page_map() {
     assert_eq!(page_map[0].1, mem::mb(2));
 }
 
+#[test]
+fn test_virt_translate_list() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let translator = x64::new_translator(dtb);
+    let arch = x64::ARCH;
+
+    let ranges = [
+        CTup2(virt_base + 0x1234_usize, 8),
+        CTup2(virt_base + size::mb(2) - 4_usize, 8),
+        CTup2(virt_base + 0x1234_usize, 0),
+        CTup2(virt_base - 0x1000_usize, 0x2000),
+        CTup2(virt_base + 0x10, 0x4000),
+    ];
+
+    let expected = ranges
+        .iter()
+        .map(|&CTup2(addr, _)| dummy_os.vtop(dtb, addr))
+        .collect::<Vec<_>>();
+
+    let mut virt_mem = VirtualDma::new(dummy_os.forward_mut(), arch, translator);
+    let translated = virt_mem.virt_translate_list(&ranges);
+
+    assert_eq!(translated.len(), ranges.len());
+    assert_eq!(translated[0].map(|p| p.address()), expected[0]);
+    assert_eq!(translated[1], None);
+    assert_eq!(translated[2].map(|p| p.address()), expected[0]);
+    assert_eq!(translated[3], None);
+    assert_eq!(translated[4].map(|p| p.address()), expected[4]);
+}
+
+#[test]
+fn test_virt_page_map_gaps() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let second_base = virt_base + mem::gb(1);
+    dummy_os.alloc_mem_to_dtb(dtb, second_base, size::mb(2), &[]);
+    let translator = x64::new_translator(dtb);
+    let arch = x64::ARCH;
+    let mut virt_mem = VirtualDma::new(dummy_os.forward_mut(), arch, translator);
+
+    let gap = (mem::gb(1) - mem::mb(2)) as imem;
+
+    assert_eq!(virt_mem.virt_page_map_vec(0).len(), 2);
+    assert_eq!(virt_mem.virt_page_map_vec(gap - 1).len(), 2);
+
+    let page_map = virt_mem.virt_page_map_vec(gap);
+    assert_eq!(page_map.len(), 1);
+    assert_eq!(page_map[0].0, virt_base);
+    assert_eq!(page_map[0].1, mem::gb(1) + mem::mb(2));
+
+    // ranges with differing page attributes must not be merged
+    {
+        let mut phys_view = virt_mem.phys_mem().phys_view();
+        let pml4_idx = (second_base.to_umem() as u64 >> 39) & 0x1ffu64;
+        let pml4e = phys_view.read_addr64(dtb + pml4_idx * 8).unwrap().to_umem() as u64;
+        let pdpt = Address::from(pml4e & 0x000f_ffff_ffff_f000u64);
+        let pdpt_idx = (second_base.to_umem() as u64 >> 30) & 0x1ffu64;
+        let pdpte = phys_view
+            .read_addr64(pdpt + pdpt_idx * 8)
+            .unwrap()
+            .to_umem() as u64;
+        // Set nx bit
+        phys_view
+            .write(pdpt + pdpt_idx * 8, &(pdpte | !(!0u64 >> 1)))
+            .unwrap();
+    }
+
+    let page_map = virt_mem.virt_page_map_vec(gap);
+    assert_eq!(page_map.len(), 2);
+    assert!(!page_map[0].2.contains(PageType::NOEXEC));
+    assert_eq!(page_map[1].0, second_base);
+    assert!(page_map[1].2.contains(PageType::NOEXEC));
+}
+
 #[test]
 fn test_virt_read_small() {
     let dummy_mem = DummyMemory::new(size::mb(2));
@@ -331,3 +413,453 @@ fn test_virt_write_big_shifted() {
     assert_eq!(buf.to_vec().len(), input.len());
     assert_eq!(buf.to_vec(), input);
 }
+
+#[test]
+fn test_vtop_trace() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let translator = x64::new_translator(dtb);
+
+    let trace = translator
+        .virt_to_phys_trace(&mut dummy_os, virt_base + 0x123)
+        .unwrap();
+    let paddr = translator
+        .virt_to_phys(&mut dummy_os, virt_base + 0x123)
+        .unwrap();
+    assert_eq!(trace.phys_addr, Some(paddr));
+    assert_eq!(trace.steps[0].table, dtb);
+    assert_eq!(
+        trace.steps.last().unwrap().decision,
+        TranslationDecision::Page
+    );
+    assert!(trace.steps[..trace.steps.len() - 1]
+        .iter()
+        .all(|s| s.decision == TranslationDecision::NextTable));
+
+    let trace = translator
+        .virt_to_phys_trace(&mut dummy_os, virt_base - 1)
+        .unwrap();
+    assert_eq!(trace.phys_addr, None);
+    assert_eq!(
+        trace.steps.last().unwrap().decision,
+        TranslationDecision::NotPresent
+    );
+
+    // non-canonical addresses are rejected upfront
+    assert!(translator
+        .virt_to_phys_trace(&mut dummy_os, Address::from(0x8000_0000_0000u64))
+        .is_err());
+}
+
+#[test]
+fn test_x64_la57() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let virt_base = Address::from(0x1000_0000_0000u64);
+
+    let pml4 = dummy_os.alloc_dtb_const_base(virt_base, size::kb(4), &[]);
+    // turn a second root table into a PML5 that maps the lowest 256TB through the first one
+    let pml5 = dummy_os.alloc_dtb_const_base(virt_base, size::kb(4), &[]);
+    {
+        let mut phys_view = dummy_os.phys_view();
+        phys_view.write_raw(pml5, &[0u8; 0x1000]).unwrap();
+        phys_view
+            .write(pml5, &(pml4.to_umem() as u64 | 0x3))
+            .unwrap();
+    }
+
+    let paddr = x64::new_translator(pml4)
+        .virt_to_phys(&mut dummy_os, virt_base)
+        .unwrap();
+    let la57_paddr = x64_la57::new_translator(pml5)
+        .virt_to_phys(&mut dummy_os, virt_base)
+        .unwrap();
+    assert_eq!(la57_paddr.address(), paddr.address());
+
+    assert_eq!(
+        detect_x64_arch(&mut dummy_os, pml5, virt_base).unwrap(),
+        x64_la57::ARCH
+    );
+    assert_eq!(
+        detect_x64_arch(&mut dummy_os, pml4, virt_base).unwrap(),
+        x64::ARCH
+    );
+    assert!(detect_x64_arch(&mut dummy_os, pml4, virt_base - 1).is_err());
+
+    assert_eq!(x64_arch_from_cr4(0x3506f8), x64::ARCH);
+    assert_eq!(x64_arch_from_cr4(0x3516f8), x64_la57::ARCH);
+}
+
+#[test]
+fn test_x86_pae() {
+    let mut dummy_mem = DummyMemory::new(size::mb(4));
+    {
+        let mut phys_view = dummy_mem.phys_view();
+        // page directory pointer table at 0x1020, each entry points to a page directory
+        for i in 0..4u64 {
+            phys_view
+                .write(
+                    Address::from(0x1020 + i * 8),
+                    &((0x2000 + i * 0x1000) | 0x1),
+                )
+                .unwrap();
+        }
+        // 4kb page at 0x1000, through the page table at 0x6000
+        phys_view.write(Address::from(0x2000), &0x6007u64).unwrap();
+        phys_view.write(Address::from(0x6008), &0x8007u64).unwrap();
+        // 2mb page at 0x4000_0000
+        phys_view
+            .write(Address::from(0x3000), &0x20_0083u64)
+            .unwrap();
+
+        // regular page directory at 0x10000 mapping 0x1000 to 0x9000
+        phys_view
+            .write(Address::from(0x10000), &0x11007u32)
+            .unwrap();
+        phys_view.write(Address::from(0x11004), &0x9007u32).unwrap();
+    }
+
+    let pae = x32_pae::new_translator(Address::from(0x1020));
+    let paddr = pae
+        .virt_to_phys(&mut dummy_mem, Address::from(0x1234))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x8234));
+    assert_eq!(paddr.page_size(), mem::kb(4));
+
+    let paddr = pae
+        .virt_to_phys(&mut dummy_mem, Address::from(0x4001_2345))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x21_2345));
+    assert_eq!(paddr.page_size(), mem::mb(2));
+
+    assert_eq!(
+        detect_x86_arch(&mut dummy_mem, Address::from(0x1020), Address::from(0x1000)).unwrap(),
+        x32_pae::ARCH
+    );
+    assert_eq!(
+        detect_x86_arch(
+            &mut dummy_mem,
+            Address::from(0x10000),
+            Address::from(0x1000)
+        )
+        .unwrap(),
+        x32::ARCH
+    );
+
+    assert_eq!(x86_arch_from_cr4(0x6f9), x32_pae::ARCH);
+    assert_eq!(x86_arch_from_cr4(0x6d9), x32::ARCH);
+}
+
+fn write_entries(mem: &mut DummyMemory, entries: &[(u64, u64)]) {
+    let mut phys_view = mem.phys_view();
+    for &(addr, entry) in entries {
+        phys_view.write(Address::from(addr), &entry).unwrap();
+    }
+}
+
+#[test]
+fn test_aarch64_16k_granule() {
+    let mut dummy_mem = DummyMemory::new(size::mb(1));
+    write_entries(
+        &mut dummy_mem,
+        &[
+            // TTBR0: three levels of tables down to a 16kb page
+            (0x1_0000, 0x4_0003),
+            (0x4_0008, 0x5_0003),
+            (0x5_0010, 0x6_0003),
+            (0x6_0018, 0x8_0403),
+            // TTBR1: two levels of tables down to a 32mb block
+            (0x2_0008, 0x9_0003),
+            (0xa_0008, 0x200_0401),
+            (0x9_0000, 0xa_0003),
+        ],
+    );
+
+    let translator = aarch64_16k::new_translator(Address::from(0x1_0000), Address::from(0x2_0000));
+
+    let virt = Address::from((1u64 << 36) | (2 << 25) | (3 << 14) | 0x123);
+    let paddr = translator.virt_to_phys(&mut dummy_mem, virt).unwrap();
+    assert_eq!(paddr.address(), Address::from(0x8_0123));
+    assert_eq!(paddr.page_size(), mem::kb(16));
+
+    let virt = Address::from(0xffff_8000_0000_0000u64 | (1 << 25) | 0x4567);
+    let paddr = translator.virt_to_phys(&mut dummy_mem, virt).unwrap();
+    assert_eq!(paddr.address(), Address::from(0x200_4567));
+    assert_eq!(paddr.page_size(), mem::mb(32));
+}
+
+#[test]
+fn test_aarch64_64k_granule() {
+    let mut dummy_mem = DummyMemory::new(size::mb(1));
+    write_entries(
+        &mut dummy_mem,
+        &[
+            // TTBR0: two levels of tables down to a 64kb page
+            (0x1_0000, 0x3_0003),
+            (0x3_0048, 0x4_0003),
+            (0x4_1a28, 0x5_0403),
+            // TTBR1: a 512mb block in the second level
+            (0x2_0100, 0x6_0003),
+            (0x6_0008, 0x401),
+        ],
+    );
+
+    let translator = aarch64_64k::new_translator(Address::from(0x1_0000), Address::from(0x2_0000));
+
+    let paddr = translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0x1_2345_6789u64))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x5_6789));
+    assert_eq!(paddr.page_size(), mem::kb(64));
+
+    let paddr = translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0xffff_8000_2000_1234u64))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x1234));
+    assert_eq!(paddr.page_size(), mem::mb(512));
+
+    assert!(translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0xffff_8000_4000_0000u64))
+        .is_err());
+}
+
+#[test]
+fn test_riscv_sv39() {
+    let mut dummy_mem = DummyMemory::new(size::mb(4));
+    write_entries(
+        &mut dummy_mem,
+        &[
+            // 4kb user page, readable and writeable
+            (0x1008, 0x801),
+            (0x2010, 0xc01),
+            (0x3018, 0x20d7),
+            // 2mb supervisor page, readable and executable
+            (0x1010, 0x1001),
+            (0x4028, 0x8_000b),
+        ],
+    );
+
+    let translator = sv39::new_translator(Address::from(0x1000));
+
+    let virt = Address::from((1u64 << 30) | (2 << 21) | (3 << 12) | 0x123);
+    let paddr = translator.virt_to_phys(&mut dummy_mem, virt).unwrap();
+    assert_eq!(paddr.address(), Address::from(0x8123));
+    assert_eq!(paddr.page_size(), mem::kb(4));
+    assert_eq!(
+        paddr.page_type,
+        PageType::WRITEABLE
+            | PageType::NOEXEC
+            | PageType::USER
+            | PageType::ACCESSED
+            | PageType::DIRTY
+    );
+
+    let virt = Address::from((2u64 << 30) | (5 << 21) | 0x1_2345);
+    let paddr = translator.virt_to_phys(&mut dummy_mem, virt).unwrap();
+    assert_eq!(paddr.address(), Address::from(0x21_2345));
+    assert_eq!(paddr.page_size(), mem::mb(2));
+    assert_eq!(paddr.page_type, PageType::READ_ONLY);
+
+    // addresses above 2^38 have to be sign extended
+    assert!(translator
+        .virt_to_phys(&mut dummy_mem, Address::from(1u64 << 38))
+        .is_err());
+
+    let translator = new_translator_satp(0x8000_0000_0000_0001).unwrap();
+    let paddr = translator.virt_to_phys(&mut dummy_mem, virt).unwrap();
+    assert_eq!(paddr.address(), Address::from(0x21_2345));
+    assert!(new_translator_satp(0x1).is_err());
+}
+
+#[test]
+fn test_nested_ept() {
+    let mut dummy_mem = DummyMemory::new(size::mb(8));
+    write_entries(
+        &mut dummy_mem,
+        &[
+            // EPT mapping guest physical 0-2mb to 2mb and 2-4mb read-only to 4mb
+            (0x10_0000, 0x10_1007),
+            (0x10_1000, 0x10_2007),
+            (0x10_2000, 0x20_0087),
+            (0x10_2008, 0x40_0085),
+            // guest page tables, at guest physical 0x1000
+            (0x20_1000, 0x2003),
+            (0x20_2000, 0x3003),
+            (0x20_3000, 0x4003),
+            (0x20_4028, 0x9003),
+            (0x20_4030, 0x20_0003),
+        ],
+    );
+
+    let translator = NestedTranslate::new(
+        x64::new_translator(Address::from(0x1000)),
+        x64_ept::new_translator(Address::from(0x10_0000)),
+    );
+
+    let paddr = translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0x5123))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x20_9123));
+    assert_eq!(paddr.page_size(), mem::kb(4));
+    assert_eq!(paddr.page_type, PageType::WRITEABLE);
+
+    // the guest page is writeable, but the host one is not
+    let paddr = translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0x6000))
+        .unwrap();
+    assert_eq!(paddr.address(), Address::from(0x40_0000));
+    assert_eq!(paddr.page_type, PageType::READ_ONLY);
+
+    // guest physical memory beyond 4mb is not mapped by the host
+    write_entries(&mut dummy_mem, &[(0x20_4038, 0x40_0003)]);
+    assert!(translator
+        .virt_to_phys(&mut dummy_mem, Address::from(0x7000))
+        .is_err());
+
+    dummy_mem
+        .phys_view()
+        .write(Address::from(0x20_9123), &0xdead_beef_u32)
+        .unwrap();
+    let mut virt_mem = VirtualDma::new(dummy_mem, x64::ARCH, translator);
+    assert_eq!(
+        virt_mem.read::<u32>(Address::from(0x5123)).unwrap(),
+        0xdead_beef
+    );
+}
+
+#[test]
+fn test_vtop_trace_failure() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let translator = x64::new_translator(dtb);
+
+    let trace = translator
+        .virt_to_phys_trace(&mut dummy_os, virt_base)
+        .unwrap();
+    assert_eq!(trace.failure(), None);
+
+    // clear the present bit of the final entry, but keep its contents
+    let last = *trace.steps.last().unwrap();
+    dummy_os
+        .phys_write(last.entry_addr.into(), &(last.entry & !1))
+        .unwrap();
+
+    let failure = translator
+        .virt_to_phys_trace(&mut dummy_os, virt_base)
+        .unwrap()
+        .failure()
+        .unwrap();
+    assert_eq!(failure.virt_addr, virt_base);
+    assert_eq!(failure.level, last.step);
+    assert_eq!(failure.entry, last.entry & !1);
+    assert_eq!(failure.reason, TranslationFailureReason::PagedOut);
+
+    let mut virt_mem = VirtualDma::new(dummy_os.into_inner(), x64::ARCH, translator);
+    assert_eq!(
+        virt_mem
+            .translate_diag(Address::from(0x8000_0000_0000u64))
+            .unwrap()
+            .unwrap()
+            .reason,
+        TranslationFailureReason::NonCanonical
+    );
+}
+
+#[test]
+fn test_dump_page_tables() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let kernel_base = Address::from(0xffff_8000_0000_0000u64);
+    dummy_os.alloc_mem_to_dtb(dtb, kernel_base, size::kb(4), &[]);
+    let translator = x64::new_translator(dtb);
+
+    let dump = translator.dump_page_tables(&mut dummy_os).unwrap();
+    assert_eq!(dump.roots.len(), 1);
+    assert_eq!(dump.roots[0].address, dtb);
+
+    let pages = dump.pages();
+    let (user, kernel) = pages.split_at(pages.len() - 1);
+
+    let mut next = virt_base;
+    for page in user {
+        assert_eq!(page.virt_addr, next);
+        let phys_addr = page.phys_addr.unwrap();
+        assert_eq!(
+            translator.virt_to_phys(&mut dummy_os, next).unwrap(),
+            phys_addr
+        );
+        next += phys_addr.page_size();
+    }
+    assert_eq!(next, virt_base + size::mb(2));
+
+    // upper half addresses are sign extended
+    assert_eq!(kernel[0].virt_addr, kernel_base);
+    assert!(kernel[0].page_type.contains(PageType::WRITEABLE));
+}
+
+#[test]
+fn test_virt_write_coalesced() {
+    let dummy_mem = DummyMemory::new(size::mb(16));
+    let mut dummy_os = DummyOs::new(dummy_mem);
+    let (dtb, virt_base) = dummy_os.alloc_dtb(size::mb(2), &[]);
+    let translator = x64::new_translator(dtb);
+    let mut virt_mem = VirtualDma::new(dummy_os.forward_mut(), x64::ARCH, translator);
+
+    let input = (0..0x3800).map(|i| i as u8).collect::<Vec<u8>>();
+    virt_mem.write(virt_base + 0x400usize, &input[..]).unwrap();
+
+    let stats = virt_mem.stats();
+    assert!(stats.phys_writes <= stats.translations);
+    assert_eq!(stats.bytes_written, input.len() as umem);
+
+    let mut buf = vec![0u8; input.len()];
+    virt_mem
+        .read_into(virt_base + 0x400usize, &mut buf[..])
+        .unwrap();
+    assert_eq!(buf, input);
+
+    virt_mem
+        .write_list(&[(virt_base, 1u32), (virt_base + 0x1000usize, 2u32)])
+        .unwrap();
+    assert_eq!(virt_mem.read::<u32>(virt_base + 0x1000usize).unwrap(), 2);
+}
+
+#[test]
+fn test_virt_write_coalesced_contiguous() {
+    let mut dummy_mem = DummyMemory::new(size::mb(2));
+    let mut phys_view = dummy_mem.phys_view();
+    // the first 4 pages are mapped to physically contiguous memory at 1mb
+    for &(addr, entry) in &[
+        (0x1000u64, 0x2003u64),
+        (0x2000, 0x3003),
+        (0x3000, 0x4003),
+        (0x4000, 0x10_0003),
+        (0x4008, 0x10_1003),
+        (0x4010, 0x10_2003),
+        (0x4018, 0x10_3003),
+    ] {
+        phys_view.write(Address::from(addr), &entry).unwrap();
+    }
+
+    let translator = x64::new_translator(Address::from(0x1000));
+    let mut virt_mem = VirtualDma::new(dummy_mem, x64::ARCH, translator);
+
+    let input = (0..0x3800).map(|i| i as u8).collect::<Vec<u8>>();
+    virt_mem.write(Address::from(0x400), &input[..]).unwrap();
+
+    let stats = virt_mem.stats();
+    assert_eq!(stats.translations, 4);
+    assert_eq!(stats.phys_writes, 1);
+    assert_eq!(stats.partial_failures, 0);
+
+    let (mut phys_mem, _) = virt_mem.into_inner();
+    let mut buf = vec![0u8; input.len()];
+    phys_mem
+        .phys_view()
+        .read_into(Address::from(0x10_0400), &mut buf[..])
+        .unwrap();
+    assert_eq!(buf, input);
+}
@@ -0,0 +1,299 @@
+/*!
+Polling based watches on memory ranges.
+
+A [`MemoryWatcher`] keeps a copy of every registered range and re-reads all of them with a
+single batched request on every poll. Callbacks are invoked for all ranges whose contents
+changed since the previous poll.
+*/
+
+use std::prelude::v1::*;
+
+use crate::dataview::Pod;
+use crate::error::{PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+use cglue::callback::OpaqueCallback;
+use cglue::tuple::{CTup2, CTup3};
+
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Identifies a watch registered on a [`MemoryWatcher`].
+pub type WatchId = usize;
+
+/// Describes the changed part of a watched range.
+///
+/// `addr` and `len` span from the first to the last byte that changed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct WatchEvent {
+    pub id: WatchId,
+    pub addr: Address,
+    pub len: umem,
+}
+
+/// Callback invoked when a watched range changes.
+pub type WatchCallback<'a> = OpaqueCallback<'a, WatchEvent>;
+
+/// Callback invoked with the address, the previous and the new value of a watched value.
+pub type ValueWatchCallback<'a, T> = OpaqueCallback<'a, CTup3<Address, T, T>>;
+
+type ChangeHandler<'a> = Box<dyn FnMut(WatchId, Address, &[u8], &[u8]) -> bool + 'a>;
+
+struct Watch<'a> {
+    id: WatchId,
+    addr: Address,
+    data: Vec<u8>,
+    prev: Vec<u8>,
+    primed: bool,
+    on_change: ChangeHandler<'a>,
+}
+
+/// Polls a set of memory ranges and reports changes through callbacks.
+///
+/// The first poll after a range has been registered only records its contents.
+/// Returning `false` from a callback removes the watch.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::cglue::CTup3;
+/// use memflow::mem::watch::MemoryWatcher;
+/// use memflow::mem::{MemoryView, PhysicalMemory};
+/// use memflow::types::{size, Address};
+/// # use memflow::dummy::DummyMemory;
+/// # let mut mem = DummyMemory::new(size::mb(2));
+/// let mut view = mem.phys_view();
+///
+/// let mut health = vec![];
+/// let mut on_change = |CTup3(_, _, new): CTup3<Address, u32, u32>| {
+///     health.push(new);
+///     true
+/// };
+///
+/// let mut watcher = MemoryWatcher::new();
+/// watcher.watch_value(Address::from(0x1000), (&mut on_change).into());
+///
+/// watcher.poll(&mut view).unwrap();
+/// view.write(Address::from(0x1000), &100u32).unwrap();
+/// assert_eq!(watcher.poll(&mut view).unwrap(), 1);
+/// assert_eq!(watcher.poll(&mut view).unwrap(), 0);
+///
+/// std::mem::drop(watcher);
+/// assert_eq!(health, vec![100]);
+/// ```
+pub struct MemoryWatcher<'a> {
+    watches: Vec<Watch<'a>>,
+    next_id: WatchId,
+    #[cfg(feature = "std")]
+    interval: Duration,
+    #[cfg(feature = "std")]
+    last_poll: Option<Instant>,
+}
+
+impl<'a> Default for MemoryWatcher<'a> {
+    fn default() -> Self {
+        Self {
+            watches: vec![],
+            next_id: 0,
+            #[cfg(feature = "std")]
+            interval: Duration::from_millis(100),
+            #[cfg(feature = "std")]
+            last_poll: None,
+        }
+    }
+}
+
+impl<'a> MemoryWatcher<'a> {
+    /// Creates a new watcher that polls every 100 milliseconds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the interval used by [`MemoryWatcher::tick`].
+    #[cfg(feature = "std")]
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Watches the range `addr..addr + len`.
+    pub fn watch(&mut self, addr: Address, len: usize, mut callback: WatchCallback<'a>) -> WatchId {
+        self.insert(
+            addr,
+            len,
+            Box::new(move |id, addr, before, after| {
+                let first = before.iter().zip(after).position(|(b, a)| b != a);
+                let last = before.iter().zip(after).rposition(|(b, a)| b != a);
+                match (first, last) {
+                    (Some(first), Some(last)) => callback.call(WatchEvent {
+                        id,
+                        addr: addr + first as umem,
+                        len: (last - first + 1) as umem,
+                    }),
+                    _ => true,
+                }
+            }),
+        )
+    }
+
+    /// Watches a single value of type `T` at `addr`.
+    pub fn watch_value<T: Pod + Default + Copy + 'a>(
+        &mut self,
+        addr: Address,
+        mut callback: ValueWatchCallback<'a, T>,
+    ) -> WatchId {
+        self.insert(
+            addr,
+            std::mem::size_of::<T>(),
+            Box::new(move |_, addr, before, after| {
+                let (mut old, mut new) = (T::default(), T::default());
+                old.as_bytes_mut().copy_from_slice(before);
+                new.as_bytes_mut().copy_from_slice(after);
+                callback.call(CTup3(addr, old, new))
+            }),
+        )
+    }
+
+    /// Removes a watch. Returns `false` if no watch with the given id exists.
+    pub fn unwatch(&mut self, id: WatchId) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|w| w.id != id);
+        self.watches.len() != len
+    }
+
+    /// Returns the number of registered watches.
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    /// Returns `true` if no watches are registered.
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    /// Re-reads all watched ranges and invokes the callbacks of the ranges that changed.
+    ///
+    /// Returns the number of changed ranges. Parts of the ranges that can not be read are
+    /// treated as zeroes.
+    pub fn poll<M: MemoryView>(&mut self, mem: &mut M) -> Result<usize> {
+        for w in self.watches.iter_mut() {
+            std::mem::swap(&mut w.data, &mut w.prev);
+        }
+
+        {
+            let mut list = self
+                .watches
+                .iter_mut()
+                .map(|w| CTup2(w.addr, w.data.as_mut_slice().into()))
+                .collect::<Vec<_>>();
+            mem.read_raw_list(&mut list).data_part()?;
+        }
+
+        let mut changes = 0;
+        let mut i = 0;
+        while i < self.watches.len() {
+            let w = &mut self.watches[i];
+            let mut keep = true;
+            if !w.primed {
+                w.primed = true;
+            } else if w.data != w.prev {
+                changes += 1;
+                keep = (w.on_change)(w.id, w.addr, &w.prev, &w.data);
+            }
+
+            if keep {
+                i += 1;
+            } else {
+                self.watches.remove(i);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Waits until the configured interval has passed since the last poll and polls afterwards.
+    ///
+    /// This is meant to be called in a loop.
+    #[cfg(feature = "std")]
+    pub fn tick<M: MemoryView>(&mut self, mem: &mut M) -> Result<usize> {
+        if let Some(remaining) = self
+            .last_poll
+            .and_then(|last_poll| self.interval.checked_sub(last_poll.elapsed()))
+        {
+            std::thread::sleep(remaining);
+        }
+        self.last_poll = Some(Instant::now());
+        self.poll(mem)
+    }
+
+    fn insert(&mut self, addr: Address, len: usize, on_change: ChangeHandler<'a>) -> WatchId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watches.push(Watch {
+            id,
+            addr,
+            data: vec![0; len],
+            prev: vec![0; len],
+            primed: false,
+            on_change,
+        });
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn watch_range() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        let mut events = vec![];
+        let mut on_change = |event: WatchEvent| {
+            events.push(event);
+            // remove the watch after the second change
+            events.len() < 2
+        };
+
+        let mut watcher = MemoryWatcher::new();
+        let id = watcher.watch(Address::from(0x1000), 0x100, (&mut on_change).into());
+        assert_eq!(watcher.poll(&mut view).unwrap(), 0);
+
+        view.write(Address::from(0x1010), &[1u8, 0, 0, 1]).unwrap();
+        assert_eq!(watcher.poll(&mut view).unwrap(), 1);
+
+        // outside of the watched range
+        view.write(Address::from(0x1100), &1u8).unwrap();
+        assert_eq!(watcher.poll(&mut view).unwrap(), 0);
+
+        view.write(Address::from(0x10ff), &1u8).unwrap();
+        assert_eq!(watcher.poll(&mut view).unwrap(), 1);
+        assert!(watcher.is_empty());
+        assert!(!watcher.unwatch(id));
+
+        std::mem::drop(watcher);
+        assert_eq!(
+            events,
+            vec![
+                WatchEvent {
+                    id,
+                    addr: Address::from(0x1010),
+                    len: 4,
+                },
+                WatchEvent {
+                    id,
+                    addr: Address::from(0x10ff),
+                    len: 1,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! Optional bridge to an agent running inside of the target.
+//!
+//! Some setups allow running a small helper inside of the guest which reports data
+//! straight from the guest's own APIs (e.g. via a shared memory page or a network socket).
+//! Such data is authoritative in the sense that it is exactly what the guest itself believes,
+//! but it is only as trustworthy as the guest. Data derived from memory on the other hand can not
+//! be tampered with from within the guest as easily, but is subject to parsing errors.
+//!
+//! Comparing both views helps to detect bugs in the memory-derived results as well as manipulation
+//! on either side, like a process that unlinked itself from the kernel process list or
+//! a rootkit that hides entries from user-mode APIs.
+//!
+//! The transport is not part of this module. Implementors of [`GuestAgent`] are free
+//! to communicate with their agent in whatever way suits the setup.
+//!
+//! # Examples
+//!
+//! ```
+//! use memflow::os::agent::{cross_validate_processes, AgentProcessInfo, GuestAgent};
+//! use memflow::prelude::v1::*;
+//! # use memflow::dummy::{DummyMemory, DummyOs};
+//!
+//! struct StaticAgent(Vec<AgentProcessInfo>);
+//!
+//! impl GuestAgent for StaticAgent {
+//!     fn process_list(&mut self) -> Result<Vec<AgentProcessInfo>> {
+//!         Ok(self.0.clone())
+//!     }
+//! }
+//!
+//! # let mut os = DummyOs::new(DummyMemory::new(size::mb(16)));
+//! let pid = os.alloc_process(size::mb(1), &[]);
+//! let mut agent = StaticAgent(vec![AgentProcessInfo {
+//!     pid,
+//!     name: "Dummy".into(),
+//!     path: "".into(),
+//! }]);
+//!
+//! let divergences = cross_validate_processes(&mut os, &mut agent).unwrap();
+//! assert!(divergences.is_empty());
+//! ```
+
+use std::prelude::v1::*;
+
+use super::{ModuleInfo, OsInner, Pid, Process, ProcessInfo};
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::types::{umem, Address};
+
+/// Windows only stores the first 15 characters of the image name in the process structure.
+const TRUNCATED_NAME_LEN: usize = 15;
+
+/// Process information as reported by a [`GuestAgent`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AgentProcessInfo {
+    /// ID of the process.
+    pub pid: Pid,
+    /// Name of the process.
+    pub name: String,
+    /// Full path of the process binary or an empty string if it is not known.
+    pub path: String,
+}
+
+/// Module information as reported by a [`GuestAgent`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AgentModuleInfo {
+    /// The base address the module is loaded at.
+    pub base: Address,
+    /// Size of the module.
+    pub size: umem,
+    /// Full path of the module.
+    pub path: String,
+}
+
+/// Interface to an agent running inside of the target.
+///
+/// Only the process list is mandatory. Agents that are not able to report module
+/// lists can keep the default implementation.
+pub trait GuestAgent {
+    /// Retrieves the list of running processes from the agent.
+    fn process_list(&mut self) -> Result<Vec<AgentProcessInfo>>;
+
+    /// Retrieves the list of modules loaded into the process with the given pid.
+    fn module_list(&mut self, _pid: Pid) -> Result<Vec<AgentModuleInfo>> {
+        Err(Error(ErrorOrigin::OsLayer, ErrorKind::NotSupported))
+    }
+}
+
+/// A difference between the data reported by a [`GuestAgent`] and the data read from memory.
+///
+/// Note that both views are never taken at exactly the same time.
+/// Processes that were started or stopped in between will show up as divergences as well.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Divergence {
+    /// The agent reports a process that could not be found in memory.
+    ProcessMissingFromMemory { pid: Pid, name: String },
+    /// A process was found in memory but the agent did not report it.
+    ProcessMissingFromAgent { pid: Pid, name: String },
+    /// Both sides report the process but with a different name.
+    ProcessNameMismatch {
+        pid: Pid,
+        agent: String,
+        memory: String,
+    },
+    /// The agent reports a module that could not be found in memory.
+    ModuleMissingFromMemory {
+        pid: Pid,
+        base: Address,
+        path: String,
+    },
+    /// A module was found in memory but the agent did not report it.
+    ModuleMissingFromAgent {
+        pid: Pid,
+        base: Address,
+        path: String,
+    },
+    /// Both sides report a module at the same base address but with a different path.
+    ModulePathMismatch {
+        pid: Pid,
+        base: Address,
+        agent: String,
+        memory: String,
+    },
+}
+
+/// Compares the process list of the os with the one reported by the agent.
+pub fn cross_validate_processes<O, A>(os: &mut O, agent: &mut A) -> Result<Vec<Divergence>>
+where
+    O: for<'a> OsInner<'a>,
+    A: GuestAgent,
+{
+    let agent_list = agent.process_list()?;
+    let memory_list = os.process_info_list()?;
+    Ok(compare_process_lists(&memory_list, &agent_list))
+}
+
+/// Compares the module list of a process with the one reported by the agent.
+pub fn cross_validate_modules<P, A>(process: &mut P, agent: &mut A) -> Result<Vec<Divergence>>
+where
+    P: Process,
+    A: GuestAgent,
+{
+    let pid = process.info().pid;
+    let agent_list = agent.module_list(pid)?;
+    let memory_list = process.module_list()?;
+    Ok(compare_module_lists(pid, &memory_list, &agent_list))
+}
+
+/// Compares two process lists that have been retrieved beforehand.
+///
+/// Names are compared case-insensitively. Since some operating systems truncate the
+/// process name stored in kernel memory, a truncated name read from memory is accepted
+/// as long as it is a prefix of the name reported by the agent.
+pub fn compare_process_lists(
+    memory: &[ProcessInfo],
+    agent: &[AgentProcessInfo],
+) -> Vec<Divergence> {
+    let mut out = vec![];
+
+    for a in agent {
+        match memory.iter().find(|m| m.pid == a.pid) {
+            Some(m) if !process_name_matches(m.name.as_ref(), &a.name) => {
+                out.push(Divergence::ProcessNameMismatch {
+                    pid: a.pid,
+                    agent: a.name.clone(),
+                    memory: m.name.to_string(),
+                })
+            }
+            Some(_) => {}
+            None => out.push(Divergence::ProcessMissingFromMemory {
+                pid: a.pid,
+                name: a.name.clone(),
+            }),
+        }
+    }
+
+    for m in memory
+        .iter()
+        .filter(|m| m.state.is_alive() || m.state.is_unknown())
+        .filter(|m| !agent.iter().any(|a| a.pid == m.pid))
+    {
+        out.push(Divergence::ProcessMissingFromAgent {
+            pid: m.pid,
+            name: m.name.to_string(),
+        });
+    }
+
+    out
+}
+
+/// Compares two module lists of the process with the given pid.
+///
+/// Modules are matched by their base address and paths are compared case-insensitively.
+pub fn compare_module_lists(
+    pid: Pid,
+    memory: &[ModuleInfo],
+    agent: &[AgentModuleInfo],
+) -> Vec<Divergence> {
+    let mut out = vec![];
+
+    for a in agent {
+        match memory.iter().find(|m| m.base == a.base) {
+            Some(m) if !m.path.as_ref().eq_ignore_ascii_case(&a.path) => {
+                out.push(Divergence::ModulePathMismatch {
+                    pid,
+                    base: a.base,
+                    agent: a.path.clone(),
+                    memory: m.path.to_string(),
+                })
+            }
+            Some(_) => {}
+            None => out.push(Divergence::ModuleMissingFromMemory {
+                pid,
+                base: a.base,
+                path: a.path.clone(),
+            }),
+        }
+    }
+
+    for m in memory
+        .iter()
+        .filter(|m| !agent.iter().any(|a| a.base == m.base))
+    {
+        out.push(Divergence::ModuleMissingFromAgent {
+            pid,
+            base: m.base,
+            path: m.path.to_string(),
+        });
+    }
+
+    out
+}
+
+fn process_name_matches(memory: &str, agent: &str) -> bool {
+    if memory.eq_ignore_ascii_case(agent) {
+        true
+    } else if memory.len() >= TRUNCATED_NAME_LEN && agent.len() > memory.len() {
+        agent
+            .get(..memory.len())
+            .map(|prefix| prefix.eq_ignore_ascii_case(memory))
+            .unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::os::ProcessState;
+
+    fn memory_process(pid: Pid, name: &str) -> ProcessInfo {
+        ProcessInfo {
+            address: Address::NULL,
+            pid,
+            state: ProcessState::Alive,
+            name: name.into(),
+            path: "".into(),
+            command_line: "".into(),
+            sys_arch: x64::ARCH.ident(),
+            proc_arch: x64::ARCH.ident(),
+            parent_pid: 0,
+            session_id: 0,
+            create_time: 0,
+            exit_time: 0,
+        }
+    }
+
+    fn agent_process(pid: Pid, name: &str) -> AgentProcessInfo {
+        AgentProcessInfo {
+            pid,
+            name: name.into(),
+            path: "".into(),
+        }
+    }
+
+    #[test]
+    fn process_divergences() {
+        let memory = vec![
+            memory_process(4, "System"),
+            memory_process(100, "averylongproces"),
+            memory_process(200, "explorer.exe"),
+        ];
+        let agent = vec![
+            agent_process(4, "system"),
+            agent_process(100, "averylongprocessname.exe"),
+            agent_process(200, "notepad.exe"),
+            agent_process(300, "hidden.exe"),
+        ];
+
+        let divergences = compare_process_lists(&memory, &agent);
+        assert_eq!(
+            divergences,
+            vec![
+                Divergence::ProcessNameMismatch {
+                    pid: 200,
+                    agent: "notepad.exe".into(),
+                    memory: "explorer.exe".into(),
+                },
+                Divergence::ProcessMissingFromMemory {
+                    pid: 300,
+                    name: "hidden.exe".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn process_missing_from_agent() {
+        let memory = vec![memory_process(4, "System"), memory_process(8, "rootkit")];
+        let agent = vec![agent_process(4, "System")];
+
+        assert_eq!(
+            compare_process_lists(&memory, &agent),
+            vec![Divergence::ProcessMissingFromAgent {
+                pid: 8,
+                name: "rootkit".into(),
+            }]
+        );
+    }
+}
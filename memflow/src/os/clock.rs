@@ -0,0 +1,291 @@
+//! Helpers for reading time sources of the target and correlating them with the host clock.
+//!
+//! Events that are observed through memflow are usually timestamped with the host clock.
+//! Since the target can be paused, slowed down or run on a different time base, these
+//! timestamps do not necessarily match the time on the target. The [`ClockCorrelation`]
+//! helper takes samples of a target [`ClockSource`] and converts host timestamps into
+//! target time.
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::{MemoryView, PhysicalMemory};
+use crate::types::{umem, Address};
+
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// Default physical base address of the HPET register block on x86 systems.
+pub const HPET_DEFAULT_BASE: umem = 0xfed0_0000;
+
+/// Address of `KUSER_SHARED_DATA` in the user-mode address space of windows processes.
+pub const KUSER_SHARED_DATA_USER: umem = 0x7ffe_0000;
+
+/// Address of `KUSER_SHARED_DATA` in the kernel address space of 64-bit windows.
+pub const KUSER_SHARED_DATA_KERNEL_X64: umem = 0xffff_f780_0000_0000;
+
+/// A monotonic time source on the target.
+pub trait ClockSource {
+    /// Reads the current tick count of the clock.
+    fn ticks(&mut self) -> Result<u64>;
+
+    /// Returns the amount of ticks per second.
+    fn frequency(&self) -> u64;
+}
+
+/// Reads the main counter of the HPET through physical memory.
+pub struct HpetClock<T> {
+    mem: T,
+    base: Address,
+    frequency: u64,
+}
+
+impl<T: PhysicalMemory> HpetClock<T> {
+    /// Constructs a new `HpetClock` from the register block at the given physical address.
+    ///
+    /// The counter period is read from the capabilities register. If the register
+    /// does not contain a valid period an error is returned.
+    pub fn new(mut mem: T, base: Address) -> Result<Self> {
+        let mut caps = 0u64;
+        mem.phys_read_into(base.into(), &mut caps)?;
+
+        // the upper 32 bits contain the tick period in femtoseconds (at most 100ns)
+        let period = u64::from_le(caps) >> 32;
+        if period == 0 || period > 100_000_000 {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::NotFound)
+                .log_warn("no valid hpet found at the given address"));
+        }
+
+        Ok(Self {
+            mem,
+            base,
+            frequency: 1_000_000_000_000_000 / period,
+        })
+    }
+
+    /// Consumes this clock and returns the underlying memory object.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+}
+
+impl<T: PhysicalMemory> ClockSource for HpetClock<T> {
+    fn ticks(&mut self) -> Result<u64> {
+        let mut counter = 0u64;
+        self.mem
+            .phys_read_into((self.base + 0xf0usize).into(), &mut counter)?;
+        Ok(u64::from_le(counter))
+    }
+
+    fn frequency(&self) -> u64 {
+        self.frequency
+    }
+}
+
+/// Reads the interrupt time from the windows `KUSER_SHARED_DATA` page.
+///
+/// The interrupt time is counted in 100ns units since boot and does not include time
+/// the system spent in sleep or hibernation.
+pub struct KUserSharedDataClock<T> {
+    mem: T,
+    address: Address,
+}
+
+impl<T: MemoryView> KUserSharedDataClock<T> {
+    /// Constructs a new `KUserSharedDataClock` reading `KUSER_SHARED_DATA` at the given virtual address.
+    ///
+    /// See [`KUSER_SHARED_DATA_USER`] and [`KUSER_SHARED_DATA_KERNEL_X64`] for well known locations.
+    pub fn new(mem: T, address: Address) -> Self {
+        Self { mem, address }
+    }
+
+    /// Consumes this clock and returns the underlying memory object.
+    pub fn into_inner(self) -> T {
+        self.mem
+    }
+}
+
+impl<T: MemoryView> ClockSource for KUserSharedDataClock<T> {
+    fn ticks(&mut self) -> Result<u64> {
+        // _KSYSTEM_TIME { LowPart, High1Time, High2Time }
+        // the value is consistent if both high parts are equal
+        for _ in 0..16 {
+            let time = self.mem.read::<[u32; 3]>(self.address + 0x8).data()?;
+            if time[1] == time[2] {
+                return Ok(((time[1] as u64) << 32) | time[0] as u64);
+            }
+        }
+
+        Err(Error(ErrorOrigin::OsLayer, ErrorKind::PartialData)
+            .log_warn("unable to read a consistent interrupt time"))
+    }
+
+    fn frequency(&self) -> u64 {
+        10_000_000
+    }
+}
+
+/// Correlates the host clock with a [`ClockSource`] on the target.
+///
+/// With a single sample the nominal frequency of the clock is used to extrapolate target time.
+/// Once a second sample has been taken the observed tick rate is used instead,
+/// which also accounts for the target running slower than real time.
+/// If the clock did not advance between the last two samples the target is considered paused
+/// and its time is not extrapolated past the last sample.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct ClockCorrelation {
+    frequency: u64,
+    first: (Instant, u64),
+    prev: (Instant, u64),
+    last: (Instant, u64),
+}
+
+#[cfg(feature = "std")]
+impl ClockCorrelation {
+    /// Creates a new correlation by taking the initial sample of the given clock.
+    ///
+    /// An error is returned if the clock reports a frequency of 0.
+    pub fn new(clock: &mut impl ClockSource) -> Result<Self> {
+        let frequency = clock.frequency();
+        if frequency == 0 {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::InvalidArgument)
+                .log_warn("the clock source reports a frequency of 0"));
+        }
+
+        let sample = Self::take_sample(clock)?;
+        Ok(Self {
+            frequency,
+            first: sample,
+            prev: sample,
+            last: sample,
+        })
+    }
+
+    /// Takes another sample of the clock to refine the correlation.
+    pub fn sample(&mut self, clock: &mut impl ClockSource) -> Result<()> {
+        self.prev = self.last;
+        self.last = Self::take_sample(clock)?;
+        Ok(())
+    }
+
+    /// Returns `true` if the target clock did not advance between the last two samples.
+    pub fn is_paused(&self) -> bool {
+        self.last.0 > self.prev.0 && self.last.1 == self.prev.1
+    }
+
+    /// Returns the observed amount of target ticks per host second.
+    ///
+    /// The nominal frequency is returned until two samples at different host times were taken
+    /// or if the target clock went backwards in the meantime.
+    pub fn tick_rate(&self) -> f64 {
+        let host = self.last.0.saturating_duration_since(self.first.0);
+        if host.as_nanos() > 0 && self.last.1 >= self.first.1 {
+            (self.last.1 - self.first.1) as f64 / host.as_secs_f64()
+        } else {
+            self.frequency as f64
+        }
+    }
+
+    /// Converts a host timestamp into the tick count the target clock had at that moment.
+    pub fn guest_ticks_at(&self, instant: Instant) -> u64 {
+        let (host, ticks) = self.last;
+        let rate = self.tick_rate();
+
+        if instant >= host {
+            if self.is_paused() {
+                ticks
+            } else {
+                ticks.saturating_add(((instant - host).as_secs_f64() * rate) as u64)
+            }
+        } else {
+            ticks.saturating_sub(((host - instant).as_secs_f64() * rate) as u64)
+        }
+    }
+
+    /// Converts a host timestamp into the target time (relative to the clock's origin).
+    pub fn guest_time_at(&self, instant: Instant) -> Duration {
+        self.ticks_to_duration(self.guest_ticks_at(instant))
+    }
+
+    /// Converts a tick count of the target clock into a `Duration`.
+    pub fn ticks_to_duration(&self, ticks: u64) -> Duration {
+        Duration::from_nanos((ticks as u128 * 1_000_000_000 / self.frequency as u128) as u64)
+    }
+
+    fn take_sample(clock: &mut impl ClockSource) -> Result<(Instant, u64)> {
+        // use the midpoint of the read to minimize the error introduced by the read latency
+        let before = Instant::now();
+        let ticks = clock.ticks()?;
+        let after = Instant::now();
+        Ok((before + (after - before) / 2, ticks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::types::size;
+
+    #[test]
+    fn hpet_frequency() {
+        let mut mem = DummyMemory::new(size::kb(16));
+        // 69841279 fs period (~14.318 MHz)
+        mem.phys_write(0x1000.into(), &(69_841_279u64 << 32).to_le())
+            .unwrap();
+        mem.phys_write(0x10f0.into(), &1234u64.to_le()).unwrap();
+
+        let mut clock = HpetClock::new(mem, 0x1000.into()).unwrap();
+        assert_eq!(clock.frequency(), 14_318_179);
+        assert_eq!(clock.ticks().unwrap(), 1234);
+    }
+
+    #[test]
+    fn hpet_invalid() {
+        let mem = DummyMemory::new(size::kb(16));
+        assert!(HpetClock::new(mem, 0x1000.into()).is_err());
+    }
+
+    struct FixedClock(u64);
+
+    impl ClockSource for FixedClock {
+        fn ticks(&mut self) -> Result<u64> {
+            Ok(self.0)
+        }
+
+        fn frequency(&self) -> u64 {
+            1000
+        }
+    }
+
+    #[test]
+    fn correlation_paused_guest() {
+        let mut clock = FixedClock(5000);
+        let mut corr = ClockCorrelation::new(&mut clock).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        corr.sample(&mut clock).unwrap();
+
+        assert_eq!(corr.ticks_to_duration(5000), Duration::from_secs(5));
+        // the guest clock has not advanced, so its time is not extrapolated
+        assert!(corr.is_paused());
+        assert_eq!(corr.tick_rate(), 0.0);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(corr.guest_ticks_at(Instant::now()), 5000);
+    }
+
+    struct ZeroClock;
+
+    impl ClockSource for ZeroClock {
+        fn ticks(&mut self) -> Result<u64> {
+            Ok(0)
+        }
+
+        fn frequency(&self) -> u64 {
+            0
+        }
+    }
+
+    #[test]
+    fn correlation_zero_frequency() {
+        assert!(ClockCorrelation::new(&mut ZeroClock).is_err());
+    }
+}
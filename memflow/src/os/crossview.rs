@@ -0,0 +1,204 @@
+//! Cross-view analysis of process lists.
+//!
+//! Rootkits commonly hide processes by unlinking them from the list the os uses to
+//! enumerate processes (DKOM). The process object itself has to stay in memory though,
+//! so it can still be found through other means, like the handle table of client ids,
+//! a scan for pool allocations or by looking at the owners of scheduled threads.
+//!
+//! The [`CrossView`] collects the results of several of these enumeration methods and reports
+//! all processes that have not been found by every method.
+//!
+//! # Examples
+//!
+//! ```
+//! use memflow::os::crossview::{CrossView, ProcessSource};
+//! use memflow::prelude::v1::*;
+//! # use memflow::dummy::{DummyMemory, DummyOs};
+//!
+//! # let mut os = DummyOs::new(DummyMemory::new(size::mb(16)));
+//! # os.alloc_process(size::mb(1), &[]);
+//! let mut view = CrossView::new();
+//! view.add_os(&mut os).unwrap();
+//!
+//! // results of other enumeration methods would be added here, e.g.:
+//! let list = os.process_info_list().unwrap();
+//! view.add(ProcessSource::PoolScan, list);
+//!
+//! assert!(view.discrepancies().is_empty());
+//! ```
+
+use std::prelude::v1::*;
+
+use super::{OsInner, ProcessInfo};
+
+/// A method that has been used to enumerate processes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ProcessSource {
+    /// The process list of the os (e.g. `ActiveProcessLinks` on windows).
+    ProcessList,
+    /// The handle table of client ids (e.g. `PspCidTable` on windows).
+    CidTable,
+    /// Scanning memory for process object allocations.
+    PoolScan,
+    /// The processes owning the threads known to the scheduler.
+    ThreadOwnership,
+    /// Any other enumeration method.
+    Other(String),
+}
+
+/// A process that has not been found by all enumeration methods.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Discrepancy {
+    /// Information about the process, as reported by the first method that found it.
+    pub info: ProcessInfo,
+    /// The methods that found this process.
+    pub found_by: Vec<ProcessSource>,
+    /// The methods that did not find this process.
+    pub missing_from: Vec<ProcessSource>,
+}
+
+impl Discrepancy {
+    /// Returns `true` if the process is missing from the os process list but was found otherwise.
+    ///
+    /// Processes that are still alive and match this criteria are a strong indicator of DKOM.
+    /// Processes that exited recently can show up here as well, as long as their object is still referenced.
+    pub fn is_unlinked(&self) -> bool {
+        self.missing_from.contains(&ProcessSource::ProcessList)
+    }
+}
+
+/// Compares the results of multiple process enumeration methods.
+///
+/// Processes are identified by their address (see [`ProcessInfo::address`]),
+/// since pids can be reused or tampered with.
+#[derive(Debug, Clone, Default)]
+pub struct CrossView {
+    sources: Vec<ProcessSource>,
+    processes: Vec<(ProcessInfo, Vec<ProcessSource>)>,
+}
+
+impl CrossView {
+    /// Creates a new and empty cross view.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all processes that were found by the given method.
+    ///
+    /// Adding a method that has already been added merges the results.
+    pub fn add(&mut self, source: ProcessSource, processes: impl IntoIterator<Item = ProcessInfo>) {
+        if !self.sources.contains(&source) {
+            self.sources.push(source.clone());
+        }
+
+        for info in processes {
+            match self
+                .processes
+                .iter_mut()
+                .find(|(p, _)| p.address == info.address)
+            {
+                Some((_, found_by)) => {
+                    if !found_by.contains(&source) {
+                        found_by.push(source.clone())
+                    }
+                }
+                None => self.processes.push((info, vec![source.clone()])),
+            }
+        }
+    }
+
+    /// Adds the process list of the given os as [`ProcessSource::ProcessList`].
+    pub fn add_os<O: for<'a> OsInner<'a>>(&mut self, os: &mut O) -> crate::error::Result<()> {
+        let list = os.process_info_list()?;
+        self.add(ProcessSource::ProcessList, list);
+        Ok(())
+    }
+
+    /// Returns the methods that have been added to this view.
+    pub fn sources(&self) -> &[ProcessSource] {
+        &self.sources
+    }
+
+    /// Returns all processes that have not been found by every method.
+    pub fn discrepancies(&self) -> Vec<Discrepancy> {
+        self.processes
+            .iter()
+            .filter(|(_, found_by)| found_by.len() < self.sources.len())
+            .map(|(info, found_by)| Discrepancy {
+                info: info.clone(),
+                found_by: found_by.clone(),
+                missing_from: self
+                    .sources
+                    .iter()
+                    .filter(|s| !found_by.contains(s))
+                    .cloned()
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Returns all processes that are missing from the os process list
+    /// but have been found by at least one other method.
+    pub fn unlinked(&self) -> Vec<Discrepancy> {
+        self.discrepancies()
+            .into_iter()
+            .filter(Discrepancy::is_unlinked)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::os::ProcessState;
+    use crate::types::Address;
+
+    fn process(address: u64, pid: u32) -> ProcessInfo {
+        ProcessInfo {
+            address: Address::from(address),
+            pid,
+            state: ProcessState::Alive,
+            name: "test".into(),
+            path: "".into(),
+            command_line: "".into(),
+            sys_arch: x64::ARCH.ident(),
+            proc_arch: x64::ARCH.ident(),
+            parent_pid: 0,
+            session_id: 0,
+            create_time: 0,
+            exit_time: 0,
+        }
+    }
+
+    #[test]
+    fn hidden_process() {
+        let mut view = CrossView::new();
+        view.add(
+            ProcessSource::ProcessList,
+            vec![process(0x1000, 4), process(0x2000, 8)],
+        );
+        view.add(
+            ProcessSource::CidTable,
+            vec![process(0x1000, 4), process(0x2000, 8), process(0x3000, 12)],
+        );
+        view.add(
+            ProcessSource::PoolScan,
+            vec![process(0x1000, 4), process(0x3000, 12)],
+        );
+
+        let discrepancies = view.discrepancies();
+        assert_eq!(discrepancies.len(), 2);
+
+        let unlinked = view.unlinked();
+        assert_eq!(unlinked.len(), 1);
+        assert_eq!(unlinked[0].info.pid, 12);
+        assert_eq!(
+            unlinked[0].found_by,
+            vec![ProcessSource::CidTable, ProcessSource::PoolScan]
+        );
+        assert_eq!(unlinked[0].missing_from, vec![ProcessSource::ProcessList]);
+    }
+}
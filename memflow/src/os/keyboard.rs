@@ -1,4 +1,13 @@
 //! Describes optional keyboard input for a Operating System
+//!
+//! The keyboard abstraction is split up similar to the [`CpuState`](crate::connector::cpu_state::CpuState)
+//! of connectors: an OS plugin optionally implements [`OsKeyboardInner`] which hands out
+//! a [`Keyboard`] object. The keyboard can then be queried for individual keys or
+//! a snapshot of the current state can be taken via [`Keyboard::state`].
+//!
+//! Keys are identified by their virtual key code. OS plugins are expected to use the
+//! windows virtual key codes (e.g. `0x20` for the space bar) and translate them if the
+//! target uses a different scheme.
 
 use crate::cglue::*;
 use crate::prelude::v1::Result;
@@ -33,9 +42,12 @@ pub trait Keyboard {
     #[wrap_with_obj(crate::os::keyboard::KeyboardState)]
     type KeyboardStateType: crate::os::keyboard::KeyboardState;
 
+    /// Returns `true` if the key with the given virtual key code is currently pressed.
     fn is_down(&mut self, vk: i32) -> bool;
+    /// Overwrites the state of the key with the given virtual key code on the target.
     fn set_down(&mut self, vk: i32, down: bool);
 
+    /// Reads the state of all keys at once.
     fn state(&mut self) -> Result<Self::KeyboardStateType>;
 }
 
@@ -43,5 +55,6 @@ pub trait Keyboard {
 #[int_result]
 #[cglue_forward]
 pub trait KeyboardState {
+    /// Returns `true` if the key with the given virtual key code was pressed when this state was taken.
     fn is_down(&self, vk: i32) -> bool;
 }
@@ -14,13 +14,58 @@
 //! `ModuleInfo` currently is just an information block, without any memory access, or special
 //! functions. It might be wise to implement helpers for exported functions, memory protection
 //! flags, and other things concerned with individual modules.
+//!
+//! # Callbacks
+//!
+//! Enumeration functions like [`OsInner::process_info_list_callback`] pass every entry to an
+//! opaque callback, so they can be used across the FFI boundary. Any `FnMut(T) -> bool` closure
+//! can be converted into such a callback, including closures that capture mutable state.
+//! Returning `false` from the closure stops the enumeration early. A `Vec<T>` converts into a
+//! callback as well and simply collects every entry.
+//!
+//! ```
+//! use memflow::os::{OsInner, ProcessInfo};
+//! # use memflow::dummy::{DummyMemory, DummyOs};
+//! # use memflow::types::size;
+//! # let mut os = DummyOs::new(DummyMemory::new(size::mb(64)));
+//! # for _ in 0..4 {
+//! #     os.alloc_process(size::mb(1), &[]);
+//! # }
+//!
+//! // take the first two processes and skip the rest of the list
+//! let mut first = vec![];
+//! os.process_info_list_callback(
+//!     (&mut |info: ProcessInfo| {
+//!         first.push(info);
+//!         first.len() < 2
+//!     })
+//!         .into(),
+//! )
+//! .unwrap();
+//! assert_eq!(first.len(), 2);
+//! ```
 
+pub mod agent;
+pub mod clock;
+pub mod crossview;
 pub mod keyboard;
 pub mod module;
+#[cfg(feature = "overlay")]
+pub mod overlay;
+pub mod path;
 pub mod process;
 pub mod root;
+pub mod security;
 pub mod util;
+pub mod walkers;
+
+#[cfg(feature = "std")]
+pub use clock::ClockCorrelation;
+pub use clock::{ClockSource, HpetClock, KUserSharedDataClock};
 
+#[doc(hidden)]
+#[cfg(feature = "plugins")]
+pub use keyboard::{IntoKeyboardArcBox, KeyboardArcBox, KeyboardStateArcBox};
 pub use keyboard::{Keyboard, KeyboardState, OsKeyboard, OsKeyboardInner};
 
 pub use module::{
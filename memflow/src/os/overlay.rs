@@ -0,0 +1,389 @@
+/*!
+Helpers for overlay style frontends that read the same set of values every frame.
+
+Frontends of this kind usually locate a handful of globals through pointer chains that start
+at a module base, read a view matrix and read a large array of entities every frame.
+An [`OverlayProfile`] describes the pointer chains in a TOML file:
+
+```toml
+[pointers.entity_list]
+module = "client.dll"
+offsets = [0x4dd0ab4]
+static = true
+
+[pointers.local_player]
+module = "client.dll"
+offsets = [0x10f4f4, 0x18, 0x0]
+```
+
+The first offset is added to the base of the module, every following offset is added after
+dereferencing the previous address. Chains marked as `static` are resolved once and then
+cached by the [`OverlayResolver`] until they are invalidated.
+*/
+
+use std::prelude::v1::*;
+
+use crate::architecture::ArchitectureObj;
+use crate::dataview::Pod;
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::os::walkers::decode_ptr;
+use crate::os::Process;
+use crate::types::{umem, Address};
+
+use std::collections::BTreeMap;
+
+use hashbrown::HashMap;
+
+/// A set of named pointer chains.
+#[derive(Debug, Clone, Default, ::serde::Deserialize)]
+pub struct OverlayProfile {
+    #[serde(default)]
+    pub pointers: BTreeMap<String, PointerPath>,
+}
+
+/// A pointer chain relative to the base of a module.
+#[derive(Debug, Clone, ::serde::Deserialize)]
+pub struct PointerPath {
+    /// Name of the module the chain starts at.
+    pub module: String,
+    /// Offsets that are applied along the chain.
+    pub offsets: Vec<umem>,
+    /// The resolved address does not change while the process is running and can be cached.
+    #[serde(default, rename = "static")]
+    pub is_static: bool,
+}
+
+impl OverlayProfile {
+    /// Parses a profile from a TOML string.
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        ::toml::from_str(contents).map_err(|err| {
+            Error(ErrorOrigin::OsLayer, ErrorKind::Configuration)
+                .log_error(format!("unable to parse the overlay profile: {}", err))
+        })
+    }
+
+    /// Reads and parses a profile from a TOML file.
+    pub fn open<P: AsRef<::std::path::Path>>(path: P) -> Result<Self> {
+        let contents = ::std::fs::read_to_string(path).map_err(|err| {
+            Error(ErrorOrigin::OsLayer, ErrorKind::UnableToReadFile)
+                .log_error(format!("unable to open the overlay profile: {}", err))
+        })?;
+        Self::from_toml_str(&contents)
+    }
+}
+
+/// Resolves the pointer chains of an [`OverlayProfile`] against a process.
+///
+/// Module bases and the addresses of static chains are cached. Call
+/// [`OverlayResolver::invalidate`] when the target process restarts.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::mem::MemoryView;
+/// use memflow::os::overlay::{OverlayProfile, OverlayResolver};
+/// use memflow::os::Process;
+/// use memflow::types::Address;
+///
+/// fn local_player(process: &mut (impl Process + MemoryView)) -> Address {
+///     let profile = OverlayProfile::from_toml_str(
+///         r#"
+///         [pointers.local_player]
+///         module = "client.dll"
+///         offsets = [0x10f4f4, 0x18]
+///         "#,
+///     )
+///     .unwrap();
+///
+///     let mut resolver = OverlayResolver::new(profile);
+///     resolver.resolve(process, "local_player").unwrap_or(Address::NULL)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverlayResolver {
+    profile: OverlayProfile,
+    modules: HashMap<String, Address>,
+    statics: HashMap<String, Address>,
+}
+
+impl OverlayResolver {
+    /// Creates a new resolver for the given profile.
+    pub fn new(profile: OverlayProfile) -> Self {
+        Self {
+            profile,
+            modules: HashMap::new(),
+            statics: HashMap::new(),
+        }
+    }
+
+    /// Returns the profile of this resolver.
+    pub fn profile(&self) -> &OverlayProfile {
+        &self.profile
+    }
+
+    /// Clears all cached module bases and static pointers.
+    pub fn invalidate(&mut self) {
+        self.modules.clear();
+        self.statics.clear();
+    }
+
+    /// Resolves the pointer chain with the given name.
+    ///
+    /// Pointers are read with the pointer width of the process architecture.
+    pub fn resolve<P: Process + MemoryView>(
+        &mut self,
+        process: &mut P,
+        name: &str,
+    ) -> Result<Address> {
+        if let Some(&addr) = self.statics.get(name) {
+            return Ok(addr);
+        }
+
+        let path = self.profile.pointers.get(name).ok_or_else(|| {
+            Error(ErrorOrigin::OsLayer, ErrorKind::NotFound).log_warn(format!(
+                "pointer {} is not defined in the overlay profile",
+                name
+            ))
+        })?;
+
+        let base = match self.modules.get(&path.module) {
+            Some(&base) => base,
+            None => {
+                let base = process.module_by_name(&path.module)?.base;
+                self.modules.insert(path.module.clone(), base);
+                base
+            }
+        };
+
+        let arch = process.info().proc_arch.into_obj();
+        let addr = follow_chain(process, arch, base, &path.offsets)?;
+
+        if path.is_static {
+            self.statics.insert(name.to_string(), addr);
+        }
+        Ok(addr)
+    }
+
+    /// Resolves all pointer chains of the profile.
+    ///
+    /// Chains that can not be resolved are skipped.
+    pub fn resolve_all<P: Process + MemoryView>(
+        &mut self,
+        process: &mut P,
+    ) -> BTreeMap<String, Address> {
+        let names = self.profile.pointers.keys().cloned().collect::<Vec<_>>();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let addr = self.resolve(process, &name).ok()?;
+                Some((name, addr))
+            })
+            .collect()
+    }
+}
+
+/// Follows the offsets starting at `base`, dereferencing the address before every offset but the first.
+fn follow_chain<M: MemoryView>(
+    mem: &mut M,
+    arch: ArchitectureObj,
+    base: Address,
+    offsets: &[umem],
+) -> Result<Address> {
+    let mut offsets = offsets.iter();
+    let mut addr = base + offsets.next().copied().unwrap_or(0);
+    for &offset in offsets {
+        let ptr = mem.read_addr_arch(arch, addr).data_part()?;
+        if ptr.is_null() {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::NotFound)
+                .log_trace(format!("null pointer in chain at {:x}", addr)));
+        }
+        addr = ptr + offset;
+    }
+    Ok(addr)
+}
+
+/// Describes an array of entities in memory.
+///
+/// The array either stores the entities inline or, when it is `indirect`, stores pointers to
+/// the entities. In both cases all entities are read with a single batched request.
+#[derive(Debug, Clone, Copy)]
+pub struct EntityList {
+    /// Address of the first element.
+    pub addr: Address,
+    /// Distance between two elements.
+    pub stride: umem,
+    /// The elements are pointers to the entities.
+    pub indirect: bool,
+}
+
+impl EntityList {
+    /// Creates a list of entities that are stored inline.
+    pub fn inline(addr: Address, stride: umem) -> Self {
+        Self {
+            addr,
+            stride,
+            indirect: false,
+        }
+    }
+
+    /// Creates a list of pointers to entities.
+    ///
+    /// `stride` is the distance between two pointers, which usually is the pointer size.
+    pub fn indirect(addr: Address, stride: umem) -> Self {
+        Self {
+            addr,
+            stride,
+            indirect: true,
+        }
+    }
+
+    /// Reads `out.len()` entities.
+    ///
+    /// Entities behind null pointers are zeroed. Returns the number of entities that were read.
+    pub fn read_into<M: MemoryView, T: Pod + Sized>(
+        &self,
+        mem: &mut M,
+        arch: ArchitectureObj,
+        out: &mut [T],
+    ) -> Result<usize> {
+        let addrs = if self.indirect {
+            let ptr_size = arch.size_addr();
+            let mut table = vec![0u8; out.len() * self.stride as usize];
+            mem.read_raw_into(self.addr, &mut table).data_part()?;
+            table
+                .chunks(self.stride as usize)
+                .map(|chunk| decode_ptr(arch, &chunk[..ptr_size]))
+                .collect::<Vec<_>>()
+        } else {
+            (0..out.len())
+                .map(|i| self.addr + i as umem * self.stride)
+                .collect::<Vec<_>>()
+        };
+
+        let mut batcher = mem.batcher();
+        let mut count = 0;
+        for (entity, &addr) in out.iter_mut().zip(addrs.iter()) {
+            if addr.is_null() {
+                entity.as_bytes_mut().iter_mut().for_each(|b| *b = 0);
+            } else {
+                batcher.read_into(addr, entity);
+                count += 1;
+            }
+        }
+        batcher.commit_rw().data_part()?;
+        Ok(count)
+    }
+}
+
+/// A row-major 4x4 view projection matrix as used by Direct3D titles.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Pod)]
+pub struct ViewMatrix(pub [[f32; 4]; 4]);
+
+impl ViewMatrix {
+    /// Projects a world position onto a screen of the given size.
+    ///
+    /// Returns `None` if the position is behind the camera.
+    pub fn world_to_screen(&self, pos: [f32; 3], width: f32, height: f32) -> Option<[f32; 2]> {
+        let m = &self.0;
+        let clip = |row: &[f32; 4]| row[0] * pos[0] + row[1] * pos[1] + row[2] * pos[2] + row[3];
+
+        let w = clip(&m[3]);
+        if w < 0.001 {
+            return None;
+        }
+
+        let x = clip(&m[0]) / w;
+        let y = clip(&m[1]) / w;
+        Some([width / 2.0 * (1.0 + x), height / 2.0 * (1.0 - y)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn parse_profile() {
+        let profile = OverlayProfile::from_toml_str(
+            r#"
+            [pointers.entity_list]
+            module = "client.dll"
+            offsets = [0x4dd0ab4]
+            static = true
+
+            [pointers.local_player]
+            module = "client.dll"
+            offsets = [0x10f4f4, 0x18, 0x0]
+            "#,
+        )
+        .unwrap();
+
+        let entity_list = &profile.pointers["entity_list"];
+        assert_eq!(entity_list.offsets, vec![0x4dd0ab4]);
+        assert!(entity_list.is_static);
+
+        let local_player = &profile.pointers["local_player"];
+        assert_eq!(local_player.module, "client.dll");
+        assert_eq!(local_player.offsets, vec![0x10f4f4, 0x18, 0]);
+        assert!(!local_player.is_static);
+
+        assert!(OverlayProfile::from_toml_str("[pointers.x]\noffsets = [1]").is_err());
+    }
+
+    #[test]
+    fn chain_and_entities() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        // module base 0x1000, global at +0x10 points to a table of entity pointers at 0x2000
+        view.write(Address::from(0x1010), &0x2000u64).unwrap();
+        view.write(Address::from(0x2000), &[0x3000u64, 0, 0x3100])
+            .unwrap();
+        view.write(Address::from(0x3000), &[1u32, 2]).unwrap();
+        view.write(Address::from(0x3100), &[3u32, 4]).unwrap();
+
+        let list = follow_chain(&mut view, x64::ARCH, 0x1000.into(), &[0x10, 0]).unwrap();
+        assert_eq!(list, Address::from(0x2000));
+
+        let mut entities = [[0xffu32; 2]; 3];
+        let count = EntityList::indirect(list, 8)
+            .read_into(&mut view, x64::ARCH, &mut entities)
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(entities, [[1, 2], [0, 0], [3, 4]]);
+
+        let mut entities = [[0u32; 2]; 2];
+        EntityList::inline(0x3000.into(), 0x100)
+            .read_into(&mut view, x64::ARCH, &mut entities)
+            .unwrap();
+        assert_eq!(entities, [[1, 2], [3, 4]]);
+    }
+
+    #[test]
+    fn world_to_screen() {
+        let mut identity = ViewMatrix::default();
+        for i in 0..4 {
+            identity.0[i][i] = 1.0;
+        }
+        assert_eq!(
+            identity.world_to_screen([0.0, 0.0, 0.0], 1920.0, 1080.0),
+            Some([960.0, 540.0])
+        );
+        assert_eq!(
+            identity.world_to_screen([1.0, 1.0, 0.0], 1920.0, 1080.0),
+            Some([1920.0, 0.0])
+        );
+
+        identity.0[3][3] = -1.0;
+        assert_eq!(
+            identity.world_to_screen([0.0, 0.0, 0.0], 1920.0, 1080.0),
+            None
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Normalization of windows paths that have been read from memory.
+//!
+//! Paths stored in kernel and loader structures rarely look like what a user would expect:
+//! * image paths of processes use NT device paths (`\Device\HarddiskVolume3\...`)
+//! * drivers are often referenced relative to `\SystemRoot\`
+//! * 32-bit modules of WoW64 processes are reported in `System32` even though the file
+//!   that is actually mapped is located in `SysWOW64`.
+//!
+//! The [`PathNormalizer`] rewrites such paths into regular DOS paths. The mapping of
+//! device names to drive letters is not known by the normalizer itself, it has to be
+//! supplied by the os layer (e.g. from the symbolic links in the `\GLOBAL??` object directory).
+
+use std::prelude::v1::*;
+
+use super::{ModuleInfo, ProcessInfo};
+
+/// Directories below `System32` that are exempt from the WoW64 file system redirection.
+const WOW64_EXEMPT_DIRS: &[&str] = &[
+    "catroot",
+    "catroot2",
+    "driverstore",
+    "drivers\\etc",
+    "logfiles",
+    "spool",
+];
+
+/// Converts NT and redirected paths into the DOS paths a user would expect.
+///
+/// # Examples
+/// ```
+/// use memflow::os::path::PathNormalizer;
+///
+/// let normalizer = PathNormalizer::new().device("\\Device\\HarddiskVolume3", "C:");
+///
+/// assert_eq!(
+///     normalizer.normalize("\\Device\\HarddiskVolume3\\Windows\\explorer.exe", false),
+///     "C:\\Windows\\explorer.exe"
+/// );
+/// assert_eq!(
+///     normalizer.normalize("C:\\Windows\\System32\\kernel32.dll", true),
+///     "C:\\Windows\\SysWOW64\\kernel32.dll"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathNormalizer {
+    devices: Vec<(String, String)>,
+    system_root: String,
+}
+
+impl Default for PathNormalizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PathNormalizer {
+    /// Creates a new normalizer without any device mappings and `C:\Windows` as the system root.
+    pub fn new() -> Self {
+        Self {
+            devices: vec![],
+            system_root: "C:\\Windows".into(),
+        }
+    }
+
+    /// Maps the given NT device (e.g. `\Device\HarddiskVolume3`) to a DOS drive (e.g. `C:`).
+    pub fn device(mut self, device: &str, drive: &str) -> Self {
+        self.devices.push((
+            device.trim_end_matches('\\').into(),
+            drive.trim_end_matches('\\').into(),
+        ));
+        // prefer the longest match, `\Device\HarddiskVolume1` is a prefix of `\Device\HarddiskVolume10`
+        self.devices
+            .sort_by_key(|(device, _)| std::cmp::Reverse(device.len()));
+        self
+    }
+
+    /// Sets the windows directory that `\SystemRoot` refers to.
+    pub fn system_root(mut self, system_root: &str) -> Self {
+        self.system_root = system_root.trim_end_matches('\\').into();
+        self
+    }
+
+    /// Normalizes the given path.
+    ///
+    /// If `wow64` is set the path is treated as being observed by a 32-bit process running on
+    /// a 64-bit system and `System32` is redirected to `SysWOW64`.
+    pub fn normalize(&self, path: &str, wow64: bool) -> String {
+        let path = strip_prefix_ci(path, "\\??\\")
+            .or_else(|| strip_prefix_ci(path, "\\\\?\\"))
+            .unwrap_or(path);
+
+        let mut out = if let Some(rest) = strip_component_ci(path, "\\SystemRoot") {
+            format!("{}{}", self.system_root, rest)
+        } else if let Some((drive, rest)) = self
+            .devices
+            .iter()
+            .find_map(|(dev, drive)| strip_component_ci(path, dev).map(|rest| (drive, rest)))
+        {
+            format!("{}{}", drive, rest)
+        } else {
+            path.to_string()
+        };
+
+        if wow64 {
+            let system32 = format!("{}\\System32", self.system_root);
+            if let Some(rest) = strip_component_ci(&out, &system32) {
+                let relative = rest.trim_start_matches('\\');
+                let exempt = WOW64_EXEMPT_DIRS
+                    .iter()
+                    .any(|dir| strip_component_ci(relative, dir).is_some());
+                if !exempt {
+                    out = format!("{}\\SysWOW64{}", self.system_root, rest);
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Normalizes the path of a process binary.
+    pub fn normalize_process(&self, info: &ProcessInfo) -> String {
+        self.normalize(info.path.as_ref(), false)
+    }
+
+    /// Normalizes the path of a module loaded into the given process.
+    ///
+    /// The module is considered to be affected by the WoW64 redirection if its
+    /// architecture differs from the system architecture.
+    pub fn normalize_module(&self, process: &ProcessInfo, module: &ModuleInfo) -> String {
+        self.normalize(module.path.as_ref(), module.arch != process.sys_arch)
+    }
+}
+
+/// Strips `prefix` from `path` ignoring ascii case.
+fn strip_prefix_ci<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    match path.get(..prefix.len()) {
+        Some(head) if head.eq_ignore_ascii_case(prefix) => Some(&path[prefix.len()..]),
+        _ => None,
+    }
+}
+
+/// Strips `prefix` from `path` ignoring ascii case, but only if it is followed by a path separator.
+fn strip_component_ci<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    strip_prefix_ci(path, prefix).filter(|rest| rest.is_empty() || rest.starts_with('\\'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer() -> PathNormalizer {
+        PathNormalizer::new()
+            .device("\\Device\\HarddiskVolume1", "D:")
+            .device("\\Device\\HarddiskVolume10\\", "C:\\")
+    }
+
+    #[test]
+    fn device_paths() {
+        let n = normalizer();
+        assert_eq!(
+            n.normalize("\\Device\\HarddiskVolume10\\Windows\\notepad.exe", false),
+            "C:\\Windows\\notepad.exe"
+        );
+        assert_eq!(
+            n.normalize("\\device\\harddiskvolume1\\tools\\a.exe", false),
+            "D:\\tools\\a.exe"
+        );
+        assert_eq!(
+            n.normalize("\\Device\\HarddiskVolume2\\a.exe", false),
+            "\\Device\\HarddiskVolume2\\a.exe"
+        );
+        assert_eq!(
+            n.normalize("\\??\\C:\\Windows\\a.exe", false),
+            "C:\\Windows\\a.exe"
+        );
+        assert_eq!(
+            n.normalize("\\SystemRoot\\system32\\ntoskrnl.exe", false),
+            "C:\\Windows\\system32\\ntoskrnl.exe"
+        );
+    }
+
+    #[test]
+    fn wow64_redirection() {
+        let n = normalizer();
+        assert_eq!(
+            n.normalize("C:\\WINDOWS\\SYSTEM32\\ntdll.dll", true),
+            "C:\\Windows\\SysWOW64\\ntdll.dll"
+        );
+        assert_eq!(
+            n.normalize("C:\\Windows\\System32\\ntdll.dll", false),
+            "C:\\Windows\\System32\\ntdll.dll"
+        );
+        assert_eq!(
+            n.normalize("C:\\Windows\\System32\\drivers\\etc\\hosts", true),
+            "C:\\Windows\\System32\\drivers\\etc\\hosts"
+        );
+        assert_eq!(
+            n.normalize("C:\\Windows\\System32Extra\\a.dll", true),
+            "C:\\Windows\\System32Extra\\a.dll"
+        );
+    }
+}
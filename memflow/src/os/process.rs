@@ -343,6 +343,24 @@ pub struct ProcessInfo {
     ///
     /// On windows this technique is called [`WOW64`](https://docs.microsoft.com/en-us/windows/win32/winprog64/wow64-implementation-details).
     pub proc_arch: ArchitectureIdent,
+    /// ID of the parent process.
+    ///
+    /// # Remarks
+    ///
+    /// This is 0 if the parent is not known. Note that the parent process
+    /// might have already exited and its pid might have been reused.
+    pub parent_pid: Pid,
+    /// ID of the session (e.g. windows terminal session) this process belongs to, or 0 if not applicable.
+    pub session_id: u32,
+    /// Time the process was created at in nanoseconds since the unix epoch, or 0 if it is not known.
+    pub create_time: u64,
+    /// Time the process exited at in nanoseconds since the unix epoch.
+    ///
+    /// # Remarks
+    ///
+    /// This is 0 if the process is still running or if the time is not known.
+    /// The exit code of the process is contained in [`ProcessState::Dead`].
+    pub exit_time: u64,
 }
 
 pub type ProcessInfoCallback<'a> = OpaqueCallback<'a, ProcessInfo>;
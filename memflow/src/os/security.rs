@@ -0,0 +1,453 @@
+//! Security posture of processes.
+//!
+//! This module contains os independent containers describing which exploit mitigations
+//! are in effect for a process, as well as helpers to derive them from the loaded images.
+//! Values that can only be retrieved from os specific structures (e.g. the randomization of
+//! stacks and heaps) are optional and are expected to be filled in by the os layer.
+
+use std::prelude::v1::*;
+
+use super::{ModuleInfo, Pid, Process, ProcessInfo};
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{imem, umem, Address};
+
+const IMAGE_NT_SIGNATURE: u32 = 0x0000_4550;
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10b;
+const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20b;
+
+const IMAGE_FILE_RELOCS_STRIPPED: u16 = 0x0001;
+
+const IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA: u16 = 0x0020;
+const IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE: u16 = 0x0040;
+const IMAGE_DLLCHARACTERISTICS_NX_COMPAT: u16 = 0x0100;
+const IMAGE_DLLCHARACTERISTICS_GUARD_CF: u16 = 0x4000;
+
+/// Mitigation related properties of a single PE image loaded into memory.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct ImageMitigations {
+    /// The address the image has actually been loaded at.
+    pub base: Address,
+    /// The base address the image has been linked against.
+    pub preferred_base: Address,
+    /// The image opted into address space layout randomization.
+    pub dynamic_base: bool,
+    /// The image supports 64-bit high entropy randomization.
+    pub high_entropy_va: bool,
+    /// The image is compatible with data execution prevention.
+    pub nx_compat: bool,
+    /// The image has been compiled with control flow guard.
+    pub guard_cf: bool,
+    /// The relocation information has been stripped from the image, so it can not be relocated.
+    pub relocs_stripped: bool,
+}
+
+impl ImageMitigations {
+    /// Parses the PE headers of the image loaded at `base`.
+    pub fn from_image(mem: &mut impl MemoryView, base: Address) -> Result<Self> {
+        let e_lfanew = u32::from_le(mem.read::<u32>(base + 0x3c).data()?);
+        let nt_headers = base + e_lfanew;
+
+        if u32::from_le(mem.read::<u32>(nt_headers).data()?) != IMAGE_NT_SIGNATURE {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::InvalidExeFile));
+        }
+
+        let characteristics = u16::from_le(mem.read::<u16>(nt_headers + 0x16).data()?);
+
+        let optional_header = nt_headers + 0x18;
+        let preferred_base = match u16::from_le(mem.read::<u16>(optional_header).data()?) {
+            IMAGE_NT_OPTIONAL_HDR32_MAGIC => Address::from(u32::from_le(
+                mem.read::<u32>(optional_header + 0x1c).data()?,
+            )),
+            IMAGE_NT_OPTIONAL_HDR64_MAGIC => Address::from(u64::from_le(
+                mem.read::<u64>(optional_header + 0x18).data()?,
+            )),
+            _ => return Err(Error(ErrorOrigin::OsLayer, ErrorKind::InvalidExeFile)),
+        };
+
+        // DllCharacteristics is located at the same offset for PE32 and PE32+
+        let dll_characteristics = u16::from_le(mem.read::<u16>(optional_header + 0x46).data()?);
+
+        Ok(Self {
+            base,
+            preferred_base,
+            dynamic_base: dll_characteristics & IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE != 0,
+            high_entropy_va: dll_characteristics & IMAGE_DLLCHARACTERISTICS_HIGH_ENTROPY_VA != 0,
+            nx_compat: dll_characteristics & IMAGE_DLLCHARACTERISTICS_NX_COMPAT != 0,
+            guard_cf: dll_characteristics & IMAGE_DLLCHARACTERISTICS_GUARD_CF != 0,
+            relocs_stripped: characteristics & IMAGE_FILE_RELOCS_STRIPPED != 0,
+        })
+    }
+
+    /// Returns the difference between the actual and the preferred base address.
+    pub fn base_delta(&self) -> imem {
+        self.base - self.preferred_base
+    }
+
+    /// Returns `true` if the image has been loaded at a different address than it was linked for.
+    pub fn is_relocated(&self) -> bool {
+        self.base != self.preferred_base
+    }
+}
+
+/// Address space layout randomization characteristics of a process.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AslrReport {
+    /// Mitigations of the primary module of the process.
+    pub image: ImageMitigations,
+    /// Mitigations of all other loaded modules that could be parsed.
+    pub modules: Vec<(ModuleInfo, ImageMitigations)>,
+    /// Whether the stack of the process is randomized, if known.
+    pub stack_randomized: Option<bool>,
+    /// Whether the heaps of the process are randomized, if known.
+    pub heap_randomized: Option<bool>,
+}
+
+impl AslrReport {
+    /// Creates a report for the given process by parsing all loaded images.
+    ///
+    /// Modules whose headers can not be read (e.g. because they have been paged out) are skipped.
+    /// Stack and heap randomization can not be derived from the images and are left empty.
+    pub fn from_process<P: Process + MemoryView>(process: &mut P) -> Result<Self> {
+        let primary = process.primary_module_address()?;
+        let image = ImageMitigations::from_image(process, primary)?;
+
+        let modules = process
+            .module_list()?
+            .into_iter()
+            .filter(|m| m.base != primary)
+            .filter_map(|m| {
+                ImageMitigations::from_image(process, m.base)
+                    .ok()
+                    .map(|i| (m, i))
+            })
+            .collect();
+
+        Ok(Self {
+            image,
+            modules,
+            stack_randomized: None,
+            heap_randomized: None,
+        })
+    }
+
+    /// Returns all modules that do not participate in address space randomization.
+    ///
+    /// A single such module gives an attacker a known address in the process.
+    pub fn non_randomized_modules(&self) -> impl Iterator<Item = &ModuleInfo> {
+        self.modules
+            .iter()
+            .filter(|(_, i)| !i.dynamic_base || i.relocs_stripped)
+            .map(|(m, _)| m)
+    }
+
+    /// Returns `true` if the primary image and all modules opted into randomization.
+    pub fn fully_randomized(&self) -> bool {
+        self.image.dynamic_base
+            && !self.image.relocs_stripped
+            && self.non_randomized_modules().next().is_none()
+            && self.stack_randomized != Some(false)
+            && self.heap_randomized != Some(false)
+    }
+
+    /// Returns the load delta of every module that could be parsed.
+    pub fn base_deltas(&self) -> impl Iterator<Item = (&ModuleInfo, imem)> {
+        self.modules.iter().map(|(m, i)| (m, i.base_delta()))
+    }
+
+    /// Returns the size of the largest module base delta, which gives
+    /// a rough idea of how much entropy was applied to the address space.
+    pub fn max_base_delta(&self) -> umem {
+        core::iter::once(self.image.base_delta())
+            .chain(self.modules.iter().map(|(_, i)| i.base_delta()))
+            .map(|d| d.unsigned_abs() as umem)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// Mandatory integrity level of a process token.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub enum IntegrityLevel {
+    Untrusted,
+    Low,
+    Medium,
+    MediumPlus,
+    High,
+    System,
+    Protected,
+}
+
+impl IntegrityLevel {
+    /// Converts the relative identifier of a mandatory label SID (`S-1-16-<rid>`) into an integrity level.
+    ///
+    /// Values in between the well known levels are rounded down.
+    pub fn from_rid(rid: u32) -> Self {
+        match rid {
+            0..=0x0fff => IntegrityLevel::Untrusted,
+            0x1000..=0x1fff => IntegrityLevel::Low,
+            0x2000..=0x20ff => IntegrityLevel::Medium,
+            0x2100..=0x2fff => IntegrityLevel::MediumPlus,
+            0x3000..=0x3fff => IntegrityLevel::High,
+            0x4000..=0x4fff => IntegrityLevel::System,
+            _ => IntegrityLevel::Protected,
+        }
+    }
+}
+
+/// Raw protection level of a process (`_PS_PROTECTION` on windows).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct ProcessProtection(pub u8);
+
+impl ProcessProtection {
+    /// Returns the type of the protection.
+    pub fn kind(self) -> ProtectionType {
+        match self.0 & 0x7 {
+            0 => ProtectionType::None,
+            1 => ProtectionType::ProtectedLight,
+            2 => ProtectionType::Protected,
+            _ => ProtectionType::Unknown,
+        }
+    }
+
+    /// Returns the signer that is required for code loaded into the process.
+    pub fn signer(self) -> ProtectionSigner {
+        match self.0 >> 4 {
+            0 => ProtectionSigner::None,
+            1 => ProtectionSigner::Authenticode,
+            2 => ProtectionSigner::CodeGen,
+            3 => ProtectionSigner::Antimalware,
+            4 => ProtectionSigner::Lsa,
+            5 => ProtectionSigner::Windows,
+            6 => ProtectionSigner::WinTcb,
+            7 => ProtectionSigner::WinSystem,
+            8 => ProtectionSigner::App,
+            _ => ProtectionSigner::Unknown,
+        }
+    }
+
+    /// Returns `true` if the audit bit is set.
+    pub fn audit(self) -> bool {
+        self.0 & 0x8 != 0
+    }
+
+    /// Returns `true` if the process is protected in any way.
+    pub fn is_protected(self) -> bool {
+        self.kind() != ProtectionType::None
+    }
+
+    /// Returns `true` if the process is a protected process light (PPL).
+    pub fn is_light(self) -> bool {
+        self.kind() == ProtectionType::ProtectedLight
+    }
+
+    /// Checks if the protection level is plausible for a process with the given image path.
+    ///
+    /// Protection levels with an os signer (`Windows` and above) are only handed out to binaries
+    /// shipped with the os. A process outside of the windows directory carrying such a level,
+    /// or any process with an invalid protection value, most likely had its protection tampered with.
+    pub fn is_anomalous_for(self, path: &str) -> bool {
+        match (self.kind(), self.signer()) {
+            (ProtectionType::None, ProtectionSigner::None) => false,
+            (ProtectionType::None, _) | (_, ProtectionSigner::None) => true,
+            (ProtectionType::Unknown, _) | (_, ProtectionSigner::Unknown) => true,
+            (_, signer)
+                if signer >= ProtectionSigner::Windows && signer != ProtectionSigner::App =>
+            {
+                !path.to_ascii_lowercase().contains("\\windows\\")
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Type of a process protection.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub enum ProtectionType {
+    None,
+    ProtectedLight,
+    Protected,
+    Unknown,
+}
+
+/// Signer level of a process protection.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub enum ProtectionSigner {
+    None,
+    Authenticode,
+    CodeGen,
+    Antimalware,
+    Lsa,
+    Windows,
+    WinTcb,
+    WinSystem,
+    App,
+    Unknown,
+}
+
+/// Returns all summaries of processes with the given protection type.
+pub fn filter_protection(
+    summaries: &[SecuritySummary],
+    kind: ProtectionType,
+) -> impl Iterator<Item = &SecuritySummary> {
+    summaries
+        .iter()
+        .filter(move |s| s.protection.map(ProcessProtection::kind) == Some(kind))
+}
+
+/// One-glance summary of the security relevant properties of a process.
+///
+/// All properties are optional since not every os layer is able to retrieve all of them.
+/// The os independent parts can be filled in via [`SecuritySummary::from_process`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct SecuritySummary {
+    /// ID of the process.
+    pub pid: Pid,
+    /// Name of the process.
+    pub name: String,
+    /// Data execution prevention is enabled.
+    pub dep: Option<bool>,
+    /// The process and all of its modules are randomized.
+    pub aslr: Option<bool>,
+    /// The process image uses high entropy randomization.
+    pub high_entropy_va: Option<bool>,
+    /// The process image has been compiled with control flow guard.
+    pub cfg: Option<bool>,
+    /// Protection level of the process.
+    pub protection: Option<ProcessProtection>,
+    /// Minimum signing level required for the process image.
+    pub signature_level: Option<u8>,
+    /// Minimum signing level required for dlls loaded into the process.
+    pub section_signature_level: Option<u8>,
+    /// Integrity level of the primary token of the process.
+    pub integrity: Option<IntegrityLevel>,
+    /// Names of the mitigation policies that are enabled for the process.
+    pub mitigation_policies: Vec<String>,
+}
+
+impl SecuritySummary {
+    /// Creates an empty summary for the given process.
+    pub fn new(info: &ProcessInfo) -> Self {
+        Self {
+            pid: info.pid,
+            name: info.name.to_string(),
+            dep: None,
+            aslr: None,
+            high_entropy_va: None,
+            cfg: None,
+            protection: None,
+            signature_level: None,
+            section_signature_level: None,
+            integrity: None,
+            mitigation_policies: vec![],
+        }
+    }
+
+    /// Creates a summary for the given process and fills in all properties that can be derived from its images.
+    pub fn from_process<P: Process + MemoryView>(process: &mut P) -> Result<Self> {
+        let mut summary = Self::new(process.info());
+        summary.apply_aslr_report(&AslrReport::from_process(process)?);
+        Ok(summary)
+    }
+
+    /// Fills in the image related properties from an existing [`AslrReport`].
+    ///
+    /// DEP is only reported as disabled here if the process image is not NX compatible,
+    /// os layers that know the actual execute options of the process should overwrite it.
+    pub fn apply_aslr_report(&mut self, report: &AslrReport) {
+        self.dep = Some(report.image.nx_compat);
+        self.aslr = Some(report.fully_randomized());
+        self.high_entropy_va = Some(report.image.high_entropy_va);
+        self.cfg = Some(report.image.guard_cf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    fn write_pe64(mem: &mut DummyMemory, base: u64, preferred: u64, dll_characteristics: u16) {
+        let mut view = mem.phys_view();
+        let base = Address::from(base);
+        view.write(base + 0x3c, &0x80u32).unwrap();
+        view.write(base + 0x80, &IMAGE_NT_SIGNATURE).unwrap();
+        view.write(base + 0x98, &IMAGE_NT_OPTIONAL_HDR64_MAGIC)
+            .unwrap();
+        view.write(base + 0xb0, &preferred).unwrap();
+        view.write(base + 0xde, &dll_characteristics).unwrap();
+    }
+
+    #[test]
+    fn parse_image() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        write_pe64(
+            &mut mem,
+            0x2000,
+            0x1000,
+            IMAGE_DLLCHARACTERISTICS_DYNAMIC_BASE | IMAGE_DLLCHARACTERISTICS_NX_COMPAT,
+        );
+
+        let image = ImageMitigations::from_image(&mut mem.phys_view(), 0x2000.into()).unwrap();
+        assert_eq!(image.preferred_base, Address::from(0x1000));
+        assert_eq!(image.base_delta(), 0x1000);
+        assert!(image.is_relocated());
+        assert!(image.dynamic_base);
+        assert!(image.nx_compat);
+        assert!(!image.high_entropy_va);
+        assert!(!image.guard_cf);
+    }
+
+    #[test]
+    fn invalid_image() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        assert!(ImageMitigations::from_image(&mut mem.phys_view(), 0x2000.into()).is_err());
+    }
+
+    #[test]
+    fn integrity_levels() {
+        assert_eq!(IntegrityLevel::from_rid(0x0), IntegrityLevel::Untrusted);
+        assert_eq!(IntegrityLevel::from_rid(0x2000), IntegrityLevel::Medium);
+        assert_eq!(IntegrityLevel::from_rid(0x2100), IntegrityLevel::MediumPlus);
+        assert_eq!(IntegrityLevel::from_rid(0x4000), IntegrityLevel::System);
+        assert_eq!(IntegrityLevel::from_rid(0x5000), IntegrityLevel::Protected);
+        assert!(IntegrityLevel::Low < IntegrityLevel::High);
+    }
+
+    #[test]
+    fn protection_levels() {
+        // PsProtectedSignerWinTcb-Light
+        let lsass = ProcessProtection(0x61);
+        assert_eq!(lsass.kind(), ProtectionType::ProtectedLight);
+        assert_eq!(lsass.signer(), ProtectionSigner::WinTcb);
+        assert!(lsass.is_light());
+        assert!(!lsass.is_anomalous_for("C:\\Windows\\System32\\lsass.exe"));
+        assert!(lsass.is_anomalous_for("C:\\Users\\user\\evil.exe"));
+
+        // PsProtectedSignerAntimalware-Light
+        let av = ProcessProtection(0x31);
+        assert!(!av.is_anomalous_for("C:\\Program Files\\av\\av.exe"));
+
+        assert!(!ProcessProtection(0).is_protected());
+        assert!(ProcessProtection(0x60).is_anomalous_for("C:\\Windows\\a.exe"));
+    }
+}
@@ -6,6 +6,7 @@ use crate::os::*;
 use crate::types::umem;
 use cglue::prelude::v1::ReprCString;
 use dataview::Pod;
+use std::convert::TryFrom;
 use std::vec::Vec;
 
 #[cfg(feature = "goblin")]
@@ -18,8 +19,16 @@ use goblin::{
     Object,
 };
 
-fn aligned_alloc(bytes: usize) -> Vec<u64> {
-    vec![0; (bytes + 8 - 1) / 8]
+/// Allocates a zeroed, 8-byte aligned buffer able to hold `bytes` bytes.
+///
+/// Fails instead of truncating if the target size does not fit into the host's `usize`,
+/// which can happen when a 32-bit host introspects a 64-bit target.
+fn aligned_alloc(bytes: umem) -> Result<Vec<u64>> {
+    let bytes = usize::try_from(bytes).map_err(|_| {
+        Error(ErrorOrigin::OsLayer, ErrorKind::InvalidMemorySize)
+            .log_error("module image does not fit into host memory")
+    })?;
+    Ok(vec![0; (bytes + 8 - 1) / 8])
 }
 
 #[cfg(feature = "goblin")]
@@ -132,7 +141,7 @@ pub fn import_list_callback(
     size: umem,
     mut callback: ImportCallback,
 ) -> Result<()> {
-    let mut module_image = aligned_alloc(size as usize);
+    let mut module_image = aligned_alloc(size)?;
     let module_image = module_image.as_bytes_mut();
 
     mem.read_raw_into(base, module_image).data_part()?;
@@ -238,7 +247,7 @@ pub fn export_list_callback(
     size: umem,
     mut callback: ExportCallback,
 ) -> Result<()> {
-    let mut module_image = aligned_alloc(size as usize);
+    let mut module_image = aligned_alloc(size)?;
     let module_image = module_image.as_bytes_mut();
 
     mem.read_raw_into(base, module_image).data_part()?;
@@ -344,7 +353,7 @@ pub fn section_list_callback(
     size: umem,
     mut callback: SectionCallback,
 ) -> Result<()> {
-    let mut module_image = aligned_alloc(size as usize);
+    let mut module_image = aligned_alloc(size)?;
     let module_image = module_image.as_bytes_mut();
 
     mem.read_raw_into(base, module_image).data_part()?;
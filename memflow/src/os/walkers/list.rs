@@ -0,0 +1,229 @@
+//! Walker for intrusive doubly linked lists.
+//!
+//! Kernel lists are usually intrusive: a `LIST_ENTRY` (or `list_head` on linux) is embedded
+//! somewhere inside of the records that are linked together and the list head is a separate
+//! `LIST_ENTRY` in a global variable or another structure. The address of a record is obtained by
+//! subtracting the offset of the embedded entry from the address of the link (`CONTAINING_RECORD`).
+
+use std::prelude::v1::*;
+
+use super::decode_ptr;
+use crate::architecture::ArchitectureObj;
+use crate::error::PartialResultExt;
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+use hashbrown::HashSet;
+
+/// Describes an intrusive linked list starting at a list head.
+///
+/// The first pointer of every list entry is expected to point to the next entry (`Flink`),
+/// the second pointer to the previous one (`Blink`).
+///
+/// # Examples
+///
+/// ```
+/// use memflow::architecture::x86::x64;
+/// use memflow::mem::MemoryView;
+/// use memflow::os::walkers::ListWalker;
+/// use memflow::types::Address;
+///
+/// // `ActiveProcessLinks` is located at offset 0x448 inside of `_EPROCESS` on recent windows 10 builds
+/// fn processes(mem: &mut impl MemoryView, ps_active_process_head: Address) -> Vec<Address> {
+///     ListWalker::new(ps_active_process_head, 0x448)
+///         .iter(mem, x64::ARCH)
+///         .collect()
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ListWalker {
+    head: Address,
+    entry_offset: umem,
+    max_entries: usize,
+    include_head: bool,
+    backwards: bool,
+}
+
+impl ListWalker {
+    /// Creates a new walker for the list with the given head.
+    ///
+    /// `entry_offset` is the offset of the list entry inside of the records.
+    pub fn new(head: Address, entry_offset: umem) -> Self {
+        Self {
+            head,
+            entry_offset,
+            max_entries: 0x10_0000,
+            include_head: false,
+            backwards: false,
+        }
+    }
+
+    /// Sets the maximum amount of records that are returned.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Treats the head as a list entry that is embedded in a record as well.
+    ///
+    /// This is useful when the walk starts at an arbitrary record of the list instead of the
+    /// global list head, e.g. when walking all processes starting from a known process.
+    pub fn include_head(mut self) -> Self {
+        self.include_head = true;
+        self
+    }
+
+    /// Follows the `Blink` pointers instead of the `Flink` pointers.
+    pub fn backwards(mut self) -> Self {
+        self.backwards = true;
+        self
+    }
+
+    /// Returns an iterator over the addresses of all records in the list.
+    ///
+    /// The iteration ends when the list wraps around to the head, when a null pointer
+    /// or an unreadable entry is encountered, when an entry is visited twice or when
+    /// the maximum amount of entries has been reached.
+    pub fn iter<'a, M: MemoryView>(
+        &self,
+        mem: &'a mut M,
+        arch: ArchitectureObj,
+    ) -> ListIter<'a, M> {
+        ListIter {
+            mem,
+            arch,
+            walker: *self,
+            current: None,
+            visited: HashSet::new(),
+            done: self.head.is_null(),
+        }
+    }
+}
+
+/// Iterator over the records of a linked list, created by [`ListWalker::iter`].
+pub struct ListIter<'a, M> {
+    mem: &'a mut M,
+    arch: ArchitectureObj,
+    walker: ListWalker,
+    current: Option<Address>,
+    visited: HashSet<umem>,
+    done: bool,
+}
+
+impl<'a, M: MemoryView> ListIter<'a, M> {
+    fn next_link(&mut self, link: Address) -> Address {
+        let ptr_size = self.arch.size_addr();
+        let offset = if self.walker.backwards { ptr_size } else { 0 };
+
+        let mut ptr = [0u8; 8];
+        match self
+            .mem
+            .read_raw_into(link + offset as umem, &mut ptr[..ptr_size])
+            .data()
+        {
+            Ok(_) => decode_ptr(self.arch, &ptr),
+            Err(_) => Address::NULL,
+        }
+    }
+}
+
+impl<'a, M: MemoryView> Iterator for ListIter<'a, M> {
+    type Item = Address;
+
+    fn next(&mut self) -> Option<Address> {
+        if self.done {
+            return None;
+        }
+
+        let link = match self.current {
+            None if self.walker.include_head => self.walker.head,
+            None => self.next_link(self.walker.head),
+            Some(current) => self.next_link(current),
+        };
+
+        // the head only counts as a record when it is explicitly included and only the first time
+        if link.is_null()
+            || (link == self.walker.head && (self.current.is_some() || !self.walker.include_head))
+        {
+            self.done = true;
+            return None;
+        }
+
+        if !self.visited.insert(link.to_umem()) {
+            log::warn!("cycle detected in list at {:x}", link);
+            self.done = true;
+            return None;
+        }
+
+        if self.visited.len() > self.walker.max_entries {
+            log::warn!("maximum number of list entries exceeded");
+            self.done = true;
+            return None;
+        }
+
+        self.current = Some(link);
+        Some(link - self.walker.entry_offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    // links the list entries at offset 0x10 of the records at the given addresses
+    fn link(view: &mut impl MemoryView, head: u64, records: &[u64]) {
+        let mut links = vec![head];
+        links.extend(records.iter().map(|r| r + 0x10));
+        for (i, &l) in links.iter().enumerate() {
+            let next = links[(i + 1) % links.len()];
+            let prev = links[(i + links.len() - 1) % links.len()];
+            view.write(Address::from(l), &[next, prev]).unwrap();
+        }
+    }
+
+    #[test]
+    fn walk() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+        link(&mut view, 0x100, &[0x1000, 0x2000, 0x3000]);
+
+        let records = ListWalker::new(0x100.into(), 0x10)
+            .iter(&mut view, x64::ARCH)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                Address::from(0x1000),
+                Address::from(0x2000),
+                Address::from(0x3000)
+            ]
+        );
+
+        let records = ListWalker::new(0x100.into(), 0x10)
+            .backwards()
+            .max_entries(2)
+            .iter(&mut view, x64::ARCH)
+            .collect::<Vec<_>>();
+        assert_eq!(records, vec![Address::from(0x3000), Address::from(0x2000)]);
+    }
+
+    #[test]
+    fn embedded_head() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        // the record at 0x2000 links back to 0x1000 instead of the head
+        link(&mut view, 0x100, &[0x1000, 0x2000]);
+        view.write(Address::from(0x2010), &0x1010u64).unwrap();
+
+        let records = ListWalker::new(0x2010.into(), 0x10)
+            .include_head()
+            .iter(&mut view, x64::ARCH)
+            .collect::<Vec<_>>();
+        assert_eq!(records, vec![Address::from(0x2000), Address::from(0x1000)]);
+    }
+}
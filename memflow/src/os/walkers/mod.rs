@@ -0,0 +1,34 @@
+//! Generic walkers for data structures commonly found in operating system kernels.
+//!
+//! Kernels keep most of their bookkeeping in a handful of container types: linked lists,
+//! balanced trees and multi-level tables. The walkers in this module only know about the
+//! shape of these containers. Decoding of the individual entries is left to the caller,
+//! so a new enumeration usually only has to supply offsets and an entry callback.
+
+pub mod list;
+pub mod table;
+pub mod tree;
+
+pub use list::{ListIter, ListWalker};
+pub use table::SparseTable;
+pub use tree::TreeWalker;
+
+use crate::architecture::{ArchitectureObj, Endianess};
+use crate::types::Address;
+
+/// Decodes a pointer of the given architecture from the start of `bytes`.
+pub(crate) fn decode_ptr(arch: ArchitectureObj, bytes: &[u8]) -> Address {
+    let size = arch.size_addr();
+    let mut raw = [0u8; 8];
+
+    match arch.endianess() {
+        Endianess::LittleEndian => {
+            raw[..size].copy_from_slice(&bytes[..size]);
+            Address::from(u64::from_le_bytes(raw))
+        }
+        Endianess::BigEndian => {
+            raw[8 - size..].copy_from_slice(&bytes[..size]);
+            Address::from(u64::from_be_bytes(raw))
+        }
+    }
+}
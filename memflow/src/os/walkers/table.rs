@@ -0,0 +1,252 @@
+//! Walker for sparse multi-level tables.
+//!
+//! Multi-level tables store their entries in leaf pages which are referenced by one or more
+//! levels of pointer pages. Unused parts of the table are represented by null pointers.
+//! This layout is used by the windows handle tables (including the `PspCidTable`), where the
+//! lowest bits of the `TableCode` contain the number of pointer levels.
+
+use std::prelude::v1::*;
+
+use super::decode_ptr;
+use crate::architecture::ArchitectureObj;
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+/// A sparse table consisting of leaf pages and up to 3 levels of pointer pages.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::architecture::x86::x64;
+/// use memflow::mem::MemoryView;
+/// use memflow::os::walkers::SparseTable;
+/// use memflow::types::Address;
+///
+/// // handle table entries on x64 are 16 bytes in size
+/// fn count_handles(mem: &mut impl MemoryView, table_code: Address) -> usize {
+///     let table = SparseTable::from_table_code(x64::ARCH, table_code, 0x1000, 16);
+///
+///     let mut count = 0;
+///     table
+///         .walk(mem, |_index, entry| {
+///             if entry.iter().any(|&b| b != 0) {
+///                 count += 1;
+///             }
+///             true
+///         })
+///         .unwrap();
+///     count
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SparseTable {
+    arch: ArchitectureObj,
+    root: Address,
+    levels: u8,
+    page_size: usize,
+    entry_size: usize,
+}
+
+impl SparseTable {
+    /// The maximum amount of pointer levels supported by the walker.
+    pub const MAX_LEVELS: u8 = 3;
+
+    /// Creates a new table with the given root page and the number of pointer levels above the leaf pages.
+    ///
+    /// A table with 0 levels consists of a single leaf page.
+    pub fn new(
+        arch: ArchitectureObj,
+        root: Address,
+        levels: u8,
+        page_size: usize,
+        entry_size: usize,
+    ) -> Self {
+        Self {
+            arch,
+            root,
+            levels,
+            page_size,
+            entry_size,
+        }
+    }
+
+    /// Creates a new table from a windows style table code.
+    ///
+    /// The lower 2 bits of the table code contain the number of levels, the rest is the address of the root page.
+    pub fn from_table_code(
+        arch: ArchitectureObj,
+        table_code: Address,
+        page_size: usize,
+        entry_size: usize,
+    ) -> Self {
+        let levels = (table_code.to_umem() & 0b11) as u8;
+        Self::new(
+            arch,
+            table_code.as_mem_aligned(4),
+            levels,
+            page_size,
+            entry_size,
+        )
+    }
+
+    /// Returns the amount of entries a single leaf page holds.
+    pub fn entries_per_page(&self) -> usize {
+        self.page_size / self.entry_size
+    }
+
+    /// Returns the amount of pointers a single pointer page holds.
+    pub fn pointers_per_page(&self) -> usize {
+        self.page_size / self.arch.size_addr()
+    }
+
+    /// Walks all leaf pages of the table and calls `callback` for every entry.
+    ///
+    /// The callback receives the index of the entry in the table and the raw entry data.
+    /// Pages referenced by null pointers are skipped, pages that can only be read partially
+    /// are zero filled. Returning `false` from the callback stops the walk.
+    pub fn walk<M, F>(&self, mem: &mut M, mut callback: F) -> Result<()>
+    where
+        M: MemoryView,
+        F: FnMut(usize, &[u8]) -> bool,
+    {
+        if self.levels > Self::MAX_LEVELS
+            || self.entry_size == 0
+            || self.entry_size > self.page_size
+            || self.root.is_null()
+        {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::InvalidArgument));
+        }
+
+        // one buffer for each pointer level plus the leaf page
+        let mut bufs = vec![vec![0u8; self.page_size]; self.levels as usize + 1];
+        self.walk_level(mem, self.root, self.levels, 0, &mut bufs, &mut callback)
+            .map(|_| ())
+    }
+
+    /// Returns the entry with the given index.
+    pub fn entry<M: MemoryView>(&self, mem: &mut M, index: usize, out: &mut [u8]) -> Result<()> {
+        let entries_per_page = self.entries_per_page();
+        let pointers_per_page = self.pointers_per_page();
+        let ptr_size = self.arch.size_addr();
+
+        let mut page = self.root;
+        let mut page_index = index / entries_per_page;
+
+        for level in (0..self.levels).rev() {
+            let span = pointers_per_page.pow(level as u32);
+            let slot = page_index / span;
+            page_index %= span;
+
+            if slot >= pointers_per_page {
+                return Err(Error(ErrorOrigin::OsLayer, ErrorKind::OutOfBounds));
+            }
+
+            let mut ptr = [0u8; 8];
+            mem.read_raw_into(page + (slot * ptr_size) as umem, &mut ptr[..ptr_size])
+                .data_part()?;
+            page = decode_ptr(self.arch, &ptr);
+            if page.is_null() {
+                return Err(Error(ErrorOrigin::OsLayer, ErrorKind::NotFound));
+            }
+        }
+
+        if page_index != 0 {
+            return Err(Error(ErrorOrigin::OsLayer, ErrorKind::OutOfBounds));
+        }
+
+        let offset = (index % entries_per_page) * self.entry_size;
+        let len = std::cmp::min(out.len(), self.entry_size);
+        mem.read_raw_into(page + offset as umem, &mut out[..len])
+            .data_part()
+    }
+
+    /// Walks a single page, returns `false` if the walk has been stopped by the callback.
+    fn walk_level<M, F>(
+        &self,
+        mem: &mut M,
+        page: Address,
+        level: u8,
+        first_index: usize,
+        bufs: &mut [Vec<u8>],
+        callback: &mut F,
+    ) -> Result<bool>
+    where
+        M: MemoryView,
+        F: FnMut(usize, &[u8]) -> bool,
+    {
+        let (buf, rest) = bufs.split_first_mut().unwrap();
+        mem.read_raw_into(page, buf).data_part()?;
+
+        if level == 0 {
+            for (i, entry) in buf.chunks_exact(self.entry_size).enumerate() {
+                if !callback(first_index + i, entry) {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+
+        // the amount of entries covered by a single pointer on this level
+        let span = self.entries_per_page() * self.pointers_per_page().pow(level as u32 - 1);
+
+        for (i, ptr) in buf.chunks_exact(self.arch.size_addr()).enumerate() {
+            let child = decode_ptr(self.arch, ptr);
+            if child.is_null() {
+                continue;
+            }
+
+            if !self.walk_level(
+                mem,
+                child,
+                level - 1,
+                first_index + i * span,
+                rest,
+                callback,
+            )? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    #[test]
+    fn walk_two_levels() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        // pointer page at 0x1000 referencing leaf pages at 0x2000 and 0x3000
+        view.write(0x1000.into(), &0x2000u64).unwrap();
+        view.write(0x1010.into(), &0x3000u64).unwrap();
+        view.write(0x2010.into(), &0xaau8).unwrap();
+        view.write(0x3020.into(), &0xbbu8).unwrap();
+
+        let table = SparseTable::from_table_code(x64::ARCH, Address::from(0x1001), 0x1000, 16);
+
+        let mut found = vec![];
+        table
+            .walk(&mut view, |idx, entry| {
+                if entry[0] != 0 {
+                    found.push((idx, entry[0]));
+                }
+                true
+            })
+            .unwrap();
+        assert_eq!(found, vec![(1, 0xaa), (2 * 256 + 2, 0xbb)]);
+
+        let mut entry = [0u8; 16];
+        table.entry(&mut view, 2 * 256 + 2, &mut entry).unwrap();
+        assert_eq!(entry[0], 0xbb);
+        assert!(table.entry(&mut view, 256, &mut entry).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Walker for binary search trees.
+//!
+//! Balanced trees (AVL and red-black trees) are used by kernels for a lot of lookups,
+//! for example the VAD tree of windows processes or the object directory. The balancing
+//! information does not matter for a traversal, so the [`TreeWalker`] only needs the offsets
+//! of the child pointers within a node.
+
+use std::prelude::v1::*;
+
+use super::decode_ptr;
+use crate::architecture::ArchitectureObj;
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialResultExt, Result};
+use crate::mem::MemoryView;
+use crate::types::{umem, Address};
+
+use hashbrown::HashSet;
+
+/// In-order walker for binary trees with configurable node layouts.
+///
+/// Since trees read from a running target can be inconsistent, the walker keeps track
+/// of all visited nodes and stops descending when it encounters a cycle
+/// or when the tree exceeds the maximum depth.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::architecture::x86::x64;
+/// use memflow::mem::MemoryView;
+/// use memflow::os::walkers::TreeWalker;
+/// use memflow::types::Address;
+///
+/// fn vad_count(mem: &mut impl MemoryView, vad_root: Address) -> usize {
+///     let mut count = 0;
+///     TreeWalker::rtl_balanced_node(x64::ARCH)
+///         .walk(mem, vad_root, |_node| {
+///             count += 1;
+///             true
+///         })
+///         .unwrap();
+///     count
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TreeWalker {
+    arch: ArchitectureObj,
+    left_offset: umem,
+    right_offset: umem,
+    max_depth: usize,
+    max_nodes: usize,
+}
+
+impl TreeWalker {
+    /// Creates a new walker for nodes with the child pointers at the given offsets.
+    pub fn new(arch: ArchitectureObj, left_offset: umem, right_offset: umem) -> Self {
+        Self {
+            arch,
+            left_offset,
+            right_offset,
+            max_depth: 64,
+            max_nodes: 0x10_0000,
+        }
+    }
+
+    /// Creates a walker for `_RTL_BALANCED_NODE` based trees (e.g. `_RTL_AVL_TREE` on windows 8 and newer).
+    pub fn rtl_balanced_node(arch: ArchitectureObj) -> Self {
+        Self::new(arch, 0, arch.size_addr() as umem)
+    }
+
+    /// Creates a walker for `_MMADDRESS_NODE` based trees (`_MM_AVL_TABLE` on windows 7 and older).
+    ///
+    /// The actual root of a `_MM_AVL_TABLE` is the right child of its `BalancedRoot` member.
+    pub fn mm_address_node(arch: ArchitectureObj) -> Self {
+        let ptr_size = arch.size_addr() as umem;
+        Self::new(arch, ptr_size, ptr_size * 2)
+    }
+
+    /// Sets the maximum depth of the tree.
+    ///
+    /// Balanced trees with 2^64 nodes have a depth of less than 64, so deeper trees are considered corrupted.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum amount of nodes that are visited.
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// Walks the tree starting at `root` in order and calls `callback` with the address of every node.
+    ///
+    /// Returning `false` from the callback stops the walk. Subtrees that can not be read are skipped.
+    /// An error is returned if the tree exceeds the configured limits.
+    pub fn walk<M, F>(&self, mem: &mut M, root: Address, mut callback: F) -> Result<()>
+    where
+        M: MemoryView,
+        F: FnMut(Address) -> bool,
+    {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<Address> = vec![];
+        let mut node = root;
+
+        loop {
+            // descend to the leftmost node of the current subtree
+            while !node.is_null() {
+                if !visited.insert(node.to_umem()) {
+                    log::warn!("cycle detected in tree at {:x}", node);
+                    break;
+                }
+                if stack.len() >= self.max_depth {
+                    return Err(Error(ErrorOrigin::OsLayer, ErrorKind::OutOfBounds)
+                        .log_warn("maximum tree depth exceeded"));
+                }
+                if visited.len() > self.max_nodes {
+                    return Err(Error(ErrorOrigin::OsLayer, ErrorKind::OutOfBounds)
+                        .log_warn("maximum number of tree nodes exceeded"));
+                }
+
+                stack.push(node);
+                node = self.child(mem, node, self.left_offset);
+            }
+
+            match stack.pop() {
+                Some(current) => {
+                    if !callback(current) {
+                        return Ok(());
+                    }
+                    node = self.child(mem, current, self.right_offset);
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Collects the addresses of all nodes of the tree in order.
+    pub fn collect<M: MemoryView>(&self, mem: &mut M, root: Address) -> Result<Vec<Address>> {
+        let mut out = vec![];
+        self.walk(mem, root, |node| {
+            out.push(node);
+            true
+        })?;
+        Ok(out)
+    }
+
+    fn child<M: MemoryView>(&self, mem: &mut M, node: Address, offset: umem) -> Address {
+        let mut ptr = [0u8; 8];
+        let ptr = &mut ptr[..self.arch.size_addr()];
+        match mem.read_raw_into(node + offset, ptr).data() {
+            Ok(_) => decode_ptr(self.arch, ptr),
+            Err(_) => Address::NULL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::x86::x64;
+    use crate::dummy::DummyMemory;
+    use crate::mem::PhysicalMemory;
+    use crate::types::size;
+
+    fn write_node(view: &mut impl MemoryView, node: u64, left: u64, right: u64) {
+        view.write(Address::from(node), &[left, right]).unwrap();
+    }
+
+    #[test]
+    fn in_order() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        //       0x200
+        //      /     \
+        //   0x100   0x400
+        //           /
+        //        0x300
+        write_node(&mut view, 0x200, 0x100, 0x400);
+        write_node(&mut view, 0x100, 0, 0);
+        write_node(&mut view, 0x400, 0x300, 0);
+        write_node(&mut view, 0x300, 0, 0);
+
+        let walker = TreeWalker::rtl_balanced_node(x64::ARCH);
+        let nodes = walker.collect(&mut view, 0x200.into()).unwrap();
+        assert_eq!(
+            nodes,
+            vec![
+                Address::from(0x100),
+                Address::from(0x200),
+                Address::from(0x300),
+                Address::from(0x400)
+            ]
+        );
+    }
+
+    #[test]
+    fn cycle() {
+        let mut mem = DummyMemory::new(size::kb(64));
+        let mut view = mem.phys_view();
+
+        // the left child points back to the root
+        write_node(&mut view, 0x200, 0x100, 0);
+        write_node(&mut view, 0x100, 0x200, 0);
+
+        let walker = TreeWalker::rtl_balanced_node(x64::ARCH);
+        let nodes = walker.collect(&mut view, 0x200.into()).unwrap();
+        assert_eq!(nodes, vec![Address::from(0x100), Address::from(0x200)]);
+    }
+}
@@ -6,6 +6,7 @@ use std::fmt;
 use std::prelude::v1::*;
 
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+use crate::types::umem;
 
 use cglue::{repr_cstring::ReprCString, vec::CVec};
 
@@ -200,6 +201,26 @@ impl Args {
     pub fn get_default(&self) -> Option<&str> {
         self.get("default")
     }
+
+    /// Tries to retrieve an entry from the options map and parses it as a [`ByteSize`].
+    /// If the entry was not found this function returns a `None` value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::plugins::Args;
+    /// use memflow::types::mem;
+    ///
+    /// let args: Args = "size=64mb,cache=2MiB".parse().unwrap();
+    /// assert_eq!(args.get_size("size").unwrap(), Some(mem::mb(64)));
+    /// assert_eq!(args.get_size("cache").unwrap(), Some(mem::mb(2)));
+    /// assert_eq!(args.get_size("missing").unwrap(), None);
+    /// ```
+    pub fn get_size(&self, key: &str) -> Result<Option<umem>> {
+        self.get(key)
+            .map(|value| value.parse::<ByteSize>().map(|size| size.0))
+            .transpose()
+    }
 }
 
 impl TryFrom<&str> for Args {
@@ -259,6 +280,28 @@ impl ArgsValidator {
         self
     }
 
+    /// Returns a FFI-safe description of all arguments known to this validator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::plugins::{ArgDescriptor, ArgType, ArgsValidator};
+    ///
+    /// let validator = ArgsValidator::new().arg(
+    ///     ArgDescriptor::new("size")
+    ///         .arg_type(ArgType::Size)
+    ///         .default_value("2mb"),
+    /// );
+    ///
+    /// let args = validator.arg_list();
+    /// assert_eq!(&*args[0].name, "size");
+    /// assert_eq!(args[0].arg_type, ArgType::Size);
+    /// assert_eq!(&*args[0].default, "2mb");
+    /// ```
+    pub fn arg_list(&self) -> Vec<ArgInfo> {
+        self.args.iter().map(ArgInfo::from).collect()
+    }
+
     pub fn validate(&self, args: &Args) -> Result<()> {
         // check if all given args exist
         for arg in args.args.iter() {
@@ -323,6 +366,8 @@ pub type ArgValidator = Box<dyn Fn(&str) -> ::std::result::Result<(), &'static s
 pub struct ArgDescriptor {
     pub name: String,
     pub description: Option<String>,
+    pub arg_type: ArgType,
+    pub default: Option<String>,
     pub required: bool,
     pub validator: Option<ArgValidator>,
 }
@@ -333,6 +378,8 @@ impl ArgDescriptor {
         Self {
             name: name.to_owned(),
             description: None,
+            arg_type: ArgType::String,
+            default: None,
             required: false,
             validator: None,
         }
@@ -346,6 +393,22 @@ impl ArgDescriptor {
         self
     }
 
+    /// Set the type of value this argument expects.
+    ///
+    /// By default arguments are strings.
+    pub fn arg_type(mut self, arg_type: ArgType) -> Self {
+        self.arg_type = arg_type;
+        self
+    }
+
+    /// Set the value that is used when this argument is not provided.
+    ///
+    /// By default there is no default value.
+    pub fn default_value(mut self, default: &str) -> Self {
+        self.default = Some(default.to_owned());
+        self
+    }
+
     /// Set the required state for this argument.
     ///
     /// By default arguments are optional.
@@ -404,6 +467,149 @@ impl fmt::Debug for ArgDescriptor {
     }
 }
 
+/// The type of value an argument expects.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ArgType {
+    /// Any string
+    String,
+    /// `true` or `false`
+    Bool,
+    /// A signed or unsigned integer
+    Int,
+    /// A memory size, parsed as [`ByteSize`]
+    Size,
+    /// A path on the host file system
+    Path,
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            ArgType::String => "string",
+            ArgType::Bool => "bool",
+            ArgType::Int => "int",
+            ArgType::Size => "size",
+            ArgType::Path => "path",
+        })
+    }
+}
+
+/// Machine-readable description of an argument accepted by a plugin.
+///
+/// This is the FFI-safe counterpart of [`ArgDescriptor`] which plugins hand out to
+/// the inventory, so frontends can present the options of a plugin.
+#[repr(C)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ArgInfo {
+    /// Name of the argument
+    pub name: ReprCString,
+    /// Description of the argument, empty if there is none
+    pub description: ReprCString,
+    /// Type of the argument value
+    pub arg_type: ArgType,
+    /// Default value of the argument, empty if there is none
+    pub default: ReprCString,
+    /// Whether the argument has to be provided
+    pub required: bool,
+}
+
+impl From<&ArgDescriptor> for ArgInfo {
+    fn from(desc: &ArgDescriptor) -> Self {
+        Self {
+            name: desc.name.as_str().into(),
+            description: desc.description.as_deref().unwrap_or_default().into(),
+            arg_type: desc.arg_type,
+            default: desc.default.as_deref().unwrap_or_default().into(),
+            required: desc.required,
+        }
+    }
+}
+
+/// A size in bytes that is parsed from, and displayed as, a human readable string.
+///
+/// Sizes consist of a number and an optional, case insensitive unit. Numbers are decimal unless
+/// they are prefixed with `0x`, hexadecimal numbers can not have a unit. The units `k`, `kb` and
+/// `kib` (and their counterparts for `m`, `g` and `t`) all denote multiples of 1024 bytes.
+///
+/// Sizes are displayed in the largest unit that divides them evenly.
+///
+/// # Examples
+///
+/// ```
+/// use memflow::plugins::args::ByteSize;
+/// use memflow::types::mem;
+///
+/// let size: ByteSize = "64mb".parse().unwrap();
+/// assert_eq!(size, ByteSize(mem::mb(64)));
+/// assert_eq!(size.to_string(), "64mb");
+///
+/// assert_eq!("0x1000".parse::<ByteSize>().unwrap(), ByteSize(mem::kb(4)));
+/// assert_eq!(ByteSize(1000).to_string(), "1000");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub umem);
+
+const SIZE_UNITS: [(&str, u32); 4] = [("t", 40), ("g", 30), ("m", 20), ("k", 10)];
+
+impl std::str::FromStr for ByteSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_lowercase();
+
+        if let Some(hex) = s.strip_prefix("0x") {
+            return umem::from_str_radix(hex, 16).map(ByteSize).map_err(|_| {
+                Error(ErrorOrigin::Args, ErrorKind::InvalidMemorySize)
+                    .log_error(format!("invalid size: {}", s))
+            });
+        }
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (num, unit) = s.split_at(split);
+
+        let shift = match unit.trim_start() {
+            "" | "b" => 0,
+            unit => SIZE_UNITS
+                .iter()
+                .find(|(prefix, _)| {
+                    unit.strip_prefix(prefix)
+                        .map(|rest| matches!(rest, "" | "b" | "ib"))
+                        .unwrap_or(false)
+                })
+                .map(|&(_, shift)| shift)
+                .ok_or_else(|| {
+                    Error(ErrorOrigin::Args, ErrorKind::InvalidMemorySizeUnit)
+                        .log_error(format!("invalid size unit: {}", unit))
+                })?,
+        };
+
+        num.parse::<umem>()
+            .ok()
+            .and_then(|num| num.checked_mul(1 << shift))
+            .map(ByteSize)
+            .ok_or_else(|| {
+                Error(ErrorOrigin::Args, ErrorKind::InvalidMemorySize)
+                    .log_error(format!("invalid size: {}", s))
+            })
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 != 0 {
+            for &(prefix, shift) in SIZE_UNITS.iter() {
+                if self.0.trailing_zeros() >= shift {
+                    return write!(f, "{}{}b", self.0 >> shift, prefix);
+                }
+            }
+        }
+        write!(f, "{}", self.0)
+    }
+}
+
 /// Split a string into a list of separate parts based on ':' delimiter
 ///
 /// This is a more advanced version of splitting that allows to do some basic escaping with
@@ -501,6 +707,7 @@ fn parse_vatcache_args(vargs: &str) -> Result<(usize, u64)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::mem;
 
     #[test]
     pub fn from_str() {
@@ -647,6 +854,35 @@ mod tests {
         assert_eq!(validator.validate(&args), Ok(()));
     }
 
+    #[test]
+    pub fn parse_size() {
+        assert_eq!("4096".parse::<ByteSize>().unwrap(), ByteSize(4096));
+        assert_eq!("64mb".parse::<ByteSize>().unwrap(), ByteSize(mem::mb(64)));
+        assert_eq!("2MiB".parse::<ByteSize>().unwrap(), ByteSize(mem::mb(2)));
+        assert_eq!("1 G".parse::<ByteSize>().unwrap(), ByteSize(mem::gb(1)));
+        assert_eq!("16k".parse::<ByteSize>().unwrap(), ByteSize(mem::kb(16)));
+        assert_eq!("0x2000".parse::<ByteSize>().unwrap(), ByteSize(0x2000));
+
+        assert_eq!(
+            "12qb".parse::<ByteSize>(),
+            Err(Error(ErrorOrigin::Args, ErrorKind::InvalidMemorySizeUnit))
+        );
+        assert_eq!(
+            "mb".parse::<ByteSize>(),
+            Err(Error(ErrorOrigin::Args, ErrorKind::InvalidMemorySize))
+        );
+    }
+
+    #[test]
+    pub fn size_to_string() {
+        for size in [0, 1000, mem::kb(3), mem::mb(64), mem::gb(2) + mem::kb(1)] {
+            let s = ByteSize(size).to_string();
+            assert_eq!(s.parse::<ByteSize>().unwrap(), ByteSize(size));
+        }
+        assert_eq!(ByteSize(mem::mb(64)).to_string(), "64mb");
+        assert_eq!(ByteSize(mem::gb(2) + mem::kb(1)).to_string(), "2097153kb");
+    }
+
     #[test]
     pub fn validator_validate_fail() {
         let validator =
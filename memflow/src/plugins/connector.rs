@@ -8,8 +8,8 @@ use crate::types::{cache::TimedCacheValidator, size};
 use std::time::Duration;
 
 use super::{
-    args::split_str_args, Args, LibArc, LibContext, Loadable, OsInstanceArcBox, PluginDescriptor,
-    PluginLogger, TargetInfo,
+    args::split_str_args, ArgInfo, Args, LibArc, LibContext, Loadable, OsInstanceArcBox,
+    PluginDescriptor, PluginLogger, TargetInfo,
 };
 
 use crate::connector::cpu_state::*;
@@ -232,6 +232,14 @@ impl Loadable for LoadableConnector {
         unsafe { self.descriptor.name.into_str() }
     }
 
+    fn version(&self) -> &str {
+        unsafe { self.descriptor.version.into_str() }
+    }
+
+    fn description(&self) -> &str {
+        unsafe { self.descriptor.description.into_str() }
+    }
+
     fn export_prefix() -> &'static str {
         "MEMFLOW_CONNECTOR_"
     }
@@ -283,6 +291,23 @@ impl Loadable for LoadableConnector {
         }
     }
 
+    /// Retrieves the list of arguments accepted by this plugin
+    fn arg_list(&self) -> Result<Vec<ArgInfo>> {
+        match self.descriptor.arg_list_callback {
+            Some(arg_list_callback) => {
+                let mut ret = vec![];
+                (arg_list_callback)((&mut ret).into());
+                Ok(ret)
+            }
+            None => Err(
+                Error(ErrorOrigin::Connector, ErrorKind::NotSupported).log_error(&format!(
+                    "Connector `{}` does not support argument listing.",
+                    self.ident()
+                )),
+            ),
+        }
+    }
+
     /// Creates a new connector instance from this library.
     ///
     /// The connector is initialized with the arguments provided to this function.
@@ -10,7 +10,7 @@ use std::prelude::v1::*;
 
 pub mod args;
 #[doc(hidden)]
-pub use args::{ArgDescriptor, Args, ArgsValidator};
+pub use args::{ArgDescriptor, ArgInfo, ArgType, Args, ArgsValidator, ByteSize};
 
 // cbindgen fails to properly parse this as return type
 pub type OptionVoid = Option<&'static mut c_void>;
@@ -35,6 +35,9 @@ pub type OsInputArg = <LoadableOs as Loadable>::InputArg;
 pub mod logger;
 pub use logger::*; // TODO: restrict
 
+pub mod scan_cache;
+pub use scan_cache::ScanCache;
+
 pub(crate) mod util;
 pub use util::create_bare;
 
@@ -50,7 +53,13 @@ use libloading::Library;
 use once_cell::sync::OnceCell;
 
 /// Exported memflow plugins version
-pub const MEMFLOW_PLUGIN_VERSION: i32 = -8;
+pub const MEMFLOW_PLUGIN_VERSION: i32 = -9;
+
+/// Plugin api version that introduced `PluginDescriptor::compatible_version`.
+///
+/// Plugin api versions count downwards, descriptors of older plugins do not declare a
+/// compatible version range.
+const COMPATIBLE_VERSION_SINCE: i32 = -9;
 
 /// Help and Target callbacks
 pub type HelpCallback<'a> = OpaqueCallback<'a, ReprCString>;
@@ -100,14 +109,41 @@ pub struct TargetInfo {
 
 pub type TargetCallback<'a> = OpaqueCallback<'a, TargetInfo>;
 
+/// Callback that receives the arguments accepted by a plugin
+pub type ArgListCallback<'a> = OpaqueCallback<'a, ArgInfo>;
+
+/// Metadata of a plugin, queried from its descriptor without instantiating the plugin.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PluginInfo {
+    /// The name of the plugin
+    pub name: String,
+    /// The semantic version of the plugin, usually the version of its crate
+    pub version: String,
+    /// A short description of the plugin
+    pub description: String,
+    /// The arguments accepted by the plugin, empty if the plugin does not describe them
+    pub args: Vec<ArgInfo>,
+}
+
 #[repr(C)]
 pub struct PluginDescriptor<T: Loadable> {
     /// The plugin api version for when the plugin was built.
     /// This has to be set to `MEMFLOW_PLUGIN_VERSION` of memflow.
     ///
-    /// If the versions mismatch the inventory will refuse to load.
+    /// If the version of the inventory is not in the range between `plugin_version` and
+    /// `compatible_version` the inventory will refuse to load the plugin.
+    ///
+    /// This field and `compatible_version` must stay at the start of the descriptor in all
+    /// plugin api versions, they are checked before the rest of the descriptor is read.
     pub plugin_version: i32,
 
+    /// The oldest plugin api version this plugin is still compatible with.
+    ///
+    /// This has to be set to `plugin_version` unless the plugin is known to work with the
+    /// descriptor and instance layouts of older versions of memflow.
+    pub compatible_version: i32,
+
     /// Layout of the input type.
     pub input_layout: &'static TypeLayout,
 
@@ -133,6 +169,9 @@ pub struct PluginDescriptor<T: Loadable> {
     /// Retrieves a list of available targets for the plugin
     pub target_list_callback: Option<extern "C" fn(callback: TargetCallback) -> i32>,
 
+    /// Retrieves a machine-readable list of the arguments accepted by the plugin
+    pub arg_list_callback: Option<extern "C" fn(callback: ArgListCallback) -> ()>,
+
     /// Create instance of the plugin
     pub create: CreateFn<T>,
 }
@@ -154,15 +193,18 @@ pub trait Loadable: Sized {
 
     /// Checks if plugin with the same `ident` already exists in input list
     fn exists(&self, instances: &[LibInstance<Self>]) -> bool {
-        instances
-            .iter()
-            .filter_map(|i| i.state.as_option())
-            .any(|(_, l)| l.ident() == self.ident())
+        instances.iter().any(|i| i.ident() == Some(self.ident()))
     }
 
     /// Identifier string of the plugin
     fn ident(&self) -> &str;
 
+    /// Version string of the plugin
+    fn version(&self) -> &str;
+
+    /// Description of the plugin
+    fn description(&self) -> &str;
+
     fn plugin_type() -> &'static str;
 
     /// Constant prefix for the plugin type
@@ -176,28 +218,36 @@ pub trait Loadable: Sized {
         export: &str,
     ) -> Result<LibInstance<Self>> {
         // find os descriptor
-        let descriptor = unsafe {
-            library
+        let descriptor_ptr = unsafe {
+            *library
                 .as_ref()
                 // TODO: support loading without arc
                 .ok_or(Error(ErrorOrigin::Inventory, ErrorKind::Uninitialized))?
                 .lib
                 .get::<*mut PluginDescriptor<Self>>(format!("{}\0", export).as_bytes())
                 .map_err(|_| Error(ErrorOrigin::Inventory, ErrorKind::MemflowExportsNotFound))?
-                .read()
         };
 
-        // check version
-        if descriptor.plugin_version != MEMFLOW_PLUGIN_VERSION {
+        // check version before the rest of the descriptor is read,
+        // its layout is only known to be valid if the versions are compatible
+        let (found, compatible) = unsafe { read_plugin_version(descriptor_ptr as *const i32) };
+        if !is_compatible_version(found, compatible) {
             warn!(
-                "{} has a different version. version {} required, found {}.",
-                export, MEMFLOW_PLUGIN_VERSION, descriptor.plugin_version
+                "{} in {:?} was built for plugin api version {} (compatible with {}), but memflow requires version {}",
+                export,
+                path.as_ref(),
+                found,
+                compatible,
+                MEMFLOW_PLUGIN_VERSION
             );
-            Ok(LibInstance {
+            return Ok(LibInstance {
                 path: path.as_ref().to_path_buf(),
-                state: LibInstanceState::VersionMismatch,
-            })
-        } else if VerifyLayout::check::<Self::CInputArg>(Some(descriptor.input_layout))
+                state: LibInstanceState::VersionMismatch { found, compatible },
+            });
+        }
+
+        let descriptor = unsafe { descriptor_ptr.read() };
+        if VerifyLayout::check::<Self::CInputArg>(Some(descriptor.input_layout))
             .and(VerifyLayout::check::<Self::Instance>(Some(
                 descriptor.output_layout,
             )))
@@ -242,19 +292,7 @@ pub trait Loadable: Sized {
             ));
         }
 
-        // load library
-        let library = unsafe { Library::new(path.as_ref()) }
-            .map_err(|err| {
-                debug!(
-                    "found {:?} in library '{:?}' but could not load it: {}",
-                    exports,
-                    path.as_ref(),
-                    err
-                );
-                Error(ErrorOrigin::Inventory, ErrorKind::UnableToLoadLibrary)
-            })
-            .map(LibContext::from)
-            .map(CArc::from)?;
+        let library = open_library(path.as_ref(), &exports)?;
 
         Ok(exports
             .into_iter()
@@ -262,6 +300,17 @@ pub trait Loadable: Sized {
             .collect())
     }
 
+    /// Opens the library at `path` and loads the plugin behind the given export.
+    ///
+    /// # Safety
+    ///
+    /// Same as `load_all` - the compiler can not guarantee the safety of
+    /// third party library implementations.
+    fn load_export(path: impl AsRef<Path>, export: &str) -> Result<LibInstance<Self>> {
+        let library = open_library(path.as_ref(), &[export])?;
+        Self::load(path, &library, export)
+    }
+
     /// Helper function to load a plugin into a list of library instances
     ///
     /// This function will try finding appropriate plugin entry, and add it into the list if there
@@ -314,12 +363,82 @@ pub trait Loadable: Sized {
         Ok(())
     }
 
+    /// Helper function to add the plugins of a library into a list without opening the library
+    ///
+    /// The exports of the library are only parsed from the file on disk. The plugin identifier
+    /// is derived from the export name, the library itself is loaded the first time the plugin
+    /// is used.
+    fn defer_append(path: impl AsRef<Path>, out: &mut Vec<LibInstance<Self>>) -> Result<()> {
+        let exports = util::find_export_by_prefix(path.as_ref(), Self::export_prefix())?;
+        if exports.is_empty() {
+            return Err(Error(
+                ErrorOrigin::Inventory,
+                ErrorKind::MemflowExportsNotFound,
+            ));
+        }
+
+        // try to get the canonical path
+        let canonical_path =
+            std::fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_owned());
+        if out.iter().any(|o| o.path == canonical_path) {
+            debug!(
+                "skipping library at '{:?}' because it was added already",
+                path.as_ref()
+            );
+            return Err(Error(ErrorOrigin::Inventory, ErrorKind::AlreadyExists));
+        }
+
+        for export in exports.into_iter() {
+            let ident = export[Self::export_prefix().len()..].to_lowercase();
+            if out.iter().any(|o| o.ident() == Some(ident.as_str())) {
+                debug!(
+                    "skipping library '{}' because it was added already: {:?}",
+                    ident,
+                    path.as_ref()
+                );
+                continue;
+            }
+
+            info!(
+                "adding deferred plugin '{}/{}': {:?}",
+                Self::plugin_type(),
+                ident,
+                path.as_ref()
+            );
+            out.push(LibInstance {
+                path: canonical_path.clone(),
+                state: LibInstanceState::Deferred {
+                    ident,
+                    export,
+                    loaded: OnceCell::new(),
+                },
+            });
+        }
+
+        Ok(())
+    }
+
     /// Retrieves the help text for this plugin
     fn help(&self) -> Result<String>;
 
     /// Retrieves the list of available targets for this plugin
     fn target_list(&self) -> Result<Vec<TargetInfo>>;
 
+    /// Retrieves the list of arguments accepted by this plugin
+    fn arg_list(&self) -> Result<Vec<ArgInfo>>;
+
+    /// Retrieves the metadata of this plugin
+    ///
+    /// Plugins that do not describe their arguments report an empty argument list.
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            name: self.ident().to_string(),
+            version: self.version().to_string(),
+            description: self.description().to_string(),
+            args: self.arg_list().unwrap_or_default(),
+        }
+    }
+
     /// Creates an `Instance` of the library
     ///
     /// This function assumes that `load` performed necessary safety checks
@@ -332,6 +451,42 @@ pub trait Loadable: Sized {
     ) -> Result<Self::Instance>;
 }
 
+/// Reads the plugin api version and the oldest compatible version from a plugin descriptor.
+///
+/// # Safety
+///
+/// `descriptor` has to point to a descriptor exported by a memflow plugin.
+unsafe fn read_plugin_version(descriptor: *const i32) -> (i32, i32) {
+    let version = descriptor.read();
+    if version <= COMPATIBLE_VERSION_SINCE {
+        (version, descriptor.add(1).read())
+    } else {
+        (version, version)
+    }
+}
+
+/// Checks if a plugin built for `version` that is compatible down to `compatible_version`
+/// can be loaded by this version of memflow.
+fn is_compatible_version(version: i32, compatible_version: i32) -> bool {
+    let newest = std::cmp::min(version, compatible_version);
+    let oldest = std::cmp::max(version, compatible_version);
+    (newest..=oldest).contains(&MEMFLOW_PLUGIN_VERSION)
+}
+
+/// Opens a plugin library, `exports` are only used for diagnostics.
+fn open_library<S: std::fmt::Debug>(path: &Path, exports: &[S]) -> Result<CArc<LibContext>> {
+    unsafe { Library::new(path) }
+        .map_err(|err| {
+            debug!(
+                "found {:?} in library '{:?}' but could not load it: {}",
+                exports, path, err
+            );
+            Error(ErrorOrigin::Inventory, ErrorKind::UnableToLoadLibrary)
+        })
+        .map(LibContext::from)
+        .map(CArc::from)
+}
+
 /// The core of the plugin system
 ///
 /// It scans system directories and collects valid memflow plugins. They can then be instantiated
@@ -373,9 +528,14 @@ pub trait Loadable: Sized {
 /// # }
 /// # test().ok();
 /// ```
+///
+/// Libraries are opened while scanning, so the plugin descriptors can be validated up front.
+/// Use [`Inventory::scan_lazy`] to defer this until a plugin is actually instantiated.
 pub struct Inventory {
     connectors: Vec<LibInstance<connector::LoadableConnector>>,
     os_layers: Vec<LibInstance<os::LoadableOs>>,
+    scan_cache: Option<ScanCache>,
+    lazy: bool,
 }
 
 impl Inventory {
@@ -398,6 +558,8 @@ impl Inventory {
         let mut ret = Self {
             connectors: vec![],
             os_layers: vec![],
+            scan_cache: None,
+            lazy: false,
         };
         ret.add_dir(dir)?;
         Ok(ret)
@@ -418,6 +580,62 @@ impl Inventory {
     /// let inventory = Inventory::scan();
     /// ```
     pub fn scan() -> Self {
+        Self::scan_internal(None, false)
+    }
+
+    /// Creates a new inventory of plugins by searching the same paths as [`Inventory::scan`],
+    /// without opening any of the found libraries.
+    ///
+    /// Plugins are identified by their exported symbols only, the libraries are loaded and their
+    /// descriptors are checked once a plugin is used for the first time. This keeps the scan
+    /// cheap when many plugins are installed but only one of them is needed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use memflow::plugins::Inventory;
+    ///
+    /// let inventory = Inventory::scan_lazy();
+    /// let connector = inventory
+    ///     .create_connector("qemu", None, None)
+    ///     .unwrap();
+    /// ```
+    pub fn scan_lazy() -> Self {
+        Self::scan_internal(None, true)
+    }
+
+    /// Creates a new inventory of plugins by searching various paths
+    /// and caches the results of the scan on disk.
+    ///
+    /// Subsequent calls to this function skip all unchanged files that were found not to be
+    /// memflow plugins in an earlier scan. Unchanged plugins are not opened until they are
+    /// used, like in [`Inventory::scan_lazy`]. See [`ScanCache`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::plugins::Inventory;
+    ///
+    /// let inventory = Inventory::scan_cached();
+    /// ```
+    pub fn scan_cached() -> Self {
+        let cache_path = ScanCache::default_path();
+        let cache = cache_path.as_ref().map(ScanCache::load).unwrap_or_default();
+
+        let mut ret = Self::scan_internal(Some(cache), false);
+
+        if let (Some(path), Some(mut cache)) = (cache_path, ret.scan_cache.take()) {
+            cache.prune();
+            cache
+                .save(path)
+                .map_err(|err| warn!("unable to store plugin scan cache: {}", err))
+                .ok();
+        }
+
+        ret
+    }
+
+    fn scan_internal(scan_cache: Option<ScanCache>, lazy: bool) -> Self {
         // add default paths
         #[cfg(unix)]
         let extra_paths: Vec<&str> = vec![
@@ -459,6 +677,8 @@ impl Inventory {
         let mut ret = Self {
             connectors: vec![],
             os_layers: vec![],
+            scan_cache,
+            lazy,
         };
 
         for mut path in path_iter {
@@ -550,8 +770,39 @@ impl Inventory {
     /// Same as previous functions - compiler can not guarantee the safety of
     /// third party library implementations.
     pub fn load(&mut self, path: PathBuf) -> &mut Self {
-        Loadable::load_append(&path, &mut self.connectors).ok();
-        Loadable::load_append(&path, &mut self.os_layers).ok();
+        let cached = self.scan_cache.as_ref().and_then(|c| c.is_plugin(&path));
+        if let Some(false) = cached {
+            trace!(
+                "skipping {:?} because it is cached as not being a plugin",
+                path
+            );
+            return self;
+        }
+
+        // unchanged libraries that are cached as plugins are only opened once they are used
+        let (connectors, os_layers) = if self.lazy || cached == Some(true) {
+            (
+                Loadable::defer_append(&path, &mut self.connectors),
+                Loadable::defer_append(&path, &mut self.os_layers),
+            )
+        } else {
+            (
+                Loadable::load_append(&path, &mut self.connectors),
+                Loadable::load_append(&path, &mut self.os_layers),
+            )
+        };
+
+        if let Some(cache) = &mut self.scan_cache {
+            let is_plugin = |res: &Result<()>| {
+                !matches!(
+                    res,
+                    Err(Error(_, ErrorKind::MemflowExportsNotFound))
+                        | Err(Error(_, ErrorKind::InvalidExeFile))
+                )
+            };
+            cache.insert(&path, is_plugin(&connectors) || is_plugin(&os_layers));
+        }
+
         self
     }
 
@@ -559,8 +810,8 @@ impl Inventory {
     pub fn available_connectors(&self) -> Vec<String> {
         self.connectors
             .iter()
-            .filter_map(|c| c.state.as_option())
-            .map(|s| s.1.ident().to_string())
+            .filter_map(LibInstance::ident)
+            .map(str::to_string)
             .collect::<Vec<_>>()
     }
 
@@ -568,8 +819,8 @@ impl Inventory {
     pub fn available_os(&self) -> Vec<String> {
         self.os_layers
             .iter()
-            .filter_map(|c| c.state.as_option())
-            .map(|s| s.1.ident().to_string())
+            .filter_map(LibInstance::ident)
+            .map(str::to_string)
             .collect::<Vec<_>>()
     }
 
@@ -587,11 +838,45 @@ impl Inventory {
         Self::help_internal(&self.os_layers, name)
     }
 
+    /// Returns the metadata of the given Connector, including the arguments it accepts.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use memflow::plugins::Inventory;
+    ///
+    /// let inventory = Inventory::scan();
+    /// let info = inventory.connector_info("qemu").unwrap();
+    /// for arg in info.args.iter() {
+    ///     println!("{} ({}): {}", &*arg.name, arg.arg_type, &*arg.description);
+    /// }
+    /// ```
+    pub fn connector_info(&self, name: &str) -> Result<PluginInfo> {
+        Self::info_internal(&self.connectors, name)
+    }
+
+    /// Returns the metadata of the given Os Plugin, including the arguments it accepts.
+    pub fn os_info(&self, name: &str) -> Result<PluginInfo> {
+        Self::info_internal(&self.os_layers, name)
+    }
+
+    fn info_internal<T: Loadable>(libs: &[LibInstance<T>], name: &str) -> Result<PluginInfo> {
+        let (_, loader) = libs
+            .iter()
+            .find(|l| l.ident() == Some(name))
+            .ok_or_else(|| {
+                error!("unable to find plugin with name '{}'.", name,);
+                Error(ErrorOrigin::Inventory, ErrorKind::PluginNotFound)
+            })?
+            .resolve()?;
+
+        Ok(loader.info())
+    }
+
     fn help_internal<T: Loadable>(libs: &[LibInstance<T>], name: &str) -> Result<String> {
-        let loader = libs
+        let (_, loader) = libs
             .iter()
-            .filter_map(|c| c.state.as_option().map(|s| s.1))
-            .find(|s| s.ident() == name)
+            .find(|l| l.ident() == Some(name))
             .ok_or_else(|| {
                 error!("unable to find plugin with name '{}'.", name,);
                 error!(
@@ -605,7 +890,8 @@ impl Inventory {
                     Self::plugin_list_unavailable(libs),
                 );
                 Error(ErrorOrigin::Inventory, ErrorKind::PluginNotFound)
-            })?;
+            })?
+            .resolve()?;
 
         loader.help()
     }
@@ -614,11 +900,10 @@ impl Inventory {
     ///
     /// This function returns an error in case the connector does not implement this feature.
     pub fn connector_target_list(&self, name: &str) -> Result<Vec<TargetInfo>> {
-        let loader = self
+        let (_, loader) = self
             .connectors
             .iter()
-            .filter_map(|c| c.state.as_option().map(|s| s.1))
-            .find(|s| s.ident() == name)
+            .find(|l| l.ident() == Some(name))
             .ok_or_else(|| {
                 error!("unable to find plugin with name '{}'.", name,);
                 error!(
@@ -632,7 +917,8 @@ impl Inventory {
                     Self::plugin_list_unavailable(&self.connectors),
                 );
                 Error(ErrorOrigin::Inventory, ErrorKind::PluginNotFound)
-            })?;
+            })?
+            .resolve()?;
 
         loader.target_list()
     }
@@ -776,7 +1062,6 @@ impl Inventory {
     ) -> Result<T::Instance> {
         let lib = libs
             .iter()
-            .filter(|l| l.state.is_loaded())
             .find(|l| l.ident() == Some(name))
             .ok_or_else(|| {
                 error!("unable to find plugin with name '{}'.", name,);
@@ -793,18 +1078,25 @@ impl Inventory {
                 Error(ErrorOrigin::Inventory, ErrorKind::PluginNotFound)
             })?;
 
-        if let LibInstanceState::Loaded { library, loader } = &lib.state {
-            info!(
-                "attempting to load `{}` type plugin `{}` from `{}`",
+        let (library, loader) = lib.resolve().map_err(|err| {
+            error!(
+                "unable to load `{}` plugin `{}` from `{}`: {}",
                 T::plugin_type(),
-                loader.ident(),
+                name,
                 lib.path.to_string_lossy(),
+                err
             );
+            err
+        })?;
+
+        info!(
+            "attempting to load `{}` type plugin `{}` from `{}`",
+            T::plugin_type(),
+            loader.ident(),
+            lib.path.to_string_lossy(),
+        );
 
-            loader.instantiate(library.clone(), input, args)
-        } else {
-            unreachable!()
-        }
+        loader.instantiate(library.clone(), input, args)
     }
 
     /// Sets the maximum logging level in all plugins and updates the
@@ -835,7 +1127,7 @@ impl Inventory {
     /// Returns a comma-seperated list of plugin identifiers of all available plugins
     fn plugin_list_available<T: Loadable>(libs: &[LibInstance<T>]) -> String {
         libs.iter()
-            .filter_map(|c| c.state.as_option().map(|s| s.1.ident().to_string()))
+            .filter_map(LibInstance::ident)
             .collect::<Vec<_>>()
             .join(", ")
     }
@@ -843,7 +1135,7 @@ impl Inventory {
     /// Returns a comma-seperated list of plugin paths of all un-available plugins that where found but could not be loaded. (e.g. because of ABI mismatch)
     fn plugin_list_unavailable<T: Loadable>(libs: &[LibInstance<T>]) -> String {
         libs.iter()
-            .filter(|c| !c.state.is_loaded())
+            .filter(|c| c.ident().is_none())
             .map(|c| c.path.to_string_lossy())
             .collect::<Vec<_>>()
             .join(", ")
@@ -1153,8 +1445,45 @@ pub struct LibInstance<T> {
 }
 
 impl<T: Loadable> LibInstance<T> {
+    /// Returns the identifier of the plugin, or `None` if the plugin can not be used.
     pub fn ident(&self) -> Option<&str> {
-        self.state.as_option().map(|s| s.1.ident())
+        match &self.state {
+            LibInstanceState::Deferred { ident, loaded, .. } => match loaded.get() {
+                Some(Ok(state)) => state.as_option().map(|s| s.1.ident()),
+                Some(Err(_)) => None,
+                None => Some(ident.as_str()),
+            },
+            state => state.as_option().map(|s| s.1.ident()),
+        }
+    }
+
+    /// Returns the library and loader of the plugin.
+    ///
+    /// Deferred plugins are loaded the first time this function is called.
+    pub fn resolve(&self) -> Result<(&CArc<LibContext>, &T)> {
+        let state = match &self.state {
+            LibInstanceState::Deferred { export, loaded, .. } => loaded
+                .get_or_init(|| T::load_export(&self.path, export).map(|l| Box::new(l.state)))
+                .as_deref()
+                .map_err(|err| *err)?,
+            state => state,
+        };
+
+        match state {
+            LibInstanceState::Loaded { library, loader } => Ok((library, loader)),
+            LibInstanceState::VersionMismatch { found, compatible } => {
+                Err(Error(ErrorOrigin::Inventory, ErrorKind::VersionMismatch).log_error(format!(
+                    "plugin at {:?} was built for plugin api version {} (compatible with {}), but memflow requires version {}",
+                    self.path, found, compatible, MEMFLOW_PLUGIN_VERSION
+                )))
+            }
+            LibInstanceState::InvalidAbi => {
+                Err(Error(ErrorOrigin::Inventory, ErrorKind::InvalidAbi))
+            }
+            LibInstanceState::Deferred { .. } => {
+                Err(Error(ErrorOrigin::Inventory, ErrorKind::Uninitialized))
+            }
+        }
     }
 }
 
@@ -1165,25 +1494,76 @@ pub enum LibInstanceState<T> {
         library: CArc<LibContext>,
         loader: T,
     },
-    VersionMismatch,
+    /// The plugin was built for an incompatible plugin api version.
+    VersionMismatch {
+        found: i32,
+        compatible: i32,
+    },
     InvalidAbi,
+    /// The plugin was found by a lazy scan, the library is opened on first use.
+    Deferred {
+        ident: String,
+        export: String,
+        loaded: OnceCell<Result<Box<LibInstanceState<T>>>>,
+    },
 }
 
 impl<T> LibInstanceState<T> {
     pub fn is_loaded(&self) -> bool {
-        matches!(
-            self,
-            LibInstanceState::Loaded {
-                library: _,
-                loader: _,
-            }
-        )
+        self.as_option().is_some()
     }
 
     pub fn as_option(&self) -> Option<(&CArc<LibContext>, &T)> {
         match self {
             LibInstanceState::Loaded { library, loader } => Some((library, loader)),
+            LibInstanceState::Deferred { loaded, .. } => loaded
+                .get()
+                .and_then(|state| state.as_ref().ok())
+                .and_then(|state| state.as_option()),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compatible_version() {
+        assert!(is_compatible_version(
+            MEMFLOW_PLUGIN_VERSION,
+            MEMFLOW_PLUGIN_VERSION
+        ));
+        assert!(!is_compatible_version(
+            MEMFLOW_PLUGIN_VERSION + 1,
+            MEMFLOW_PLUGIN_VERSION + 1
+        ));
+
+        // a newer plugin that is still compatible with this version
+        assert!(is_compatible_version(
+            MEMFLOW_PLUGIN_VERSION - 1,
+            MEMFLOW_PLUGIN_VERSION
+        ));
+        assert!(!is_compatible_version(
+            MEMFLOW_PLUGIN_VERSION - 2,
+            MEMFLOW_PLUGIN_VERSION - 1
+        ));
+    }
+
+    #[test]
+    fn read_version() {
+        let descriptor = [MEMFLOW_PLUGIN_VERSION - 1, MEMFLOW_PLUGIN_VERSION];
+        assert_eq!(
+            unsafe { read_plugin_version(descriptor.as_ptr()) },
+            (MEMFLOW_PLUGIN_VERSION - 1, MEMFLOW_PLUGIN_VERSION)
+        );
+
+        // plugins from before the compatible version was introduced only declare their version
+        let descriptor = [COMPATIBLE_VERSION_SINCE + 1, 0];
+        assert_eq!(
+            unsafe { read_plugin_version(descriptor.as_ptr()) },
+            (COMPATIBLE_VERSION_SINCE + 1, COMPATIBLE_VERSION_SINCE + 1)
+        );
+    }
+}
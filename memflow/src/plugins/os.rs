@@ -8,7 +8,7 @@ use crate::os::process::*;
 use crate::os::root::*;
 
 use super::{
-    args::split_str_args, Args, ConnectorInstanceArcBox, LibArc, LibContext, Loadable,
+    args::split_str_args, ArgInfo, Args, ConnectorInstanceArcBox, LibArc, LibContext, Loadable,
     PluginDescriptor, PluginLogger, TargetInfo,
 };
 
@@ -92,6 +92,14 @@ impl Loadable for LoadableOs {
         unsafe { self.descriptor.name.into_str() }
     }
 
+    fn version(&self) -> &str {
+        unsafe { self.descriptor.version.into_str() }
+    }
+
+    fn description(&self) -> &str {
+        unsafe { self.descriptor.description.into_str() }
+    }
+
     fn plugin_type() -> &'static str {
         "OS"
     }
@@ -128,6 +136,23 @@ impl Loadable for LoadableOs {
             .log_error("Os-Plugin does not support target listing."))
     }
 
+    /// Retrieves the list of arguments accepted by this plugin
+    fn arg_list(&self) -> Result<Vec<ArgInfo>> {
+        match self.descriptor.arg_list_callback {
+            Some(arg_list_callback) => {
+                let mut ret = vec![];
+                (arg_list_callback)((&mut ret).into());
+                Ok(ret)
+            }
+            None => Err(
+                Error(ErrorOrigin::Connector, ErrorKind::NotSupported).log_error(&format!(
+                    "Os-Plugin `{}` does not support argument listing.",
+                    self.ident()
+                )),
+            ),
+        }
+    }
+
     /// Creates a new OS instance from this library.
     ///
     /// The OS is initialized with the arguments provided to this function.
@@ -0,0 +1,191 @@
+/*!
+On-disk cache of plugin directory scans.
+
+Scanning the default plugin directories requires parsing the symbol table of every
+library in there, most of which are not memflow plugins at all. The [`ScanCache`] remembers
+which files are plugins, keyed by their size and modification time, so unchanged libraries
+that are known not to contain any memflow exports can be skipped on subsequent scans.
+Unchanged libraries that are known to be plugins are not opened during the scan, they are
+only loaded once they are used.
+*/
+
+use std::prelude::v1::*;
+
+use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Identifier of the file format, bumped whenever the format changes.
+const SCAN_CACHE_HEADER: &str = "memflow-scan-cache 1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CacheEntry {
+    size: u64,
+    mtime: u128,
+    is_plugin: bool,
+}
+
+/// Cached results of previous plugin scans.
+#[derive(Debug, Clone, Default)]
+pub struct ScanCache {
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+impl ScanCache {
+    /// Creates a new and empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the default location of the cache file in the user's cache directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("memflow").join("plugin_scan_cache"))
+    }
+
+    /// Loads the cache from the given file.
+    ///
+    /// Missing or invalid cache files result in an empty cache.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let content = match fs::read_to_string(path.as_ref()) {
+            Ok(content) => content,
+            Err(_) => return Self::new(),
+        };
+
+        let mut lines = content.lines();
+        if lines.next() != Some(SCAN_CACHE_HEADER) {
+            log::debug!("ignoring outdated plugin scan cache {:?}", path.as_ref());
+            return Self::new();
+        }
+
+        let entries = lines
+            .filter_map(|line| {
+                let mut parts = line.splitn(4, '\t');
+                let is_plugin = parts.next()? == "1";
+                let size = parts.next()?.parse().ok()?;
+                let mtime = parts.next()?.parse().ok()?;
+                let path = PathBuf::from(parts.next()?);
+                Some((
+                    path,
+                    CacheEntry {
+                        size,
+                        mtime,
+                        is_plugin,
+                    },
+                ))
+            })
+            .collect();
+
+        Self { entries }
+    }
+
+    /// Writes the cache to the given file, creating parent directories if necessary.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                Error(ErrorOrigin::Inventory, ErrorKind::UnableToCreateDirectory).log_debug(err)
+            })?;
+        }
+
+        let mut content = String::from(SCAN_CACHE_HEADER);
+        content.push('\n');
+        for (path, entry) in self.entries.iter() {
+            if let Some(path) = path.to_str() {
+                content.push_str(&format!(
+                    "{}\t{}\t{}\t{}\n",
+                    entry.is_plugin as u8, entry.size, entry.mtime, path
+                ));
+            }
+        }
+
+        fs::write(path.as_ref(), content).map_err(|err| {
+            Error(ErrorOrigin::Inventory, ErrorKind::UnableToWriteFile).log_debug(err)
+        })
+    }
+
+    /// Returns whether the file at the given path is a memflow plugin.
+    ///
+    /// `None` is returned if the file is not in the cache or if it changed since it was cached.
+    pub fn is_plugin(&self, path: impl AsRef<Path>) -> Option<bool> {
+        let entry = self.entries.get(path.as_ref())?;
+        let (size, mtime) = file_stamp(path.as_ref())?;
+        if entry.size == size && entry.mtime == mtime {
+            Some(entry.is_plugin)
+        } else {
+            None
+        }
+    }
+
+    /// Records whether the file at the given path is a memflow plugin.
+    pub fn insert(&mut self, path: impl AsRef<Path>, is_plugin: bool) {
+        if let Some((size, mtime)) = file_stamp(path.as_ref()) {
+            self.entries.insert(
+                path.as_ref().to_path_buf(),
+                CacheEntry {
+                    size,
+                    mtime,
+                    is_plugin,
+                },
+            );
+        }
+    }
+
+    /// Removes all entries of files that no longer exist.
+    pub fn prune(&mut self) {
+        self.entries.retain(|path, _| path.exists());
+    }
+
+    /// Returns the number of cached files.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the cache does not contain any files.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Returns the size and modification time (in nanoseconds since the unix epoch) of a file.
+fn file_stamp(path: &Path) -> Option<(u64, u128)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    Some((metadata.len(), mtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let dir = std::env::temp_dir().join(format!("memflow-scan-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let lib = dir.join("libfoo.so");
+        fs::write(&lib, b"not a plugin").unwrap();
+
+        let mut cache = ScanCache::new();
+        assert_eq!(cache.is_plugin(&lib), None);
+        cache.insert(&lib, false);
+        assert_eq!(cache.is_plugin(&lib), Some(false));
+
+        let cache_file = dir.join("cache");
+        cache.save(&cache_file).unwrap();
+        let loaded = ScanCache::load(&cache_file);
+        assert_eq!(loaded.is_plugin(&lib), Some(false));
+
+        // changing the file invalidates the entry
+        fs::write(&lib, b"now it is a different file").unwrap();
+        assert_eq!(loaded.is_plugin(&lib), None);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -32,6 +32,35 @@ use core::marker::PhantomData;
 /// };
 /// test.byte_swap();
 /// ```
+///
+/// The derive macro also supports tuple structs, generic structs and fieldless enums with a
+/// primitive representation:
+///
+/// ```
+/// use memflow::types::ByteSwap;
+/// use memflow::derive::*;
+///
+/// #[derive(ByteSwap)]
+/// pub struct Wrapper<T>(pub T, pub [u16; 2]);
+///
+/// #[repr(u32)]
+/// #[derive(ByteSwap, Debug, PartialEq)]
+/// pub enum State {
+///     Running = 0x1,
+///     Stopped = 0x0100_0000,
+///     Paused = 0x2,
+/// }
+///
+/// let mut wrapper = Wrapper(State::Running, [1, 2]);
+/// wrapper.byte_swap();
+/// assert_eq!(wrapper.0, State::Stopped);
+/// assert_eq!(wrapper.1, [0x100, 0x200]);
+///
+/// let mut state = State::Paused;
+/// state.byte_swap();
+/// // 0x0200_0000 is not a valid discriminant, so the value is left unchanged
+/// assert_eq!(state, State::Paused);
+/// ```
 pub trait ByteSwap {
     fn byte_swap(&mut self);
 }
@@ -150,9 +179,17 @@ impl<T: ByteSwap> ByteSwap for [T] {
     }
 }
 
+// array types
+impl<T: ByteSwap, const N: usize> ByteSwap for [T; N] {
+    fn byte_swap(&mut self) {
+        self.iter_mut().for_each(|e| e.byte_swap());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::derive::ByteSwap;
 
     #[test]
     fn swap_i8() {
@@ -244,6 +281,51 @@ mod tests {
         assert_eq!(num, 1234);
     }
 
+    #[test]
+    fn swap_array() {
+        let mut arr = [[1u16, 2], [3, 4]];
+        arr.byte_swap();
+        assert_eq!(arr, [[0x100, 0x200], [0x300, 0x400]]);
+    }
+
+    #[test]
+    fn derive_tuple_struct() {
+        #[derive(ByteSwap)]
+        struct Tuple(u32, [u16; 2], Inner<u64>);
+
+        #[derive(ByteSwap)]
+        struct Inner<T> {
+            value: T,
+        }
+
+        let mut t = Tuple(1, [2, 3], Inner { value: 4 });
+        t.byte_swap();
+        assert_eq!(t.0, 1u32.swap_bytes());
+        assert_eq!(t.1, [0x200, 0x300]);
+        assert_eq!(t.2.value, 4u64.swap_bytes());
+    }
+
+    #[test]
+    fn derive_enum() {
+        #[repr(u16)]
+        #[derive(ByteSwap, Debug, PartialEq)]
+        enum Kind {
+            A = 0x0001,
+            B = 0x0100,
+            C = 0x0002,
+        }
+
+        let mut kind = Kind::A;
+        kind.byte_swap();
+        assert_eq!(kind, Kind::B);
+        kind.byte_swap();
+        assert_eq!(kind, Kind::A);
+
+        let mut kind = Kind::C;
+        kind.byte_swap();
+        assert_eq!(kind, Kind::C);
+    }
+
     #[test]
     fn swap_slice_i16() {
         let mut slice = [1234i16, 50, 64, 128, 200];
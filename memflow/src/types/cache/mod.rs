@@ -3,6 +3,11 @@ pub mod timed_validator;
 
 pub mod count_validator;
 
+pub mod stats;
+pub use stats::CacheStats;
+#[cfg(feature = "std")]
+pub(crate) use stats::StatsLogger;
+
 #[cfg(feature = "std")]
 #[doc(hidden)]
 pub use timed_validator::*;
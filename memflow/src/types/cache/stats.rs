@@ -0,0 +1,101 @@
+//! Counters collected by the page and translation caches.
+//!
+//! The counters help choosing cache sizes for a specific connector. A low hit rate with a high
+//! amount of evictions usually means the cache is too small, while a low hit rate without
+//! evictions means the cached data expires too early.
+
+use crate::types::umem;
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use coarsetime::{Duration, Instant};
+
+/// Hit and miss counters of a cache.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct CacheStats {
+    /// Number of lookups that were answered from the cache.
+    pub hits: umem,
+    /// Number of lookups that had to be forwarded to the underlying layer.
+    pub misses: umem,
+    /// Number of valid entries that were replaced by other entries.
+    pub evictions: umem,
+    /// Number of valid entries that were invalidated explicitly, e.g. because of a write.
+    pub invalidations: umem,
+    /// Number of bytes that were served from the cache.
+    pub bytes_served: umem,
+}
+
+impl CacheStats {
+    /// Returns the fraction of lookups that were answered from the cache.
+    pub fn hit_rate(&self) -> f32 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f32 / total as f32,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions, {} invalidations, {} bytes served",
+            self.hits,
+            self.misses,
+            self.hit_rate() * 100.0,
+            self.evictions,
+            self.invalidations,
+            self.bytes_served
+        )
+    }
+}
+
+/// Logs the statistics of a cache in a fixed interval.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub(crate) struct StatsLogger {
+    name: &'static str,
+    interval: Duration,
+    last: Instant,
+}
+
+#[cfg(feature = "std")]
+impl StatsLogger {
+    pub fn new(name: &'static str, interval: Duration) -> Self {
+        Self {
+            name,
+            interval,
+            last: Instant::now(),
+        }
+    }
+
+    /// Logs `stats` if the interval has passed since the last output.
+    pub fn tick(&mut self, stats: &CacheStats) {
+        if self.last.elapsed() >= self.interval {
+            log::info!("{} cache: {}", self.name, stats);
+            self.last = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_rate() {
+        let mut stats = CacheStats::default();
+        assert_eq!(stats.hit_rate(), 0.0);
+
+        stats.hits = 3;
+        stats.misses = 1;
+        assert_eq!(stats.hit_rate(), 0.75);
+        assert!(stats
+            .to_string()
+            .starts_with("3 hits, 1 misses (75.0% hit rate)"));
+    }
+}
@@ -10,8 +10,6 @@ use std::default::Default;
 use std::fmt;
 use std::prelude::v1::*;
 
-// those only required when compiling under std environment
-#[cfg(feature = "std")]
 use crate::error::{Error, ErrorKind, ErrorOrigin, Result};
 
 /// The `MemoryMap`struct provides a mechanism to map addresses from the linear address space
@@ -60,11 +58,17 @@ impl<M> MemoryMapping<M> {
         self.base
     }
 
-    pub fn output(&self) -> std::cell::Ref<M> {
+    pub fn output(&self) -> std::cell::Ref<'_, M> {
         self.output.borrow()
     }
 }
 
+impl<M: SplitAtIndex> MemoryMapping<M> {
+    fn end(&self) -> Address {
+        self.base + self.output.borrow().length()
+    }
+}
+
 impl<M: SplitAtIndex> Default for MemoryMap<M> {
     fn default() -> Self {
         Self {
@@ -166,43 +170,80 @@ impl<M: SplitAtIndex> MemoryMap<M> {
         )
     }
 
+    /// Returns the mapping containing the given address.
+    ///
+    /// The lookup is a binary search over the sorted mappings.
+    pub fn mapping_at(&self, addr: Address) -> Option<&MemoryMapping<M>> {
+        self.overlapping(addr, addr + 1_usize)
+    }
+
+    /// Returns true if any mapping overlaps the range starting at `base` with the given `size`.
+    pub fn overlaps(&self, base: Address, size: umem) -> bool {
+        self.overlapping(base, base + size).is_some()
+    }
+
+    /// Returns the last mapping starting before `end` if it reaches past `start`.
+    ///
+    /// Mappings never overlap, so their end addresses are sorted as well and no mapping before
+    /// the returned one can overlap the range.
+    fn overlapping(&self, start: Address, end: Address) -> Option<&MemoryMapping<M>> {
+        let idx = self.mappings.partition_point(|m| m.base < end);
+        idx.checked_sub(1)
+            .map(|idx| &self.mappings[idx])
+            .filter(|m| m.end() > start)
+    }
+
     /// Adds a new memory mapping to this memory map.
     ///
     /// When adding overlapping memory regions this function will panic!
     pub fn push(&mut self, base: Address, output: M) -> &mut Self {
-        let mapping = MemoryMapping {
-            base,
-            output: output.into(),
-        };
-
-        let mut shift_idx = self.mappings.len();
-
-        // bounds check. In reverse order, because most likely
-        // all mappings will be inserted in increasing order
-        for (i, m) in self.mappings.iter().enumerate().rev() {
-            let start = base;
-            let end = base + mapping.output.borrow().length();
-            if m.base <= start && start < m.base + m.output.borrow().length()
-                || m.base <= end && end < m.base + m.output.borrow().length()
-            {
-                // overlapping memory regions should not be possible
-                panic!(
-                    "MemoryMap::push overlapping regions: {:x}-{:x} ({:x}) | {:x}-{:x} ({:x})",
+        let end = base + output.length();
+        if let Some(m) = self.overlapping(base, end) {
+            // overlapping memory regions should not be possible
+            panic!(
+                "MemoryMap::push overlapping regions: {:x}-{:x} ({:x}) | {:x}-{:x} ({:x})",
+                base,
+                end,
+                end - base,
+                m.base,
+                m.end(),
+                m.end() - m.base
+            );
+        }
+
+        self.insert(base, output)
+    }
+
+    /// Adds a new memory mapping to this memory map.
+    ///
+    /// Unlike [`push`](Self::push) this function returns an error instead of panicking when the
+    /// new region overlaps an existing one.
+    pub fn try_push(&mut self, base: Address, output: M) -> Result<&mut Self> {
+        let end = base + output.length();
+        if let Some(m) = self.overlapping(base, end) {
+            return Err(
+                Error(ErrorOrigin::MemoryMap, ErrorKind::AlreadyExists).log_error(format!(
+                    "region {:x}-{:x} overlaps {:x}-{:x}",
                     base,
                     end,
-                    mapping.output.borrow().length(),
                     m.base,
-                    m.base + m.output.borrow().length(),
-                    m.output.borrow().length()
-                );
-            } else if m.base + m.output.borrow().length() <= start {
-                shift_idx = i + 1;
-                break;
-            }
+                    m.end()
+                )),
+            );
         }
 
-        self.mappings.insert(shift_idx, mapping);
+        Ok(self.insert(base, output))
+    }
 
+    fn insert(&mut self, base: Address, output: M) -> &mut Self {
+        let idx = self.mappings.partition_point(|m| m.base < base);
+        self.mappings.insert(
+            idx,
+            MemoryMapping {
+                base,
+                output: output.into(),
+            },
+        );
         self
     }
 }
@@ -789,6 +830,56 @@ mod tests {
         map.push_range(0x2000.into(), 0x20ff.into(), 0.into());
     }
 
+    #[test]
+    #[should_panic]
+    fn test_overlapping_regions_containing() {
+        let mut map = MemoryMap::new();
+        map.push_range(0x2000.into(), 0x2100.into(), 0.into());
+
+        // should panic
+        map.push_range(0x1000.into(), 0x3000.into(), 0.into());
+    }
+
+    #[test]
+    fn test_try_push() {
+        let mut map = MemoryMap::new();
+        map.push_remap(0x3000.into(), 0x1000, 0.into());
+        map.try_push(0x1000.into(), (0x1000.into(), 0x1000))
+            .unwrap();
+
+        assert!(map
+            .try_push(0x1800.into(), (0x2000.into(), 0x2000))
+            .is_err());
+        assert!(map.try_push(0x0.into(), (0x2000.into(), 0x5000)).is_err());
+        assert!(map.try_push(0x2000.into(), (0x2000.into(), 0x1000)).is_ok());
+
+        let bases = map.iter().map(|m| m.base()).collect::<Vec<_>>();
+        assert_eq!(
+            bases,
+            vec![
+                Address::from(0x1000),
+                Address::from(0x2000),
+                Address::from(0x3000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mapping_at() {
+        let mut map = MemoryMap::new();
+        map.push_remap(0x1000.into(), 0x1000, 0.into());
+        map.push_remap(0x3000.into(), 0x1000, 0x1000.into());
+
+        assert_eq!(map.mapping_at(0x1fff.into()).unwrap().base(), 0x1000.into());
+        assert_eq!(map.mapping_at(0x3000.into()).unwrap().base(), 0x3000.into());
+        assert!(map.mapping_at(0x2000.into()).is_none());
+        assert!(map.mapping_at(0x4000.into()).is_none());
+        assert!(map.mapping_at(0x0.into()).is_none());
+
+        assert!(map.overlaps(0x1800.into(), 0x2000));
+        assert!(!map.overlaps(0x2000.into(), 0x1000));
+    }
+
     #[test]
     fn test_max_address() {
         let mut map = MemoryMap::new();
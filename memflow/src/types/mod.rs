@@ -18,13 +18,19 @@ pub use page::{Page, PageType};
 pub mod physical_address;
 pub use physical_address::PhysicalAddress;
 
+pub mod virtual_address;
+pub use virtual_address::VirtualAddress;
+
 pub mod pointer;
 pub use pointer::{Pointer, Pointer32, Pointer64};
 
 pub mod byte_swap;
 pub use byte_swap::ByteSwap;
 
+pub mod mem_map;
+pub use mem_map::{MemoryMap, PhysicalMemoryMapping};
+
 pub mod cache;
-pub use cache::{CacheValidator, DefaultCacheValidator};
+pub use cache::{CacheStats, CacheValidator, DefaultCacheValidator};
 
 pub mod util;
@@ -9,19 +9,35 @@ bitflags! {
     #[repr(transparent)]
     #[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
     #[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
-    pub struct PageType: u8 {
+    pub struct PageType: u16 {
         /// The page explicitly has no flags.
-        const NONE = 0b0000_0000;
+        const NONE = 0b0000_0000_0000_0000;
         /// The page type is not known.
-        const UNKNOWN = 0b0000_0001;
+        const UNKNOWN = 0b0000_0000_0000_0001;
         /// The page contains page table entries.
-        const PAGE_TABLE = 0b0000_0010;
+        const PAGE_TABLE = 0b0000_0000_0000_0010;
         /// The page is a writeable page.
-        const WRITEABLE = 0b0000_0100;
+        const WRITEABLE = 0b0000_0000_0000_0100;
         /// The page is read only.
-        const READ_ONLY = 0b0000_1000;
+        const READ_ONLY = 0b0000_0000_0000_1000;
         /// The page is not executable.
-        const NOEXEC = 0b0001_0000;
+        const NOEXEC = 0b0000_0000_0001_0000;
+        /// The page is accessible from user mode.
+        const USER = 0b0000_0000_0010_0000;
+        /// The page was accessed since the flag was last cleared by the OS.
+        const ACCESSED = 0b0000_0000_0100_0000;
+        /// The page was written to since the flag was last cleared by the OS.
+        const DIRTY = 0b0000_0000_1000_0000;
+        /// The page is mapped as write-combined memory, like framebuffers.
+        const WRITE_COMBINED = 0b0000_0001_0000_0000;
+        /// The page is uncacheable, like memory mapped device registers (MMIO).
+        const UNCACHEABLE = 0b0000_0010_0000_0000;
+        /// The page is encrypted by the hardware (e.g. AMD SEV or Intel TME).
+        const ENCRYPTED = 0b0000_0100_0000_0000;
+        /// The page content is compressed by the OS.
+        const COMPRESSED = 0b0000_1000_0000_0000;
+        /// The page is backed by the pagefile and currently not resident in memory.
+        const PAGEFILE = 0b0001_0000_0000_0000;
     }
 }
 
@@ -44,6 +60,51 @@ impl PageType {
         }
     }
 
+    pub fn user(mut self, flag: bool) -> Self {
+        self &= !(PageType::USER);
+        if flag {
+            self | PageType::USER
+        } else {
+            self
+        }
+    }
+
+    pub fn accessed(mut self, flag: bool) -> Self {
+        self &= !(PageType::ACCESSED);
+        if flag {
+            self | PageType::ACCESSED
+        } else {
+            self
+        }
+    }
+
+    pub fn dirty(mut self, flag: bool) -> Self {
+        self &= !(PageType::DIRTY);
+        if flag {
+            self | PageType::DIRTY
+        } else {
+            self
+        }
+    }
+
+    /// Returns `true` if the page is known to be writeable.
+    pub fn is_writeable(&self) -> bool {
+        self.contains(PageType::WRITEABLE)
+    }
+
+    /// Returns `true` if code can be executed from the page.
+    ///
+    /// Pages of unknown type are treated as executable.
+    pub fn is_executable(&self) -> bool {
+        !self.contains(PageType::NOEXEC)
+    }
+
+    /// Returns `false` if reads of the page may have side effects or may not return the
+    /// same data twice, in which case the page must not be cached.
+    pub fn is_cacheable(&self) -> bool {
+        !self.intersects(PageType::UNCACHEABLE | PageType::WRITE_COMBINED)
+    }
+
     pub fn page_table(mut self, flag: bool) -> Self {
         self &= !(PageType::PAGE_TABLE | PageType::UNKNOWN);
         if flag {
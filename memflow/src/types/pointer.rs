@@ -4,7 +4,7 @@ Pointer abstraction.
 
 use crate::cglue::ReprCString;
 use crate::dataview::Pod;
-use crate::error::{PartialError, PartialResult};
+use crate::error::{Error, ErrorKind, ErrorOrigin, PartialError, PartialResult};
 use crate::mem::MemoryView;
 use crate::types::{imem, umem, Address, ByteSwap, PrimitiveAddress};
 
@@ -88,7 +88,7 @@ const _: [(); std::mem::size_of::<Pointer64<()>>()] = [(); std::mem::size_of::<u
 /// # read_foo_bar(&mut DummyOs::quick_process(size::mb(2), &[]));
 /// ```
 #[repr(transparent)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Pointer<U: Sized, T: ?Sized = ()> {
     pub inner: U,
     phantom_data: PhantomData<fn() -> T>,
@@ -302,6 +302,89 @@ impl<U: PrimitiveAddress, T: Pod + Sized> Pointer<U, T> {
     pub fn write<M: MemoryView>(self, mem: &mut M, data: &T) -> PartialResult<()> {
         mem.write_ptr(self, data)
     }
+
+    /// Reads the value this pointer points to.
+    ///
+    /// Unlike [`Pointer::read`] this fails with `ErrorKind::NotFound` if the pointer is null,
+    /// which allows walking a graph of structs with `?`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::types::Pointer64;
+    /// use memflow::mem::MemoryView;
+    /// use memflow::dataview::Pod;
+    /// use memflow::error::{PartialResultExt, Result};
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone, Copy, Debug, Pod)]
+    /// struct Entity {
+    ///     pub next: Pointer64<Entity>,
+    ///     pub health: u64,
+    /// }
+    ///
+    /// #[repr(C)]
+    /// #[derive(Clone, Copy, Debug, Pod)]
+    /// struct World {
+    ///     pub entities: Pointer64<Entity>,
+    ///     pub count: u64,
+    /// }
+    ///
+    /// fn second_health(mem: &mut impl MemoryView, world: Pointer64<World>) -> Result<u64> {
+    ///     let world = world.deref(mem).data()?;
+    ///     let first = world.entities.deref(mem).data()?;
+    ///     Ok(first.next.deref(mem).data()?.health)
+    /// }
+    ///
+    /// fn total_health(mem: &mut impl MemoryView, world: Pointer64<World>) -> Result<u64> {
+    ///     let world = world.deref(mem).data()?;
+    ///     let mut entities = vec![Entity { next: Pointer64::null(), health: 0 }; world.count as usize];
+    ///     world.entities.deref_slice(mem, &mut entities).data()?;
+    ///     Ok(entities.iter().map(|e| e.health).sum())
+    /// }
+    /// # use memflow::dummy::DummyOs;
+    /// # use memflow::os::Process;
+    /// # use memflow::types::size;
+    /// # let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+    /// # let base = proc.info().address.to_umem() as u64;
+    /// # proc.write(base.into(), &World { entities: (base + 0x100).into(), count: 2 }).unwrap();
+    /// # proc.write((base + 0x100).into(), &[
+    /// #     Entity { next: (base + 0x110).into(), health: 10 },
+    /// #     Entity { next: Pointer64::null(), health: 20 },
+    /// # ]).unwrap();
+    /// # assert_eq!(second_health(&mut proc, base.into()).unwrap(), 20);
+    /// # assert_eq!(total_health(&mut proc, base.into()).unwrap(), 30);
+    /// # assert!(Pointer64::<World>::null().deref(&mut proc).is_err());
+    /// ```
+    pub fn deref<M: MemoryView>(self, mem: &mut M) -> PartialResult<T> {
+        self.check_null()?;
+        mem.read_ptr(self)
+    }
+
+    /// Reads the `index`-th element of the array this pointer points to.
+    ///
+    /// Fails with `ErrorKind::NotFound` if the pointer is null.
+    pub fn deref_index<M: MemoryView>(self, mem: &mut M, index: umem) -> PartialResult<T> {
+        self.check_null()?;
+        mem.read_ptr(self.add(index))
+    }
+
+    /// Reads `out.len()` consecutive elements of the array this pointer points to.
+    ///
+    /// Fails with `ErrorKind::NotFound` if the pointer is null.
+    pub fn deref_slice<M: MemoryView>(self, mem: &mut M, out: &mut [T]) -> PartialResult<()> {
+        self.check_null()?;
+        mem.read_into(self.address(), out)
+    }
+
+    fn check_null(self) -> Result<(), Error> {
+        if self.is_null() {
+            Err(Error(ErrorOrigin::Pointer, ErrorKind::NotFound)
+                .log_trace("null pointer dereference"))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 /// Implement special phys/virt read/write for CReprStr
@@ -536,4 +619,26 @@ mod tests {
         assert_eq!(ptr2.offset_from(ptr1), 4);
         assert_eq!(ptr1.offset_from(ptr2), -4);
     }
+
+    #[test]
+    fn deref_null() {
+        use crate::dummy::DummyOs;
+        use crate::os::Process;
+        use crate::types::size;
+
+        let mut proc = DummyOs::quick_process(size::mb(2), &[]);
+        let base = proc.info().address;
+        proc.write(base, &[1u32, 2, 3]).unwrap();
+
+        let ptr = Pointer64::<u32>::from(base);
+        assert_eq!(ptr.deref(&mut proc).unwrap(), 1);
+        assert_eq!(ptr.deref_index(&mut proc, 2).unwrap(), 3);
+
+        let mut values = [0u32; 3];
+        ptr.deref_slice(&mut proc, &mut values).unwrap();
+        assert_eq!(values, [1, 2, 3]);
+
+        assert!(Pointer64::<u32>::null().deref(&mut proc).is_err());
+        assert!(Pointer64::<u32>::null().deref_index(&mut proc, 1).is_err());
+    }
 }
@@ -72,3 +72,10 @@ impl<'a> Extend<MemoryRange> for GapRemover<'a> {
         iter.into_iter().for_each(|r| self.push_range(r));
     }
 }
+
+/// 64-bit FNV-1a hash of the given bytes.
+pub(crate) fn fnv1a(data: impl IntoIterator<Item = u8>) -> u64 {
+    data.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
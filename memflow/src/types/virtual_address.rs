@@ -0,0 +1,184 @@
+/*!
+Abstraction over a virtual address.
+*/
+
+use super::{umem, Address, PhysicalAddress};
+use crate::error::Result;
+use crate::mem::VirtualTranslate;
+
+use std::fmt;
+use std::ops;
+
+/// This type represents an [`Address`] in the virtual memory domain.
+///
+/// Physical memory functions take a [`PhysicalAddress`], which can be created from a plain
+/// `Address` but not from a `VirtualAddress`. The only way of turning a virtual address into a
+/// physical one is translating it, so a virtual address can not be passed into a physical read
+/// by accident:
+///
+/// ```compile_fail
+/// use memflow::mem::PhysicalMemory;
+/// use memflow::types::VirtualAddress;
+/// # use memflow::dummy::DummyMemory;
+/// # use memflow::types::size;
+/// # let mut mem = DummyMemory::new(size::mb(2));
+///
+/// let addr = VirtualAddress::from(0x1000);
+/// let mut buf = [0u8; 8];
+/// mem.phys_read_into(addr.into(), &mut buf).unwrap();
+/// ```
+///
+/// Converting between `Address` and `VirtualAddress` is always explicit.
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "abi_stable", derive(::abi_stable::StableAbi))]
+pub struct VirtualAddress(Address);
+
+impl VirtualAddress {
+    /// A virtual address with a value of zero.
+    pub const NULL: VirtualAddress = VirtualAddress(Address::NULL);
+
+    /// A virtual address with an invalid value.
+    pub const INVALID: VirtualAddress = VirtualAddress(Address::INVALID);
+
+    /// Constructs a new `VirtualAddress` from an `Address`.
+    #[inline]
+    pub const fn new(address: Address) -> Self {
+        Self(address)
+    }
+
+    /// Returns a virtual address with a value of zero.
+    #[inline]
+    pub const fn null() -> Self {
+        VirtualAddress::NULL
+    }
+
+    /// Checks wether the virtual address is zero or not.
+    #[inline]
+    pub const fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+
+    /// Checks wether the virtual address is valid or not.
+    #[inline]
+    pub const fn is_valid(self) -> bool {
+        self.0.is_valid()
+    }
+
+    /// Returns the underlying address.
+    #[inline]
+    pub const fn address(self) -> Address {
+        self.0
+    }
+
+    /// Converts the virtual address into a `umem` value.
+    #[inline]
+    pub const fn to_umem(self) -> umem {
+        self.0.to_umem()
+    }
+
+    /// Translates the virtual address into the physical address it is mapped to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use memflow::architecture::x86::x64;
+    /// use memflow::mem::VirtualDma;
+    /// use memflow::types::VirtualAddress;
+    /// # use memflow::dummy::{DummyMemory, DummyOs};
+    /// # use memflow::types::size;
+    /// # let mem = DummyMemory::new(size::mb(4));
+    /// # let (mut os, dtb, virt_base) = DummyOs::new_and_dtb(mem, size::mb(2), &[]);
+    /// # let mem = os.into_inner();
+    ///
+    /// let mut virt_mem = VirtualDma::new(mem, x64::ARCH, x64::new_translator(dtb));
+    ///
+    /// let phys = VirtualAddress::new(virt_base).translate(&mut virt_mem).unwrap();
+    /// assert!(phys.is_valid());
+    /// ```
+    #[inline]
+    pub fn translate<T: VirtualTranslate>(self, mem: &mut T) -> Result<PhysicalAddress> {
+        mem.virt_addr_to_phys(self)
+    }
+}
+
+impl Default for VirtualAddress {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl From<Address> for VirtualAddress {
+    #[inline(always)]
+    fn from(address: Address) -> Self {
+        Self(address)
+    }
+}
+
+impl From<umem> for VirtualAddress {
+    #[inline(always)]
+    fn from(address: umem) -> Self {
+        Self(address.into())
+    }
+}
+
+impl From<VirtualAddress> for Address {
+    #[inline(always)]
+    fn from(address: VirtualAddress) -> Self {
+        address.0
+    }
+}
+
+impl ops::Add<umem> for VirtualAddress {
+    type Output = Self;
+
+    fn add(self, other: umem) -> Self {
+        Self(self.0 + other)
+    }
+}
+
+impl ops::AddAssign<umem> for VirtualAddress {
+    fn add_assign(&mut self, other: umem) {
+        self.0 += other
+    }
+}
+
+impl ops::Sub<umem> for VirtualAddress {
+    type Output = Self;
+
+    fn sub(self, other: umem) -> Self {
+        Self(self.0 - other)
+    }
+}
+
+impl ops::Sub for VirtualAddress {
+    type Output = super::imem;
+
+    fn sub(self, other: Self) -> super::imem {
+        self.0 - other.0
+    }
+}
+
+impl fmt::Debug for VirtualAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}
+impl fmt::UpperHex for VirtualAddress {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:X}", self.0)
+    }
+}
+impl fmt::LowerHex for VirtualAddress {
+    #[inline(always)]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}
+impl fmt::Display for VirtualAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}", self.0)
+    }
+}